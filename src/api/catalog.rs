@@ -0,0 +1,774 @@
+//! Catalog introspection queries run over the SQL endpoint.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::client::{quote_ident, quote_literal, result_rows, value_as_bool};
+use super::NeonClient;
+use crate::models::{
+    ColumnDef, ConstraintInfo, DatabaseSizes, ExtensionInfo, ForeignKeyInfo, FunctionInfo,
+    IndexInfo, Introspection, SchemaSnapshot, TableColumn, TableDetail, TableSize, ViewInfo,
+};
+
+/// Excludes system schemas; `n` is the `pg_namespace` alias.
+const USER_SCHEMAS: &str =
+    "n.nspname NOT IN ('pg_catalog', 'information_schema') AND n.nspname NOT LIKE 'pg\\_%'";
+
+/// Excludes objects created by extensions; `c` is the `pg_class` alias.
+const NOT_EXTENSION_MEMBER: &str = "NOT EXISTS (SELECT 1 FROM pg_catalog.pg_depend d WHERE d.classid = 'pg_class'::regclass AND d.objid = c.oid AND d.deptype = 'e')";
+
+impl NeonClient {
+    /// Capture a column-level snapshot of every user table in a database.
+    ///
+    /// Types come from `format_type`, so they read the way Postgres prints
+    /// them (`character varying(255)`, `timestamp with time zone`).
+    pub async fn get_schema_snapshot(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+    ) -> Result<SchemaSnapshot> {
+        let query = "SELECT n.nspname AS schema, c.relname AS table, a.attname AS column, format_type(a.atttypid, a.atttypmod) AS data_type, NOT a.attnotnull AS nullable FROM pg_catalog.pg_attribute a JOIN pg_catalog.pg_class c ON c.oid = a.attrelid JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace WHERE c.relkind IN ('r', 'p') AND a.attnum > 0 AND NOT a.attisdropped AND n.nspname NOT IN ('pg_catalog', 'information_schema') AND n.nspname NOT LIKE 'pg_toast%' ORDER BY n.nspname, c.relname, a.attnum";
+
+        let rows = self
+            .catalog_rows(project_id, branch_id, database, query)
+            .await?;
+
+        let mut snapshot = SchemaSnapshot::default();
+        for row in &rows {
+            let (Some(schema), Some(table), Some(column), Some(data_type)) = (
+                row.get("schema").and_then(|v| v.as_str()),
+                row.get("table").and_then(|v| v.as_str()),
+                row.get("column").and_then(|v| v.as_str()),
+                row.get("data_type").and_then(|v| v.as_str()),
+            ) else {
+                continue;
+            };
+            let nullable = row.get("nullable").and_then(value_as_bool).unwrap_or(true);
+
+            snapshot
+                .tables
+                .entry(format!("{}.{}", schema, table))
+                .or_default()
+                .columns
+                .insert(
+                    column.to_string(),
+                    ColumnDef {
+                        data_type: data_type.to_string(),
+                        nullable,
+                    },
+                );
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Generate DDL that recreates a database's user schema, in dependency
+    /// order: schemas, extensions, sequences, tables, constraints (foreign
+    /// keys last), indexes, then views.
+    ///
+    /// Partitioned tables, triggers, functions, and grants are not included.
+    /// With `tables` (qualified, quoted names), only those tables are
+    /// described, with the sequences they own and the foreign keys among
+    /// them, and views are left out.
+    pub async fn get_schema_ddl(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        tables: Option<&[String]>,
+    ) -> Result<Vec<String>> {
+        let mut statements = Vec::new();
+
+        // Conditions limiting each query to `tables`; empty for every table
+        let (in_tables, owned_by_tables, references_tables) = match tables {
+            Some(tables) => {
+                let oids = tables
+                    .iter()
+                    .map(|t| format!("to_regclass({})", quote_literal(t)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                (
+                    format!(" AND c.oid IN ({})", oids),
+                    format!(" AND EXISTS (SELECT 1 FROM pg_catalog.pg_depend d WHERE d.objid = s.seqrelid AND d.deptype = 'a' AND d.refobjid IN ({}))", oids),
+                    format!(" AND (con.contype <> 'f' OR con.confrelid IN ({}))", oids),
+                )
+            }
+            None => Default::default(),
+        };
+
+        let schemas = format!(
+            "SELECT n.nspname AS name FROM pg_catalog.pg_namespace n WHERE {} AND n.nspname <> 'public' ORDER BY 1",
+            USER_SCHEMAS
+        );
+        for row in self
+            .catalog_rows(project_id, branch_id, database, &schemas)
+            .await?
+        {
+            statements.push(format!(
+                "CREATE SCHEMA IF NOT EXISTS {}",
+                quote_ident(str_field(&row, "name"))
+            ));
+        }
+
+        let extensions = "SELECT extname AS name FROM pg_catalog.pg_extension WHERE extname <> 'plpgsql' ORDER BY 1";
+        for row in self
+            .catalog_rows(project_id, branch_id, database, extensions)
+            .await?
+        {
+            statements.push(format!(
+                "CREATE EXTENSION IF NOT EXISTS {}",
+                quote_ident(str_field(&row, "name"))
+            ));
+        }
+
+        let sequences = format!(
+            "SELECT n.nspname AS schema, c.relname AS name, format_type(s.seqtypid, NULL) AS data_type, s.seqstart AS start, s.seqincrement AS increment, s.seqmin AS min, s.seqmax AS max, s.seqcycle AS cycle FROM pg_catalog.pg_sequence s JOIN pg_catalog.pg_class c ON c.oid = s.seqrelid JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace WHERE {} AND {} AND NOT EXISTS (SELECT 1 FROM pg_catalog.pg_depend d WHERE d.objid = s.seqrelid AND d.deptype = 'i'){} ORDER BY 1, 2",
+            USER_SCHEMAS, NOT_EXTENSION_MEMBER, owned_by_tables
+        );
+        for row in self
+            .catalog_rows(project_id, branch_id, database, &sequences)
+            .await?
+        {
+            let cycle = row.get("cycle").and_then(value_as_bool).unwrap_or(false);
+            statements.push(format!(
+                "CREATE SEQUENCE IF NOT EXISTS {}.{} AS {} INCREMENT BY {} MINVALUE {} MAXVALUE {} START WITH {}{}",
+                quote_ident(str_field(&row, "schema")),
+                quote_ident(str_field(&row, "name")),
+                str_field(&row, "data_type"),
+                scalar_field(&row, "increment"),
+                scalar_field(&row, "min"),
+                scalar_field(&row, "max"),
+                scalar_field(&row, "start"),
+                if cycle { " CYCLE" } else { "" }
+            ));
+        }
+
+        let columns = format!(
+            "SELECT n.nspname AS schema, c.relname AS table, a.attname AS column, format_type(a.atttypid, a.atttypmod) AS data_type, a.attnotnull AS not_null, pg_get_expr(ad.adbin, ad.adrelid) AS default, a.attidentity AS identity, a.attgenerated AS generated FROM pg_catalog.pg_attribute a JOIN pg_catalog.pg_class c ON c.oid = a.attrelid JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace LEFT JOIN pg_catalog.pg_attrdef ad ON ad.adrelid = a.attrelid AND ad.adnum = a.attnum WHERE c.relkind = 'r' AND NOT c.relispartition AND a.attnum > 0 AND NOT a.attisdropped AND {} AND {}{} ORDER BY n.nspname, c.relname, a.attnum",
+            USER_SCHEMAS, NOT_EXTENSION_MEMBER, in_tables
+        );
+        let mut tables: Vec<(String, Vec<String>)> = Vec::new();
+        for row in self
+            .catalog_rows(project_id, branch_id, database, &columns)
+            .await?
+        {
+            let table = format!(
+                "{}.{}",
+                quote_ident(str_field(&row, "schema")),
+                quote_ident(str_field(&row, "table"))
+            );
+            if tables.last().map(|(t, _)| t) != Some(&table) {
+                tables.push((table, Vec::new()));
+            }
+            if let Some((_, defs)) = tables.last_mut() {
+                defs.push(column_ddl(&row));
+            }
+        }
+        for (table, defs) in tables {
+            statements.push(format!(
+                "CREATE TABLE {} (\n    {}\n)",
+                table,
+                defs.join(",\n    ")
+            ));
+        }
+
+        let constraints = format!(
+            "SELECT n.nspname AS schema, c.relname AS table, con.conname AS name, pg_get_constraintdef(con.oid) AS definition FROM pg_catalog.pg_constraint con JOIN pg_catalog.pg_class c ON c.oid = con.conrelid JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace WHERE c.relkind = 'r' AND NOT c.relispartition AND con.contype IN ('p', 'u', 'c', 'x', 'f') AND {} AND {}{}{} ORDER BY con.contype = 'f', 1, 2, 3",
+            USER_SCHEMAS, NOT_EXTENSION_MEMBER, in_tables, references_tables
+        );
+        for row in self
+            .catalog_rows(project_id, branch_id, database, &constraints)
+            .await?
+        {
+            statements.push(format!(
+                "ALTER TABLE {}.{} ADD CONSTRAINT {} {}",
+                quote_ident(str_field(&row, "schema")),
+                quote_ident(str_field(&row, "table")),
+                quote_ident(str_field(&row, "name")),
+                str_field(&row, "definition")
+            ));
+        }
+
+        // Indexes backing constraints were created with the constraint
+        let indexes = format!(
+            "SELECT pg_get_indexdef(i.indexrelid) AS definition FROM pg_catalog.pg_index i JOIN pg_catalog.pg_class c ON c.oid = i.indrelid JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace WHERE c.relkind = 'r' AND NOT c.relispartition AND NOT EXISTS (SELECT 1 FROM pg_catalog.pg_constraint con WHERE con.conrelid = i.indrelid AND con.conindid = i.indexrelid) AND {} AND {}{} ORDER BY 1",
+            USER_SCHEMAS, NOT_EXTENSION_MEMBER, in_tables
+        );
+        for row in self
+            .catalog_rows(project_id, branch_id, database, &indexes)
+            .await?
+        {
+            statements.push(str_field(&row, "definition").to_string());
+        }
+
+        if tables.is_some() {
+            return Ok(statements);
+        }
+
+        // Creation order (oid) keeps views that select from views valid
+        let views = format!(
+            "SELECT n.nspname AS schema, c.relname AS name, c.relkind AS kind, pg_get_viewdef(c.oid) AS definition FROM pg_catalog.pg_class c JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace WHERE c.relkind IN ('v', 'm') AND {} AND {} ORDER BY c.oid",
+            USER_SCHEMAS, NOT_EXTENSION_MEMBER
+        );
+        for row in self
+            .catalog_rows(project_id, branch_id, database, &views)
+            .await?
+        {
+            let kind = if str_field(&row, "kind") == "m" {
+                "MATERIALIZED VIEW"
+            } else {
+                "VIEW"
+            };
+            statements.push(format!(
+                "CREATE {} {}.{} AS {}",
+                kind,
+                quote_ident(str_field(&row, "schema")),
+                quote_ident(str_field(&row, "name")),
+                str_field(&row, "definition").trim().trim_end_matches(';')
+            ));
+        }
+
+        Ok(statements)
+    }
+
+    /// Describe one table: columns, primary key, constraints, foreign keys in
+    /// both directions, and indexes.
+    pub async fn get_table_detail(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        schema: &str,
+        table: &str,
+    ) -> Result<TableDetail> {
+        let relation = format!(
+            "to_regclass({})",
+            quote_literal(&format!("{}.{}", quote_ident(schema), quote_ident(table)))
+        );
+
+        let columns = format!(
+            "SELECT a.attname AS name, format_type(a.atttypid, a.atttypmod) AS data_type, NOT a.attnotnull AS nullable, pg_get_expr(ad.adbin, ad.adrelid) AS default FROM pg_catalog.pg_attribute a LEFT JOIN pg_catalog.pg_attrdef ad ON ad.adrelid = a.attrelid AND ad.adnum = a.attnum WHERE a.attrelid = {} AND a.attnum > 0 AND NOT a.attisdropped ORDER BY a.attnum",
+            relation
+        );
+        let columns: Vec<TableColumn> = self
+            .catalog_rows(project_id, branch_id, database, &columns)
+            .await?
+            .iter()
+            .map(table_column)
+            .collect();
+        if columns.is_empty() {
+            anyhow::bail!("Table {}.{} not found", schema, table);
+        }
+
+        let mut detail = TableDetail {
+            schema: schema.to_string(),
+            table: table.to_string(),
+            columns,
+            primary_key: None,
+            constraints: Vec::new(),
+            foreign_keys: Vec::new(),
+            referenced_by: Vec::new(),
+            indexes: Vec::new(),
+        };
+
+        let foreign_key_select = foreign_key_select();
+
+        let constraints = format!(
+            "SELECT con.conname AS name, con.contype AS kind, {} AS columns, pg_get_constraintdef(con.oid) AS definition FROM pg_catalog.pg_constraint con WHERE con.conrelid = {} AND con.contype IN ('p', 'u', 'c', 'x') ORDER BY con.contype, con.conname",
+            key_columns("con.conrelid", "con.conkey"),
+            relation
+        );
+        for row in self
+            .catalog_rows(project_id, branch_id, database, &constraints)
+            .await?
+        {
+            add_constraint(&mut detail, constraint(&row));
+        }
+
+        let outgoing = format!(
+            "{} AND con.conrelid = {} ORDER BY con.conname",
+            foreign_key_select, relation
+        );
+        for row in self
+            .catalog_rows(project_id, branch_id, database, &outgoing)
+            .await?
+        {
+            detail.foreign_keys.push(foreign_key(&row));
+        }
+
+        let incoming = format!(
+            "{} AND con.confrelid = {} ORDER BY 2, con.conname",
+            foreign_key_select, relation
+        );
+        for row in self
+            .catalog_rows(project_id, branch_id, database, &incoming)
+            .await?
+        {
+            detail.referenced_by.push(foreign_key(&row));
+        }
+
+        let indexes = format!(
+            "SELECT ci.relname AS name, i.indisunique AS unique, i.indisprimary AS primary, am.amname AS method, pg_get_expr(i.indpred, i.indrelid) AS predicate, pg_get_indexdef(i.indexrelid) AS definition, to_json(ARRAY(SELECT pg_get_indexdef(i.indexrelid, k + 1, true) FROM generate_subscripts(i.indkey, 1) AS k ORDER BY k)) AS columns FROM pg_catalog.pg_index i JOIN pg_catalog.pg_class ci ON ci.oid = i.indexrelid JOIN pg_catalog.pg_am am ON am.oid = ci.relam WHERE i.indrelid = {} ORDER BY i.indisprimary DESC, ci.relname",
+            relation
+        );
+        for row in self
+            .catalog_rows(project_id, branch_id, database, &indexes)
+            .await?
+        {
+            detail.indexes.push(index(&row));
+        }
+
+        Ok(detail)
+    }
+
+    /// Describe every user table (as [`get_table_detail`] does) and view in
+    /// a database, or in `schema` only, with one query per kind of object
+    /// rather than several per table.
+    ///
+    /// [`get_table_detail`]: Self::get_table_detail
+    pub async fn get_introspection(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        schema: Option<&str>,
+    ) -> Result<Introspection> {
+        let in_scope = format!("{}{}", USER_SCHEMAS, schema_filter(schema));
+        let qualified = "format('%I.%I', n.nspname, c.relname) AS qualified";
+
+        let columns = format!(
+            "SELECT n.nspname AS schema, c.relname AS table_name, {}, a.attname AS name, format_type(a.atttypid, a.atttypmod) AS data_type, NOT a.attnotnull AS nullable, pg_get_expr(ad.adbin, ad.adrelid) AS default FROM pg_catalog.pg_class c JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace LEFT JOIN pg_catalog.pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped LEFT JOIN pg_catalog.pg_attrdef ad ON ad.adrelid = a.attrelid AND ad.adnum = a.attnum WHERE c.relkind IN ('r', 'p') AND {} AND {} ORDER BY 1, 2, a.attnum",
+            qualified, in_scope, NOT_EXTENSION_MEMBER
+        );
+        let constraints = format!(
+            "SELECT {}, con.conname AS name, con.contype AS kind, {} AS columns, pg_get_constraintdef(con.oid) AS definition FROM pg_catalog.pg_constraint con JOIN pg_catalog.pg_class c ON c.oid = con.conrelid JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace WHERE con.contype IN ('p', 'u', 'c', 'x') AND {} ORDER BY con.contype, con.conname",
+            qualified,
+            key_columns("con.conrelid", "con.conkey"),
+            in_scope
+        );
+        // Every user schema's, so keys from other schemas show in referenced_by
+        let foreign_keys = format!(
+            "{} AND {} ORDER BY 2, con.conname",
+            foreign_key_select(),
+            USER_SCHEMAS
+        );
+        let indexes = format!(
+            "SELECT {}, ci.relname AS name, i.indisunique AS unique, i.indisprimary AS primary, am.amname AS method, pg_get_expr(i.indpred, i.indrelid) AS predicate, pg_get_indexdef(i.indexrelid) AS definition, to_json(ARRAY(SELECT pg_get_indexdef(i.indexrelid, k + 1, true) FROM generate_subscripts(i.indkey, 1) AS k ORDER BY k)) AS columns FROM pg_catalog.pg_index i JOIN pg_catalog.pg_class ci ON ci.oid = i.indexrelid JOIN pg_catalog.pg_am am ON am.oid = ci.relam JOIN pg_catalog.pg_class c ON c.oid = i.indrelid JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace WHERE c.relkind IN ('r', 'p') AND {} ORDER BY 1, i.indisprimary DESC, ci.relname",
+            qualified, in_scope
+        );
+
+        let (columns, constraints, foreign_keys, indexes, views) = tokio::try_join!(
+            self.catalog_rows(project_id, branch_id, database, &columns),
+            self.catalog_rows(project_id, branch_id, database, &constraints),
+            self.catalog_rows(project_id, branch_id, database, &foreign_keys),
+            self.catalog_rows(project_id, branch_id, database, &indexes),
+            self.list_views(project_id, branch_id, database, schema),
+        )?;
+
+        let mut tables: Vec<TableDetail> = Vec::new();
+        let mut by_name: HashMap<String, usize> = HashMap::new();
+        for row in &columns {
+            let qualified = str_field(row, "qualified");
+            let at = *by_name.entry(qualified.to_string()).or_insert_with(|| {
+                tables.push(TableDetail {
+                    schema: str_field(row, "schema").to_string(),
+                    table: str_field(row, "table_name").to_string(),
+                    columns: Vec::new(),
+                    primary_key: None,
+                    constraints: Vec::new(),
+                    foreign_keys: Vec::new(),
+                    referenced_by: Vec::new(),
+                    indexes: Vec::new(),
+                });
+                tables.len() - 1
+            });
+            // A table without columns still gets a row, with a null name
+            if row.get("name").is_some_and(|n| n.is_string()) {
+                tables[at].columns.push(table_column(row));
+            }
+        }
+
+        for row in &constraints {
+            if let Some(&at) = by_name.get(str_field(row, "qualified")) {
+                add_constraint(&mut tables[at], constraint(row));
+            }
+        }
+        for row in &foreign_keys {
+            let key = foreign_key(row);
+            if let Some(&at) = by_name.get(&key.referenced_table) {
+                tables[at].referenced_by.push(key.clone());
+            }
+            if let Some(&at) = by_name.get(&key.table) {
+                tables[at].foreign_keys.push(key);
+            }
+        }
+        for row in &indexes {
+            if let Some(&at) = by_name.get(str_field(row, "qualified")) {
+                tables[at].indexes.push(index(row));
+            }
+        }
+
+        Ok(Introspection {
+            database: database.to_string(),
+            schema: schema.map(|s| s.to_string()),
+            tables,
+            views,
+        })
+    }
+
+    /// Views and materialized views in user schemas, or in `schema` only.
+    pub async fn list_views(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        schema: Option<&str>,
+    ) -> Result<Vec<ViewInfo>> {
+        let query = format!(
+            "SELECT n.nspname AS schema, c.relname AS name, c.relkind = 'm' AS materialized, pg_get_userbyid(c.relowner) AS owner, CASE WHEN c.relkind = 'm' THEN c.relispopulated END AS populated, pg_get_viewdef(c.oid, true) AS definition FROM pg_catalog.pg_class c JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace WHERE c.relkind IN ('v', 'm') AND {} AND {}{} ORDER BY 1, 2",
+            USER_SCHEMAS,
+            NOT_EXTENSION_MEMBER,
+            schema_filter(schema)
+        );
+
+        let rows = self
+            .catalog_rows(project_id, branch_id, database, &query)
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| ViewInfo {
+                schema: str_field(row, "schema").to_string(),
+                name: str_field(row, "name").to_string(),
+                materialized: row
+                    .get("materialized")
+                    .and_then(value_as_bool)
+                    .unwrap_or(false),
+                owner: str_field(row, "owner").to_string(),
+                populated: row.get("populated").and_then(value_as_bool),
+                definition: str_field(row, "definition").trim().to_string(),
+            })
+            .collect())
+    }
+
+    /// Functions, procedures, and aggregates in user schemas, or in
+    /// `schema` only. Those installed by extensions are left out.
+    pub async fn list_functions(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        schema: Option<&str>,
+    ) -> Result<Vec<FunctionInfo>> {
+        let query = format!(
+            "SELECT n.nspname AS schema, p.proname AS name, p.prokind AS kind, pg_get_function_identity_arguments(p.oid) AS arguments, CASE WHEN p.prokind <> 'p' THEN pg_get_function_result(p.oid) END AS returns, l.lanname AS language, p.provolatile AS volatility, p.prosecdef AS security_definer, pg_get_userbyid(p.proowner) AS owner FROM pg_catalog.pg_proc p JOIN pg_catalog.pg_namespace n ON n.oid = p.pronamespace JOIN pg_catalog.pg_language l ON l.oid = p.prolang WHERE {} AND NOT EXISTS (SELECT 1 FROM pg_catalog.pg_depend d WHERE d.classid = 'pg_proc'::regclass AND d.objid = p.oid AND d.deptype = 'e'){} ORDER BY 1, 2, 4",
+            USER_SCHEMAS,
+            schema_filter(schema)
+        );
+
+        let rows = self
+            .catalog_rows(project_id, branch_id, database, &query)
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| FunctionInfo {
+                schema: str_field(row, "schema").to_string(),
+                name: str_field(row, "name").to_string(),
+                kind: match str_field(row, "kind") {
+                    "p" => "procedure",
+                    "a" => "aggregate",
+                    "w" => "window",
+                    _ => "function",
+                }
+                .to_string(),
+                arguments: str_field(row, "arguments").to_string(),
+                returns: row
+                    .get("returns")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string()),
+                language: str_field(row, "language").to_string(),
+                volatility: match str_field(row, "volatility") {
+                    "i" => "immutable",
+                    "s" => "stable",
+                    _ => "volatile",
+                }
+                .to_string(),
+                security_definer: row
+                    .get("security_definer")
+                    .and_then(value_as_bool)
+                    .unwrap_or(false),
+                owner: str_field(row, "owner").to_string(),
+            })
+            .collect())
+    }
+
+    /// Installed extensions, with the version an update would move to.
+    pub async fn list_extensions(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+    ) -> Result<Vec<ExtensionInfo>> {
+        let query = "SELECT e.extname AS name, e.extversion AS version, n.nspname AS schema, a.default_version, a.comment AS description FROM pg_catalog.pg_extension e JOIN pg_catalog.pg_namespace n ON n.oid = e.extnamespace LEFT JOIN pg_catalog.pg_available_extensions a ON a.name = e.extname ORDER BY 1";
+
+        let rows = self
+            .catalog_rows(project_id, branch_id, database, query)
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let version = str_field(row, "version").to_string();
+                let default_version = row
+                    .get("default_version")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string());
+                ExtensionInfo {
+                    name: str_field(row, "name").to_string(),
+                    update_available: default_version.as_ref().is_some_and(|d| *d != version),
+                    version,
+                    schema: str_field(row, "schema").to_string(),
+                    default_version,
+                    description: row
+                        .get("description")
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string()),
+                }
+            })
+            .collect())
+    }
+
+    /// The database's size and its `limit` largest tables by `sort`: `total`,
+    /// `table`, `index`, or `toast` bytes.
+    pub async fn get_sizes(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        sort: &str,
+        limit: usize,
+    ) -> Result<DatabaseSizes> {
+        let order = match sort {
+            "total" => "total_bytes",
+            "table" => "table_bytes",
+            "index" => "index_bytes",
+            "toast" => "toast_bytes",
+            _ => anyhow::bail!("sort must be one of: total, table, index, toast"),
+        };
+
+        let summary = format!(
+            "SELECT pg_database_size(current_database()) AS size_bytes, pg_size_pretty(pg_database_size(current_database())) AS size, (SELECT count(*) FROM pg_catalog.pg_class c JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace WHERE c.relkind IN ('r', 'm') AND {}) AS table_count",
+            USER_SCHEMAS
+        );
+        let rows = self
+            .catalog_rows(project_id, branch_id, database, &summary)
+            .await?;
+        let row = rows.first().cloned().unwrap_or_default();
+
+        let tables = format!(
+            "SELECT schema, name, total_bytes, total_bytes - index_bytes - toast_bytes AS table_bytes, index_bytes, toast_bytes, pg_size_pretty(total_bytes) AS total, rows_estimate FROM (SELECT n.nspname AS schema, c.relname AS name, pg_total_relation_size(c.oid) AS total_bytes, pg_indexes_size(c.oid) AS index_bytes, COALESCE(pg_total_relation_size(NULLIF(c.reltoastrelid, 0)), 0) AS toast_bytes, c.reltuples::bigint AS rows_estimate FROM pg_catalog.pg_class c JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace WHERE c.relkind IN ('r', 'm') AND {}) s ORDER BY {} DESC, schema, name LIMIT {}",
+            USER_SCHEMAS, order, limit
+        );
+        let tables = self
+            .catalog_rows(project_id, branch_id, database, &tables)
+            .await?
+            .iter()
+            .map(|row| TableSize {
+                schema: str_field(row, "schema").to_string(),
+                name: str_field(row, "name").to_string(),
+                total_bytes: u64_field(row, "total_bytes"),
+                table_bytes: u64_field(row, "table_bytes"),
+                index_bytes: u64_field(row, "index_bytes"),
+                toast_bytes: u64_field(row, "toast_bytes"),
+                total: str_field(row, "total").to_string(),
+                rows_estimate: scalar_field(row, "rows_estimate").parse().unwrap_or(-1),
+            })
+            .collect();
+
+        Ok(DatabaseSizes {
+            database: database.to_string(),
+            size_bytes: u64_field(&row, "size_bytes"),
+            size: str_field(&row, "size").to_string(),
+            table_count: u64_field(&row, "table_count"),
+            tables,
+        })
+    }
+
+    /// Run a catalog query, failing rather than returning a partial result.
+    pub(super) async fn catalog_rows(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        query: &str,
+    ) -> Result<Vec<Value>> {
+        let result = self.run_sql(project_id, branch_id, database, query).await?;
+
+        if result.get("truncated").and_then(|v| v.as_bool()) == Some(true) {
+            anyhow::bail!(
+                "Schema has more objects than the configured max_rows; raise limits.max_rows to snapshot it"
+            );
+        }
+
+        Ok(result_rows(&result).to_vec())
+    }
+}
+
+/// Column definition inside `CREATE TABLE`.
+fn column_ddl(row: &Value) -> String {
+    let mut def = format!(
+        "{} {}",
+        quote_ident(str_field(row, "column")),
+        str_field(row, "data_type")
+    );
+    let default = row.get("default").and_then(|v| v.as_str());
+
+    match (str_field(row, "generated"), str_field(row, "identity")) {
+        ("s", _) => def.push_str(&format!(
+            " GENERATED ALWAYS AS ({}) STORED",
+            default.unwrap_or("NULL")
+        )),
+        (_, "a") => def.push_str(" GENERATED ALWAYS AS IDENTITY"),
+        (_, "d") => def.push_str(" GENERATED BY DEFAULT AS IDENTITY"),
+        _ => {
+            if let Some(default) = default {
+                def.push_str(&format!(" DEFAULT {}", default));
+            }
+        }
+    }
+
+    if row.get("not_null").and_then(value_as_bool) == Some(true) {
+        def.push_str(" NOT NULL");
+    }
+    def
+}
+
+/// Restricts a listing to one schema; `n` is the `pg_namespace` alias.
+fn schema_filter(schema: Option<&str>) -> String {
+    match schema {
+        Some(schema) => format!(" AND n.nspname = {}", quote_literal(schema)),
+        None => String::new(),
+    }
+}
+
+/// Key columns in key order, as a JSON array.
+fn key_columns(rel: &str, keys: &str) -> String {
+    format!(
+        "to_json(ARRAY(SELECT a.attname FROM unnest({keys}) WITH ORDINALITY AS k(attnum, ord) JOIN pg_catalog.pg_attribute a ON a.attrelid = {rel} AND a.attnum = k.attnum ORDER BY k.ord))"
+    )
+}
+
+/// Foreign keys, to be narrowed with `AND ...`; `c` and `n` are the
+/// referencing table and its schema.
+fn foreign_key_select() -> String {
+    format!(
+        "SELECT con.conname AS name, format('%I.%I', n.nspname, c.relname) AS table, {} AS columns, format('%I.%I', fn.nspname, fc.relname) AS referenced_table, {} AS referenced_columns, con.confupdtype AS on_update, con.confdeltype AS on_delete, pg_get_constraintdef(con.oid) AS definition FROM pg_catalog.pg_constraint con JOIN pg_catalog.pg_class c ON c.oid = con.conrelid JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace JOIN pg_catalog.pg_class fc ON fc.oid = con.confrelid JOIN pg_catalog.pg_namespace fn ON fn.oid = fc.relnamespace WHERE con.contype = 'f'",
+        key_columns("con.conrelid", "con.conkey"),
+        key_columns("con.confrelid", "con.confkey")
+    )
+}
+
+fn table_column(row: &Value) -> TableColumn {
+    TableColumn {
+        name: str_field(row, "name").to_string(),
+        data_type: str_field(row, "data_type").to_string(),
+        nullable: row.get("nullable").and_then(value_as_bool).unwrap_or(true),
+        default: row
+            .get("default")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+    }
+}
+
+fn constraint(row: &Value) -> ConstraintInfo {
+    let kind = match str_field(row, "kind") {
+        "p" => "primary_key",
+        "u" => "unique",
+        "c" => "check",
+        _ => "exclusion",
+    };
+    ConstraintInfo {
+        name: str_field(row, "name").to_string(),
+        kind: kind.to_string(),
+        columns: list_field(row, "columns"),
+        definition: str_field(row, "definition").to_string(),
+    }
+}
+
+/// File a constraint as the table's primary key or among its others.
+fn add_constraint(detail: &mut TableDetail, constraint: ConstraintInfo) {
+    if constraint.kind == "primary_key" {
+        detail.primary_key = Some(constraint);
+    } else {
+        detail.constraints.push(constraint);
+    }
+}
+
+fn index(row: &Value) -> IndexInfo {
+    IndexInfo {
+        name: str_field(row, "name").to_string(),
+        columns: list_field(row, "columns"),
+        unique: row.get("unique").and_then(value_as_bool).unwrap_or(false),
+        primary: row.get("primary").and_then(value_as_bool).unwrap_or(false),
+        method: str_field(row, "method").to_string(),
+        predicate: row
+            .get("predicate")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        definition: str_field(row, "definition").to_string(),
+    }
+}
+
+/// A foreign key row from `foreign_key_select`.
+fn foreign_key(row: &Value) -> ForeignKeyInfo {
+    let action = |key: &str| {
+        match str_field(row, key) {
+            "r" => "restrict",
+            "c" => "cascade",
+            "n" => "set null",
+            "d" => "set default",
+            _ => "no action",
+        }
+        .to_string()
+    };
+    ForeignKeyInfo {
+        name: str_field(row, "name").to_string(),
+        table: str_field(row, "table").to_string(),
+        columns: list_field(row, "columns"),
+        referenced_table: str_field(row, "referenced_table").to_string(),
+        referenced_columns: list_field(row, "referenced_columns"),
+        on_update: action("on_update"),
+        on_delete: action("on_delete"),
+        definition: str_field(row, "definition").to_string(),
+    }
+}
+
+/// A JSON array column, which may arrive parsed or as JSON text.
+fn list_field(row: &Value, key: &str) -> Vec<String> {
+    let items = match row.get(key) {
+        Some(Value::Array(items)) => items.clone(),
+        Some(Value::String(s)) => serde_json::from_str(s).unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    items
+        .into_iter()
+        .filter_map(|v| v.as_str().map(|s| s.to_string()))
+        .collect()
+}
+
+fn str_field<'a>(row: &'a Value, key: &str) -> &'a str {
+    row.get(key).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+/// Numbers may arrive as JSON numbers or strings (bigint).
+fn scalar_field(row: &Value, key: &str) -> String {
+    match row.get(key) {
+        Some(Value::String(s)) => s.clone(),
+        Some(v) => v.to_string(),
+        None => String::new(),
+    }
+}
+
+fn u64_field(row: &Value, key: &str) -> u64 {
+    scalar_field(row, key).parse().unwrap_or(0)
+}
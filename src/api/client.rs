@@ -1,103 +1,293 @@
 //! Neon HTTP API client with connection pooling.
 
 use anyhow::{Context, Result};
-use reqwest::Client;
-use serde::Deserialize;
+use reqwest::{Client, Method, Response, StatusCode};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
-use crate::models::{Branch, Database, Project};
+use super::normalize;
+use super::passwords::RolePasswords;
+use super::postgres::{self, ConnectTarget, PgPool, PoolHealth, PoolKey};
+use super::retry::{self, ApiError, RateLimited};
+use super::routes::EndpointRoutes;
+use crate::config::{Config, SqlBackend};
+use crate::credentials::{AuthStatus, AuthTracker, Credentials};
+use crate::models::{
+    ApiKey, AuthIntegration, Branch, CreateProjectRequest, CreatedProject, DataApi, Database,
+    Endpoint, Jwks, Operation, Project, ProjectConsumption, RestoreBranchRequest, RestoredBranch,
+    Role, UpdateProjectRequest,
+};
+use crate::sqltext;
+use crate::stats::ColdStartStats;
+use crate::trace;
 
 const API_BASE: &str = "https://console.neon.tech/api/v2";
 
+/// Largest `limit` the projects listing accepts.
+const MAX_PAGE_SIZE: i32 = 400;
+
+/// Identity provider behind Neon Auth.
+const AUTH_PROVIDER: &str = "stack";
+
+/// Filtering and paging options for table listings.
+#[derive(Debug, Clone, Default)]
+pub struct TablePage {
+    /// Only include tables whose name starts with this prefix.
+    pub prefix: Option<String>,
+    /// Resume after the table a `next_cursor` names (keyset paging).
+    pub cursor: Option<String>,
+    /// Rows to skip when no cursor is given.
+    pub offset: usize,
+    /// Maximum tables to return; capped below `[limits] max_rows`.
+    pub limit: Option<usize>,
+}
+
+/// Row window for a SQL call.
+#[derive(Debug, Clone, Copy)]
+pub struct SqlPage<'a> {
+    /// Maximum rows to return.
+    pub max_rows: usize,
+    /// Rows to skip.
+    pub offset: usize,
+    /// Execution backend; the configured one when `None`.
+    pub backend: Option<SqlBackend>,
+    /// Which of the branch's computes runs the statement.
+    pub target: SqlTarget,
+    /// Role to connect as; the configured one when `None`.
+    pub role: Option<&'a str>,
+    /// Run in a read-only transaction, so Postgres refuses any write.
+    pub read_only: bool,
+}
+
+/// Compute a SQL call is routed to on a branch with read replicas.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SqlTarget {
+    /// A read-only endpoint; fails when the branch has none.
+    Read,
+    /// The read-write endpoint.
+    Write,
+    /// Read-only statements to a read-only endpoint when there is one,
+    /// everything else to the read-write endpoint.
+    #[default]
+    Auto,
+}
+
+/// One statement of a transaction.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TxStatement {
+    pub query: String,
+    #[serde(default)]
+    pub params: Vec<Value>,
+}
+
+/// A statement as sent to a compute.
+#[derive(Debug, Clone, Copy)]
+struct Statement<'a> {
+    query: &'a str,
+    params: &'a [Value],
+    /// Run in a read-only transaction.
+    read_only: bool,
+}
+
+/// Transaction isolation level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IsolationLevel {
+    #[default]
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevel {
+    /// Value of the SQL endpoint's `Neon-Batch-Isolation-Level` header.
+    fn header(self) -> &'static str {
+        match self {
+            Self::ReadCommitted => "ReadCommitted",
+            Self::RepeatableRead => "RepeatableRead",
+            Self::Serializable => "Serializable",
+        }
+    }
+
+    /// As written in `BEGIN ISOLATION LEVEL ...`.
+    pub(super) fn sql(self) -> &'static str {
+        match self {
+            Self::ReadCommitted => "READ COMMITTED",
+            Self::RepeatableRead => "REPEATABLE READ",
+            Self::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+/// How a transaction runs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TxOptions {
+    pub isolation: IsolationLevel,
+    pub read_only: bool,
+    /// Execution backend; the configured one when `None`.
+    pub backend: Option<SqlBackend>,
+}
+
+/// Planner estimates for a statement's top plan node.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PlanEstimate {
+    pub total_cost: f64,
+    pub plan_rows: f64,
+}
+
 /// Neon HTTP API client with persistent connection.
 pub struct NeonClient {
     client: Client,
     api_key: String,
+    auth: AuthTracker,
     org_id: String,
+    config: Config,
+    cold_starts: ColdStartStats,
+    pg: PgPool,
+    /// Round-robin position across read replicas.
+    next_replica: AtomicUsize,
+    routes: EndpointRoutes,
+    passwords: RolePasswords,
 }
 
 impl NeonClient {
-    /// Create a new Neon client with API key and org_id.
-    pub fn new(api_key: String, org_id: String) -> Result<Self> {
+    /// Create a new Neon client with credentials, org_id, and daemon config.
+    pub fn new(credentials: &Credentials, org_id: String, config: &Config) -> Result<Self> {
         let client = Client::builder()
             .pool_max_idle_per_host(5)
-            .timeout(std::time::Duration::from_secs(30))
+            .timeout(Duration::from_secs(config.retry.request_timeout_secs))
             .build()
             .context("Failed to build HTTP client")?;
 
         Ok(Self {
             client,
-            api_key,
+            api_key: credentials.token.clone(),
+            auth: AuthTracker::new(credentials),
             org_id,
+            config: config.clone(),
+            cold_starts: ColdStartStats::default(),
+            pg: PgPool::new(&config.sql)?,
+            next_replica: AtomicUsize::new(0),
+            routes: EndpointRoutes::new(Duration::from_secs(config.sql.endpoint_cache_secs)),
+            passwords: RolePasswords::new(Duration::from_secs(config.sql.password_cache_secs)),
         })
     }
 
-    /// Make an authenticated GET request.
-    async fn get<T: for<'de> Deserialize<'de>>(&self, endpoint: &str) -> Result<T> {
-        let url = format!("{}{}", API_BASE, endpoint);
+    /// Maximum rows a single SQL call may return.
+    pub fn max_rows(&self) -> usize {
+        self.config.limits.max_rows
+    }
 
-        let response = self
-            .client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Accept", "application/json")
-            .send()
-            .await
-            .context("Failed to send request")?;
+    /// Cold-start counters for endpoints this client has queried.
+    pub fn cold_starts(&self) -> &ColdStartStats {
+        &self.cold_starts
+    }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            anyhow::bail!("API request failed: {} - {}", status, text);
-        }
+    /// Credential expiry and whether the API last accepted them.
+    pub fn auth_status(&self) -> AuthStatus {
+        self.auth.status()
+    }
+
+    /// State of the Postgres connection pool.
+    pub fn pool_health(&self) -> PoolHealth {
+        self.pg.health()
+    }
 
+    /// Make an authenticated GET request.
+    pub(super) async fn get<T: for<'de> Deserialize<'de>>(&self, endpoint: &str) -> Result<T> {
+        let response = self.send(Method::GET, endpoint, None).await?;
         response.json().await.context("Failed to parse response")
     }
 
     /// Make an authenticated POST request.
     async fn post<T: for<'de> Deserialize<'de>>(&self, endpoint: &str, body: &Value) -> Result<T> {
-        let url = format!("{}{}", API_BASE, endpoint);
-
-        let response = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Accept", "application/json")
-            .header("Content-Type", "application/json")
-            .json(body)
-            .send()
-            .await
-            .context("Failed to send request")?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            anyhow::bail!("API request failed: {} - {}", status, text);
-        }
+        let response = self.send(Method::POST, endpoint, Some(body)).await?;
+        response.json().await.context("Failed to parse response")
+    }
 
+    /// Make an authenticated PATCH request.
+    async fn patch<T: for<'de> Deserialize<'de>>(&self, endpoint: &str, body: &Value) -> Result<T> {
+        let response = self.send(Method::PATCH, endpoint, Some(body)).await?;
         response.json().await.context("Failed to parse response")
     }
 
     /// Make an authenticated DELETE request.
     async fn delete(&self, endpoint: &str) -> Result<()> {
+        self.send(Method::DELETE, endpoint, None).await?;
+        Ok(())
+    }
+
+    /// Send an authenticated request, retrying rate limits and transient
+    /// failures per `[retry]`. Fails with [`ApiError`] on any other
+    /// non-success status, and with [`RateLimited`] when the API is still
+    /// rate limiting at the end.
+    async fn send(&self, method: Method, endpoint: &str, body: Option<&Value>) -> Result<Response> {
         let url = format!("{}{}", API_BASE, endpoint);
+        let policy = &self.config.retry;
+        let max_wait = Duration::from_millis(policy.max_backoff_ms);
+        let mut attempt = 1;
 
-        let response = self
-            .client
-            .delete(&url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Accept", "application/json")
-            .send()
-            .await
-            .context("Failed to send request")?;
+        loop {
+            let mut request = self
+                .client
+                .request(method.clone(), &url)
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .headers(trace::headers())
+                .header("Accept", "application/json");
+            if let Some(body) = body {
+                request = request.json(body);
+            }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            anyhow::bail!("API request failed: {} - {}", status, text);
-        }
+            let wait = match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    self.auth.accepted();
+                    return Ok(response);
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    if status == StatusCode::UNAUTHORIZED {
+                        self.auth.rejected();
+                    }
+                    let asked = retry::retry_after(&response);
+                    let give_up = attempt >= policy.max_attempts
+                        || !retry::retryable_status(status, &method)
+                        || asked.is_some_and(|w| w > max_wait);
+                    if give_up {
+                        if status == StatusCode::TOO_MANY_REQUESTS {
+                            return Err(RateLimited {
+                                attempts: attempt,
+                                retry_after: asked,
+                            }
+                            .into());
+                        }
+                        let body = response.text().await.unwrap_or_default();
+                        return Err(ApiError { status, body }.into());
+                    }
+                    asked.unwrap_or_else(|| retry::backoff(policy, attempt))
+                }
+                Err(e) => {
+                    if attempt >= policy.max_attempts || !retry::retryable_error(&e, &method) {
+                        return Err(anyhow::Error::new(e).context("Failed to send request"));
+                    }
+                    retry::backoff(policy, attempt)
+                }
+            };
 
-        Ok(())
+            tracing::debug!(
+                "Retrying {} {} in {}ms (attempt {} of {})",
+                method,
+                endpoint,
+                wait.as_millis(),
+                attempt + 1,
+                policy.max_attempts
+            );
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
     }
 
     /// Check if the client can connect to Neon API.
@@ -109,26 +299,73 @@ impl NeonClient {
             .client
             .get(&url)
             .header("Authorization", format!("Bearer {}", self.api_key))
+            .headers(trace::headers())
             .header("Accept", "application/json")
             .send()
             .await
             .context("Failed to ping Neon API")?;
 
+        match response.status() {
+            status if status.is_success() => self.auth.accepted(),
+            StatusCode::UNAUTHORIZED => self.auth.rejected(),
+            _ => {}
+        }
         Ok(response.status().is_success())
     }
 
-    /// List all projects.
+    /// List the first `limit` projects (default 10).
     pub async fn list_projects(&self, limit: Option<i32>) -> Result<Vec<Project>> {
-        let limit = limit.unwrap_or(10);
-        let endpoint = format!("/projects?org_id={}&limit={}", self.org_id, limit);
+        let (projects, _) = self.list_projects_page(limit.unwrap_or(10), None).await?;
+        Ok(projects)
+    }
+
+    /// List up to `limit` projects after `cursor`. Returns the cursor for the
+    /// next page when this one was full.
+    pub async fn list_projects_page(
+        &self,
+        limit: i32,
+        cursor: Option<&str>,
+    ) -> Result<(Vec<Project>, Option<String>)> {
+        let mut endpoint = format!("/projects?org_id={}&limit={}", self.org_id, limit);
+        if let Some(c) = cursor {
+            endpoint.push_str(&format!("&cursor={}", c));
+        }
 
         #[derive(Deserialize)]
         struct ProjectsResponse {
             projects: Vec<Project>,
+            #[serde(default)]
+            pagination: Option<Pagination>,
+        }
+
+        #[derive(Deserialize)]
+        struct Pagination {
+            cursor: Option<String>,
         }
 
         let response: ProjectsResponse = self.get(&endpoint).await?;
-        Ok(response.projects)
+        let next = if response.projects.len() < limit.max(1) as usize {
+            None
+        } else {
+            response.pagination.and_then(|p| p.cursor)
+        };
+        Ok((response.projects, next))
+    }
+
+    /// List every project in the org, following cursors.
+    pub async fn list_all_projects(&self) -> Result<Vec<Project>> {
+        let mut projects = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let (page, next) = self
+                .list_projects_page(MAX_PAGE_SIZE, cursor.as_deref())
+                .await?;
+            projects.extend(page);
+            match next {
+                Some(c) => cursor = Some(c),
+                None => return Ok(projects),
+            }
+        }
     }
 
     /// Get a specific project.
@@ -151,10 +388,51 @@ impl NeonClient {
         #[derive(Deserialize)]
         struct BranchesResponse {
             branches: Vec<Branch>,
+            #[serde(default)]
+            annotations: HashMap<String, BranchAnnotation>,
         }
 
         let response: BranchesResponse = self.get(&endpoint).await?;
-        Ok(response.branches)
+        let mut branches = response.branches;
+        annotate(&mut branches, response.annotations);
+        Ok(branches)
+    }
+
+    /// List every branch of a project a page at a time, following cursors.
+    /// A branch created or renamed mid-listing can appear on two pages.
+    pub async fn list_all_branches(&self, project_id: &str) -> Result<Vec<Branch>> {
+        #[derive(Deserialize)]
+        struct BranchesResponse {
+            branches: Vec<Branch>,
+            #[serde(default)]
+            annotations: HashMap<String, BranchAnnotation>,
+            #[serde(default)]
+            pagination: Option<Pagination>,
+        }
+
+        #[derive(Deserialize)]
+        struct Pagination {
+            next: Option<String>,
+        }
+
+        let mut branches = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut endpoint = format!("/projects/{}/branches?limit={}", project_id, MAX_PAGE_SIZE);
+            if let Some(c) = &cursor {
+                endpoint.push_str(&format!("&cursor={}", c));
+            }
+
+            let response: BranchesResponse = self.get(&endpoint).await?;
+            let full = response.branches.len() >= MAX_PAGE_SIZE as usize;
+            let mut page = response.branches;
+            annotate(&mut page, response.annotations);
+            branches.extend(page);
+            match response.pagination.and_then(|p| p.next) {
+                Some(next) if full => cursor = Some(next),
+                _ => return Ok(branches),
+            }
+        }
     }
 
     /// List databases for a project branch.
@@ -170,16 +448,81 @@ impl NeonClient {
         Ok(response.databases)
     }
 
-    /// Get database tables.
+    /// Get database tables, optionally filtered by name prefix and paged.
+    ///
+    /// Paging is keyset-based when `page.cursor` is set (the `schema.name` of the
+    /// last table seen) and offset-based otherwise. When `page.limit` is set and
+    /// more tables remain, the result includes a `next_cursor`.
     pub async fn get_tables(
         &self,
         project_id: &str,
         branch_id: &str,
         database: &str,
+        page: &TablePage,
     ) -> Result<Value> {
         // Use the SQL endpoint to query tables
-        let query = "SELECT schemaname as schema, tablename as name FROM pg_catalog.pg_tables WHERE schemaname NOT IN ('pg_catalog', 'information_schema') ORDER BY schemaname, tablename";
-        self.run_sql(project_id, branch_id, database, query).await
+        let mut query = String::from(
+            "SELECT schemaname as schema, tablename as name FROM pg_catalog.pg_tables WHERE schemaname NOT IN ('pg_catalog', 'information_schema')",
+        );
+
+        if let Some(prefix) = &page.prefix {
+            query.push_str(&format!(
+                " AND tablename LIKE {}",
+                quote_literal(&format!("{}%", escape_like(prefix)))
+            ));
+        }
+
+        if let Some(cursor) = &page.cursor {
+            // Schema and table names may themselves contain dots
+            let (schema, name): (String, String) = serde_json::from_str(cursor).map_err(|_| {
+                anyhow::anyhow!(
+                    "Invalid cursor {:?}; pass a next_cursor from neon.tables",
+                    cursor
+                )
+            })?;
+            query.push_str(&format!(
+                " AND (schemaname, tablename) > ({}, {})",
+                quote_literal(&schema),
+                quote_literal(&name)
+            ));
+        }
+
+        query.push_str(" ORDER BY schemaname, tablename");
+
+        // The extra row that shows another page follows must fit under the
+        // row limit, or run_sql drops it first
+        let limit = page
+            .limit
+            .map(|limit| limit.min(self.max_rows().saturating_sub(1).max(1)));
+        if let Some(limit) = limit {
+            query.push_str(&format!(" LIMIT {}", limit + 1));
+        }
+        if page.cursor.is_none() && page.offset > 0 {
+            query.push_str(&format!(" OFFSET {}", page.offset));
+        }
+
+        let mut result = self
+            .run_sql(project_id, branch_id, database, &query)
+            .await?;
+
+        if let Some(limit) = limit {
+            let next_cursor = result
+                .get_mut("rows")
+                .and_then(|r| r.as_array_mut())
+                .filter(|rows| rows.len() > limit)
+                .and_then(|rows| {
+                    rows.truncate(limit);
+                    let last = rows.last()?;
+                    let key = (last.get("schema")?.as_str()?, last.get("name")?.as_str()?);
+                    serde_json::to_string(&key).ok()
+                });
+
+            if let Some(obj) = result.as_object_mut() {
+                obj.insert("next_cursor".into(), serde_json::json!(next_cursor));
+            }
+        }
+
+        Ok(result)
     }
 
     /// Get table schema.
@@ -197,7 +540,27 @@ impl NeonClient {
         self.run_sql(project_id, branch_id, database, &query).await
     }
 
-    /// Run a SQL query via the Neon SQL endpoint.
+    /// Get per-column planner statistics for a table from `pg_stats`.
+    ///
+    /// `min`/`max` come from the histogram bounds, so they are estimates and
+    /// are null for columns whose values are all covered by the MCV list.
+    pub async fn get_column_stats(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        schema: &str,
+        table: &str,
+    ) -> Result<Value> {
+        let query = format!(
+            "SELECT attname AS column, null_frac, n_distinct, most_common_vals::text::text[] AS most_common_vals, most_common_freqs, (histogram_bounds::text::text[])[1] AS min, (histogram_bounds::text::text[])[cardinality(histogram_bounds::text::text[])] AS max FROM pg_catalog.pg_stats WHERE schemaname = {} AND tablename = {} ORDER BY attname",
+            quote_literal(schema),
+            quote_literal(table)
+        );
+        self.run_sql(project_id, branch_id, database, &query).await
+    }
+
+    /// Run a SQL query via the Neon SQL endpoint, truncated to the configured row limit.
     pub async fn run_sql(
         &self,
         project_id: &str,
@@ -205,62 +568,232 @@ impl NeonClient {
         database: &str,
         query: &str,
     ) -> Result<Value> {
-        // First, get the connection string / endpoint for this branch
-        let endpoints_url = format!("{}/projects/{}/endpoints", API_BASE, project_id);
+        let page = SqlPage {
+            max_rows: self.config.limits.max_rows,
+            offset: 0,
+            backend: None,
+            target: SqlTarget::Auto,
+            role: None,
+            read_only: false,
+        };
+        self.run_sql_page(project_id, branch_id, database, query, &[], page)
+            .await
+    }
+
+    /// Run a SQL query with positional parameters (`$1`, `$2`, ...), returning
+    /// at most `page.max_rows` rows starting at `page.offset`.
+    ///
+    /// Plain SELECTs are wrapped in `LIMIT`/`OFFSET` so Neon never sends more
+    /// than one extra row; other statements are truncated after the fact. A
+    /// response body over `max_response_bytes` is cut to the rows that fit
+    /// either way, with `truncated` and a `continuation` hint set.
+    pub async fn run_sql_page(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        query: &str,
+        params: &[Value],
+        page: SqlPage<'_>,
+    ) -> Result<Value> {
+        let SqlPage {
+            max_rows,
+            offset,
+            backend,
+            target,
+            role,
+            read_only,
+        } = page;
+        let role = role.unwrap_or_else(|| self.config.default_role(project_id, database));
 
-        #[derive(Deserialize)]
-        struct EndpointsResponse {
-            endpoints: Vec<Endpoint>,
+        let endpoints = self.routing_endpoints(project_id).await?;
+        let endpoint = self.route_endpoint(&endpoints, branch_id, query, target)?;
+
+        let paged = is_pageable(query);
+        let statement = if paged {
+            format!(
+                "SELECT * FROM ({}) AS fgp_page LIMIT {} OFFSET {}",
+                strip_trailing_semicolon(query),
+                max_rows + 1,
+                offset
+            )
+        } else {
+            query.to_string()
+        };
+
+        let statement = Statement {
+            query: &statement,
+            params,
+            read_only,
+        };
+        let backend = backend.unwrap_or(self.config.sql.backend);
+        let result = match backend {
+            SqlBackend::Http => {
+                self.run_sql_http(project_id, endpoint, database, role, statement)
+                    .await
+            }
+            SqlBackend::Postgres => self
+                .run_sql_postgres(project_id, endpoint, database, role, statement)
+                .await
+                .map(|result| (result, false)),
+        };
+        // The endpoint may have moved or gone, or the password been reset;
+        // look both up again next time
+        let (result, cut) = result.inspect_err(|_| self.invalidate(project_id))?;
+        self.routes.set_state(project_id, &endpoint.id, "active");
+
+        let typed = normalize::query_result(result, backend == SqlBackend::Postgres)?;
+        let mut result = serde_json::to_value(typed)?;
+        let cut_at = cut.then_some(self.config.limits.max_response_bytes);
+        truncate_rows(&mut result, max_rows, offset, paged, cut_at);
+        if let Some(obj) = result.as_object_mut() {
+            obj.insert("endpoint_id".into(), Value::String(endpoint.id.clone()));
         }
+        Ok(result)
+    }
 
-        #[derive(Deserialize)]
-        #[allow(dead_code)]
-        struct Endpoint {
-            id: String,
-            host: String,
-            branch_id: String,
+    /// A project's endpoints for routing SQL and building connection strings,
+    /// from the route cache when fresh.
+    async fn routing_endpoints(&self, project_id: &str) -> Result<Vec<Endpoint>> {
+        if let Some(endpoints) = self.routes.get(project_id) {
+            return Ok(endpoints);
         }
+        let endpoints = self.list_endpoints(project_id).await?;
+        self.routes.put(project_id, endpoints.clone());
+        Ok(endpoints)
+    }
 
-        let endpoints: EndpointsResponse = self
-            .client
-            .get(&endpoints_url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Accept", "application/json")
-            .send()
-            .await?
-            .json()
-            .await?;
+    /// Forget a project's cached endpoints and role passwords after a change
+    /// made elsewhere.
+    pub fn invalidate_routes(&self, project_id: &str) {
+        self.invalidate(project_id);
+    }
+
+    /// Forget a project's cached endpoints and role passwords.
+    fn invalidate(&self, project_id: &str) {
+        self.routes.invalidate(project_id);
+        self.passwords.invalidate(project_id);
+    }
+
+    /// The read-write endpoint of a branch, as SQL routing sees it.
+    pub async fn write_endpoint(&self, project_id: &str, branch_id: &str) -> Result<Endpoint> {
+        let endpoints = self.routing_endpoints(project_id).await?;
+        self.route_endpoint(&endpoints, branch_id, "", SqlTarget::Write)
+            .cloned()
+    }
+
+    /// Run `SELECT 1` on `endpoint`'s host over the SQL endpoint, whatever
+    /// the configured backend, to check that queries get through.
+    pub async fn probe_sql(
+        &self,
+        project_id: &str,
+        endpoint: &Endpoint,
+        database: &str,
+    ) -> Result<()> {
+        let role = self.config.default_role(project_id, database);
+        let statement = Statement {
+            query: "SELECT 1",
+            params: &[],
+            read_only: false,
+        };
+        self.run_sql_http(project_id, endpoint, database, role, statement)
+            .await
+            .map(|_| ())
+    }
 
-        // Find the endpoint for this branch
-        let endpoint = endpoints
-            .endpoints
+    /// The branch endpoint `query` should run on for `target`. Replicas are
+    /// taken in turn so analytical load spreads across them.
+    fn route_endpoint<'a>(
+        &self,
+        endpoints: &'a [Endpoint],
+        branch_id: &str,
+        query: &str,
+        target: SqlTarget,
+    ) -> Result<&'a Endpoint> {
+        let on_branch: Vec<&Endpoint> = endpoints
             .iter()
-            .find(|e| e.branch_id == branch_id)
-            .ok_or_else(|| anyhow::anyhow!("No endpoint found for branch {}", branch_id))?;
+            .filter(|e| e.branch_id == branch_id)
+            .collect();
+        let primary = on_branch
+            .iter()
+            .find(|e| e.endpoint_type == "read_write")
+            .copied();
+        let replicas: Vec<&Endpoint> = on_branch
+            .iter()
+            .filter(|e| e.endpoint_type == "read_only")
+            .copied()
+            .collect();
+        let replica = || {
+            (!replicas.is_empty()).then(|| {
+                let turn = self.next_replica.fetch_add(1, Ordering::Relaxed);
+                replicas[turn % replicas.len()]
+            })
+        };
+
+        match target {
+            SqlTarget::Read => replica().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Branch {} has no read-only endpoint; use target \"write\" or \"auto\"",
+                    branch_id
+                )
+            }),
+            SqlTarget::Write => primary
+                .ok_or_else(|| anyhow::anyhow!("Branch {} has no read-write endpoint", branch_id)),
+            SqlTarget::Auto => {
+                let routed = if is_read_only(query) {
+                    replica().or(primary)
+                } else {
+                    primary
+                };
+                routed
+                    .or_else(|| on_branch.first().copied())
+                    .ok_or_else(|| anyhow::anyhow!("No endpoint found for branch {}", branch_id))
+            }
+        }
+    }
+
+    /// Execute one statement via the serverless driver endpoint. A response
+    /// over `max_response_bytes` is cut to the rows that fit, and flagged.
+    async fn run_sql_http(
+        &self,
+        project_id: &str,
+        endpoint: &Endpoint,
+        database: &str,
+        role: &str,
+        statement: Statement<'_>,
+    ) -> Result<(Value, bool)> {
+        #[derive(Deserialize)]
+        struct BatchResponse {
+            results: Vec<Value>,
+        }
+        // How a batch response starts, before its one result
+        const BATCH_PREFIX: &[u8] = b"{\"results\":[";
 
-        // Execute SQL via the serverless driver endpoint
         // Neon's SQL API: POST https://{host}/sql
         let sql_url = format!("https://{}/sql", endpoint.host);
 
-        let body = serde_json::json!({
-            "query": query,
-            "params": []
+        let query = serde_json::json!({
+            "query": statement.query,
+            "params": statement.params
         });
+        // Only a batch can run in a read-only transaction
+        let body = if statement.read_only {
+            serde_json::json!({ "queries": [query] })
+        } else {
+            query
+        };
 
-        let response = self
-            .client
-            .post(&sql_url)
-            .header(
-                "Neon-Connection-String",
-                format!(
-                    "postgres://{}:{}@{}/{}",
-                    "neondb_owner", // Default role
-                    self.api_key,
-                    endpoint.host,
-                    database
-                ),
-            )
-            .header("Content-Type", "application/json")
+        // A compute that isn't active resumes on this query
+        let was_active = endpoint.current_state.as_deref() == Some("active");
+        let started = Instant::now();
+
+        let mut request = self
+            .sql_request(&sql_url, project_id, endpoint, database, role)
+            .await?;
+        if statement.read_only {
+            request = request.header("Neon-Batch-Read-Only", "true");
+        }
+        let response = request
             .json(&body)
             .send()
             .await
@@ -272,88 +805,464 @@ impl NeonClient {
             anyhow::bail!("SQL execution failed: {} - {}", status, text);
         }
 
-        response
-            .json()
-            .await
-            .context("Failed to parse SQL response")
+        self.cold_starts.record(
+            &endpoint.id,
+            project_id,
+            &endpoint.branch_id,
+            was_active,
+            started.elapsed().as_secs_f64() * 1000.0,
+        );
+
+        let (bytes, cut) =
+            read_body_limited(response, self.config.limits.max_response_bytes).await?;
+        if cut {
+            let body = if statement.read_only {
+                bytes.strip_prefix(BATCH_PREFIX).unwrap_or(&bytes)
+            } else {
+                &bytes
+            };
+            return Ok((parse_cut_response(body)?, true));
+        }
+        let result = if statement.read_only {
+            let batch: BatchResponse =
+                serde_json::from_slice(&bytes).context("Failed to parse SQL response")?;
+            batch
+                .results
+                .into_iter()
+                .next()
+                .context("SQL response has no result")?
+        } else {
+            serde_json::from_slice(&bytes).context("Failed to parse SQL response")?
+        };
+        Ok((result, false))
     }
 
-    /// Get current user/account info.
-    pub async fn get_user(&self) -> Result<Value> {
-        self.get("/users/me").await
+    /// A POST to a compute's SQL endpoint, authenticated as `role`.
+    async fn sql_request(
+        &self,
+        sql_url: &str,
+        project_id: &str,
+        endpoint: &Endpoint,
+        database: &str,
+        role: &str,
+    ) -> Result<reqwest::RequestBuilder> {
+        let password = self
+            .role_password(project_id, &endpoint.branch_id, role)
+            .await?;
+        Ok(self
+            .client
+            .post(sql_url)
+            .headers(trace::headers())
+            .header(
+                "Neon-Connection-String",
+                format!(
+                    "postgres://{}:{}@{}/{}",
+                    url_encode(role),
+                    url_encode(&password),
+                    endpoint.host,
+                    url_encode(database)
+                ),
+            )
+            .header("Content-Type", "application/json"))
     }
 
-    /// Create a new branch for a project.
-    pub async fn create_branch(
+    /// Run `statements` in one transaction on the branch's read-write
+    /// endpoint, returning each statement's result. Nothing is committed if
+    /// any statement fails.
+    pub async fn run_transaction(
         &self,
         project_id: &str,
-        name: Option<&str>,
-        parent_id: Option<&str>,
-    ) -> Result<Branch> {
-        let endpoint = format!("/projects/{}/branches", project_id);
+        branch_id: &str,
+        database: &str,
+        statements: &[TxStatement],
+        options: TxOptions,
+    ) -> Result<Vec<Value>> {
+        let endpoints = self.routing_endpoints(project_id).await?;
+        let endpoint = self.route_endpoint(&endpoints, branch_id, "", SqlTarget::Write)?;
 
-        let mut branch_data = serde_json::json!({});
-        if let Some(n) = name {
-            branch_data["name"] = serde_json::json!(n);
-        }
-        if let Some(p) = parent_id {
-            branch_data["parent_id"] = serde_json::json!(p);
-        }
+        let backend = options.backend.unwrap_or(self.config.sql.backend);
+        let results = match backend {
+            SqlBackend::Http => {
+                self.run_transaction_http(project_id, endpoint, database, statements, options)
+                    .await
+            }
+            SqlBackend::Postgres => {
+                let role = self.config.default_role(project_id, database);
+                let (key, client) = self
+                    .pg_checkout(project_id, endpoint, database, role)
+                    .await?;
+                let results = postgres::transaction(
+                    &client,
+                    statements,
+                    options.isolation,
+                    options.read_only,
+                    self.config.limits.max_response_bytes,
+                )
+                .await;
+                self.pg_release(key, client).await;
+                results
+            }
+        };
+        let results = results.inspect_err(|_| self.invalidate(project_id))?;
+        self.routes.set_state(project_id, &endpoint.id, "active");
 
-        let body = serde_json::json!({
-            "branch": branch_data
-        });
+        results
+            .into_iter()
+            .map(|result| {
+                let typed = normalize::query_result(result, backend == SqlBackend::Postgres)?;
+                let mut result = serde_json::to_value(typed)?;
+                truncate_rows(&mut result, self.config.limits.max_rows, 0, false, None);
+                Ok(result)
+            })
+            .collect()
+    }
 
+    /// Run a transaction through the SQL endpoint's batch mode.
+    async fn run_transaction_http(
+        &self,
+        project_id: &str,
+        endpoint: &Endpoint,
+        database: &str,
+        statements: &[TxStatement],
+        options: TxOptions,
+    ) -> Result<Vec<Value>> {
         #[derive(Deserialize)]
-        struct CreateBranchResponse {
-            branch: Branch,
+        struct BatchResponse {
+            results: Vec<Value>,
         }
 
-        let response: CreateBranchResponse = self.post(&endpoint, &body).await?;
-        Ok(response.branch)
-    }
-
-    /// Delete a branch from a project.
-    pub async fn delete_branch(&self, project_id: &str, branch_id: &str) -> Result<()> {
-        let endpoint = format!("/projects/{}/branches/{}", project_id, branch_id);
-        self.delete(&endpoint).await
-    }
+        let sql_url = format!("https://{}/sql", endpoint.host);
+        let queries: Vec<Value> = statements
+            .iter()
+            .map(|s| serde_json::json!({ "query": s.query, "params": s.params }))
+            .collect();
 
-    /// Get connection string for a project/branch.
-    pub async fn get_connection_string(
+        let response = self
+            .sql_request(
+                &sql_url,
+                project_id,
+                endpoint,
+                database,
+                self.config.default_role(project_id, database),
+            )
+            .await?
+            .header("Neon-Batch-Isolation-Level", options.isolation.header())
+            .header("Neon-Batch-Read-Only", options.read_only.to_string())
+            .json(&serde_json::json!({ "queries": queries }))
+            .send()
+            .await
+            .context("Failed to execute transaction")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Transaction rolled back: {} - {}", status, text);
+        }
+
+        let (bytes, cut) =
+            read_body_limited(response, self.config.limits.max_response_bytes).await?;
+        if cut {
+            anyhow::bail!(
+                "Transaction response exceeded the {} byte limit. Add a LIMIT or select fewer columns.",
+                self.config.limits.max_response_bytes
+            );
+        }
+        let batch: BatchResponse =
+            serde_json::from_slice(&bytes).context("Failed to parse transaction response")?;
+        Ok(batch.results)
+    }
+
+    /// A pooled connection to `endpoint` as `user`, or a new one.
+    async fn pg_checkout(
         &self,
         project_id: &str,
-        branch_id: Option<&str>,
-        database: Option<&str>,
-        pooled: bool,
+        endpoint: &Endpoint,
+        database: &str,
+        user: &str,
+    ) -> Result<(PoolKey, tokio_postgres::Client)> {
+        let key = (
+            project_id.to_string(),
+            endpoint.id.clone(),
+            database.to_string(),
+            user.to_string(),
+        );
+        if let Some(client) = self.pg.checkout(&key) {
+            return Ok((key, client));
+        }
+
+        let password = self
+            .role_password(project_id, &endpoint.branch_id, user)
+            .await?;
+        let target = ConnectTarget {
+            host: &endpoint.host,
+            user,
+            password: &password,
+            database,
+        };
+        let client = self.pg.connect(&target).await?;
+        Ok((key, client))
+    }
+
+    /// Run `COPY ... FROM STDIN` on a branch's read-write endpoint over a
+    /// direct connection, whatever the configured backend, streaming the
+    /// chunks `next_chunk` returns. Returns the rows copied.
+    pub async fn copy_in<F>(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        statement: &str,
+        next_chunk: F,
+    ) -> Result<u64>
+    where
+        F: FnMut() -> Result<Option<Vec<u8>>>,
+    {
+        let endpoints = self.routing_endpoints(project_id).await?;
+        let endpoint = self.route_endpoint(&endpoints, branch_id, "", SqlTarget::Write)?;
+
+        let role = self.config.default_role(project_id, database);
+        let (key, client) = self
+            .pg_checkout(project_id, endpoint, database, role)
+            .await?;
+        let copied = postgres::copy_in(&client, statement, next_chunk).await;
+        self.pg_release(key, client).await;
+        let copied = copied.inspect_err(|_| self.invalidate(project_id))?;
+        self.routes.set_state(project_id, &endpoint.id, "active");
+        Ok(copied)
+    }
+
+    /// Return a connection to the pool with its session state reset.
+    async fn pg_release(&self, key: PoolKey, client: tokio_postgres::Client) {
+        // Session state set by this call mustn't leak into the next one; a
+        // connection left inside a transaction fails this and is dropped
+        if client.simple_query("DISCARD ALL").await.is_ok() {
+            self.pg.checkin(key, client);
+        }
+    }
+
+    /// Execute a statement or script over a pooled Postgres connection.
+    async fn run_sql_postgres(
+        &self,
+        project_id: &str,
+        endpoint: &Endpoint,
+        database: &str,
+        role: &str,
+        statement: Statement<'_>,
     ) -> Result<Value> {
-        let endpoints_url = format!("/projects/{}/endpoints", project_id);
+        let (key, client) = self
+            .pg_checkout(project_id, endpoint, database, role)
+            .await?;
+        let max_bytes = self.config.limits.max_response_bytes;
+        let result = if statement.read_only {
+            postgres::read_only_query(&client, statement.query, statement.params, max_bytes).await
+        } else {
+            postgres::query(&client, statement.query, statement.params, max_bytes).await
+        };
+        self.pg_release(key, client).await;
+        result
+    }
 
+    /// Password of a branch role, revealed by the API or from the cache.
+    async fn role_password(&self, project_id: &str, branch_id: &str, role: &str) -> Result<String> {
         #[derive(Deserialize)]
-        struct Endpoint {
-            id: String,
-            host: String,
-            branch_id: String,
-            pooler_host: Option<String>,
+        struct RevealResponse {
+            password: String,
+        }
+
+        if let Some(password) = self.passwords.get(project_id, branch_id, role) {
+            return Ok(password);
+        }
+
+        let endpoint = format!(
+            "/projects/{}/branches/{}/roles/{}/reveal_password",
+            project_id,
+            branch_id,
+            path_segment(role)?
+        );
+        let response: RevealResponse = match self.get(&endpoint).await {
+            Ok(response) => response,
+            Err(e) if ApiError::is_not_found(&e) => {
+                let roles = self.list_roles(project_id, branch_id).await?;
+                let names: Vec<&str> = roles.iter().map(|r| r.name.as_str()).collect();
+                anyhow::bail!(
+                    "Role {} doesn't exist on branch {}; its roles are: {}",
+                    role,
+                    branch_id,
+                    names.join(", ")
+                );
+            }
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to get the password for role {}", role))
+            }
+        };
+        self.passwords
+            .put(project_id, branch_id, role, &response.password);
+        Ok(response.password)
+    }
+
+    /// Planner estimates for `query` from `EXPLAIN (FORMAT JSON)`, or `None`
+    /// for statements EXPLAIN doesn't accept (DDL, multiple statements).
+    pub async fn estimate_plan(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        query: &str,
+        params: &[Value],
+        role: Option<&str>,
+    ) -> Result<Option<PlanEstimate>> {
+        if !is_explainable(query) {
+            return Ok(None);
+        }
+
+        let explain = format!("EXPLAIN (FORMAT JSON) {}", strip_trailing_semicolon(query));
+        let page = SqlPage {
+            max_rows: 1,
+            offset: 0,
+            backend: None,
+            target: SqlTarget::Auto,
+            role,
+            read_only: false,
+        };
+        let result = self
+            .run_sql_page(project_id, branch_id, database, &explain, params, page)
+            .await
+            .context("EXPLAIN failed")?;
+
+        // The plan column arrives either parsed or as JSON text
+        let plan = result_rows(&result)
+            .first()
+            .and_then(|row| row.get("QUERY PLAN"))
+            .cloned()
+            .map(|v| match v {
+                Value::String(s) => serde_json::from_str(&s).unwrap_or(Value::Null),
+                v => v,
+            })
+            .unwrap_or(Value::Null);
+        let node = &plan[0]["Plan"];
+
+        match (node["Total Cost"].as_f64(), node["Plan Rows"].as_f64()) {
+            (Some(total_cost), Some(plan_rows)) => Ok(Some(PlanEstimate {
+                total_cost,
+                plan_rows,
+            })),
+            _ => anyhow::bail!("Unexpected EXPLAIN output"),
+        }
+    }
+
+    /// Get current user/account info.
+    pub async fn get_user(&self) -> Result<Value> {
+        self.get("/users/me").await
+    }
+
+    /// Create a new branch for a project.
+    pub async fn create_branch(
+        &self,
+        project_id: &str,
+        name: Option<&str>,
+        parent_id: Option<&str>,
+    ) -> Result<Branch> {
+        self.create_annotated_branch(project_id, name, parent_id, &BTreeMap::new())
+            .await
+    }
+
+    /// Create a branch carrying `annotation` key/values.
+    pub async fn create_annotated_branch(
+        &self,
+        project_id: &str,
+        name: Option<&str>,
+        parent_id: Option<&str>,
+        annotation: &BTreeMap<String, String>,
+    ) -> Result<Branch> {
+        let endpoint = format!("/projects/{}/branches", project_id);
+
+        let mut branch_data = serde_json::json!({});
+        if let Some(n) = name {
+            branch_data["name"] = serde_json::json!(n);
+        }
+        if let Some(p) = parent_id {
+            branch_data["parent_id"] = serde_json::json!(p);
+        }
+
+        let mut body = serde_json::json!({
+            "branch": branch_data
+        });
+        if !annotation.is_empty() {
+            body["annotation_value"] = serde_json::json!(annotation);
         }
 
         #[derive(Deserialize)]
-        struct EndpointsResponse {
-            endpoints: Vec<Endpoint>,
+        struct CreateBranchResponse {
+            branch: Branch,
         }
 
-        let endpoints: EndpointsResponse = self.get(&endpoints_url).await?;
+        let response: CreateBranchResponse = self.post(&endpoint, &body).await?;
+        self.invalidate(project_id);
+        let mut branch = response.branch;
+        branch.annotations = annotation.clone();
+        Ok(branch)
+    }
+
+    /// Replace a branch's annotation key/values.
+    pub async fn annotate_branch(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        annotation: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        let endpoint = format!("/projects/{}/branches/{}", project_id, branch_id);
+        let body = serde_json::json!({
+            "branch": {},
+            "annotation_value": annotation,
+        });
+        let _: Value = self.patch(&endpoint, &body).await?;
+        Ok(())
+    }
+
+    /// Restore a branch to a point in its own or another branch's history.
+    pub async fn restore_branch(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        request: &RestoreBranchRequest,
+    ) -> Result<RestoredBranch> {
+        let endpoint = format!("/projects/{}/branches/{}/restore", project_id, branch_id);
+        let restored: RestoredBranch = self
+            .post(&endpoint, &serde_json::to_value(request)?)
+            .await?;
+        self.invalidate(project_id);
+        Ok(restored)
+    }
+
+    /// Delete a branch from a project.
+    pub async fn delete_branch(&self, project_id: &str, branch_id: &str) -> Result<()> {
+        let endpoint = format!("/projects/{}/branches/{}", project_id, branch_id);
+        self.delete(&endpoint).await?;
+        self.invalidate(project_id);
+        Ok(())
+    }
+
+    /// Get connection string for a project/branch.
+    pub async fn get_connection_string(
+        &self,
+        project_id: &str,
+        branch_id: Option<&str>,
+        database: Option<&str>,
+        role: Option<&str>,
+        pooled: bool,
+    ) -> Result<Value> {
+        let endpoints = self.routing_endpoints(project_id).await?;
 
-        // Find the appropriate endpoint
+        // Find the appropriate endpoint, preferring the read-write one
         let endpoint = if let Some(bid) = branch_id {
             endpoints
-                .endpoints
                 .iter()
-                .find(|e| e.branch_id == bid)
+                .filter(|e| e.branch_id == bid)
+                .max_by_key(|e| e.endpoint_type == "read_write")
                 .ok_or_else(|| anyhow::anyhow!("No endpoint found for branch {}", bid))?
         } else {
             endpoints
-                .endpoints
                 .first()
                 .ok_or_else(|| anyhow::anyhow!("No endpoints found for project"))?
         };
@@ -367,18 +1276,995 @@ impl NeonClient {
         let db = database.unwrap_or("neondb");
         let port = if pooled { 5432 } else { 5432 };
 
-        // Standard connection string format
-        let connection_string = format!("postgres://neondb_owner@{}/{}?sslmode=require", host, db);
+        let role = role.unwrap_or_else(|| self.config.default_role(project_id, db));
+        let password = self
+            .role_password(project_id, &endpoint.branch_id, role)
+            .await?;
+
+        let connection_string = connection_uri(role, &password, host, db);
 
         Ok(serde_json::json!({
             "connection_string": connection_string,
             "host": host,
             "port": port,
-            "user": "neondb_owner",
+            "user": role,
+            "password": password,
             "database": db,
             "pooled": pooled,
             "branch_id": endpoint.branch_id,
             "endpoint_id": endpoint.id,
         }))
     }
+
+    /// Connection details for `endpoint` as `role` (the configured one when
+    /// `None`): its host and, when it has one, its pooler host, each with a
+    /// connection string carrying the role's password.
+    pub async fn endpoint_connection(
+        &self,
+        project_id: &str,
+        endpoint: &Endpoint,
+        database: &str,
+        role: Option<&str>,
+    ) -> Result<Value> {
+        let role = role.unwrap_or_else(|| self.config.default_role(project_id, database));
+        let password = self
+            .role_password(project_id, &endpoint.branch_id, role)
+            .await?;
+        let uri = |host: &str| connection_uri(role, &password, host, database);
+
+        Ok(serde_json::json!({
+            "host": endpoint.host,
+            "port": 5432,
+            "user": role,
+            "password": password,
+            "database": database,
+            "connection_string": uri(&endpoint.host),
+            "pooler_host": endpoint.pooler_host,
+            "pooled_connection_string": endpoint.pooler_host.as_deref().map(uri),
+        }))
+    }
+
+    /// Create a project in the configured organization.
+    pub async fn create_project(&self, request: &CreateProjectRequest) -> Result<CreatedProject> {
+        let mut project = serde_json::to_value(request)?;
+        project["org_id"] = serde_json::json!(self.org_id);
+
+        self.post("/projects", &serde_json::json!({ "project": project }))
+            .await
+    }
+
+    /// Change a project's name or settings.
+    pub async fn update_project(
+        &self,
+        project_id: &str,
+        request: &UpdateProjectRequest,
+    ) -> Result<Project> {
+        #[derive(Deserialize)]
+        struct UpdateProjectResponse {
+            project: Project,
+        }
+
+        let endpoint = format!("/projects/{}", project_id);
+        let response: UpdateProjectResponse = self
+            .patch(&endpoint, &serde_json::json!({ "project": request }))
+            .await?;
+        Ok(response.project)
+    }
+
+    /// Delete a project with all its branches and data.
+    pub async fn delete_project(&self, project_id: &str) -> Result<()> {
+        let endpoint = format!("/projects/{}", project_id);
+        self.delete(&endpoint).await?;
+        self.invalidate(project_id);
+        Ok(())
+    }
+
+    /// List compute endpoints for a project.
+    pub async fn list_endpoints(&self, project_id: &str) -> Result<Vec<Endpoint>> {
+        let endpoint = format!("/projects/{}/endpoints", project_id);
+
+        #[derive(Deserialize)]
+        struct EndpointsResponse {
+            endpoints: Vec<Endpoint>,
+        }
+
+        let response: EndpointsResponse = self.get(&endpoint).await?;
+        Ok(response.endpoints)
+    }
+
+    /// Create a compute endpoint. `settings` holds optional endpoint fields
+    /// such as autoscaling limits and suspend timeout.
+    pub async fn create_endpoint(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        endpoint_type: &str,
+        settings: &Value,
+    ) -> Result<Endpoint> {
+        let endpoint = format!("/projects/{}/endpoints", project_id);
+
+        let mut data = serde_json::json!({
+            "branch_id": branch_id,
+            "type": endpoint_type,
+        });
+        merge_object(&mut data, settings);
+
+        #[derive(Deserialize)]
+        struct EndpointResponse {
+            endpoint: Endpoint,
+        }
+
+        let response: EndpointResponse = self
+            .post(&endpoint, &serde_json::json!({ "endpoint": data }))
+            .await?;
+        self.invalidate(project_id);
+        Ok(response.endpoint)
+    }
+
+    /// Update settings on a compute endpoint.
+    pub async fn update_endpoint(
+        &self,
+        project_id: &str,
+        endpoint_id: &str,
+        settings: &Value,
+    ) -> Result<Endpoint> {
+        let endpoint = format!("/projects/{}/endpoints/{}", project_id, endpoint_id);
+
+        #[derive(Deserialize)]
+        struct EndpointResponse {
+            endpoint: Endpoint,
+        }
+
+        let response: EndpointResponse = self
+            .patch(&endpoint, &serde_json::json!({ "endpoint": settings }))
+            .await?;
+        self.invalidate(project_id);
+        Ok(response.endpoint)
+    }
+
+    /// List roles on a branch.
+    pub async fn list_roles(&self, project_id: &str, branch_id: &str) -> Result<Vec<Role>> {
+        let endpoint = format!("/projects/{}/branches/{}/roles", project_id, branch_id);
+
+        #[derive(Deserialize)]
+        struct RolesResponse {
+            roles: Vec<Role>,
+        }
+
+        let response: RolesResponse = self.get(&endpoint).await?;
+        Ok(response.roles)
+    }
+
+    /// Create a role on a branch.
+    pub async fn create_role(&self, project_id: &str, branch_id: &str, name: &str) -> Result<Role> {
+        let endpoint = format!("/projects/{}/branches/{}/roles", project_id, branch_id);
+
+        #[derive(Deserialize)]
+        struct RoleResponse {
+            role: Role,
+        }
+
+        let response: RoleResponse = self
+            .post(&endpoint, &serde_json::json!({ "role": { "name": name } }))
+            .await?;
+        Ok(response.role)
+    }
+
+    /// Delete a role from a branch.
+    pub async fn delete_role(&self, project_id: &str, branch_id: &str, name: &str) -> Result<()> {
+        let endpoint = format!(
+            "/projects/{}/branches/{}/roles/{}",
+            project_id,
+            branch_id,
+            path_segment(name)?
+        );
+        self.delete(&endpoint).await?;
+        self.invalidate(project_id);
+        Ok(())
+    }
+
+    /// Create a database on a branch.
+    pub async fn create_database(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        name: &str,
+        owner_name: &str,
+    ) -> Result<Database> {
+        let endpoint = format!("/projects/{}/branches/{}/databases", project_id, branch_id);
+
+        #[derive(Deserialize)]
+        struct DatabaseResponse {
+            database: Database,
+        }
+
+        let body = serde_json::json!({
+            "database": { "name": name, "owner_name": owner_name }
+        });
+        let response: DatabaseResponse = self.post(&endpoint, &body).await?;
+        Ok(response.database)
+    }
+
+    /// Delete a database from a branch.
+    pub async fn delete_database(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        name: &str,
+    ) -> Result<()> {
+        let endpoint = format!(
+            "/projects/{}/branches/{}/databases/{}",
+            project_id,
+            branch_id,
+            path_segment(name)?
+        );
+        self.delete(&endpoint).await
+    }
+
+    /// List recent operations for a project, newest first.
+    pub async fn list_operations(&self, project_id: &str, limit: i32) -> Result<Vec<Operation>> {
+        let endpoint = format!("/projects/{}/operations?limit={}", project_id, limit);
+
+        #[derive(Deserialize)]
+        struct OperationsResponse {
+            operations: Vec<Operation>,
+        }
+
+        let response: OperationsResponse = self.get(&endpoint).await?;
+        Ok(response.operations)
+    }
+
+    /// Wait until a project has no pending operations.
+    ///
+    /// Neon rejects conflicting changes while operations run, so sequential
+    /// mutations call this in between.
+    pub async fn wait_for_idle(&self, project_id: &str, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let operations = self.list_operations(project_id, 20).await?;
+
+            // Only the newest operation reflects the change we just made
+            if let Some(latest) = operations.first().filter(|op| op.is_failed()) {
+                return Err(self.operation_failure(latest, &operations).await);
+            }
+
+            if !operations.iter().any(|op| op.is_pending()) {
+                return Ok(());
+            }
+
+            if Instant::now() >= deadline {
+                anyhow::bail!(
+                    "Timed out waiting for operations on project {} to finish",
+                    project_id
+                );
+            }
+
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    /// Get a single compute endpoint.
+    pub async fn get_endpoint(&self, project_id: &str, endpoint_id: &str) -> Result<Endpoint> {
+        let endpoint = format!("/projects/{}/endpoints/{}", project_id, endpoint_id);
+
+        #[derive(Deserialize)]
+        struct EndpointResponse {
+            endpoint: Endpoint,
+        }
+
+        let response: EndpointResponse = self.get(&endpoint).await?;
+        Ok(response.endpoint)
+    }
+
+    /// Ask Neon to start a (possibly suspended) compute endpoint.
+    pub async fn start_endpoint(&self, project_id: &str, endpoint_id: &str) -> Result<Endpoint> {
+        let endpoint = format!("/projects/{}/endpoints/{}/start", project_id, endpoint_id);
+
+        #[derive(Deserialize)]
+        struct EndpointResponse {
+            endpoint: Endpoint,
+        }
+
+        let response: EndpointResponse = self.post(&endpoint, &serde_json::json!({})).await?;
+        self.routes.set_state(project_id, endpoint_id, "active");
+        Ok(response.endpoint)
+    }
+
+    /// Suspend a compute endpoint; it resumes on the next connection.
+    pub async fn suspend_endpoint(&self, project_id: &str, endpoint_id: &str) -> Result<Endpoint> {
+        let endpoint = format!("/projects/{}/endpoints/{}/suspend", project_id, endpoint_id);
+
+        #[derive(Deserialize)]
+        struct EndpointResponse {
+            endpoint: Endpoint,
+        }
+
+        let response: EndpointResponse = self.post(&endpoint, &serde_json::json!({})).await?;
+        self.routes.set_state(project_id, endpoint_id, "idle");
+        Ok(response.endpoint)
+    }
+
+    /// Delete a compute endpoint.
+    pub async fn delete_endpoint(&self, project_id: &str, endpoint_id: &str) -> Result<()> {
+        let endpoint = format!("/projects/{}/endpoints/{}", project_id, endpoint_id);
+        self.delete(&endpoint).await?;
+        self.invalidate(project_id);
+        Ok(())
+    }
+
+    /// Start a branch's read-write endpoint and wait until it reports `active`.
+    pub async fn wake_endpoint(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        timeout: Duration,
+    ) -> Result<Endpoint> {
+        let endpoint = self
+            .list_endpoints(project_id)
+            .await?
+            .into_iter()
+            .filter(|e| e.branch_id == branch_id)
+            .max_by_key(|e| e.endpoint_type == "read_write")
+            .ok_or_else(|| anyhow::anyhow!("No endpoint found for branch {}", branch_id))?;
+
+        if endpoint.current_state.as_deref() == Some("active") {
+            return Ok(endpoint);
+        }
+
+        self.start_endpoint(project_id, &endpoint.id).await?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let current = self.get_endpoint(project_id, &endpoint.id).await?;
+            if current.current_state.as_deref() == Some("active") {
+                return Ok(current);
+            }
+
+            if Instant::now() >= deadline {
+                anyhow::bail!(
+                    "Timed out waiting for endpoint {} to become active",
+                    endpoint.id
+                );
+            }
+
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    /// Per-project consumption between `from` and `to` (RFC 3339), bucketed
+    /// by `granularity` (`hourly`, `daily`, or `monthly`). Follows pagination.
+    pub async fn get_consumption(
+        &self,
+        from: &str,
+        to: &str,
+        granularity: &str,
+        project_ids: &[String],
+    ) -> Result<Vec<ProjectConsumption>> {
+        #[derive(Deserialize)]
+        struct ConsumptionResponse {
+            projects: Vec<ProjectConsumption>,
+            #[serde(default)]
+            pagination: Option<Pagination>,
+        }
+
+        #[derive(Deserialize)]
+        struct Pagination {
+            cursor: Option<String>,
+        }
+
+        let mut projects = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut endpoint = format!(
+                "/consumption_history/projects?org_id={}&from={}&to={}&granularity={}&limit=100",
+                self.org_id, from, to, granularity
+            );
+            if !project_ids.is_empty() {
+                endpoint.push_str(&format!("&project_ids={}", project_ids.join(",")));
+            }
+            if let Some(c) = &cursor {
+                endpoint.push_str(&format!("&cursor={}", c));
+            }
+
+            let response: ConsumptionResponse = self.get(&endpoint).await?;
+            let page_len = response.projects.len();
+            projects.extend(response.projects);
+
+            cursor = response.pagination.and_then(|p| p.cursor);
+            if page_len < 100 || cursor.is_none() {
+                return Ok(projects);
+            }
+        }
+    }
+
+    /// Provision Neon Auth for a project, syncing users into `database`.
+    pub async fn create_auth_integration(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        role: &str,
+    ) -> Result<AuthIntegration> {
+        let body = serde_json::json!({
+            "auth_provider": AUTH_PROVIDER,
+            "project_id": project_id,
+            "branch_id": branch_id,
+            "database_name": database,
+            "role_name": role,
+        });
+        self.post("/projects/auth/create", &body).await
+    }
+
+    /// List a project's Neon Auth integrations.
+    pub async fn list_auth_integrations(&self, project_id: &str) -> Result<Vec<AuthIntegration>> {
+        let endpoint = format!("/projects/{}/auth/integrations", project_id);
+
+        #[derive(Deserialize)]
+        struct IntegrationsResponse {
+            data: Vec<AuthIntegration>,
+        }
+
+        let response: IntegrationsResponse = self.get(&endpoint).await?;
+        Ok(response.data)
+    }
+
+    /// Remove a project's Neon Auth integration.
+    pub async fn delete_auth_integration(&self, project_id: &str) -> Result<()> {
+        let endpoint = format!(
+            "/projects/{}/auth/integration/{}",
+            project_id, AUTH_PROVIDER
+        );
+        self.delete(&endpoint).await
+    }
+
+    /// Create a Neon Auth user. Returns the new user's id.
+    pub async fn create_auth_user(
+        &self,
+        project_id: &str,
+        email: &str,
+        name: Option<&str>,
+    ) -> Result<String> {
+        #[derive(Deserialize)]
+        struct UserResponse {
+            id: String,
+        }
+
+        let body = serde_json::json!({
+            "auth_provider": AUTH_PROVIDER,
+            "project_id": project_id,
+            "email": email,
+            "name": name,
+        });
+        let response: UserResponse = self.post("/projects/auth/user", &body).await?;
+        Ok(response.id)
+    }
+
+    /// Delete a Neon Auth user.
+    pub async fn delete_auth_user(&self, project_id: &str, user_id: &str) -> Result<()> {
+        let endpoint = format!("/projects/{}/auth/users/{}", project_id, user_id);
+        self.delete(&endpoint).await
+    }
+
+    /// Neon Auth users as synced into the database's `neon_auth.users_sync`
+    /// table, oldest first, in the same shape as a SQL result.
+    pub async fn list_auth_users(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        include_deleted: bool,
+    ) -> Result<Value> {
+        let query = format!(
+            "SELECT id, email, name, created_at, updated_at, deleted_at FROM neon_auth.users_sync{} ORDER BY created_at",
+            if include_deleted {
+                ""
+            } else {
+                " WHERE deleted_at IS NULL"
+            }
+        );
+        self.run_sql(project_id, branch_id, database, &query).await
+    }
+
+    /// Enable the Data API for a branch database.
+    ///
+    /// `settings` carries optional `auth_provider`, `jwks_url`,
+    /// `jwt_audience`, and `add_default_grants`.
+    pub async fn enable_data_api(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        settings: &Value,
+    ) -> Result<DataApi> {
+        let endpoint = format!(
+            "/projects/{}/branches/{}/data-api/{}",
+            project_id, branch_id, database
+        );
+        self.post(&endpoint, settings).await
+    }
+
+    /// Data API settings for a branch database, or `None` if it isn't enabled.
+    pub async fn get_data_api(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+    ) -> Result<Option<DataApi>> {
+        let endpoint = format!(
+            "/projects/{}/branches/{}/data-api/{}",
+            project_id, branch_id, database
+        );
+        match self.get(&endpoint).await {
+            Ok(data_api) => Ok(Some(data_api)),
+            Err(e) if ApiError::is_not_found(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// List the JWKS URLs configured for a project.
+    pub async fn list_jwks(&self, project_id: &str) -> Result<Vec<Jwks>> {
+        let endpoint = format!("/projects/{}/jwks", project_id);
+
+        #[derive(Deserialize)]
+        struct JwksListResponse {
+            jwks: Vec<Jwks>,
+        }
+
+        let response: JwksListResponse = self.get(&endpoint).await?;
+        Ok(response.jwks)
+    }
+
+    /// Trust a JWKS URL for JWT connections as `role_names`.
+    ///
+    /// `settings` carries optional `branch_id` and `jwt_audience`.
+    pub async fn add_jwks(
+        &self,
+        project_id: &str,
+        jwks_url: &str,
+        provider_name: &str,
+        role_names: &[String],
+        settings: &Value,
+    ) -> Result<Jwks> {
+        let endpoint = format!("/projects/{}/jwks", project_id);
+
+        #[derive(Deserialize)]
+        struct JwksResponse {
+            jwks: Jwks,
+        }
+
+        let mut body = serde_json::json!({
+            "jwks_url": jwks_url,
+            "provider_name": provider_name,
+            "role_names": role_names,
+        });
+        merge_object(&mut body, settings);
+
+        let response: JwksResponse = self.post(&endpoint, &body).await?;
+        Ok(response.jwks)
+    }
+
+    /// Stop trusting a JWKS URL.
+    pub async fn delete_jwks(&self, project_id: &str, jwks_id: &str) -> Result<()> {
+        let endpoint = format!("/projects/{}/jwks/{}", project_id, jwks_id);
+        self.delete(&endpoint).await
+    }
+    /// Create an organization API key that can only act on one project.
+    pub async fn create_project_api_key(&self, project_id: &str, name: &str) -> Result<ApiKey> {
+        if self.org_id.is_empty() {
+            anyhow::bail!(
+                "Project-scoped API keys belong to an organization; set NEON_ORG_ID to mint one"
+            );
+        }
+        let endpoint = format!("/organizations/{}/api_keys", self.org_id);
+        self.post(
+            &endpoint,
+            &serde_json::json!({ "key_name": name, "project_id": project_id }),
+        )
+        .await
+    }
+}
+
+/// Copy the keys of `extra` into `target` when both are objects.
+fn merge_object(target: &mut Value, extra: &Value) {
+    if let (Some(target), Some(extra)) = (target.as_object_mut(), extra.as_object()) {
+        for (key, value) in extra {
+            target.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+/// Quote a string as a SQL literal.
+pub(crate) fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Quote a string as a SQL identifier.
+pub(crate) fn quote_ident(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+/// Percent-encode a value for a URL path segment or the user, password, or
+/// database of a connection string, keeping only unreserved characters.
+pub(crate) fn url_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Standard `postgres://` connection string for `role` on `host`.
+fn connection_uri(role: &str, password: &str, host: &str, database: &str) -> String {
+    format!(
+        "postgres://{}:{}@{}/{}?sslmode=require",
+        url_encode(role),
+        url_encode(password),
+        host,
+        url_encode(database)
+    )
+}
+
+/// A caller-supplied name as one API path segment. URLs resolve `.` and
+/// `..` segments even when encoded, so those are refused.
+fn path_segment(name: &str) -> Result<String> {
+    if matches!(name, "" | "." | "..") {
+        anyhow::bail!("Invalid name {:?}", name);
+    }
+    Ok(url_encode(name))
+}
+
+/// Rows of a SQL-over-HTTP result, or an empty slice.
+pub(crate) fn result_rows(result: &Value) -> &[Value] {
+    result
+        .get("rows")
+        .and_then(|r| r.as_array())
+        .map(|rows| rows.as_slice())
+        .unwrap_or(&[])
+}
+
+/// Read a boolean cell that may be encoded as a JSON bool or a Postgres string.
+pub(super) fn value_as_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(b) => Some(*b),
+        Value::String(s) => match s.as_str() {
+            "t" | "true" => Some(true),
+            "f" | "false" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Read a numeric cell that may be encoded as a JSON number or a string.
+pub(super) fn value_as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// A branch's entry in a listing's `annotations`, keyed by branch id.
+#[derive(Deserialize)]
+struct BranchAnnotation {
+    #[serde(default)]
+    value: BTreeMap<String, String>,
+}
+
+/// Move listed annotations onto their branches.
+fn annotate(branches: &mut [Branch], mut annotations: HashMap<String, BranchAnnotation>) {
+    for branch in branches {
+        if let Some(annotation) = annotations.remove(&branch.id) {
+            branch.annotations = annotation.value;
+        }
+    }
+}
+
+/// Escape LIKE wildcards so a value matches literally.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Whether a query is a single read statement that can be wrapped in
+/// LIMIT/OFFSET. Postgres only allows data-modifying CTEs at the top level,
+/// and `SELECT ... INTO` creates a table, so neither is wrapped.
+pub(crate) fn is_pageable(query: &str) -> bool {
+    const MODIFYING: &[&str] = &["insert", "update", "delete", "merge", "into"];
+    let trimmed = strip_trailing_semicolon(query);
+    let head = strip_leading_comments(trimmed)
+        .split_whitespace()
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    (head == "select" || head == "with" || head == "values")
+        && !is_multi_statement(trimmed)
+        && !has_keyword(trimmed, MODIFYING)
+}
+
+/// Whether a query is a single read that a replica can answer. Anything that
+/// might write or lock (data-modifying CTEs, `FOR UPDATE`, sequences) isn't.
+pub(crate) fn is_read_only(query: &str) -> bool {
+    const WRITES: &[&str] = &["for", "nextval", "setval"];
+    is_pageable(query) && !has_keyword(query, WRITES)
+}
+
+/// Whether a query may change what the catalog methods report: it creates,
+/// alters, drops, comments on, or grants on objects, runs code (`DO`,
+/// `CALL`) that might, or is a `SELECT ... INTO`.
+pub(crate) fn is_ddl(query: &str) -> bool {
+    const DDL: &[&str] = &[
+        "create", "alter", "drop", "comment", "grant", "revoke", "import", "security", "do", "call",
+    ];
+    sqltext::split_statements(query).iter().any(|statement| {
+        let head = strip_leading_comments(statement)
+            .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        DDL.contains(&head.as_str()) || (head == "select" && has_keyword(statement, &["into"]))
+    })
+}
+
+/// Whether any of `keywords` appears in `query` as a whole word, outside
+/// string literals, quoted identifiers, dollar-quoted bodies, and comments.
+fn has_keyword(query: &str, keywords: &[&str]) -> bool {
+    let mut rest = query;
+    while let Some(c) = rest.chars().next() {
+        if let Some(len) = sqltext::opaque_len(rest) {
+            rest = &rest[len..];
+            continue;
+        }
+        let word_len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if word_len == 0 {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+        if keywords.contains(&rest[..word_len].to_ascii_lowercase().as_str()) {
+            return true;
+        }
+        rest = &rest[word_len..];
+    }
+    false
+}
+
+/// Whether a query holds more than one statement.
+fn is_multi_statement(query: &str) -> bool {
+    sqltext::split_statements(query).len() > 1
+}
+
+/// Whether a query is a single statement EXPLAIN accepts.
+fn is_explainable(query: &str) -> bool {
+    let trimmed = strip_trailing_semicolon(query);
+    let head = strip_leading_comments(trimmed)
+        .split_whitespace()
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    matches!(
+        head.as_str(),
+        "select" | "with" | "values" | "insert" | "update" | "delete" | "merge"
+    ) && !is_multi_statement(trimmed)
+}
+
+fn strip_trailing_semicolon(query: &str) -> &str {
+    query.trim().trim_end_matches(';').trim_end()
+}
+
+/// Skip leading `/* ... */` and `-- ...` comments, such as query tags.
+pub(super) fn strip_leading_comments(mut query: &str) -> &str {
+    loop {
+        query = query.trim_start();
+        if let Some(rest) = query.strip_prefix("/*") {
+            match rest.find("*/") {
+                Some(end) => query = &rest[end + 2..],
+                None => return "",
+            }
+        } else if let Some(rest) = query.strip_prefix("--") {
+            query = rest.split_once('\n').map(|(_, r)| r).unwrap_or("");
+        } else {
+            return query;
+        }
+    }
+}
+
+/// Read a response body up to `max_bytes`. A longer body is cut at the limit
+/// and the rest left unread; the flag says whether that happened.
+async fn read_body_limited(
+    mut response: reqwest::Response,
+    max_bytes: usize,
+) -> Result<(Vec<u8>, bool)> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .context("Failed to read SQL response")?
+    {
+        if body.len() + chunk.len() > max_bytes {
+            // Stop reading; dropping the response closes the connection
+            body.extend_from_slice(&chunk[..max_bytes - body.len()]);
+            return Ok((body, true));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok((body, false))
+}
+
+/// Parse a SQL response cut off mid-body: the rows that arrived whole, plus
+/// whatever of `command` and `fields` came before them.
+fn parse_cut_response(body: &[u8]) -> Result<Value> {
+    const ROWS: &[u8] = b"\"rows\":[";
+    let start = body
+        .windows(ROWS.len())
+        .position(|w| w == ROWS)
+        .context("SQL response was cut off before its rows")?;
+    let rows_start = start + ROWS.len();
+
+    // End of the last row whose closing brace arrived
+    let mut end = rows_start;
+    let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+    for (i, &byte) in body[rows_start..].iter().enumerate() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    end = rows_start + i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut repaired = body[..rows_start].to_vec();
+    repaired.extend_from_slice(&body[rows_start..end]);
+    repaired.extend_from_slice(b"]}");
+    let mut result: Value =
+        serde_json::from_slice(&repaired).context("Failed to parse truncated SQL response")?;
+    // The count is of every row, not the ones that arrived
+    if let Some(obj) = result.as_object_mut() {
+        obj.remove("rowCount");
+    }
+    Ok(result)
+}
+
+/// Trim `rows` to `max_rows` and annotate the result with truncation
+/// metadata. `cut_at` is the byte limit when the response was cut short.
+fn truncate_rows(
+    result: &mut Value,
+    max_rows: usize,
+    offset: usize,
+    paged: bool,
+    cut_at: Option<usize>,
+) {
+    let Some(obj) = result.as_object_mut() else {
+        return;
+    };
+
+    let (truncated, kept) = match obj.get_mut("rows").and_then(|r| r.as_array_mut()) {
+        Some(rows) if rows.len() > max_rows => {
+            rows.truncate(max_rows);
+            (true, max_rows)
+        }
+        Some(rows) => (cut_at.is_some(), rows.len()),
+        None => (cut_at.is_some(), 0),
+    };
+
+    obj.insert("truncated".into(), serde_json::json!(truncated));
+    if truncated {
+        let reason = match cut_at {
+            Some(bytes) if kept < max_rows => format!(
+                "Response passed the {} byte limit after {} rows.",
+                bytes, kept
+            ),
+            _ => format!("Result truncated to {} rows.", max_rows),
+        };
+        if paged {
+            let next_offset = offset + kept;
+            obj.insert("next_offset".into(), serde_json::json!(next_offset));
+            obj.insert(
+                "continuation".into(),
+                serde_json::json!(format!(
+                    "{} Call again with offset={} for more.",
+                    reason, next_offset
+                )),
+            );
+        } else {
+            obj.insert(
+                "continuation".into(),
+                serde_json::json!(format!(
+                    "{} Add LIMIT/OFFSET to the query to page through it.",
+                    reason
+                )),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords_in_string_literals_are_ignored() {
+        assert!(is_read_only("SELECT * FROM events WHERE kind = 'insert'"));
+        assert!(is_read_only("SELECT * FROM events WHERE note LIKE '%for%'"));
+        assert!(is_read_only(r"SELECT E'it\'s for update' AS note"));
+        assert!(is_read_only("SELECT $$delete$$ AS word"));
+    }
+
+    #[test]
+    fn keywords_in_quoted_identifiers_are_ignored() {
+        assert!(is_pageable(r#"SELECT "into", "update" FROM t"#));
+    }
+
+    #[test]
+    fn keywords_in_comments_are_ignored() {
+        assert!(is_read_only("/* fgp:for-report */ SELECT 1"));
+        assert!(is_read_only("SELECT 1 -- for update\n"));
+        assert!(is_read_only("SELECT 1 /* a /* nested */ delete */"));
+    }
+
+    #[test]
+    fn locking_and_sequences_are_not_read_only() {
+        assert!(!is_read_only("SELECT * FROM t FOR UPDATE"));
+        assert!(!is_read_only("SELECT * FROM t FOR SHARE"));
+        assert!(!is_read_only("SELECT nextval('s')"));
+    }
+
+    #[test]
+    fn writing_statements_are_not_pageable() {
+        assert!(!is_pageable(
+            "WITH d AS (DELETE FROM t RETURNING *) SELECT * FROM d"
+        ));
+        assert!(!is_pageable("SELECT * INTO t2 FROM t"));
+        assert!(!is_pageable("INSERT INTO t VALUES (1)"));
+        assert!(!is_pageable("SELECT 1; DELETE FROM t"));
+    }
+
+    #[test]
+    fn semicolons_in_literals_keep_one_statement() {
+        assert!(is_pageable("SELECT ';' AS sep;"));
+        assert!(is_explainable("UPDATE t SET sep = ';'"));
+    }
+
+    #[test]
+    fn url_encode_escapes_separators() {
+        assert_eq!(url_encode("app_rw-1.x~"), "app_rw-1.x~");
+        assert_eq!(url_encode("../a/b?c"), "..%2Fa%2Fb%3Fc");
+        assert_eq!(url_encode("u@h:p#é"), "u%40h%3Ap%23%C3%A9");
+        assert!(path_segment("..").is_err());
+        assert_eq!(path_segment("a/b").unwrap(), "a%2Fb");
+    }
+
+    #[test]
+    fn ddl_is_found_in_any_statement() {
+        assert!(is_ddl("CREATE TABLE t (id int)"));
+        assert!(is_ddl("/* fgp:x */ alter table t add column n int"));
+        assert!(is_ddl("INSERT INTO t VALUES (1); DROP TABLE t"));
+        assert!(is_ddl("SELECT * INTO t2 FROM t"));
+        assert!(!is_ddl("SELECT 'create table x' AS text"));
+        assert!(!is_ddl("INSERT INTO notes VALUES ('drop table t')"));
+        assert!(!is_ddl("UPDATE t SET n = 1"));
+    }
 }
@@ -1,5 +1,20 @@
 //! Neon API client module.
 
+mod catalog;
 mod client;
+mod normalize;
+mod operations;
+mod passwords;
+mod postgres;
+mod privileges;
+mod profile;
+mod retry;
+mod routes;
 
-pub use client::NeonClient;
+pub(crate) use client::{
+    is_ddl, is_pageable, is_read_only, quote_ident, quote_literal, result_rows, url_encode,
+};
+pub use client::{
+    IsolationLevel, NeonClient, PlanEstimate, SqlPage, SqlTarget, TablePage, TxOptions, TxStatement,
+};
+pub use retry::ApiError;
@@ -0,0 +1,156 @@
+//! Typed SQL results.
+//!
+//! Both backends produce the serverless driver's JSON (`command`,
+//! `rowCount`, `rows`, `fields` with `dataTypeID`). The HTTP endpoint already
+//! sends booleans, numbers, and JSON as JSON; the Postgres backend sends every
+//! value in Postgres text format. This parses either into a [`QueryResult`]
+//! and converts values by column type, so a result looks the same whichever
+//! backend ran it:
+//!
+//! - `bool` becomes a JSON boolean and integer types become JSON numbers
+//! - `float4`/`float8` become numbers, except `NaN` and infinities, which stay strings
+//! - `numeric` stays a string, so no precision is lost
+//! - `json`/`jsonb` are parsed
+//! - `timestamp` becomes ISO 8601 and `timestamptz` RFC 3339 in UTC
+//! - `bytea` is a `\x`-prefixed hex string, as Postgres writes it
+//!
+//! Values of other types, and of columns whose type isn't known, are passed
+//! through unchanged.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::models::{QueryColumn, QueryResult};
+
+/// The driver's result shape.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DriverResult {
+    #[serde(default)]
+    command: String,
+    #[serde(default)]
+    row_count: Option<u64>,
+    #[serde(default)]
+    rows: Vec<Map<String, Value>>,
+    #[serde(default)]
+    fields: Vec<DriverField>,
+    #[serde(default)]
+    copy: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DriverField {
+    name: String,
+    #[serde(default, rename = "dataTypeID")]
+    data_type_id: Option<u32>,
+}
+
+/// Parse a driver result and normalize its values. `text_format` says the
+/// values are all Postgres text, so JSON columns still need parsing.
+pub fn query_result(raw: Value, text_format: bool) -> Result<QueryResult> {
+    let raw: DriverResult = serde_json::from_value(raw).context("Unexpected SQL result shape")?;
+
+    let columns: Vec<QueryColumn> = raw
+        .fields
+        .into_iter()
+        .map(|f| QueryColumn {
+            data_type: f.data_type_id.and_then(type_name).map(|s| s.to_string()),
+            type_oid: f.data_type_id,
+            name: f.name,
+        })
+        .collect();
+
+    let mut rows = raw.rows;
+    for row in &mut rows {
+        for column in &columns {
+            if let (Some(oid), Some(value)) = (column.type_oid, row.get_mut(&column.name)) {
+                normalize(oid, value, text_format);
+            }
+        }
+    }
+
+    Ok(QueryResult {
+        command: raw.command,
+        row_count: raw.row_count.unwrap_or(rows.len() as u64),
+        columns,
+        rows,
+        copy: raw.copy,
+    })
+}
+
+/// Name of a built-in type, by OID.
+fn type_name(oid: u32) -> Option<&'static str> {
+    Some(match oid {
+        16 => "bool",
+        17 => "bytea",
+        18 => "char",
+        19 => "name",
+        20 => "int8",
+        21 => "int2",
+        23 => "int4",
+        25 => "text",
+        26 => "oid",
+        114 => "json",
+        700 => "float4",
+        701 => "float8",
+        1042 => "bpchar",
+        1043 => "varchar",
+        1082 => "date",
+        1083 => "time",
+        1114 => "timestamp",
+        1184 => "timestamptz",
+        1186 => "interval",
+        1266 => "timetz",
+        1700 => "numeric",
+        2950 => "uuid",
+        3802 => "jsonb",
+        _ => return None,
+    })
+}
+
+/// Convert a value in place according to its column's type.
+fn normalize(oid: u32, value: &mut Value, text_format: bool) {
+    let Value::String(text) = value else {
+        if oid == 1700 {
+            // Exact decimals are strings, however the backend sent them
+            if let Value::Number(n) = value {
+                *value = Value::String(n.to_string());
+            }
+        }
+        return;
+    };
+
+    let converted = match type_name(oid) {
+        Some("bool") => match text.as_str() {
+            "t" | "true" => Some(Value::Bool(true)),
+            "f" | "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        Some("int2" | "int4" | "int8" | "oid") => text.parse::<i64>().ok().map(Value::from),
+        Some("float4" | "float8") => text
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number),
+        // An already-parsed JSON string must stay a string
+        Some("json" | "jsonb") if text_format => serde_json::from_str(text).ok(),
+        Some("timestamp") => NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+            .ok()
+            .map(|t| Value::String(t.format("%Y-%m-%dT%H:%M:%S%.f").to_string())),
+        Some("timestamptz") => DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z")
+            .ok()
+            .map(|t| {
+                Value::String(
+                    t.with_timezone(&Utc)
+                        .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                )
+            }),
+        _ => None,
+    };
+    if let Some(converted) = converted {
+        *value = converted;
+    }
+}
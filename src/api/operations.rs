@@ -0,0 +1,114 @@
+//! Diagnostics for failed Neon operations.
+//!
+//! A bare "operation failed" leaves the caller to open the console to learn
+//! why. When a waited-on operation fails, the client fetches the operation
+//! itself for its full error and lists the recent operations on the same
+//! branch or endpoint, since the cause is often an earlier one (a compute
+//! that never started, a conflicting change), and puts both in the error.
+
+use anyhow::Result;
+use serde::Deserialize;
+use std::fmt;
+
+use super::NeonClient;
+use crate::models::Operation;
+
+/// Most related operations included in an error.
+const MAX_RELATED: usize = 5;
+
+/// A waited-on operation failed.
+#[derive(Debug)]
+pub struct OperationFailed {
+    pub operation: Operation,
+    /// Recent operations on the same branch or endpoint, newest first.
+    pub related: Vec<Operation>,
+}
+
+impl fmt::Display for OperationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = &self.operation;
+        write!(f, "operation_failed: Operation {} ({})", op.id, op.action)?;
+        if let Some(branch_id) = &op.branch_id {
+            write!(f, " on branch {}", branch_id)?;
+        }
+        if let Some(endpoint_id) = &op.endpoint_id {
+            write!(f, " endpoint {}", endpoint_id)?;
+        }
+        write!(f, " {}", op.status)?;
+        if let Some(at) = &op.updated_at {
+            write!(f, " at {}", at)?;
+        }
+        write!(
+            f,
+            ": {}",
+            op.error.as_deref().unwrap_or("Neon gave no error details")
+        )?;
+
+        if !self.related.is_empty() {
+            write!(f, ". Recent related operations:")?;
+            for (i, other) in self.related.iter().enumerate() {
+                let separator = if i == 0 { " " } else { "; " };
+                write!(
+                    f,
+                    "{}{} ({}) {}",
+                    separator, other.id, other.action, other.status
+                )?;
+                if let Some(at) = &other.created_at {
+                    write!(f, " at {}", at)?;
+                }
+                if let Some(error) = &other.error {
+                    write!(f, ": {}", error)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for OperationFailed {}
+
+impl NeonClient {
+    /// Get a single operation.
+    pub async fn get_operation(&self, project_id: &str, operation_id: &str) -> Result<Operation> {
+        let endpoint = format!("/projects/{}/operations/{}", project_id, operation_id);
+
+        #[derive(Deserialize)]
+        struct OperationResponse {
+            operation: Operation,
+        }
+
+        let response: OperationResponse = self.get(&endpoint).await?;
+        Ok(response.operation)
+    }
+
+    /// The error for a failed operation, with its details and the recent
+    /// operations (from `recent`, newest first) on the same branch or
+    /// endpoint.
+    pub(super) async fn operation_failure(
+        &self,
+        failed: &Operation,
+        recent: &[Operation],
+    ) -> anyhow::Error {
+        // The listing may carry less than the operation itself
+        let operation = match self.get_operation(&failed.project_id, &failed.id).await {
+            Ok(operation) => operation,
+            Err(e) => {
+                tracing::debug!("Failed to fetch operation {}: {:#}", failed.id, e);
+                failed.clone()
+            }
+        };
+
+        let related = recent
+            .iter()
+            .filter(|op| op.id != operation.id)
+            .filter(|op| {
+                (op.branch_id.is_some() && op.branch_id == operation.branch_id)
+                    || (op.endpoint_id.is_some() && op.endpoint_id == operation.endpoint_id)
+            })
+            .take(MAX_RELATED)
+            .cloned()
+            .collect();
+
+        OperationFailed { operation, related }.into()
+    }
+}
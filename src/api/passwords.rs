@@ -0,0 +1,92 @@
+//! Role password cache.
+//!
+//! SQL over HTTP, direct Postgres connections, and `neon.connection_string`
+//! all need the password of the role they connect as, which only the Neon
+//! API's `reveal_password` call returns. Passwords are kept per project,
+//! branch, and role for `[sql] password_cache_secs` so queries don't pay for
+//! that call each time. They are held only in memory, never logged or
+//! persisted, and overwritten when evicted. A project's passwords are dropped
+//! along with its endpoint routes: when the daemon changes its branches or
+//! roles and when a query on it fails, in case a password was reset.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Project, branch, and role.
+type RoleKey = (String, String, String);
+
+/// A password, overwritten in memory when dropped.
+struct Secret(String);
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        // SAFETY: zero bytes are valid UTF-8
+        for byte in unsafe { self.0.as_bytes_mut() } {
+            // Volatile so the compiler can't skip writes to memory about to be freed
+            unsafe { std::ptr::write_volatile(byte, 0) };
+        }
+    }
+}
+
+struct Cached {
+    password: Secret,
+    fetched: Instant,
+}
+
+/// Revealed role passwords.
+pub struct RolePasswords {
+    ttl: Duration,
+    roles: Mutex<HashMap<RoleKey, Cached>>,
+}
+
+impl RolePasswords {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            roles: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// A role's password, if revealed within the TTL.
+    pub fn get(&self, project_id: &str, branch_id: &str, role: &str) -> Option<String> {
+        let mut roles = self.roles.lock().unwrap();
+        let key = key(project_id, branch_id, role);
+        match roles.get(&key) {
+            Some(cached) if cached.fetched.elapsed() < self.ttl => Some(cached.password.0.clone()),
+            Some(_) => {
+                roles.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn put(&self, project_id: &str, branch_id: &str, role: &str, password: &str) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        self.roles.lock().unwrap().insert(
+            key(project_id, branch_id, role),
+            Cached {
+                password: Secret(password.to_string()),
+                fetched: Instant::now(),
+            },
+        );
+    }
+
+    pub fn invalidate(&self, project_id: &str) {
+        self.roles
+            .lock()
+            .unwrap()
+            .retain(|(project, _, _), _| project != project_id);
+    }
+}
+
+fn key(project_id: &str, branch_id: &str, role: &str) -> RoleKey {
+    (
+        project_id.to_string(),
+        branch_id.to_string(),
+        role.to_string(),
+    )
+}
@@ -0,0 +1,473 @@
+//! Direct Postgres execution backend.
+//!
+//! An alternative to the HTTP SQL endpoint: statements run over real
+//! Postgres connections (TLS, direct compute host) kept in a pool keyed by
+//! project, endpoint, and database. Session state such as `SET` and temporary
+//! tables lives for the whole call, multi-statement scripts run in one round
+//! trip, and `COPY ... TO STDOUT` is supported.
+//!
+//! Results use the same shape as the HTTP endpoint (`command`, `rowCount`,
+//! `rows`, `fields`), with values in Postgres text format; the client then
+//! types them by column (see `normalize`).
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use futures_util::{SinkExt, TryStreamExt};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_postgres::types::{ToSql, Type};
+use tokio_postgres::{Client, SimpleQueryMessage};
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+use super::client::{strip_leading_comments, IsolationLevel, TxStatement};
+use crate::config::SqlConfig;
+
+/// Pool key: project, endpoint, database, role.
+pub type PoolKey = (String, String, String, String);
+
+/// Where and as whom to connect.
+pub struct ConnectTarget<'a> {
+    pub host: &'a str,
+    pub user: &'a str,
+    pub password: &'a str,
+    pub database: &'a str,
+}
+
+struct IdleConnection {
+    client: Client,
+    since: Instant,
+}
+
+/// Pool state reported by health checks.
+#[derive(Debug, Clone, Default)]
+pub struct PoolHealth {
+    pub idle: usize,
+    /// Failed connection attempts since the last successful one.
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+/// Idle connections per (project, endpoint, database, role).
+pub struct PgPool {
+    config: SqlConfig,
+    tls: MakeRustlsConnect,
+    idle: Mutex<HashMap<PoolKey, Vec<IdleConnection>>>,
+    failures: Mutex<(u32, Option<String>)>,
+}
+
+impl PgPool {
+    pub fn new(config: &SqlConfig) -> Result<Self> {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let tls = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .context("Failed to configure TLS")?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+        Ok(Self {
+            config: config.clone(),
+            tls: MakeRustlsConnect::new(tls),
+            idle: Mutex::new(HashMap::new()),
+            failures: Mutex::new((0, None)),
+        })
+    }
+
+    /// Idle connections and recent connection failures.
+    pub fn health(&self) -> PoolHealth {
+        let idle = self.idle.lock().unwrap().values().map(Vec::len).sum();
+        let (consecutive_failures, last_error) = self.failures.lock().unwrap().clone();
+        PoolHealth {
+            idle,
+            consecutive_failures,
+            last_error,
+        }
+    }
+
+    /// An idle connection for `key`, if one is still open and not expired.
+    pub fn checkout(&self, key: &PoolKey) -> Option<Client> {
+        let ttl = Duration::from_secs(self.config.idle_timeout_secs);
+        let mut idle = self.idle.lock().unwrap();
+        let connections = idle.get_mut(key)?;
+        while let Some(conn) = connections.pop() {
+            if !conn.client.is_closed() && conn.since.elapsed() < ttl {
+                return Some(conn.client);
+            }
+        }
+        None
+    }
+
+    /// Return a connection to the pool, dropping it when the pool is full
+    /// or the connection is closed.
+    pub fn checkin(&self, key: PoolKey, client: Client) {
+        if client.is_closed() {
+            return;
+        }
+        let mut idle = self.idle.lock().unwrap();
+        let connections = idle.entry(key).or_default();
+        if connections.len() < self.config.pool_size {
+            connections.push(IdleConnection {
+                client,
+                since: Instant::now(),
+            });
+        }
+    }
+
+    /// Open a new connection.
+    pub async fn connect(&self, target: &ConnectTarget<'_>) -> Result<Client> {
+        let mut config = tokio_postgres::Config::new();
+        config
+            .host(target.host)
+            .port(5432)
+            .user(target.user)
+            .password(target.password)
+            .dbname(target.database)
+            .ssl_mode(tokio_postgres::config::SslMode::Require)
+            .connect_timeout(Duration::from_secs(self.config.connect_timeout_secs))
+            .application_name("fgp-neon");
+
+        let connected = config
+            .connect(self.tls.clone())
+            .await
+            .with_context(|| format!("Failed to connect to {}", target.host));
+        let (client, connection) = {
+            let mut failures = self.failures.lock().unwrap();
+            match connected {
+                Ok(connected) => {
+                    *failures = (0, None);
+                    connected
+                }
+                Err(e) => {
+                    *failures = (failures.0 + 1, Some(format!("{:#}", e)));
+                    return Err(e);
+                }
+            }
+        };
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::debug!("Postgres connection closed: {}", e);
+            }
+        });
+        Ok(client)
+    }
+}
+
+/// Run `statement` and return the last result set in the HTTP endpoint's shape.
+///
+/// Without bind parameters the simple query protocol is used, so scripts
+/// with several statements run as one. With parameters the statement is
+/// prepared and parameters are bound by their declared types.
+pub async fn query(
+    client: &Client,
+    statement: &str,
+    params: &[Value],
+    max_bytes: usize,
+) -> Result<Value> {
+    if is_copy_out(statement) {
+        return copy_out(client, statement, max_bytes).await;
+    }
+    if params.is_empty() {
+        simple_query(client, statement).await
+    } else {
+        prepared_query(client, statement, params).await
+    }
+}
+
+/// Run one statement in a `READ ONLY` transaction, so Postgres refuses any
+/// write it attempts.
+pub async fn read_only_query(
+    client: &Client,
+    statement: &str,
+    params: &[Value],
+    max_bytes: usize,
+) -> Result<Value> {
+    client
+        .simple_query("BEGIN READ ONLY")
+        .await
+        .context("Failed to start read-only transaction")?;
+    match query(client, statement, params, max_bytes).await {
+        Ok(result) => {
+            client
+                .simple_query("COMMIT")
+                .await
+                .context("COMMIT failed")?;
+            Ok(result)
+        }
+        Err(e) => {
+            let _ = client.simple_query("ROLLBACK").await;
+            Err(e)
+        }
+    }
+}
+
+/// Run `statements` between `BEGIN` and `COMMIT`, rolling back and naming
+/// the statement when one fails.
+pub async fn transaction(
+    client: &Client,
+    statements: &[TxStatement],
+    isolation: IsolationLevel,
+    read_only: bool,
+    max_bytes: usize,
+) -> Result<Vec<Value>> {
+    let begin = format!(
+        "BEGIN ISOLATION LEVEL {}{}",
+        isolation.sql(),
+        if read_only { " READ ONLY" } else { "" }
+    );
+    client
+        .simple_query(&begin)
+        .await
+        .context("Failed to start transaction")?;
+
+    let mut results = Vec::with_capacity(statements.len());
+    for (i, statement) in statements.iter().enumerate() {
+        match query(client, &statement.query, &statement.params, max_bytes).await {
+            Ok(result) => results.push(result),
+            Err(e) => {
+                let _ = client.simple_query("ROLLBACK").await;
+                return Err(e.context(format!(
+                    "Statement {} of {} failed; transaction rolled back",
+                    i + 1,
+                    statements.len()
+                )));
+            }
+        }
+    }
+
+    client
+        .simple_query("COMMIT")
+        .await
+        .context("COMMIT failed; transaction rolled back")?;
+    Ok(results)
+}
+
+/// Run `COPY ... FROM STDIN`, sending each chunk `next_chunk` returns until
+/// it returns `None`. Returns the rows copied. An error from `next_chunk`
+/// aborts the COPY, so nothing is loaded.
+pub async fn copy_in<F>(client: &Client, statement: &str, mut next_chunk: F) -> Result<u64>
+where
+    F: FnMut() -> Result<Option<Vec<u8>>>,
+{
+    let sink = client
+        .copy_in::<_, Bytes>(statement)
+        .await
+        .context("COPY failed")?;
+    futures_util::pin_mut!(sink);
+    while let Some(chunk) = next_chunk()? {
+        sink.send(Bytes::from(chunk)).await.context("COPY failed")?;
+    }
+    sink.as_mut().finish().await.context("COPY failed")
+}
+
+async fn simple_query(client: &Client, statement: &str) -> Result<Value> {
+    let messages = client
+        .simple_query(statement)
+        .await
+        .context("SQL execution failed")?;
+
+    let mut fields = Vec::new();
+    let mut rows = Vec::new();
+    let mut row_count = 0;
+    for message in messages {
+        match message {
+            SimpleQueryMessage::RowDescription(columns) => {
+                fields = columns.iter().map(|c| field(c.name(), None)).collect();
+                rows.clear();
+            }
+            SimpleQueryMessage::Row(row) => {
+                let mut obj = Map::new();
+                for (i, column) in row.columns().iter().enumerate() {
+                    obj.insert(column.name().to_string(), serde_json::json!(row.get(i)));
+                }
+                rows.push(Value::Object(obj));
+            }
+            SimpleQueryMessage::CommandComplete(n) => row_count = n,
+            _ => {}
+        }
+    }
+
+    // The simple protocol doesn't report column types; describing the
+    // statement does, for a single statement that returned rows
+    if !fields.is_empty() && crate::sqltext::split_statements(statement).len() == 1 {
+        if let Ok(prepared) = client.prepare(statement).await {
+            if prepared.columns().len() == fields.len() {
+                fields = prepared
+                    .columns()
+                    .iter()
+                    .map(|c| field(c.name(), Some(c.type_().oid())))
+                    .collect();
+            }
+        }
+    }
+
+    Ok(result_value(statement, row_count, rows, fields))
+}
+
+async fn prepared_query(client: &Client, statement: &str, params: &[Value]) -> Result<Value> {
+    let prepared = client
+        .prepare(statement)
+        .await
+        .context("SQL execution failed")?;
+    if prepared.params().len() != params.len() {
+        anyhow::bail!(
+            "Query expects {} parameters, got {}",
+            prepared.params().len(),
+            params.len()
+        );
+    }
+
+    let bound = prepared
+        .params()
+        .iter()
+        .zip(params)
+        .map(|(ty, value)| bind(ty, value))
+        .collect::<Result<Vec<_>>>()?;
+    let refs: Vec<&(dyn ToSql + Sync)> = bound
+        .iter()
+        .map(|b| b.as_ref() as &(dyn ToSql + Sync))
+        .collect();
+    let result = client
+        .query(&prepared, &refs)
+        .await
+        .context("SQL execution failed")?;
+
+    let fields = prepared
+        .columns()
+        .iter()
+        .map(|c| field(c.name(), Some(c.type_().oid())))
+        .collect();
+    let mut rows = Vec::with_capacity(result.len());
+    for row in &result {
+        let mut obj = Map::new();
+        for (i, column) in row.columns().iter().enumerate() {
+            obj.insert(
+                column.name().to_string(),
+                text_value(row, i, column.type_())?,
+            );
+        }
+        rows.push(Value::Object(obj));
+    }
+
+    Ok(result_value(statement, rows.len() as u64, rows, fields))
+}
+
+async fn copy_out(client: &Client, statement: &str, max_bytes: usize) -> Result<Value> {
+    let stream = client.copy_out(statement).await.context("COPY failed")?;
+    futures_util::pin_mut!(stream);
+
+    let mut data = Vec::new();
+    while let Some(chunk) = stream.try_next().await.context("COPY failed")? {
+        if data.len() + chunk.len() > max_bytes {
+            anyhow::bail!(
+                "COPY output exceeds the {} byte limit. Add a WHERE clause or select fewer columns.",
+                max_bytes
+            );
+        }
+        data.extend_from_slice(&chunk);
+    }
+    let lines = data.iter().filter(|b| **b == b'\n').count();
+
+    let mut result = result_value(statement, lines as u64, Vec::new(), Vec::new());
+    result["copy"] = Value::String(String::from_utf8_lossy(&data).into_owned());
+    Ok(result)
+}
+
+fn result_value(statement: &str, row_count: u64, rows: Vec<Value>, fields: Vec<Value>) -> Value {
+    serde_json::json!({
+        "command": command(statement),
+        "rowCount": row_count,
+        "rows": rows,
+        "fields": fields,
+    })
+}
+
+fn field(name: &str, type_oid: Option<u32>) -> Value {
+    serde_json::json!({ "name": name, "dataTypeID": type_oid })
+}
+
+/// Command keyword of the last statement, as the HTTP endpoint reports it.
+fn command(statement: &str) -> String {
+    crate::sqltext::split_statements(statement)
+        .last()
+        .and_then(|s| strip_leading_comments(s).split_whitespace().next())
+        .map(|w| w.to_ascii_uppercase())
+        .unwrap_or_default()
+}
+
+fn is_copy_out(statement: &str) -> bool {
+    let lower = strip_leading_comments(statement).to_ascii_lowercase();
+    lower.starts_with("copy") && lower.contains("to stdout")
+}
+
+/// A JSON parameter as the Rust type Postgres declared for it.
+fn bind(ty: &Type, value: &Value) -> Result<Box<dyn ToSql + Sync + Send>> {
+    if value.is_null() {
+        return Ok(Box::new(None::<String>));
+    }
+    let mismatch = || anyhow::anyhow!("Parameter {} doesn't fit type {}", value, ty);
+
+    Ok(match *ty {
+        Type::BOOL => Box::new(value.as_bool().ok_or_else(mismatch)?),
+        Type::INT2 => Box::new(i16::try_from(value.as_i64().ok_or_else(mismatch)?)?),
+        Type::INT4 => Box::new(i32::try_from(value.as_i64().ok_or_else(mismatch)?)?),
+        Type::INT8 => Box::new(value.as_i64().ok_or_else(mismatch)?),
+        Type::FLOAT4 => Box::new(value.as_f64().ok_or_else(mismatch)? as f32),
+        Type::FLOAT8 => Box::new(value.as_f64().ok_or_else(mismatch)?),
+        Type::JSON | Type::JSONB => Box::new(value.clone()),
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => {
+            Box::new(match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+        }
+        _ => anyhow::bail!(
+            "Parameter type {} isn't supported by the postgres backend; cast the placeholder (e.g. $1::text) or use backend \"http\"",
+            ty
+        ),
+    })
+}
+
+/// Column `i` of `row` in Postgres text format.
+fn text_value(row: &tokio_postgres::Row, i: usize, ty: &Type) -> Result<Value> {
+    fn text<T: ToString>(v: Option<T>) -> Value {
+        v.map(|v| Value::String(v.to_string()))
+            .unwrap_or(Value::Null)
+    }
+
+    Ok(match *ty {
+        Type::BOOL => text(row.try_get::<_, Option<bool>>(i)?.map(|b| if b { "t" } else { "f" })),
+        Type::INT2 => text(row.try_get::<_, Option<i16>>(i)?),
+        Type::INT4 => text(row.try_get::<_, Option<i32>>(i)?),
+        Type::INT8 => text(row.try_get::<_, Option<i64>>(i)?),
+        Type::OID => text(row.try_get::<_, Option<u32>>(i)?),
+        Type::FLOAT4 => text(row.try_get::<_, Option<f32>>(i)?),
+        Type::FLOAT8 => text(row.try_get::<_, Option<f64>>(i)?),
+        Type::JSON | Type::JSONB => text(row.try_get::<_, Option<Value>>(i)?),
+        Type::BYTEA => text(row.try_get::<_, Option<Vec<u8>>>(i)?.map(|bytes| {
+            let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("\\x{}", hex)
+        })),
+        Type::DATE => text(row.try_get::<_, Option<NaiveDate>>(i)?),
+        Type::TIMESTAMP => text(
+            row.try_get::<_, Option<NaiveDateTime>>(i)?
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S%.f")),
+        ),
+        Type::TIMESTAMPTZ => text(
+            row.try_get::<_, Option<DateTime<Utc>>>(i)?
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S%.f+00")),
+        ),
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => {
+            text(row.try_get::<_, Option<String>>(i)?)
+        }
+        _ => anyhow::bail!(
+            "Column {} has type {}, which the postgres backend can't decode with parameters bound; cast it to text",
+            row.columns()[i].name(),
+            ty
+        ),
+    })
+}
@@ -0,0 +1,104 @@
+//! Table privilege inspection and changes over the SQL endpoint.
+
+use anyhow::{Context, Result};
+
+use super::client::quote_ident;
+use super::NeonClient;
+use crate::models::TableGrant;
+
+/// Privileges that let a role change table contents.
+const WRITE_PRIVILEGES: &[&str] = &["INSERT", "UPDATE", "DELETE", "TRUNCATE"];
+
+impl NeonClient {
+    /// Write privileges on user tables held by roles other than each table's owner.
+    pub async fn get_write_grants(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+    ) -> Result<Vec<TableGrant>> {
+        let privileges = WRITE_PRIVILEGES
+            .iter()
+            .map(|p| format!("'{}'", p))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let query = format!(
+            "SELECT n.nspname AS schema, c.relname AS table, COALESCE(r.rolname, 'PUBLIC') AS role, a.privilege_type AS privilege FROM pg_catalog.pg_class c JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace CROSS JOIN LATERAL aclexplode(c.relacl) a LEFT JOIN pg_catalog.pg_roles r ON r.oid = a.grantee WHERE c.relkind IN ('r', 'p') AND a.grantee <> c.relowner AND a.privilege_type IN ({}) AND n.nspname NOT IN ('pg_catalog', 'information_schema') AND n.nspname NOT LIKE 'pg\\_%' ORDER BY 1, 2, 3, 4",
+            privileges
+        );
+
+        let rows = self
+            .catalog_rows(project_id, branch_id, database, &query)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| serde_json::from_value(row.clone()).ok())
+            .collect())
+    }
+
+    /// Revoke each grant.
+    pub async fn revoke_grants(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        grants: &[TableGrant],
+    ) -> Result<()> {
+        for grant in grants {
+            check_privilege(&grant.privilege)?;
+            let statement = format!(
+                "REVOKE {} ON TABLE {}.{} FROM {}",
+                grant.privilege,
+                quote_ident(&grant.schema),
+                quote_ident(&grant.table),
+                grantee(&grant.role)
+            );
+            self.run_sql(project_id, branch_id, database, &statement)
+                .await
+                .with_context(|| format!("Failed: {}", statement))?;
+        }
+        Ok(())
+    }
+
+    /// Re-grant each grant.
+    pub async fn apply_grants(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        grants: &[TableGrant],
+    ) -> Result<()> {
+        for grant in grants {
+            check_privilege(&grant.privilege)?;
+            let statement = format!(
+                "GRANT {} ON TABLE {}.{} TO {}",
+                grant.privilege,
+                quote_ident(&grant.schema),
+                quote_ident(&grant.table),
+                grantee(&grant.role)
+            );
+            self.run_sql(project_id, branch_id, database, &statement)
+                .await
+                .with_context(|| format!("Failed: {}", statement))?;
+        }
+        Ok(())
+    }
+}
+
+/// Privileges are spliced into SQL, so only known names are accepted.
+fn check_privilege(privilege: &str) -> Result<()> {
+    if !WRITE_PRIVILEGES.contains(&privilege) {
+        anyhow::bail!("Unsupported privilege {:?}", privilege);
+    }
+    Ok(())
+}
+
+/// `PUBLIC` is a keyword, not a role name, so it must not be quoted.
+fn grantee(role: &str) -> String {
+    if role == "PUBLIC" {
+        role.to_string()
+    } else {
+        quote_ident(role)
+    }
+}
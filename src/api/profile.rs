@@ -0,0 +1,148 @@
+//! Bounded data profiling for a single table.
+
+use anyhow::Result;
+use serde_json::Value;
+
+use super::client::{quote_ident, quote_literal, result_rows, value_as_f64};
+use super::NeonClient;
+
+/// Number of distinct sample values reported per column.
+const SAMPLE_VALUES: usize = 5;
+
+impl NeonClient {
+    /// Profile a table over at most `sample_rows` rows.
+    ///
+    /// Returns the planner's row estimate plus, per column, the declared type,
+    /// an inferred type for text columns, null percentage, distinct count, and a
+    /// few sample values. Everything except the row estimate is computed over
+    /// the sample, so the pass stays bounded on large tables.
+    pub async fn profile_table(
+        &self,
+        project_id: &str,
+        branch_id: &str,
+        database: &str,
+        schema: &str,
+        table: &str,
+        sample_rows: usize,
+    ) -> Result<Value> {
+        let columns_query = format!(
+            "SELECT column_name, data_type FROM information_schema.columns WHERE table_schema = {} AND table_name = {} ORDER BY ordinal_position",
+            quote_literal(schema),
+            quote_literal(table)
+        );
+        let columns_result = self
+            .run_sql(project_id, branch_id, database, &columns_query)
+            .await?;
+
+        let columns: Vec<(String, String)> = result_rows(&columns_result)
+            .iter()
+            .filter_map(|row| {
+                Some((
+                    row.get("column_name")?.as_str()?.to_string(),
+                    row.get("data_type")?.as_str()?.to_string(),
+                ))
+            })
+            .collect();
+
+        if columns.is_empty() {
+            anyhow::bail!("Table {}.{} not found or has no columns", schema, table);
+        }
+
+        let estimate_query = format!(
+            "SELECT c.reltuples::bigint AS estimate FROM pg_catalog.pg_class c JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace WHERE n.nspname = {} AND c.relname = {}",
+            quote_literal(schema),
+            quote_literal(table)
+        );
+        let estimate_result = self
+            .run_sql(project_id, branch_id, database, &estimate_query)
+            .await?;
+        let estimated_rows = result_rows(&estimate_result)
+            .first()
+            .and_then(|row| row.get("estimate"))
+            .and_then(value_as_f64)
+            .map(|v| v.max(0.0) as i64);
+
+        // One aggregate pass over the sample covers every column
+        let mut selects = vec!["count(*) AS sampled".to_string()];
+        for (i, (name, data_type)) in columns.iter().enumerate() {
+            let col = quote_ident(name);
+            selects.push(format!("count({col}) AS c{i}_nonnull"));
+            selects.push(format!("count(DISTINCT {col}::text) AS c{i}_distinct"));
+            selects.push(format!(
+                "(SELECT json_agg(v) FROM (SELECT DISTINCT {col}::text AS v FROM s WHERE {col} IS NOT NULL LIMIT {SAMPLE_VALUES}) x)::text AS c{i}_samples"
+            ));
+            if is_text_type(data_type) {
+                selects.push(format!("{} AS c{i}_inferred", infer_type_expr(&col)));
+            }
+        }
+
+        let profile_query = format!(
+            "WITH s AS (SELECT * FROM {}.{} LIMIT {}) SELECT {} FROM s",
+            quote_ident(schema),
+            quote_ident(table),
+            sample_rows,
+            selects.join(", ")
+        );
+        let profile_result = self
+            .run_sql(project_id, branch_id, database, &profile_query)
+            .await?;
+        let row = result_rows(&profile_result)
+            .first()
+            .cloned()
+            .unwrap_or(Value::Null);
+
+        let count = |key: &str| row.get(key).and_then(value_as_f64).unwrap_or(0.0);
+        let sampled = count("sampled");
+
+        let column_reports: Vec<Value> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, (name, data_type))| {
+                let non_null = count(&format!("c{i}_nonnull"));
+                let null_pct = if sampled > 0.0 {
+                    (sampled - non_null) / sampled * 100.0
+                } else {
+                    0.0
+                };
+                let samples = match row.get(format!("c{i}_samples")) {
+                    Some(Value::String(s)) => serde_json::from_str(s).unwrap_or_default(),
+                    Some(Value::Array(values)) => Value::Array(values.clone()),
+                    _ => Value::Array(Vec::new()),
+                };
+                let inferred = row
+                    .get(format!("c{i}_inferred"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(data_type);
+
+                serde_json::json!({
+                    "name": name,
+                    "data_type": data_type,
+                    "inferred_type": inferred,
+                    "null_pct": (null_pct * 100.0).round() / 100.0,
+                    "distinct": count(&format!("c{i}_distinct")) as i64,
+                    "sample_values": samples,
+                })
+            })
+            .collect();
+
+        Ok(serde_json::json!({
+            "schema": schema,
+            "table": table,
+            "estimated_rows": estimated_rows,
+            "sampled_rows": sampled as i64,
+            "columns": column_reports,
+        }))
+    }
+}
+
+/// Whether a column type is free-form text worth running type inference on.
+fn is_text_type(data_type: &str) -> bool {
+    matches!(data_type, "text" | "character varying" | "character")
+}
+
+/// SQL expression guessing the narrowest type every non-null value fits.
+fn infer_type_expr(col: &str) -> String {
+    format!(
+        r"CASE WHEN count({col}) = 0 THEN NULL WHEN bool_and({col} ~ '^-?[0-9]+$') THEN 'integer' WHEN bool_and({col} ~ '^-?[0-9]+(\.[0-9]+)?$') THEN 'numeric' WHEN bool_and(lower({col}) IN ('true', 'false', 't', 'f', 'yes', 'no')) THEN 'boolean' WHEN bool_and({col} ~ '^[0-9]{{4}}-[0-9]{{2}}-[0-9]{{2}}') THEN 'timestamp' ELSE 'text' END"
+    )
+}
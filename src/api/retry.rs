@@ -0,0 +1,106 @@
+//! Retry policy for Neon API requests, and the errors requests fail with.
+//!
+//! Rate-limited (429) requests wait for `Retry-After` when the API sends
+//! one; transient failures (5xx, timeouts, refused connections) back off
+//! exponentially with jitter. POSTs are only retried when the API can't have
+//! acted on them (429, 503, or a connection that never opened), so a create
+//! isn't repeated.
+
+use reqwest::{Method, Response, StatusCode};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::RetryConfig;
+
+/// The API kept rate limiting a request after every attempt.
+#[derive(Debug)]
+pub struct RateLimited {
+    pub attempts: u32,
+    /// How long the API asked callers to wait, if it said.
+    pub retry_after: Option<Duration>,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rate_limited: Neon API rate limit exceeded after {} attempt(s)",
+            self.attempts
+        )?;
+        if let Some(wait) = self.retry_after {
+            write!(f, "; retry after {}s", wait.as_secs().max(1))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// The API answered with a status that isn't retried.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    /// Response body, usually the API's JSON error message.
+    pub body: String,
+}
+
+impl ApiError {
+    /// Whether `error` is, or was caused by, a 404 from the API.
+    pub fn is_not_found(error: &anyhow::Error) -> bool {
+        error
+            .downcast_ref::<Self>()
+            .is_some_and(|e| e.status == StatusCode::NOT_FOUND)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "API request failed: {} - {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Whether a response with `status` is worth retrying.
+pub(super) fn retryable_status(status: StatusCode, method: &Method) -> bool {
+    match status {
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => true,
+        StatusCode::INTERNAL_SERVER_ERROR
+        | StatusCode::BAD_GATEWAY
+        | StatusCode::GATEWAY_TIMEOUT => *method != Method::POST,
+        _ => false,
+    }
+}
+
+/// Whether a failed send is worth retrying.
+pub(super) fn retryable_error(error: &reqwest::Error, method: &Method) -> bool {
+    error.is_connect() || (*method != Method::POST && (error.is_timeout() || error.is_request()))
+}
+
+/// The wait a response's `Retry-After` header (in seconds) asks for.
+pub(super) fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Wait before retry number `attempt` (1-based): the initial backoff doubled
+/// per attempt, capped, then scaled to 50-100% so clients don't retry in step.
+pub(super) fn backoff(config: &RetryConfig, attempt: u32) -> Duration {
+    let base = config
+        .initial_backoff_ms
+        .saturating_mul(1 << attempt.saturating_sub(1).min(20))
+        .min(config.max_backoff_ms);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let jitter = 0.5 + f64::from(nanos % 1000) / 2000.0;
+    Duration::from_millis((base as f64 * jitter) as u64)
+}
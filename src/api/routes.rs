@@ -0,0 +1,74 @@
+//! Branch-to-endpoint routing cache.
+//!
+//! Running SQL and building connection strings need the host of the
+//! branch's endpoint, and listing a project's endpoints costs an API round
+//! trip per call. Listings are kept per project for
+//! `[sql] endpoint_cache_secs` and dropped when the daemon creates, updates,
+//! or deletes endpoints or branches in the project, when the operations
+//! watcher sees such a change made elsewhere, and when a query on a cached
+//! endpoint fails, in case it moved or was removed. Starting and suspending
+//! an endpoint only update its cached state.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::models::Endpoint;
+
+struct Listing {
+    endpoints: Vec<Endpoint>,
+    fetched: Instant,
+}
+
+/// Cached endpoint listings per project.
+pub struct EndpointRoutes {
+    ttl: Duration,
+    projects: Mutex<HashMap<String, Listing>>,
+}
+
+impl EndpointRoutes {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            projects: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// A project's endpoints, if listed within the TTL.
+    pub fn get(&self, project_id: &str) -> Option<Vec<Endpoint>> {
+        let projects = self.projects.lock().unwrap();
+        projects
+            .get(project_id)
+            .filter(|l| l.fetched.elapsed() < self.ttl)
+            .map(|l| l.endpoints.clone())
+    }
+
+    pub fn put(&self, project_id: &str, endpoints: Vec<Endpoint>) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        self.projects.lock().unwrap().insert(
+            project_id.to_string(),
+            Listing {
+                endpoints,
+                fetched: Instant::now(),
+            },
+        );
+    }
+
+    /// Record an endpoint's new state, such as `active` after a query ran on
+    /// it; keeps cold-start tracking right while the listing is cached.
+    pub fn set_state(&self, project_id: &str, endpoint_id: &str, state: &str) {
+        let mut projects = self.projects.lock().unwrap();
+        if let Some(endpoint) = projects
+            .get_mut(project_id)
+            .and_then(|l| l.endpoints.iter_mut().find(|e| e.id == endpoint_id))
+        {
+            endpoint.current_state = Some(state.to_string());
+        }
+    }
+
+    pub fn invalidate(&self, project_id: &str) {
+        self.projects.lock().unwrap().remove(project_id);
+    }
+}
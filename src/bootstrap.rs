@@ -0,0 +1,213 @@
+//! Day-zero project setup.
+//!
+//! `neon.bootstrap` applies a `[bootstrap.<name>]` profile to a branch:
+//! roles, then databases, then extensions in each database, then baseline
+//! migrations, and finally the roles' grant templates so they cover the
+//! tables the migrations created. Roles and databases that already exist are
+//! left alone, so a profile can be re-applied; migrations are not tracked
+//! and run again.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::api::{quote_ident, NeonClient};
+use crate::config::BootstrapProfile;
+use crate::script::{self, ScriptTarget, DEFAULT_DATABASE};
+use crate::watch::EventBus;
+
+/// Wait for pending operations before each API mutation.
+const OPERATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Grant templates a bootstrap role can use.
+const GRANT_TEMPLATES: &[&str] = &["read_only", "read_write", "owner"];
+
+/// What a bootstrap did.
+#[derive(Debug, Serialize)]
+pub struct BootstrapOutcome {
+    pub profile: String,
+    pub project_id: String,
+    pub branch_id: String,
+    pub created_roles: Vec<String>,
+    pub created_databases: Vec<String>,
+    /// Databases the profile's extensions were created in.
+    pub extension_databases: Vec<String>,
+    pub migration_statements: usize,
+    pub grants: Vec<String>,
+}
+
+/// Check a profile before anything is changed.
+fn validate(profile: &BootstrapProfile) -> Result<()> {
+    for role in &profile.roles {
+        if let Some(grant) = &role.grant {
+            if !GRANT_TEMPLATES.contains(&grant.as_str()) {
+                anyhow::bail!(
+                    "Role {} has unknown grant template {:?}; use one of: {}",
+                    role.name,
+                    grant,
+                    GRANT_TEMPLATES.join(", ")
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Apply a bootstrap profile to a branch.
+///
+/// Database owners must already be resolved; SQL runs as each database's
+/// configured role, the same one `neon.sql` uses.
+///
+/// Each migration file's statements are published as `script_statement`
+/// progress events with run id `bootstrap:<profile>:<file>`.
+pub async fn run(
+    client: &NeonClient,
+    events: &EventBus,
+    profile_name: &str,
+    profile: &BootstrapProfile,
+    project_id: &str,
+    branch_id: &str,
+) -> Result<BootstrapOutcome> {
+    validate(profile)?;
+
+    let migrations = profile
+        .migrations
+        .iter()
+        .map(|path| script::read(path, "migration"))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut outcome = BootstrapOutcome {
+        profile: profile_name.to_string(),
+        project_id: project_id.to_string(),
+        branch_id: branch_id.to_string(),
+        created_roles: Vec::new(),
+        created_databases: Vec::new(),
+        extension_databases: Vec::new(),
+        migration_statements: 0,
+        grants: Vec::new(),
+    };
+
+    client.wait_for_idle(project_id, OPERATION_TIMEOUT).await?;
+
+    let existing_roles = client.list_roles(project_id, branch_id).await?;
+    for role in &profile.roles {
+        if existing_roles.iter().any(|r| r.name == role.name)
+            || outcome.created_roles.contains(&role.name)
+        {
+            continue;
+        }
+        client
+            .create_role(project_id, branch_id, &role.name)
+            .await
+            .with_context(|| format!("Failed to create role {}", role.name))?;
+        client.wait_for_idle(project_id, OPERATION_TIMEOUT).await?;
+        outcome.created_roles.push(role.name.clone());
+    }
+
+    let existing_databases = client.list_databases(project_id, branch_id).await?;
+    for database in &profile.databases {
+        if existing_databases.iter().any(|d| d.name == database.name) {
+            continue;
+        }
+        let owner = database
+            .owner
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Database {} has no owner", database.name))?;
+        client
+            .create_database(project_id, branch_id, &database.name, owner)
+            .await
+            .with_context(|| format!("Failed to create database {}", database.name))?;
+        client.wait_for_idle(project_id, OPERATION_TIMEOUT).await?;
+        outcome.created_databases.push(database.name.clone());
+    }
+
+    if !profile.extensions.is_empty() {
+        let databases: Vec<&str> = if profile.databases.is_empty() {
+            vec![DEFAULT_DATABASE]
+        } else {
+            profile.databases.iter().map(|d| d.name.as_str()).collect()
+        };
+        for database in databases {
+            let target = ScriptTarget {
+                project_id,
+                branch_id,
+                database,
+            };
+            script::create_extensions(client, &target, &profile.extensions).await?;
+            outcome.extension_databases.push(database.to_string());
+        }
+    }
+
+    let migrations_database = profile
+        .migrations_database
+        .as_deref()
+        .or_else(|| profile.databases.first().map(|d| d.name.as_str()))
+        .unwrap_or(DEFAULT_DATABASE);
+    let target = ScriptTarget {
+        project_id,
+        branch_id,
+        database: migrations_database,
+    };
+    for (path, statements) in &migrations {
+        let run_id = format!("bootstrap:{}:{}", profile_name, path);
+        script::run(client, Some(events), &target, &run_id, statements, false)
+            .await
+            .with_context(|| format!("Migration {} failed", path))?;
+        outcome.migration_statements += statements.len();
+    }
+
+    for role in &profile.roles {
+        let Some(grant) = role.grant.as_deref() else {
+            continue;
+        };
+        for statement in grant_statements(grant, &role.database, &role.name) {
+            client
+                .run_sql(project_id, branch_id, &role.database, &statement)
+                .await
+                .with_context(|| format!("Failed to grant {} to {}", grant, role.name))?;
+        }
+        outcome
+            .grants
+            .push(format!("{} on {}: {}", role.name, role.database, grant));
+    }
+
+    Ok(outcome)
+}
+
+/// Statements implementing a grant template on the `public` schema.
+///
+/// Default privileges apply to objects later created by the role running
+/// the grant, which is the role migrations run as.
+fn grant_statements(template: &str, database: &str, role: &str) -> Vec<String> {
+    let role = quote_ident(role);
+    let (schema, tables, sequences) = match template {
+        "read_only" => ("USAGE", "SELECT", "SELECT"),
+        "read_write" => ("USAGE", "SELECT, INSERT, UPDATE, DELETE", "USAGE, SELECT"),
+        _ => ("ALL", "ALL", "ALL"),
+    };
+
+    vec![
+        format!(
+            "GRANT CONNECT ON DATABASE {} TO {}",
+            quote_ident(database),
+            role
+        ),
+        format!("GRANT {} ON SCHEMA public TO {}", schema, role),
+        format!(
+            "GRANT {} ON ALL TABLES IN SCHEMA public TO {}",
+            tables, role
+        ),
+        format!(
+            "GRANT {} ON ALL SEQUENCES IN SCHEMA public TO {}",
+            sequences, role
+        ),
+        format!(
+            "ALTER DEFAULT PRIVILEGES IN SCHEMA public GRANT {} ON TABLES TO {}",
+            tables, role
+        ),
+        format!(
+            "ALTER DEFAULT PRIVILEGES IN SCHEMA public GRANT {} ON SEQUENCES TO {}",
+            sequences, role
+        ),
+    ]
+}
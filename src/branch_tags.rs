@@ -0,0 +1,289 @@
+//! Branch ownership tags.
+//!
+//! In a shared org every branch should have an accountable `owner`, a `team`
+//! its cost belongs to, and, for temporary work, an `expires_at`.
+//! `neon.create_branch` stores these as Neon branch annotations, so anyone
+//! listing the project sees them, and records them in a local index at
+//! `~/.fgp/services/neon/branch_tags.json`. `neon.tag_branch` sets them on
+//! existing branches, in both the annotation and the index; the index takes
+//! precedence over annotations.
+//! Listings report each branch's tags and filter on them, so cleanup and
+//! cost reports can find a branch's owner. `[branch_tags] required` makes
+//! tags mandatory when creating a branch.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use crate::config::{state_path, TagName};
+use crate::models::Branch;
+
+/// Longest `expires_in_hours` accepted: a year.
+const MAX_EXPIRY_HOURS: f64 = 8760.0;
+
+/// Serializes read-modify-write of the index file across tasks.
+static STORE: Mutex<()> = Mutex::new(());
+
+/// A branch's owner, team, and expiry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BranchTags {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Tags a call sets. `Some` of an empty string, or `Some(None)` for the
+/// expiry, clears a tag.
+#[derive(Debug, Default)]
+pub struct TagUpdate {
+    pub owner: Option<String>,
+    pub team: Option<String>,
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+}
+
+impl TagUpdate {
+    /// Tags from a call's `owner`, `team`, and `expires_at` (RFC 3339) or
+    /// `expires_in_hours`.
+    pub fn from_params(params: &HashMap<String, Value>) -> Result<Self> {
+        let text = |name: &str| -> Result<Option<String>> {
+            match params.get(name) {
+                None | Some(Value::Null) => Ok(None),
+                Some(Value::String(s)) => Ok(Some(s.trim().to_string())),
+                Some(_) => anyhow::bail!("{} must be a string", name),
+            }
+        };
+
+        let expires_at = match (
+            text("expires_at")?,
+            params.get("expires_in_hours").and_then(|v| v.as_f64()),
+        ) {
+            (Some(_), Some(_)) => anyhow::bail!("Pass expires_at or expires_in_hours, not both"),
+            (Some(at), None) if at.is_empty() => Some(None),
+            (Some(at), None) => Some(Some(
+                DateTime::parse_from_rfc3339(&at)
+                    .with_context(|| format!("expires_at {:?} is not an RFC 3339 time", at))?
+                    .with_timezone(&Utc),
+            )),
+            (None, Some(hours)) if hours > 0.0 && hours <= MAX_EXPIRY_HOURS => Some(Some(
+                Utc::now()
+                    .checked_add_signed(ChronoDuration::seconds((hours * 3600.0) as i64))
+                    .ok_or_else(|| anyhow::anyhow!("expires_in_hours is too far in the future"))?,
+            )),
+            (None, Some(_)) => anyhow::bail!(
+                "expires_in_hours must be more than 0 and at most {}",
+                MAX_EXPIRY_HOURS
+            ),
+            (None, None) => None,
+        };
+
+        Ok(Self {
+            owner: text("owner")?,
+            team: text("team")?,
+            expires_at,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.owner.is_none() && self.team.is_none() && self.expires_at.is_none()
+    }
+}
+
+impl BranchTags {
+    pub fn is_empty(&self) -> bool {
+        self.owner.is_none() && self.team.is_none() && self.expires_at.is_none()
+    }
+
+    /// Fail unless every tag in `required` is set.
+    pub fn check_required(&self, required: &[TagName]) -> Result<()> {
+        let missing: Vec<&str> = required
+            .iter()
+            .filter_map(|name| match name {
+                TagName::Owner if self.owner.is_none() => Some("owner"),
+                TagName::Team if self.team.is_none() => Some("team"),
+                TagName::ExpiresAt if self.expires_at.is_none() => {
+                    Some("expires_at or expires_in_hours")
+                }
+                _ => None,
+            })
+            .collect();
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "[branch_tags] requires tags on new branches; pass {}",
+                missing.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Annotation key/values for a new branch.
+    pub fn annotation(&self) -> BTreeMap<String, String> {
+        let mut annotation = BTreeMap::new();
+        if let Some(owner) = &self.owner {
+            annotation.insert("owner".to_string(), owner.clone());
+        }
+        if let Some(team) = &self.team {
+            annotation.insert("team".to_string(), team.clone());
+        }
+        if let Some(at) = self.expires_at {
+            annotation.insert("expires_at".to_string(), at.to_rfc3339());
+        }
+        annotation
+    }
+
+    fn from_annotation(annotation: &BTreeMap<String, String>) -> Self {
+        Self {
+            owner: annotation.get("owner").cloned(),
+            team: annotation.get("team").cloned(),
+            expires_at: annotation
+                .get("expires_at")
+                .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+                .map(|at| at.with_timezone(&Utc)),
+        }
+    }
+
+    /// These tags with the ones `update` sets replaced.
+    pub fn apply(mut self, update: &TagUpdate) -> Self {
+        if let Some(owner) = &update.owner {
+            self.owner = Some(owner.clone()).filter(|o| !o.is_empty());
+        }
+        if let Some(team) = &update.team {
+            self.team = Some(team.clone()).filter(|t| !t.is_empty());
+        }
+        if let Some(at) = update.expires_at {
+            self.expires_at = at;
+        }
+        self
+    }
+
+    pub fn expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+/// Listing filters on tags.
+#[derive(Debug, Default)]
+pub struct TagFilter {
+    pub owner: Option<String>,
+    pub team: Option<String>,
+    /// Only branches without an owner.
+    pub unowned: bool,
+    /// Only branches past their `expires_at`.
+    pub expired: bool,
+}
+
+impl TagFilter {
+    /// Filters from a listing's `owner`, `team`, `unowned`, and `expired`.
+    pub fn from_params(params: &HashMap<String, Value>) -> Self {
+        let flag = |name: &str| params.get(name).and_then(|v| v.as_bool()).unwrap_or(false);
+        let text = |name: &str| params.get(name).and_then(|v| v.as_str()).map(String::from);
+        Self {
+            owner: text("owner"),
+            team: text("team"),
+            unowned: flag("unowned"),
+            expired: flag("expired"),
+        }
+    }
+
+    pub fn matches(&self, tags: &BranchTags, now: DateTime<Utc>) -> bool {
+        self.owner
+            .as_ref()
+            .is_none_or(|owner| tags.owner.as_ref() == Some(owner))
+            && self
+                .team
+                .as_ref()
+                .is_none_or(|team| tags.team.as_ref() == Some(team))
+            && (!self.unowned || tags.owner.is_none())
+            && (!self.expired || tags.expired(now))
+    }
+}
+
+/// A branch's tags in the local index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedTags {
+    project_id: String,
+    branch_id: String,
+    #[serde(flatten)]
+    tags: BranchTags,
+    updated_at: DateTime<Utc>,
+}
+
+/// Locally indexed tags by branch id.
+pub struct TagIndex {
+    tags: HashMap<String, BranchTags>,
+}
+
+impl TagIndex {
+    /// Read the index.
+    pub fn load() -> Result<Self> {
+        let _guard = STORE.lock().unwrap();
+        let tags = load()?
+            .into_iter()
+            .map(|entry| (entry.branch_id, entry.tags))
+            .collect();
+        Ok(Self { tags })
+    }
+
+    /// A branch's tags: from the index if set there, else its annotations.
+    pub fn tags(&self, branch: &Branch) -> BranchTags {
+        match self.tags.get(&branch.id) {
+            Some(indexed) => indexed.clone(),
+            None => BranchTags::from_annotation(&branch.annotations),
+        }
+    }
+}
+
+/// Apply `update` to a branch's tags in the index, starting from `current`;
+/// returns the branch's tags afterwards.
+pub fn set(
+    project_id: &str,
+    branch_id: &str,
+    current: BranchTags,
+    update: &TagUpdate,
+) -> Result<BranchTags> {
+    let tags = current.apply(update);
+    let indexed = IndexedTags {
+        project_id: project_id.to_string(),
+        branch_id: branch_id.to_string(),
+        tags: tags.clone(),
+        updated_at: Utc::now(),
+    };
+    modify(|entries| {
+        entries.retain(|e| e.branch_id != branch_id);
+        entries.push(indexed);
+    })?;
+    Ok(tags)
+}
+
+/// Drop a deleted branch from the index.
+pub fn forget(branch_id: &str) -> Result<()> {
+    modify(|entries| entries.retain(|e| e.branch_id != branch_id))
+}
+
+fn modify(change: impl FnOnce(&mut Vec<IndexedTags>)) -> Result<()> {
+    let _guard = STORE.lock().unwrap();
+    let mut entries = load()?;
+    change(&mut entries);
+
+    let path = state_path("branch_tags.json");
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create state directory")?;
+    }
+    std::fs::write(&path, serde_json::to_vec_pretty(&entries)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn load() -> Result<Vec<IndexedTags>> {
+    let path = state_path("branch_tags.json");
+    match std::fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
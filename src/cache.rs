@@ -0,0 +1,246 @@
+//! TTL cache for read-only metadata methods.
+//!
+//! Repeated reads of the project list, branches, databases, or a table's
+//! schema are answered from memory for a per-method TTL instead of calling
+//! the Neon API each time. A successful mutation drops what it may have
+//! changed: cached results for the same project and any org-wide results.
+//! SQL only changes a project's catalog, so a query that creates, alters, or
+//! drops objects drops just the project's catalog results, and other queries
+//! drop nothing.
+//! Callers pass `fresh: true` to skip the cache for one call (its result
+//! still refreshes the cache).
+
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::CacheConfig;
+
+/// Cached methods whose results come from a database's catalog.
+const CATALOG_METHODS: &[&str] = &[
+    "databases",
+    "tables",
+    "schema",
+    "table_detail",
+    "introspect",
+    "views",
+    "functions",
+    "extensions",
+];
+
+struct Entry {
+    /// Project the result belongs to; `None` for org-wide results.
+    project_id: Option<String>,
+    value: Value,
+    stored: Instant,
+    expires: Instant,
+}
+
+/// Cached method results.
+pub struct ResponseCache {
+    config: CacheConfig,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl ResponseCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Key for a call whose result is cached, or `None` when the method has
+    /// no TTL or caching is off.
+    pub fn key(&self, method: &str, params: &HashMap<String, Value>) -> Option<String> {
+        let name = method.strip_prefix("neon.").unwrap_or(method);
+        if !self.config.enabled || !self.config.ttls.contains_key(name) {
+            return None;
+        }
+        let params: BTreeMap<&String, &Value> = params
+            .iter()
+            .filter(|(k, _)| {
+                !matches!(
+                    k.as_str(),
+                    "fresh" | "trace_id" | "fields" | "render" | "render_only"
+                )
+            })
+            .collect();
+        Some(format!("{}:{}", name, serde_json::json!(params)))
+    }
+
+    /// A live cached result for `key`, marked `cached` with its age, unless
+    /// the call asked for a `fresh` one.
+    pub fn get(&self, key: &str, params: &HashMap<String, Value>) -> Option<Value> {
+        if params.get("fresh").and_then(|v| v.as_bool()) == Some(true) {
+            return None;
+        }
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(key).filter(|e| e.expires > Instant::now())?;
+        let mut value = entry.value.clone();
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert("cached".into(), Value::Bool(true));
+            obj.insert(
+                "cached_age_secs".into(),
+                serde_json::json!(entry.stored.elapsed().as_secs()),
+            );
+        }
+        Some(value)
+    }
+
+    /// Remember `value` for the method's TTL.
+    pub fn put(&self, key: String, params: &HashMap<String, Value>, value: &Value) {
+        let name = key.split(':').next().unwrap_or_default();
+        let Some(ttl) = self.config.ttls.get(name) else {
+            return;
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.config.max_entries {
+            let now = Instant::now();
+            entries.retain(|_, e| e.expires > now);
+            if entries.len() >= self.config.max_entries {
+                return;
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                project_id: project_id(params),
+                value: value.clone(),
+                stored: Instant::now(),
+                expires: Instant::now() + Duration::from_secs(*ttl),
+            },
+        );
+    }
+
+    /// Live entries and the most the cache holds.
+    pub fn usage(&self) -> (usize, usize) {
+        let now = Instant::now();
+        let entries = self.entries.lock().unwrap();
+        let live = entries.values().filter(|e| e.expires > now).count();
+        (live, self.config.max_entries)
+    }
+
+    /// Whether caching is on.
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Drop results a successful mutation with `params` may have changed.
+    pub fn invalidate(&self, params: &HashMap<String, Value>) {
+        let project = project_id(params);
+        let mut entries = self.entries.lock().unwrap();
+        match project {
+            Some(project) => {
+                entries.retain(|_, e| e.project_id.as_ref().is_some_and(|p| *p != project))
+            }
+            None => entries.clear(),
+        }
+    }
+
+    /// Drop catalog results a successful DDL statement with `params` may have
+    /// changed, keeping project and branch listings.
+    pub fn invalidate_catalog(&self, params: &HashMap<String, Value>) {
+        let project = project_id(params);
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|key, e| {
+            let method = key.split(':').next().unwrap_or_default();
+            !CATALOG_METHODS.contains(&method)
+                || project
+                    .as_ref()
+                    .is_some_and(|p| e.project_id.as_ref() != Some(p))
+        });
+    }
+}
+
+fn project_id(params: &HashMap<String, Value>) -> Option<String> {
+    params
+        .get("project_id")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn params(project_id: Option<&str>) -> HashMap<String, Value> {
+        project_id
+            .map(|p| ("project_id".to_string(), json!(p)))
+            .into_iter()
+            .collect()
+    }
+
+    /// A cache holding org-wide `projects` and `branches` and `tables` for
+    /// projects p1 and p2.
+    fn filled() -> ResponseCache {
+        let cache = ResponseCache::new(CacheConfig::default());
+        for (method, project) in [
+            ("neon.projects", None),
+            ("neon.branches", Some("p1")),
+            ("neon.tables", Some("p1")),
+            ("neon.branches", Some("p2")),
+            ("neon.tables", Some("p2")),
+        ] {
+            let params = params(project);
+            let key = cache.key(method, &params).unwrap();
+            cache.put(key, &params, &json!({"method": method}));
+        }
+        cache
+    }
+
+    fn cached(cache: &ResponseCache, method: &str, project_id: Option<&str>) -> bool {
+        let params = params(project_id);
+        let key = cache.key(method, &params).unwrap();
+        cache.get(&key, &params).is_some()
+    }
+
+    #[test]
+    fn hits_are_marked_and_fresh_skips_them() {
+        let cache = filled();
+        let key = cache.key("neon.branches", &params(Some("p1"))).unwrap();
+        let hit = cache.get(&key, &params(Some("p1"))).unwrap();
+        assert_eq!(hit["cached"], true);
+
+        let mut fresh = params(Some("p1"));
+        fresh.insert("fresh".into(), json!(true));
+        assert_eq!(cache.key("neon.branches", &fresh), Some(key.clone()));
+        assert!(cache.get(&key, &fresh).is_none());
+        assert!(cache.key("neon.create_branch", &fresh).is_none());
+    }
+
+    #[test]
+    fn project_mutations_keep_other_projects() {
+        let cache = filled();
+        cache.invalidate(&params(Some("p1")));
+        assert!(!cached(&cache, "neon.branches", Some("p1")));
+        assert!(!cached(&cache, "neon.tables", Some("p1")));
+        assert!(!cached(&cache, "neon.projects", None));
+        assert!(cached(&cache, "neon.branches", Some("p2")));
+        assert!(cached(&cache, "neon.tables", Some("p2")));
+    }
+
+    #[test]
+    fn org_wide_mutations_drop_everything() {
+        let cache = filled();
+        cache.invalidate(&params(None));
+        assert_eq!(cache.usage().0, 0);
+    }
+
+    #[test]
+    fn ddl_drops_only_the_project_catalog() {
+        let cache = filled();
+        cache.invalidate_catalog(&params(Some("p1")));
+        assert!(!cached(&cache, "neon.tables", Some("p1")));
+        assert!(cached(&cache, "neon.branches", Some("p1")));
+        assert!(cached(&cache, "neon.projects", None));
+        assert!(cached(&cache, "neon.tables", Some("p2")));
+
+        cache.invalidate_catalog(&params(None));
+        assert!(!cached(&cache, "neon.tables", Some("p2")));
+        assert!(cached(&cache, "neon.branches", Some("p2")));
+    }
+}
@@ -0,0 +1,166 @@
+//! Data-plane probes of a canary branch.
+//!
+//! The Neon API ping shows whether the control plane answers, not whether
+//! queries get through. With `[health.canary]` set, a background task runs
+//! `SELECT 1` on the canary branch every `interval_secs`, once straight to
+//! its compute and once through its connection pooler, and `health` reports
+//! the two latencies separately as the `data_plane` subsystem.
+//!
+//! A probe wakes a suspended compute, so probing more often than the
+//! canary's suspend timeout keeps it running; use a small branch kept for
+//! this.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::api::NeonClient;
+use crate::config::CanaryConfig;
+use crate::health::{Level, Subsystem};
+use crate::models::Endpoint;
+
+/// Shortest time between probes.
+const MIN_INTERVAL_SECS: u64 = 10;
+
+/// One probe's outcome.
+#[derive(Debug, Clone, Serialize)]
+pub struct Probe {
+    /// `direct` or `pooled`.
+    pub route: &'static str,
+    pub host: Option<String>,
+    pub ok: bool,
+    /// Time to answer, or to fail.
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+/// The latest probes.
+#[derive(Default)]
+pub struct CanaryProbes {
+    last: Mutex<Vec<Probe>>,
+}
+
+impl CanaryProbes {
+    /// The latest probe per route; empty until the first probe.
+    pub fn last(&self) -> Vec<Probe> {
+        self.last.lock().unwrap().clone()
+    }
+}
+
+/// Probe the canary every `interval_secs` forever.
+pub async fn run(client: Arc<NeonClient>, config: CanaryConfig, probes: Arc<CanaryProbes>) {
+    let interval = Duration::from_secs(config.interval_secs.max(MIN_INTERVAL_SECS));
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+        let results = probe(&client, &config).await;
+        for failed in results.iter().filter(|p| !p.ok) {
+            tracing::warn!(
+                "Canary {} probe of {} failed: {}",
+                failed.route,
+                config.branch_id,
+                failed.error.as_deref().unwrap_or("unknown")
+            );
+        }
+        *probes.last.lock().unwrap() = results;
+    }
+}
+
+/// The `data_plane` subsystem from the latest probes: degraded when one
+/// route fails or is slower than `slow_ms`, unhealthy when every route fails.
+pub fn subsystem(probes: &[Probe], slow_ms: u64) -> Subsystem {
+    let mut data_plane = Subsystem::healthy();
+    let all_failed = !probes.is_empty() && probes.iter().all(|p| !p.ok);
+
+    for probe in probes {
+        if let Some(error) = &probe.error {
+            let level = if all_failed {
+                Level::Unhealthy
+            } else {
+                Level::Degraded
+            };
+            data_plane.report(level, format!("{} probe failed: {}", probe.route, error));
+        } else if probe.latency_ms > slow_ms as f64 {
+            data_plane.report(
+                Level::Degraded,
+                format!(
+                    "{} probe slow: {:.0}ms to answer",
+                    probe.route, probe.latency_ms
+                ),
+            );
+        }
+    }
+    data_plane
+}
+
+/// Probe the canary's compute directly and through its pooler, at once.
+async fn probe(client: &NeonClient, config: &CanaryConfig) -> Vec<Probe> {
+    let endpoint = match client
+        .write_endpoint(&config.project_id, &config.branch_id)
+        .await
+    {
+        Ok(endpoint) => endpoint,
+        Err(e) => {
+            // Nothing to probe; both routes are down as far as callers can tell
+            let error = format!("couldn't find the canary's endpoint: {:#}", e);
+            return ["direct", "pooled"]
+                .into_iter()
+                .map(|route| Probe {
+                    route,
+                    host: None,
+                    ok: false,
+                    latency_ms: 0.0,
+                    error: Some(error.clone()),
+                    at: Utc::now(),
+                })
+                .collect();
+        }
+    };
+
+    let pooled = Endpoint {
+        host: pooler_host(&endpoint),
+        ..endpoint.clone()
+    };
+    let (direct, pooled) = tokio::join!(
+        probe_route(client, config, "direct", &endpoint),
+        probe_route(client, config, "pooled", &pooled),
+    );
+    vec![direct, pooled]
+}
+
+async fn probe_route(
+    client: &NeonClient,
+    config: &CanaryConfig,
+    route: &'static str,
+    endpoint: &Endpoint,
+) -> Probe {
+    let started = Instant::now();
+    let result = client
+        .probe_sql(&config.project_id, endpoint, &config.database)
+        .await;
+
+    Probe {
+        route,
+        host: Some(endpoint.host.clone()),
+        ok: result.is_ok(),
+        latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+        error: result.err().map(|e| format!("{:#}", e)),
+        at: Utc::now(),
+    }
+}
+
+/// The endpoint's pooler host: Neon's, or its host with `-pooler` after the
+/// endpoint id.
+fn pooler_host(endpoint: &Endpoint) -> String {
+    if let Some(host) = &endpoint.pooler_host {
+        return host.clone();
+    }
+    match endpoint.host.split_once('.') {
+        Some((id, domain)) => format!("{}-pooler.{}", id, domain),
+        None => endpoint.host.clone(),
+    }
+}
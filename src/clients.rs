@@ -0,0 +1,524 @@
+//! Client connection tracking and limits.
+//!
+//! The FGP server doesn't expose its connections, so the daemon accepts
+//! clients itself on the public socket and relays each request line to the
+//! server on a private inner socket. The relay records who connected (peer
+//! uid/pid), counts requests, caps concurrent connections, and applies a
+//! per-client token bucket; `neon.clients` reports the table. It also notes
+//! when each method was last called, which `neon.stats` reports and which
+//! `[clients] idle_shutdown_secs` uses to stop a daemon nobody is using.
+//!
+//! Requests are handed to a pool of workers, each holding its own inner
+//! connection, so a slow SQL call from one client occupies one worker while
+//! other clients' calls proceed on the others. Each connection's requests
+//! are handled one at a time, so its responses come back in request order,
+//! unless it negotiated `pipelining`: then they're all handed out at once and
+//! answered as they complete, and the client matches responses by `id`.
+//!
+//! The relay also answers a connection's `hello` handshake itself and checks
+//! each request's protocol version against it; see [`crate::protocol`].
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::ClientsConfig;
+use crate::envelope;
+use crate::protocol::{self, Negotiated};
+
+/// A connected client.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientInfo {
+    pub id: u64,
+    pub uid: Option<u32>,
+    /// Not available on macOS.
+    pub pid: Option<i32>,
+    pub connected_at: DateTime<Utc>,
+    pub requests: u64,
+    pub rate_limited: u64,
+    pub last_request_at: Option<DateTime<Utc>>,
+    pub last_method: Option<String>,
+    /// Protocol version the connection speaks; 1 until it sends `hello`.
+    pub protocol: u64,
+    pub features: Vec<String>,
+    #[serde(skip)]
+    bucket: TokenBucket,
+}
+
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+/// Calls to one method since the daemon started.
+#[derive(Debug, Clone, Serialize)]
+pub struct MethodActivity {
+    /// Without the `neon.` prefix, so both spellings count together.
+    pub method: String,
+    pub calls: u64,
+    pub last_called_at: DateTime<Utc>,
+    pub last_client_id: u64,
+}
+
+/// When the daemon was last used, and for what.
+#[derive(Debug, Serialize)]
+pub struct Activity {
+    pub started_at: DateTime<Utc>,
+    pub last_request_at: Option<DateTime<Utc>>,
+    /// Seconds since a request last arrived or finished.
+    pub idle_secs: u64,
+    /// Idle time after which the daemon stops; 0 when it doesn't.
+    pub idle_shutdown_secs: u64,
+    /// Most recently called first.
+    pub methods: Vec<MethodActivity>,
+    pub clients: Vec<ClientInfo>,
+}
+
+struct Usage {
+    /// Last time a request arrived or finished.
+    last_active: Instant,
+    last_request_at: Option<DateTime<Utc>>,
+    methods: BTreeMap<String, MethodActivity>,
+}
+
+/// Connected clients and the limits applied to them.
+pub struct ClientRegistry {
+    config: ClientsConfig,
+    started_at: DateTime<Utc>,
+    next_id: AtomicU64,
+    rejected_connections: AtomicU64,
+    in_flight: AtomicU64,
+    clients: Mutex<BTreeMap<u64, ClientInfo>>,
+    usage: Mutex<Usage>,
+}
+
+impl ClientRegistry {
+    pub fn new(config: ClientsConfig) -> Self {
+        Self {
+            config,
+            started_at: Utc::now(),
+            next_id: AtomicU64::new(1),
+            rejected_connections: AtomicU64::new(0),
+            in_flight: AtomicU64::new(0),
+            clients: Mutex::new(BTreeMap::new()),
+            usage: Mutex::new(Usage {
+                last_active: Instant::now(),
+                last_request_at: None,
+                methods: BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// Connected clients, oldest first.
+    pub fn list(&self) -> Vec<ClientInfo> {
+        self.clients.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn max_connections(&self) -> usize {
+        self.config.max_connections
+    }
+
+    /// Connections refused because the limit was reached.
+    pub fn rejected_connections(&self) -> u64 {
+        self.rejected_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn workers(&self) -> usize {
+        self.config.workers.max(1)
+    }
+
+    /// Requests queued or being handled by a worker.
+    pub fn in_flight(&self) -> u64 {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Time since a request last arrived or finished.
+    pub fn idle_for(&self) -> Duration {
+        self.usage.lock().unwrap().last_active.elapsed()
+    }
+
+    /// Last-use times per method and per connected client.
+    pub fn activity(&self) -> Activity {
+        // Before taking `usage`: admit_request takes `clients` first
+        let clients = self.list();
+        let usage = self.usage.lock().unwrap();
+        let mut methods: Vec<MethodActivity> = usage.methods.values().cloned().collect();
+        methods.sort_by(|a, b| b.last_called_at.cmp(&a.last_called_at));
+
+        Activity {
+            started_at: self.started_at,
+            last_request_at: usage.last_request_at,
+            idle_secs: usage.last_active.elapsed().as_secs(),
+            idle_shutdown_secs: self.config.idle_shutdown_secs,
+            methods,
+            clients,
+        }
+    }
+
+    /// Note that a request arrived or finished.
+    fn touch(&self) {
+        self.usage.lock().unwrap().last_active = Instant::now();
+    }
+
+    /// Count a call to `method` by client `id`.
+    fn record_call(&self, id: u64, method: &str) {
+        let now = Utc::now();
+        let method = method.strip_prefix("neon.").unwrap_or(method);
+        let mut usage = self.usage.lock().unwrap();
+        usage.last_active = Instant::now();
+        usage.last_request_at = Some(now);
+        let activity = usage
+            .methods
+            .entry(method.to_string())
+            .or_insert_with(|| MethodActivity {
+                method: method.to_string(),
+                calls: 0,
+                last_called_at: now,
+                last_client_id: id,
+            });
+        activity.calls += 1;
+        activity.last_called_at = now;
+        activity.last_client_id = id;
+    }
+
+    /// Admit a connection, or `None` when at the connection limit.
+    fn register(&self, uid: Option<u32>, pid: Option<i32>) -> Option<u64> {
+        let mut clients = self.clients.lock().unwrap();
+        if clients.len() >= self.config.max_connections {
+            self.rejected_connections.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        clients.insert(
+            id,
+            ClientInfo {
+                id,
+                uid,
+                pid,
+                connected_at: Utc::now(),
+                requests: 0,
+                rate_limited: 0,
+                last_request_at: None,
+                last_method: None,
+                protocol: protocol::MIN_VERSION,
+                features: Vec::new(),
+                bucket: TokenBucket {
+                    tokens: self.config.burst as f64,
+                    refilled: Instant::now(),
+                },
+            },
+        );
+        Some(id)
+    }
+
+    /// Record what a connection negotiated.
+    fn negotiated(&self, id: u64, negotiated: &Negotiated) {
+        if let Some(client) = self.clients.lock().unwrap().get_mut(&id) {
+            client.protocol = negotiated.version;
+            client.features = negotiated.features.clone();
+        }
+    }
+
+    fn unregister(&self, id: u64) {
+        self.clients.lock().unwrap().remove(&id);
+    }
+
+    /// Count a request and take a token; false when the client is over its rate.
+    fn admit_request(&self, id: u64, method: Option<&str>) -> bool {
+        let mut clients = self.clients.lock().unwrap();
+        let Some(client) = clients.get_mut(&id) else {
+            return false;
+        };
+        self.touch();
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(client.bucket.refilled).as_secs_f64();
+        client.bucket.tokens = (client.bucket.tokens + elapsed * self.config.requests_per_second)
+            .min(self.config.burst as f64);
+        client.bucket.refilled = now;
+        client.last_request_at = Some(Utc::now());
+
+        if client.bucket.tokens >= 1.0 {
+            client.bucket.tokens -= 1.0;
+            client.requests += 1;
+            if let Some(method) = method {
+                client.last_method = Some(method.to_string());
+                self.record_call(id, method);
+            }
+            true
+        } else {
+            client.rate_limited += 1;
+            false
+        }
+    }
+}
+
+/// A request waiting for a worker, with the connection its response goes to.
+struct Job {
+    line: String,
+    reply: Arc<Mutex<UnixStream>>,
+    /// Told once the response is written, so a connection without
+    /// pipelining can send its next request.
+    done: Option<Sender<()>>,
+}
+
+/// Accept clients on `listener` and relay them to the server at `inner_path`.
+/// Runs until the listener fails.
+pub fn serve(listener: UnixListener, inner_path: String, registry: Arc<ClientRegistry>) {
+    let (jobs, queue) = mpsc::channel::<Job>();
+    let queue = Arc::new(Mutex::new(queue));
+    for _ in 0..registry.workers() {
+        let (queue, inner_path, registry) = (queue.clone(), inner_path.clone(), registry.clone());
+        std::thread::spawn(move || work(&queue, &inner_path, &registry));
+    }
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let (jobs, registry) = (jobs.clone(), registry.clone());
+                std::thread::spawn(move || {
+                    if let Err(e) = relay(stream, &jobs, &registry) {
+                        tracing::debug!("Client connection ended: {:#}", e);
+                    }
+                });
+            }
+            Err(e) => tracing::warn!("Failed to accept client connection: {}", e),
+        }
+    }
+}
+
+/// Stop the daemon once it has been idle for `[clients] idle_shutdown_secs`
+/// with no request in flight, removing `files` (its sockets and PID file) so
+/// the next start finds nothing stale. Connected but quiet clients don't keep
+/// it running. Returns at once when idle shutdown is off.
+pub fn shutdown_when_idle(registry: Arc<ClientRegistry>, files: Vec<String>) {
+    if registry.config.idle_shutdown_secs == 0 {
+        return;
+    }
+    let limit = Duration::from_secs(registry.config.idle_shutdown_secs);
+    let check_every = (limit / 10).clamp(Duration::from_secs(1), Duration::from_secs(30));
+
+    loop {
+        std::thread::sleep(check_every);
+        if registry.in_flight() == 0 && registry.idle_for() >= limit {
+            tracing::info!("No requests for {}s; shutting down", limit.as_secs());
+            for file in &files {
+                let _ = std::fs::remove_file(file);
+            }
+            std::process::exit(0);
+        }
+    }
+}
+
+/// Worker loop: forward each job to the server on this worker's own inner
+/// connection and write the response back to the job's client.
+fn work(queue: &Mutex<Receiver<Job>>, inner_path: &str, registry: &ClientRegistry) {
+    let mut inner: Option<(UnixStream, BufReader<UnixStream>)> = None;
+
+    loop {
+        let job = match queue.lock().unwrap().recv() {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+
+        let response = match forward(&mut inner, inner_path, &job.line) {
+            Ok(response) => response,
+            Err(e) => {
+                // Reconnect on the next job rather than reuse a broken stream
+                inner = None;
+                error_response(request_id(&job.line), &format!("{:#}", e)).to_string()
+            }
+        };
+        registry.in_flight.fetch_sub(1, Ordering::Relaxed);
+        registry.touch();
+
+        let mut out = job.reply.lock().unwrap();
+        if let Err(e) = writeln!(out, "{}", response).and_then(|_| out.flush()) {
+            tracing::debug!("Failed to deliver response: {}", e);
+        }
+        drop(out);
+        if let Some(done) = job.done {
+            let _ = done.send(());
+        }
+    }
+}
+
+fn forward(
+    inner: &mut Option<(UnixStream, BufReader<UnixStream>)>,
+    inner_path: &str,
+    line: &str,
+) -> Result<String> {
+    if inner.is_none() {
+        let stream = UnixStream::connect(inner_path)
+            .with_context(|| format!("Failed to connect to {}", inner_path))?;
+        let reader = BufReader::new(stream.try_clone()?);
+        *inner = Some((stream, reader));
+    }
+    let (stream, reader) = inner.as_mut().unwrap();
+
+    writeln!(stream, "{}", line)?;
+    stream.flush()?;
+    let mut response = String::new();
+    if reader.read_line(&mut response)? == 0 {
+        anyhow::bail!("Server closed the connection");
+    }
+    Ok(response.trim_end().to_string())
+}
+
+fn relay(client: UnixStream, jobs: &Sender<Job>, registry: &ClientRegistry) -> Result<()> {
+    let (uid, pid) = peer_credentials(&client);
+    let writer = Arc::new(Mutex::new(client.try_clone()?));
+
+    let Some(id) = registry.register(uid, pid) else {
+        let message = format!(
+            "Too many connections (limit {})",
+            registry.max_connections()
+        );
+        write_error(&writer, Value::Null, &message)?;
+        return Ok(());
+    };
+
+    let result = relay_requests(&client, &writer, jobs, registry, id);
+    registry.unregister(id);
+    result
+}
+
+fn relay_requests(
+    client: &UnixStream,
+    writer: &Arc<Mutex<UnixStream>>,
+    jobs: &Sender<Job>,
+    registry: &ClientRegistry,
+    id: u64,
+) -> Result<()> {
+    let mut negotiated = Negotiated::default();
+    let (done, finished) = mpsc::channel();
+    for line in BufReader::new(client).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut method = None;
+        // Lines that aren't JSON go through for the server to report
+        if let Ok(request) = serde_json::from_str::<Value>(&line) {
+            let reply_id = request.get("id").cloned().unwrap_or(Value::Null);
+            if protocol::is_hello(&request) {
+                registry.touch();
+                let started = Instant::now();
+                match protocol::negotiate(request.get("params").unwrap_or(&Value::Null)) {
+                    Ok(agreed) => {
+                        registry.negotiated(id, &agreed);
+                        let result = envelope::wrap(
+                            protocol::hello_result(&agreed),
+                            started.elapsed(),
+                            None,
+                        );
+                        negotiated = agreed;
+                        write_line(
+                            writer,
+                            &serde_json::json!({ "id": reply_id, "ok": true, "result": result }),
+                        )?;
+                    }
+                    Err(message) => write_error(writer, reply_id, &message)?,
+                }
+                continue;
+            }
+            if let Err(message) = protocol::check_version(&request, &negotiated) {
+                write_error(writer, reply_id, &message)?;
+                continue;
+            }
+            method = request
+                .get("method")
+                .and_then(|m| m.as_str())
+                .map(str::to_string);
+        }
+        if registry.admit_request(id, method.as_deref()) {
+            registry.in_flight.fetch_add(1, Ordering::Relaxed);
+            let pipelined = negotiated.pipelining();
+            jobs.send(Job {
+                line,
+                reply: writer.clone(),
+                done: (!pipelined).then(|| done.clone()),
+            })
+            .context("Worker pool stopped")?;
+            // One request at a time keeps responses in request order
+            if !pipelined {
+                finished.recv().context("Worker pool stopped")?;
+            }
+        } else {
+            write_error(writer, request_id(&line), "Rate limit exceeded; slow down")?;
+        }
+    }
+    Ok(())
+}
+
+fn request_id(line: &str) -> Value {
+    serde_json::from_str::<Value>(line)
+        .ok()
+        .and_then(|v| v.get("id").cloned())
+        .unwrap_or(Value::Null)
+}
+
+fn error_response(id: Value, message: &str) -> Value {
+    serde_json::json!({ "id": id, "ok": false, "error": message })
+}
+
+fn write_error(writer: &Mutex<UnixStream>, id: Value, message: &str) -> Result<()> {
+    write_line(writer, &error_response(id, message))
+}
+
+fn write_line(writer: &Mutex<UnixStream>, response: &Value) -> Result<()> {
+    let mut out = writer.lock().unwrap();
+    writeln!(out, "{}", response)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Peer uid and pid of a Unix socket connection.
+#[cfg(target_os = "linux")]
+fn peer_credentials(stream: &UnixStream) -> (Option<u32>, Option<i32>) {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: cred and len are valid for writes and len matches cred's size.
+    let rc = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if rc == 0 {
+        (Some(cred.uid), Some(cred.pid))
+    } else {
+        (None, None)
+    }
+}
+
+/// Peer uid and pid of a Unix socket connection.
+#[cfg(not(target_os = "linux"))]
+fn peer_credentials(stream: &UnixStream) -> (Option<u32>, Option<i32>) {
+    let (mut uid, mut gid) = (0, 0);
+    // SAFETY: uid and gid are valid for writes.
+    let rc = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+    if rc == 0 {
+        (Some(uid), None)
+    } else {
+        (None, None)
+    }
+}
@@ -0,0 +1,126 @@
+//! Result set comparison for `neon.compare_query`.
+//!
+//! Rows are compared as multisets, so two results with the same rows in a
+//! different order are equal; `same_order` reports whether the order matched
+//! too. Columns are compared by position, name, and type OID.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::api::result_rows;
+
+/// Rows listed per side in `only_left` / `only_right`.
+const MAX_SAMPLE_ROWS: usize = 100;
+
+/// Structural differences between two SQL results.
+#[derive(Debug, Serialize)]
+pub struct ResultDiff {
+    pub identical: bool,
+    /// Same rows in the same order. Only meaningful when the row sets match.
+    pub same_order: bool,
+    pub left_row_count: usize,
+    pub right_row_count: usize,
+    pub column_differences: Vec<ColumnDifference>,
+    /// Rows (with multiplicity) present on the left but not the right.
+    pub only_left_count: usize,
+    pub only_right_count: usize,
+    /// Up to 100 of those rows per side.
+    pub only_left: Vec<Value>,
+    pub only_right: Vec<Value>,
+}
+
+/// A result column that differs between the two sides.
+#[derive(Debug, Serialize)]
+pub struct ColumnDifference {
+    pub position: usize,
+    pub left: Option<ColumnInfo>,
+    pub right: Option<ColumnInfo>,
+}
+
+/// Name and Postgres type OID of a result column.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub type_oid: Option<i64>,
+}
+
+/// Compare two results returned by the SQL endpoint.
+pub fn diff(left: &Value, right: &Value) -> ResultDiff {
+    let left_columns = columns(left);
+    let right_columns = columns(right);
+    let mut column_differences = Vec::new();
+    for position in 0..left_columns.len().max(right_columns.len()) {
+        let (l, r) = (left_columns.get(position), right_columns.get(position));
+        if l != r {
+            column_differences.push(ColumnDifference {
+                position,
+                left: l.cloned(),
+                right: r.cloned(),
+            });
+        }
+    }
+
+    let left_rows = result_rows(left);
+    let right_rows = result_rows(right);
+
+    // Row text -> (row, left count minus right count)
+    let mut balance: BTreeMap<String, (&Value, i64)> = BTreeMap::new();
+    for row in left_rows {
+        balance.entry(row.to_string()).or_insert((row, 0)).1 += 1;
+    }
+    for row in right_rows {
+        balance.entry(row.to_string()).or_insert((row, 0)).1 -= 1;
+    }
+
+    let (mut only_left_count, mut only_right_count) = (0, 0);
+    let (mut only_left, mut only_right) = (Vec::new(), Vec::new());
+    for (row, count) in balance.into_values() {
+        let (total, sample) = if count > 0 {
+            (&mut only_left_count, &mut only_left)
+        } else {
+            (&mut only_right_count, &mut only_right)
+        };
+        for _ in 0..count.unsigned_abs() {
+            *total += 1;
+            if sample.len() < MAX_SAMPLE_ROWS {
+                sample.push(row.clone());
+            }
+        }
+    }
+
+    let rows_match = only_left_count == 0 && only_right_count == 0;
+    let same_order = rows_match && left_rows == right_rows;
+
+    ResultDiff {
+        identical: column_differences.is_empty() && same_order,
+        same_order,
+        left_row_count: left_rows.len(),
+        right_row_count: right_rows.len(),
+        column_differences,
+        only_left_count,
+        only_right_count,
+        only_left,
+        only_right,
+    }
+}
+
+fn columns(result: &Value) -> Vec<ColumnInfo> {
+    result
+        .get("columns")
+        .and_then(|f| f.as_array())
+        .map(|fields| {
+            fields
+                .iter()
+                .map(|f| ColumnInfo {
+                    name: f
+                        .get("name")
+                        .and_then(|n| n.as_str())
+                        .unwrap_or("")
+                        .to_string(),
+                    type_oid: f.get("type_oid").and_then(|t| t.as_i64()),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,952 @@
+//! Daemon configuration.
+//!
+//! Loaded from `~/.fgp/services/neon/config.toml`. Every field has a default,
+//! so a missing file behaves like an empty one. Type errors and missing
+//! required fields fail the load with their line; keys no setting reads are
+//! reported as warnings (usually a typo), and `fgp-neon config check` lists
+//! both without starting the daemon.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Default config file location.
+pub const DEFAULT_CONFIG: &str = "~/.fgp/services/neon/config.toml";
+
+/// Directory for persisted daemon state.
+pub const STATE_DIR: &str = "~/.fgp/services/neon";
+
+/// Expanded path of a file or directory under [`STATE_DIR`].
+pub fn state_path(name: &str) -> PathBuf {
+    PathBuf::from(shellexpand::tilde(STATE_DIR).to_string()).join(name)
+}
+
+/// Check that a user-supplied name is safe to use as a file name under [`STATE_DIR`].
+pub fn check_state_name(kind: &str, name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        && !name.starts_with('.');
+    if !valid {
+        anyhow::bail!("Invalid {} {:?}: use letters, digits, _ - .", kind, name);
+    }
+    Ok(())
+}
+
+/// Error for a preset name missing from the config, listing the known ones.
+pub fn unknown_preset<'a>(
+    kind: &str,
+    name: &str,
+    known: impl Iterator<Item = &'a String>,
+) -> anyhow::Error {
+    let known: Vec<&str> = known.map(String::as_str).collect();
+    anyhow::anyhow!(
+        "Unknown {} {:?}; configured: {}",
+        kind,
+        name,
+        if known.is_empty() {
+            "none".to_string()
+        } else {
+            known.join(", ")
+        }
+    )
+}
+
+/// Top-level daemon configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub runtime: RuntimeConfig,
+    pub limits: LimitsConfig,
+    pub sql: SqlConfig,
+    pub retry: RetryConfig,
+    pub notify: NotifyConfig,
+    pub drift: DriftConfig,
+    pub watch: WatchConfig,
+    pub usage: UsageConfig,
+    pub update_check: UpdateCheckConfig,
+    pub clients: ClientsConfig,
+    pub redaction: RedactionConfig,
+    pub masking: MaskingConfig,
+    pub branch_tags: BranchTagsConfig,
+    pub cost_guard: CostGuardConfig,
+    pub idempotency: IdempotencyConfig,
+    pub cache: CacheConfig,
+    pub health: HealthConfig,
+    pub streaming: StreamingConfig,
+    pub project_index: ProjectIndexConfig,
+    pub statsd: StatsdConfig,
+    /// Named presets for `neon.create_project_from_template`.
+    pub templates: BTreeMap<String, ProjectTemplate>,
+    /// Named day-zero setups for `neon.bootstrap`.
+    pub bootstrap: BTreeMap<String, BootstrapProfile>,
+    /// Default Postgres role per project/database (instead of `neondb_owner`).
+    pub roles: Vec<RoleMapping>,
+    /// Environment profiles, selected per call with `env`.
+    pub envs: BTreeMap<String, EnvProfile>,
+}
+
+/// Thread counts for the daemon's Tokio runtimes. API and metadata calls
+/// and SQL execution run on separate runtimes so neither starves the other.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimeConfig {
+    /// Worker threads for API and metadata calls and background jobs; 0 uses one per CPU core.
+    pub worker_threads: usize,
+    /// Worker threads for SQL execution; 0 uses one per CPU core.
+    pub sql_worker_threads: usize,
+    /// Blocking-pool threads per runtime.
+    pub max_blocking_threads: usize,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            worker_threads: 0,
+            sql_worker_threads: 2,
+            max_blocking_threads: 64,
+        }
+    }
+}
+
+/// Bounds on SQL result sizes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Maximum rows returned by a single SQL call before truncating.
+    pub max_rows: usize,
+    /// Maximum SQL response body size in bytes; larger responses are cut to
+    /// the rows that fit.
+    pub max_response_bytes: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_rows: 10_000,
+            max_response_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+/// Retries and timeouts for Neon API requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Attempts per request, including the first.
+    pub max_attempts: u32,
+    /// Backoff before the first retry; doubles per retry.
+    pub initial_backoff_ms: u64,
+    /// Longest backoff. A `Retry-After` longer than this fails the request instead.
+    pub max_backoff_ms: u64,
+    /// Timeout for each HTTP request, SQL over HTTP included.
+    pub request_timeout_secs: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff_ms: 250,
+            max_backoff_ms: 10_000,
+            request_timeout_secs: 30,
+        }
+    }
+}
+
+/// How SQL is executed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SqlConfig {
+    pub backend: SqlBackend,
+    /// Idle Postgres connections kept per project/branch/database.
+    pub pool_size: usize,
+    /// Idle Postgres connections older than this are closed.
+    pub idle_timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    /// How long a project's endpoint listing is reused to route SQL; 0 disables.
+    pub endpoint_cache_secs: u64,
+    /// How long a revealed role password is reused; 0 disables.
+    pub password_cache_secs: u64,
+}
+
+impl Default for SqlConfig {
+    fn default() -> Self {
+        Self {
+            backend: SqlBackend::Http,
+            pool_size: 4,
+            idle_timeout_secs: 300,
+            connect_timeout_secs: 10,
+            endpoint_cache_secs: 60,
+            password_cache_secs: 900,
+        }
+    }
+}
+
+/// SQL execution backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SqlBackend {
+    /// Neon's SQL-over-HTTP endpoint; one statement per call.
+    Http,
+    /// Pooled direct Postgres connections; supports scripts, SET, temp tables, COPY TO STDOUT.
+    Postgres,
+}
+
+/// Limits on local client connections.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientsConfig {
+    /// Concurrent connections; further connections are refused.
+    pub max_connections: usize,
+    /// Sustained requests per second per connection.
+    pub requests_per_second: f64,
+    /// Requests a connection may send in a burst above the sustained rate.
+    pub burst: u32,
+    /// Requests handled concurrently across all connections.
+    pub workers: usize,
+    /// Stop the daemon after this long without a request; 0 never stops it.
+    pub idle_shutdown_secs: u64,
+}
+
+impl Default for ClientsConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 64,
+            requests_per_second: 50.0,
+            burst: 100,
+            workers: 16,
+            idle_shutdown_secs: 0,
+        }
+    }
+}
+
+/// EXPLAIN-based rejection of expensive queries before they run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CostGuardConfig {
+    /// Check every query. When off, the guard runs only for calls that pass a threshold.
+    pub enabled: bool,
+    /// Highest planner total cost allowed.
+    pub max_cost: f64,
+    /// Highest planner row estimate allowed.
+    pub max_rows: f64,
+}
+
+impl Default for CostGuardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_cost: 1_000_000.0,
+            max_rows: 10_000_000.0,
+        }
+    }
+}
+
+/// Memory of `idempotency_key` results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdempotencyConfig {
+    /// How long a key's result is replayed.
+    pub ttl_secs: u64,
+    /// Results kept at most; the oldest are dropped first.
+    pub max_keys: usize,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 86400,
+            max_keys: 10_000,
+        }
+    }
+}
+
+/// In-daemon cache of read-only method results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    /// Seconds to keep each method's results, by method name without `neon.`.
+    /// Methods not listed aren't cached.
+    pub ttls: BTreeMap<String, u64>,
+    /// Results kept at most.
+    pub max_entries: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        let ttls = [
+            ("projects", 30),
+            ("project", 30),
+            ("branches", 15),
+            ("databases", 60),
+            ("tables", 60),
+            ("schema", 60),
+            ("table_detail", 60),
+            ("introspect", 60),
+            ("views", 60),
+            ("functions", 60),
+            ("extensions", 60),
+        ];
+        Self {
+            enabled: true,
+            ttls: ttls.into_iter().map(|(m, t)| (m.to_string(), t)).collect(),
+            max_entries: 1000,
+        }
+    }
+}
+
+/// Reuse of the Neon API ping behind health checks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    /// Seconds a ping answers `health`, health checks, and `status`; 0 disables.
+    pub cache_secs: u64,
+    /// Ping latency above which the API is reported degraded.
+    pub slow_api_ms: u64,
+    /// Consecutive failed Postgres connections before the pool is reported
+    /// unhealthy rather than degraded.
+    pub pool_failures: u32,
+    /// Minutes before an OAuth token's expiry that credentials are reported
+    /// degraded.
+    pub expiry_warning_mins: u64,
+    /// Canary probe latency above which the data plane is reported degraded.
+    pub slow_sql_ms: u64,
+    /// Branch probed with `SELECT 1` for data-plane health; none when omitted.
+    pub canary: Option<CanaryConfig>,
+}
+
+/// A branch whose SQL endpoint is probed, directly and through its pooler.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CanaryConfig {
+    pub project_id: String,
+    pub branch_id: String,
+    #[serde(default = "default_database")]
+    pub database: String,
+    /// Seconds between probes (at least 10).
+    #[serde(default = "default_canary_interval")]
+    pub interval_secs: u64,
+}
+
+fn default_canary_interval() -> u64 {
+    60
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            cache_secs: 5,
+            slow_api_ms: 2000,
+            pool_failures: 3,
+            expiry_warning_mins: 60,
+            slow_sql_ms: 1000,
+            canary: None,
+        }
+    }
+}
+
+/// Chunked delivery of large SQL results (`stream: true`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamingConfig {
+    /// Rows per chunk unless the call passes `chunk_size`.
+    pub chunk_size: usize,
+    /// Most rows a streamed query returns; replaces `limits.max_rows` for it.
+    pub max_rows: usize,
+    /// Seconds a stream is kept without a fetch.
+    pub ttl_secs: u64,
+    /// Streams held at once; the oldest is dropped for a new one.
+    pub max_results: usize,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size: 1000,
+            max_rows: 1_000_000,
+            ttl_secs: 300,
+            max_results: 16,
+        }
+    }
+}
+
+/// Local index behind `neon.projects_search`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectIndexConfig {
+    /// Refresh in the background rather than on the first search after it goes stale.
+    pub enabled: bool,
+    /// Seconds between refreshes.
+    pub interval_secs: u64,
+    /// Seconds between full re-lists of every project while the operations
+    /// watcher feeds incremental refreshes in between.
+    pub full_refresh_secs: u64,
+    /// Tags per project id or name, matched by search.
+    pub tags: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for ProjectIndexConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 300,
+            full_refresh_secs: 3600,
+            tags: BTreeMap::new(),
+        }
+    }
+}
+
+/// Metrics export to a StatsD or DogStatsD agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatsdConfig {
+    /// Send metrics in the background.
+    pub enabled: bool,
+    /// Agent address, `host:port`.
+    pub address: String,
+    /// Prepended to every metric name.
+    pub prefix: String,
+    /// Seconds between flushes.
+    pub interval_secs: u64,
+    /// Send tags DogStatsD-style (`|#key:value`) rather than in metric names.
+    pub datadog: bool,
+    /// Tags added to every metric when `datadog` is set, e.g. `env`.
+    pub tags: BTreeMap<String, String>,
+}
+
+impl Default for StatsdConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1:8125".into(),
+            prefix: "fgp.neon".into(),
+            interval_secs: 10,
+            datadog: false,
+            tags: BTreeMap::new(),
+        }
+    }
+}
+
+/// Masking of secrets found in SQL results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    pub enabled: bool,
+    /// Let callers pass `allow_secrets: true` to see unmasked values.
+    pub allow_override: bool,
+    /// Patterns checked in addition to the built-in ones.
+    pub patterns: Vec<SecretPattern>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            allow_override: true,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// A named regex whose matches are masked as `[REDACTED:<name>]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretPattern {
+    pub name: String,
+    pub pattern: String,
+}
+
+/// Column masking for `neon.dump`, so copies of production data carry no PII.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaskingConfig {
+    /// Mixed into `hash` and `fake_email` values so they can't be matched
+    /// against hashes of guessed values.
+    pub salt: String,
+    /// Let callers pass `mask: false` to dump unmasked values.
+    pub allow_override: bool,
+    pub columns: Vec<MaskRule>,
+}
+
+/// How one column's values are replaced.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskRule {
+    /// `schema.table`, a table in `public`, or `*` for every table.
+    pub table: String,
+    pub column: String,
+    pub mask: MaskKind,
+    /// Replacement for `constant`.
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+/// Replacement for a masked column's values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaskKind {
+    Null,
+    Hash,
+    FakeEmail,
+    Constant,
+}
+
+/// Ownership tags on branches (see `branch_tags.rs`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BranchTagsConfig {
+    /// Tags `neon.create_branch` refuses to create a branch without.
+    pub required: Vec<TagName>,
+}
+
+/// A branch tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagName {
+    Owner,
+    Team,
+    ExpiresAt,
+}
+
+/// Maps a project (and optionally one database) to the role SQL runs as.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoleMapping {
+    pub project_id: String,
+    /// Database the mapping applies to; every database in the project when omitted.
+    #[serde(default)]
+    pub database: Option<String>,
+    pub role: String,
+}
+
+/// Role used when no mapping matches.
+pub const DEFAULT_ROLE: &str = "neondb_owner";
+
+/// Where daemon notifications are delivered.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    /// Webhook that receives a JSON POST per event. Events are always logged.
+    pub webhook_url: Option<String>,
+}
+
+/// Scheduled schema drift detection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DriftConfig {
+    /// Seconds between drift checks.
+    pub interval_secs: u64,
+    /// Databases to watch. Drift detection is off when empty.
+    pub targets: Vec<DriftTarget>,
+}
+
+impl Default for DriftConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 3600,
+            targets: Vec::new(),
+        }
+    }
+}
+
+/// A database watched for schema drift.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftTarget {
+    /// Name used for the history file and in notifications.
+    pub name: String,
+    pub project_id: String,
+    pub branch_id: String,
+    #[serde(default = "default_database")]
+    pub database: String,
+    /// Changes inside any of these windows are recorded without notifying.
+    #[serde(default)]
+    pub deploy_windows: Vec<DeployWindow>,
+}
+
+/// A recurring UTC time range during which schema changes are expected.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeployWindow {
+    /// Lower-case weekday abbreviations (`mon`..`sun`); empty means every day.
+    #[serde(default)]
+    pub days: Vec<String>,
+    /// Start time, `HH:MM` UTC.
+    pub start: String,
+    /// End time, `HH:MM` UTC.
+    pub end: String,
+}
+
+/// Operations watcher feeding `neon.watch`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// Poll the operations feed in the background.
+    pub enabled: bool,
+    /// Seconds between polls.
+    pub interval_secs: u64,
+    /// Projects to watch; all projects in the org when empty.
+    pub project_ids: Vec<String>,
+    /// Event kinds forwarded to the notifier.
+    pub notify_kinds: Vec<String>,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 15,
+            project_ids: Vec::new(),
+            notify_kinds: vec!["operation_failed".into()],
+        }
+    }
+}
+
+/// Background consumption polling and budget alerts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageConfig {
+    /// Poll consumption in the background.
+    pub enabled: bool,
+    /// Seconds between polls.
+    pub interval_secs: u64,
+    /// Daily limits; each fires a `usage_threshold_exceeded` event once per day.
+    pub thresholds: Vec<UsageThreshold>,
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 3600,
+            thresholds: Vec::new(),
+        }
+    }
+}
+
+/// Periodic check for newer releases.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateCheckConfig {
+    /// Check in the background. `neon.update_check` works either way.
+    pub enabled: bool,
+    /// Seconds between checks (at least an hour).
+    pub interval_secs: u64,
+}
+
+impl Default for UpdateCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 86400,
+        }
+    }
+}
+
+/// A daily usage limit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageThreshold {
+    /// `compute_hours`, `active_hours`, `written_gb`, or `storage_gb`.
+    pub metric: String,
+    pub limit: f64,
+    /// Project to check; every project in the org when omitted.
+    #[serde(default)]
+    pub project_id: Option<String>,
+}
+
+/// Settings applied to projects created from a template.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectTemplate {
+    pub region_id: Option<String>,
+    pub pg_version: Option<i32>,
+    pub autoscaling_limit_min_cu: Option<f64>,
+    pub autoscaling_limit_max_cu: Option<f64>,
+    pub suspend_timeout_seconds: Option<i64>,
+    /// Extensions created in the default database.
+    pub extensions: Vec<String>,
+    /// SQL file run against the default database after extensions.
+    pub seed_script: Option<String>,
+}
+
+/// Defaults and guardrails for calls made with `env: "<name>"`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvProfile {
+    /// Projects calls may target; any project when empty.
+    pub projects: Vec<String>,
+    /// `project_id` for calls that don't pass one.
+    pub project_id: Option<String>,
+    /// `branch_id` for calls on `project_id` that don't pass one.
+    pub branch_id: Option<String>,
+    /// Methods calls may use (with or without `neon.`); any when empty.
+    pub allowed_methods: Vec<String>,
+    pub writes: WriteMode,
+}
+
+/// Whether an environment accepts calls that change state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteMode {
+    #[default]
+    Allow,
+    /// Only with `confirm: true` in the call.
+    Confirm,
+    /// Read-only: writes are rejected.
+    Deny,
+}
+
+/// Databases, roles, extensions, and migrations applied by `neon.bootstrap`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BootstrapProfile {
+    /// Created first, so databases can name them as owners.
+    pub roles: Vec<BootstrapRole>,
+    pub databases: Vec<BootstrapDatabase>,
+    /// Extensions created in every bootstrapped database.
+    pub extensions: Vec<String>,
+    /// SQL files applied in order to `migrations_database`.
+    pub migrations: Vec<String>,
+    /// Defaults to the first entry in `databases`, else `neondb`.
+    pub migrations_database: Option<String>,
+}
+
+/// A role and the privileges it gets on one database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BootstrapRole {
+    pub name: String,
+    /// Database the grant applies to; `neondb` when omitted.
+    #[serde(default = "default_database")]
+    pub database: String,
+    /// Grant template: `read_only`, `read_write`, or `owner`; none when omitted.
+    #[serde(default)]
+    pub grant: Option<String>,
+}
+
+/// A database to create.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BootstrapDatabase {
+    pub name: String,
+    /// Owning role; the project's default role when omitted.
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+fn default_database() -> String {
+    "neondb".into()
+}
+
+impl Config {
+    /// Role to use for a project/database, preferring database-specific mappings.
+    pub fn default_role(&self, project_id: &str, database: &str) -> &str {
+        let for_project = || self.roles.iter().filter(|m| m.project_id == project_id);
+
+        for_project()
+            .find(|m| m.database.as_deref() == Some(database))
+            .or_else(|| for_project().find(|m| m.database.is_none()))
+            .map(|m| m.role.as_str())
+            .unwrap_or(DEFAULT_ROLE)
+    }
+
+    /// Load config from a path, falling back to defaults if the file is missing.
+    /// Unknown keys are printed as warnings.
+    pub fn load(path: &str) -> Result<Self> {
+        let path = shellexpand::tilde(path).to_string();
+
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                let (config, unknown) = Self::parse(&text)
+                    .with_context(|| format!("Failed to parse config {}", path))?;
+                for key in unknown {
+                    eprintln!("Warning: {}: {}", path, key);
+                }
+                Ok(config)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read config {}", path)),
+        }
+    }
+
+    /// Parse config text, also returning the keys no setting reads.
+    pub fn parse(text: &str) -> Result<(Self, Vec<UnknownKey>)> {
+        let mut unknown = Vec::new();
+        let config: Self = serde_ignored::deserialize(toml::Deserializer::new(text), |path| {
+            unknown.push(path.to_string())
+        })?;
+
+        let unknown = unknown
+            .into_iter()
+            .map(|path| UnknownKey {
+                line: key_line(text, &path),
+                path,
+            })
+            .collect();
+        Ok((config, unknown))
+    }
+
+    /// Every effective setting, noting whether `text` (the config file) set
+    /// it or it's a default.
+    pub fn settings(&self, text: &str) -> Result<Vec<Setting>> {
+        let effective = toml::Value::try_from(self).context("Failed to serialize config")?;
+        let file: toml::Table = toml::from_str(text).context("Failed to parse config")?;
+
+        let mut settings = Vec::new();
+        flatten(String::new(), &effective, &mut settings);
+        for setting in &mut settings {
+            let mut node = Some(&file);
+            let mut found = None;
+            for segment in setting.key.split('.') {
+                found = node.and_then(|t| t.get(segment));
+                node = found.and_then(|v| v.as_table());
+            }
+            setting.from_file = found.is_some();
+        }
+        Ok(settings)
+    }
+}
+
+/// One effective setting, as shown by `fgp-neon config show`.
+#[derive(Debug, Clone)]
+pub struct Setting {
+    /// Dotted path: `sql.backend`.
+    pub key: String,
+    /// The value in TOML syntax.
+    pub value: String,
+    pub from_file: bool,
+}
+
+/// Leaf settings of a table, with dotted keys.
+fn flatten(prefix: String, value: &toml::Value, out: &mut Vec<Setting>) {
+    match value {
+        toml::Value::Table(table) if !table.is_empty() => {
+            for (key, value) in table {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(key, value, out);
+            }
+        }
+        toml::Value::Table(_) => out.push(Setting {
+            key: prefix,
+            value: "{}".to_string(),
+            from_file: false,
+        }),
+        other => out.push(Setting {
+            key: prefix,
+            value: other.to_string(),
+            from_file: false,
+        }),
+    }
+}
+
+/// Set `key` (a dotted path) to `raw` in config text, keeping the rest of
+/// the file, comments included, as it was. `raw` is read as a TOML value,
+/// or as a string if it isn't one. Fails if the key isn't a setting or the
+/// value doesn't fit it.
+pub fn set_value(text: &str, key: &str, raw: &str) -> Result<String> {
+    let segments: Vec<&str> = key.split('.').collect();
+    if segments
+        .iter()
+        .any(|s| s.is_empty() || s.parse::<usize>().is_ok())
+    {
+        anyhow::bail!(
+            "Invalid key {:?}: use a dotted path like sql.backend; entries of arrays can't be set",
+            key
+        );
+    }
+    let value = raw
+        .parse::<toml_edit::Value>()
+        .unwrap_or_else(|_| raw.into());
+
+    let mut doc: toml_edit::DocumentMut = text.parse().context("Failed to parse config")?;
+    let Some((last, parents)) = segments.split_last() else {
+        anyhow::bail!("Missing key");
+    };
+    let mut item = doc.as_item_mut();
+    for (depth, segment) in parents.iter().enumerate() {
+        item = &mut item[*segment];
+        if item.is_none() {
+            *item = toml_edit::table();
+        } else if !item.is_table_like() {
+            anyhow::bail!("{} is a value, not a table", segments[..=depth].join("."));
+        }
+    }
+    item[*last] = toml_edit::value(value);
+
+    let updated = doc.to_string();
+    let (_, unknown) = Config::parse(&updated)
+        .with_context(|| format!("{} = {} doesn't fit the setting", key, raw))?;
+    if let Some(unknown) = unknown
+        .iter()
+        .find(|u| key == u.path || key.starts_with(&format!("{}.", u.path)))
+    {
+        anyhow::bail!("Unknown key `{}`", unknown.path);
+    }
+    Ok(updated)
+}
+
+/// A config key that no setting reads.
+#[derive(Debug, Clone)]
+pub struct UnknownKey {
+    /// Dotted path: `sql.bakend`, `roles.0.rol`.
+    pub path: String,
+    /// 1-based line of the key, when it can be found.
+    pub line: Option<usize>,
+}
+
+impl std::fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: unknown key `{}`", line, self.path),
+            None => write!(f, "unknown key `{}`", self.path),
+        }
+    }
+}
+
+/// Line on which the key at `path` is set, following `[table]` headers and
+/// dotted keys. Array indices in `path` are skipped, so an entry of an array
+/// of tables resolves to the first line setting that key.
+fn key_line(text: &str, path: &str) -> Option<usize> {
+    let target: Vec<&str> = path
+        .split('.')
+        .filter(|s| s.parse::<usize>().is_err())
+        .collect();
+    let mut table: Vec<String> = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.trim_start_matches('[');
+            let header = header.split(']').next().unwrap_or_default();
+            table = split_key(header);
+            if table == target {
+                return Some(i + 1);
+            }
+            continue;
+        }
+        let Some((key, _)) = line.split_once('=') else {
+            continue;
+        };
+        let mut full = table.clone();
+        full.extend(split_key(key));
+        // Keys inside an inline table resolve to the line that opens it
+        if full.len() <= target.len() && target[..full.len()] == full[..] {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+/// Segments of a (possibly dotted, possibly quoted) TOML key.
+fn split_key(key: &str) -> Vec<String> {
+    key.split('.')
+        .map(|s| s.trim().trim_matches('"').trim_matches('\'').to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
@@ -0,0 +1,76 @@
+//! Query cost guard.
+//!
+//! Before a query runs, `EXPLAIN` estimates its cost and row count; queries
+//! over the thresholds are rejected so an accidental cross join doesn't tie
+//! up a shared branch. Thresholds come from `[cost_guard]` and can be raised
+//! or lowered per call with `max_cost` / `max_estimated_rows`, or skipped with
+//! `cost_guard: false`.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::api::PlanEstimate;
+use crate::config::CostGuardConfig;
+
+/// Thresholds applied to one call.
+#[derive(Debug, Clone, Copy)]
+pub struct CostGuard {
+    max_cost: f64,
+    max_rows: f64,
+}
+
+impl CostGuard {
+    /// The guard for a call, or `None` when it shouldn't run.
+    pub fn from_params(
+        config: &CostGuardConfig,
+        params: &HashMap<String, Value>,
+    ) -> Result<Option<Self>> {
+        let threshold = |key: &str| -> Result<Option<f64>> {
+            match params.get(key) {
+                None | Some(Value::Null) => Ok(None),
+                Some(v) => match v.as_f64() {
+                    Some(n) if n > 0.0 => Ok(Some(n)),
+                    _ => anyhow::bail!("{} must be a positive number", key),
+                },
+            }
+        };
+        let max_cost = threshold("max_cost")?;
+        let max_rows = threshold("max_estimated_rows")?;
+
+        let requested = params.get("cost_guard").and_then(|v| v.as_bool());
+        let enabled = match requested {
+            Some(enabled) => enabled,
+            None => config.enabled || max_cost.is_some() || max_rows.is_some(),
+        };
+        if !enabled {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            max_cost: max_cost.unwrap_or(config.max_cost),
+            max_rows: max_rows.unwrap_or(config.max_rows),
+        }))
+    }
+
+    /// Reject a plan over either threshold.
+    pub fn check(&self, plan: &PlanEstimate) -> Result<()> {
+        if plan.total_cost > self.max_cost {
+            anyhow::bail!(
+                "Query rejected by cost guard: estimated cost {:.0} exceeds max_cost {:.0} (estimated rows {:.0}); pass a higher max_cost or cost_guard: false",
+                plan.total_cost,
+                self.max_cost,
+                plan.plan_rows
+            );
+        }
+        if plan.plan_rows > self.max_rows {
+            anyhow::bail!(
+                "Query rejected by cost guard: estimated rows {:.0} exceeds max_estimated_rows {:.0} (estimated cost {:.0}); pass a higher max_estimated_rows or cost_guard: false",
+                plan.plan_rows,
+                self.max_rows,
+                plan.total_cost
+            );
+        }
+        Ok(())
+    }
+}
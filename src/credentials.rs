@@ -0,0 +1,115 @@
+//! Neon credentials and how long they stay valid.
+//!
+//! The token comes from `NEON_API_KEY` or, failing that, the OAuth token
+//! neonctl saved. API keys don't expire; an OAuth token carries an expiry,
+//! and the daemon reads the token once at start, so it stops working when
+//! that time passes even if neonctl refreshes the file. The client records
+//! each accepted and rejected request, so health can warn before expiry and
+//! report when the API started refusing the token.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Mutex;
+
+/// Where the token was found and when it expires.
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub token: String,
+    /// `NEON_API_KEY` or `neonctl`.
+    pub source: &'static str,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Get Neon credentials from env var or neonctl config.
+pub fn load() -> Result<Credentials> {
+    // Try NEON_API_KEY first
+    if let Ok(key) = std::env::var("NEON_API_KEY") {
+        return Ok(Credentials {
+            token: key,
+            source: "NEON_API_KEY",
+            expires_at: None,
+        });
+    }
+
+    // Fall back to neonctl OAuth token
+    let creds_path = shellexpand::tilde("~/.config/neonctl/credentials.json").to_string();
+    let creds_json = std::fs::read_to_string(&creds_path).context(
+        "No NEON_API_KEY set and neonctl credentials not found. Run `neonctl auth` first.",
+    )?;
+
+    let creds: Value =
+        serde_json::from_str(&creds_json).context("Failed to parse neonctl credentials")?;
+    let token = creds
+        .get("access_token")
+        .and_then(|v| v.as_str())
+        .context("Failed to parse neonctl credentials: no access_token")?;
+
+    Ok(Credentials {
+        token: token.to_string(),
+        source: "neonctl",
+        expires_at: creds.get("expires_at").and_then(expiry),
+    })
+}
+
+/// An expiry as Unix seconds (what neonctl writes) or RFC 3339.
+fn expiry(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::Number(n) => DateTime::from_timestamp(n.as_f64()? as i64, 0),
+        Value::String(s) => DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|t| t.with_timezone(&Utc)),
+        _ => None,
+    }
+}
+
+/// Credential state reported by health.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthStatus {
+    pub source: &'static str,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Negative once expired.
+    pub expires_in_minutes: Option<i64>,
+    pub last_accepted: Option<DateTime<Utc>>,
+    /// Last 401, if the API has refused the token since it last accepted it.
+    pub rejected_at: Option<DateTime<Utc>>,
+}
+
+/// Accepted and rejected requests for one set of credentials.
+pub struct AuthTracker {
+    source: &'static str,
+    expires_at: Option<DateTime<Utc>>,
+    seen: Mutex<(Option<DateTime<Utc>>, Option<DateTime<Utc>>)>,
+}
+
+impl AuthTracker {
+    pub fn new(credentials: &Credentials) -> Self {
+        Self {
+            source: credentials.source,
+            expires_at: credentials.expires_at,
+            seen: Mutex::new((None, None)),
+        }
+    }
+
+    /// The API accepted the token.
+    pub fn accepted(&self) {
+        *self.seen.lock().unwrap() = (Some(Utc::now()), None);
+    }
+
+    /// The API answered 401.
+    pub fn rejected(&self) {
+        self.seen.lock().unwrap().1 = Some(Utc::now());
+    }
+
+    pub fn status(&self) -> AuthStatus {
+        let (last_accepted, rejected_at) = *self.seen.lock().unwrap();
+        AuthStatus {
+            source: self.source,
+            expires_at: self.expires_at,
+            expires_in_minutes: self.expires_at.map(|at| (at - Utc::now()).num_minutes()),
+            last_accepted,
+            rejected_at,
+        }
+    }
+}
@@ -0,0 +1,492 @@
+//! Row-level table comparison for `neon.data_diff`.
+//!
+//! Rows are matched by primary key (or the given key columns) and compared
+//! by an MD5 of the columns both sides have. Rather than fetching every row,
+//! each side first hashes its rows into buckets by key and returns one
+//! summary row per bucket; only buckets whose hashes differ are fetched key
+//! by key. Two mostly-equal tables therefore cost a few thousand summary
+//! rows whatever their size. `max_rows` caps the rows fetched key by key;
+//! past it the counts cover only the buckets checked and `complete` is false.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::api::{quote_ident, quote_literal, result_rows, NeonClient};
+
+/// Rows per bucket the bucket count aims for.
+const ROWS_PER_BUCKET: u64 = 1_000;
+
+/// Most buckets per comparison.
+const MAX_BUCKETS: u64 = 4_096;
+
+/// Most sample rows per kind of difference.
+pub const MAX_SAMPLES: usize = 100;
+
+/// One side of a comparison.
+#[derive(Debug, Clone)]
+pub struct Side {
+    pub project_id: String,
+    pub branch_id: String,
+    pub database: String,
+}
+
+/// What to compare.
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    pub schema: String,
+    pub table: String,
+    /// Key columns; the table's primary key when `None`.
+    pub key: Option<Vec<String>>,
+    /// Sample rows to return per kind of difference.
+    pub samples: usize,
+    /// Most rows fetched per side to compare key by key.
+    pub max_rows: u64,
+}
+
+/// Row differences between a table on two branches. Added rows are on the
+/// right only, removed rows on the left only.
+#[derive(Debug, Serialize)]
+pub struct DataDiff {
+    pub schema: String,
+    pub table: String,
+    pub key: Vec<String>,
+    /// Columns compared: those both sides have.
+    pub columns: Vec<String>,
+    pub only_left_columns: Vec<String>,
+    pub only_right_columns: Vec<String>,
+    pub left_rows: u64,
+    pub right_rows: u64,
+    pub identical: bool,
+    pub added: u64,
+    pub removed: u64,
+    /// Rows on both sides whose compared columns differ.
+    pub changed: u64,
+    /// Every differing bucket was checked. When false the counts are lower
+    /// bounds.
+    pub complete: bool,
+    pub buckets: u64,
+    pub differing_buckets: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub samples: Option<Samples>,
+}
+
+/// Up to `samples` rows per kind of difference, as JSON objects.
+#[derive(Debug, Default, Serialize)]
+pub struct Samples {
+    pub added: Vec<Value>,
+    pub removed: Vec<Value>,
+    pub changed: Vec<ChangedRow>,
+}
+
+/// A row whose values differ, on each side.
+#[derive(Debug, Serialize)]
+pub struct ChangedRow {
+    /// Key column values, in key order.
+    pub key: Value,
+    pub left: Value,
+    pub right: Value,
+}
+
+/// Keys (as text) of differing rows.
+#[derive(Debug, Default)]
+struct Differences {
+    added: Vec<String>,
+    removed: Vec<String>,
+    changed: Vec<String>,
+}
+
+/// Compare a table's rows on two sides.
+pub async fn diff(
+    client: &NeonClient,
+    left: &Side,
+    right: &Side,
+    options: &DiffOptions,
+) -> Result<DataDiff> {
+    let (schema, table) = (options.schema.as_str(), options.table.as_str());
+    let (left_detail, right_detail) = tokio::try_join!(
+        client.get_table_detail(
+            &left.project_id,
+            &left.branch_id,
+            &left.database,
+            schema,
+            table
+        ),
+        client.get_table_detail(
+            &right.project_id,
+            &right.branch_id,
+            &right.database,
+            schema,
+            table
+        ),
+    )?;
+
+    let key = match &options.key {
+        Some(key) => key.clone(),
+        None => left_detail
+            .primary_key
+            .as_ref()
+            .map(|pk| pk.columns.clone())
+            .filter(|columns| !columns.is_empty())
+            .with_context(|| {
+                format!(
+                    "{}.{} has no primary key; pass key to name the columns that identify a row",
+                    schema, table
+                )
+            })?,
+    };
+
+    let left_columns: Vec<&str> = left_detail
+        .columns
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect();
+    let right_columns: Vec<&str> = right_detail
+        .columns
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect();
+    for column in &key {
+        if !left_columns.contains(&column.as_str()) || !right_columns.contains(&column.as_str()) {
+            anyhow::bail!("Key column {} is not on both sides", column);
+        }
+    }
+    let only = |of: &[&str], other: &[&str]| -> Vec<String> {
+        of.iter()
+            .filter(|c| !other.contains(*c))
+            .map(|c| c.to_string())
+            .collect()
+    };
+    let columns: Vec<String> = left_columns
+        .iter()
+        .filter(|c| right_columns.contains(*c))
+        .map(|c| c.to_string())
+        .collect();
+    let only_left_columns = only(&left_columns, &right_columns);
+    let only_right_columns = only(&right_columns, &left_columns);
+
+    let sql = TableSql::new(schema, table, &key, &columns);
+    let (left_rows, right_rows) = tokio::try_join!(
+        count(client, left, &sql.count()),
+        count(client, right, &sql.count()),
+    )?;
+
+    // Each bucket's summary is one row, and each fetched bucket must fit in
+    // one result, so the bucket count is bounded by the row limit too
+    let per_call = client.max_rows() as u64;
+    let buckets = left_rows
+        .max(right_rows)
+        .div_ceil(ROWS_PER_BUCKET)
+        .clamp(1, MAX_BUCKETS.min(per_call).max(1));
+    let (left_buckets, right_buckets) = tokio::try_join!(
+        bucket_hashes(client, left, &sql.buckets(buckets)),
+        bucket_hashes(client, right, &sql.buckets(buckets)),
+    )?;
+
+    // Bucket -> rows to fetch from the larger side
+    let differing: Vec<(u64, u64)> = left_buckets
+        .keys()
+        .chain(right_buckets.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|bucket| {
+            let (l, r) = (left_buckets.get(bucket), right_buckets.get(bucket));
+            if l == r {
+                return None;
+            }
+            let size = l
+                .map_or(0, |(rows, _)| *rows)
+                .max(r.map_or(0, |(rows, _)| *rows));
+            Some((*bucket, size))
+        })
+        .collect();
+
+    let mut differences = Differences::default();
+    let mut complete = true;
+    let (mut fetched, mut batch, mut batch_rows) = (0, Vec::new(), 0);
+    for &(bucket, size) in &differing {
+        if size > per_call || fetched + size > options.max_rows {
+            complete = false;
+            continue;
+        }
+        if batch_rows + size > per_call {
+            compare_buckets(
+                client,
+                left,
+                right,
+                &sql.keys(buckets, &batch),
+                &mut differences,
+            )
+            .await?;
+            batch.clear();
+            batch_rows = 0;
+        }
+        batch.push(bucket);
+        batch_rows += size;
+        fetched += size;
+    }
+    if !batch.is_empty() {
+        compare_buckets(
+            client,
+            left,
+            right,
+            &sql.keys(buckets, &batch),
+            &mut differences,
+        )
+        .await?;
+    }
+
+    let samples = if options.samples > 0 {
+        Some(samples(client, left, right, &sql, &differences, options.samples).await?)
+    } else {
+        None
+    };
+
+    let (added, removed, changed) = (
+        differences.added.len() as u64,
+        differences.removed.len() as u64,
+        differences.changed.len() as u64,
+    );
+    Ok(DataDiff {
+        schema: options.schema.clone(),
+        table: options.table.clone(),
+        identical: complete
+            && differing.is_empty()
+            && only_left_columns.is_empty()
+            && only_right_columns.is_empty(),
+        key,
+        columns,
+        only_left_columns,
+        only_right_columns,
+        left_rows,
+        right_rows,
+        added,
+        removed,
+        changed,
+        complete,
+        buckets,
+        differing_buckets: differing.len(),
+        samples,
+    })
+}
+
+/// Fetch the keys and row hashes in some buckets on both sides and record
+/// how they differ.
+async fn compare_buckets(
+    client: &NeonClient,
+    left: &Side,
+    right: &Side,
+    query: &str,
+    differences: &mut Differences,
+) -> Result<()> {
+    let (left_rows, right_rows) =
+        tokio::try_join!(rows(client, left, query), rows(client, right, query))?;
+    let left_hashes = key_hashes(&left_rows);
+    let mut right_hashes = key_hashes(&right_rows);
+
+    for (key, hash) in left_hashes {
+        match right_hashes.remove(key) {
+            None => differences.removed.push(key.to_string()),
+            Some(other) if other != hash => differences.changed.push(key.to_string()),
+            Some(_) => {}
+        }
+    }
+    differences
+        .added
+        .extend(right_hashes.into_keys().map(str::to_string));
+    Ok(())
+}
+
+/// Full rows for the first `limit` keys of each kind of difference.
+async fn samples(
+    client: &NeonClient,
+    left: &Side,
+    right: &Side,
+    sql: &TableSql,
+    differences: &Differences,
+    limit: usize,
+) -> Result<Samples> {
+    let first = |keys: &[String]| -> Vec<String> {
+        let mut keys = keys.to_vec();
+        keys.sort();
+        keys.truncate(limit);
+        keys
+    };
+    let (added, removed, changed) = (
+        first(&differences.added),
+        first(&differences.removed),
+        first(&differences.changed),
+    );
+
+    let left_keys: Vec<&String> = removed.iter().chain(&changed).collect();
+    let right_keys: Vec<&String> = added.iter().chain(&changed).collect();
+    let (left_rows, right_rows) = tokio::try_join!(
+        sample_rows(client, left, sql, &left_keys),
+        sample_rows(client, right, sql, &right_keys),
+    )?;
+
+    let pick = |rows: &BTreeMap<String, Value>, keys: &[String]| -> Vec<Value> {
+        keys.iter().filter_map(|k| rows.get(k).cloned()).collect()
+    };
+    Ok(Samples {
+        added: pick(&right_rows, &added),
+        removed: pick(&left_rows, &removed),
+        changed: changed
+            .iter()
+            .filter_map(|k| {
+                Some(ChangedRow {
+                    key: serde_json::from_str(k).unwrap_or_else(|_| Value::String(k.clone())),
+                    left: left_rows.get(k)?.clone(),
+                    right: right_rows.get(k)?.clone(),
+                })
+            })
+            .collect(),
+    })
+}
+
+/// Rows by key text.
+async fn sample_rows(
+    client: &NeonClient,
+    side: &Side,
+    sql: &TableSql,
+    keys: &[&String],
+) -> Result<BTreeMap<String, Value>> {
+    if keys.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    Ok(rows(client, side, &sql.rows(keys))
+        .await?
+        .into_iter()
+        .filter_map(|mut row| {
+            let key = text(&row, "key")?.to_string();
+            Some((key, row.get_mut("row")?.take()))
+        })
+        .collect())
+}
+
+/// Row count of the table on one side.
+async fn count(client: &NeonClient, side: &Side, query: &str) -> Result<u64> {
+    let rows = rows(client, side, query).await?;
+    Ok(rows.first().map_or(0, |row| number(row, "rows")))
+}
+
+/// Bucket -> (rows, hash) on one side.
+async fn bucket_hashes(
+    client: &NeonClient,
+    side: &Side,
+    query: &str,
+) -> Result<BTreeMap<u64, (u64, String)>> {
+    Ok(rows(client, side, query)
+        .await?
+        .iter()
+        .map(|row| {
+            (
+                number(row, "bucket"),
+                (
+                    number(row, "rows"),
+                    text(row, "hash").unwrap_or_default().to_string(),
+                ),
+            )
+        })
+        .collect())
+}
+
+/// Run a query on one side, failing rather than returning a partial result.
+async fn rows(client: &NeonClient, side: &Side, query: &str) -> Result<Vec<Value>> {
+    let result = client
+        .run_sql(&side.project_id, &side.branch_id, &side.database, query)
+        .await
+        .with_context(|| format!("Query failed on {}/{}", side.project_id, side.branch_id))?;
+    if result.get("truncated").and_then(|v| v.as_bool()) == Some(true) {
+        anyhow::bail!(
+            "Table changed during the comparison; a result exceeded limits.max_rows on {}/{}",
+            side.project_id,
+            side.branch_id
+        );
+    }
+    Ok(result_rows(&result).to_vec())
+}
+
+fn key_hashes(rows: &[Value]) -> BTreeMap<&str, &str> {
+    rows.iter()
+        .filter_map(|row| Some((text(row, "key")?, text(row, "hash")?)))
+        .collect()
+}
+
+fn text<'a>(row: &'a Value, name: &str) -> Option<&'a str> {
+    row.get(name).and_then(|v| v.as_str())
+}
+
+/// A count, which Neon's HTTP endpoint returns as a string for `bigint`.
+fn number(row: &Value, name: &str) -> u64 {
+    match row.get(name) {
+        Some(Value::Number(n)) => n.as_u64().unwrap_or(0),
+        Some(Value::String(s)) => s.parse().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// Queries over the compared table. A row's key is the JSON array of its
+/// key columns as text, and its hash the MD5 of its compared columns.
+struct TableSql {
+    relation: String,
+    key: String,
+    hash: String,
+}
+
+impl TableSql {
+    fn new(schema: &str, table: &str, key: &[String], columns: &[String]) -> Self {
+        let list = |columns: &[String]| -> String {
+            columns
+                .iter()
+                .map(|c| format!("t.{}", quote_ident(c)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        Self {
+            relation: format!("{}.{}", quote_ident(schema), quote_ident(table)),
+            key: format!("jsonb_build_array({})::text", list(key)),
+            hash: format!("md5(ROW({})::text)", list(columns)),
+        }
+    }
+
+    fn count(&self) -> String {
+        format!("SELECT count(*) AS rows FROM {}", self.relation)
+    }
+
+    /// Keys, hashes, and buckets as `d`.
+    fn hashed(&self, buckets: u64) -> String {
+        format!(
+            "(SELECT h.key, h.hash, ('x' || substr(md5(h.key), 1, 7))::bit(28)::int % {} AS bucket FROM (SELECT {} AS key, {} AS hash FROM {} t) h) d",
+            buckets, self.key, self.hash, self.relation
+        )
+    }
+
+    fn buckets(&self, buckets: u64) -> String {
+        format!(
+            "SELECT d.bucket, count(*) AS rows, md5(string_agg(d.hash, '' ORDER BY d.key)) AS hash FROM {} GROUP BY d.bucket",
+            self.hashed(buckets)
+        )
+    }
+
+    fn keys(&self, buckets: u64, batch: &[u64]) -> String {
+        let batch: Vec<String> = batch.iter().map(|b| b.to_string()).collect();
+        format!(
+            "SELECT d.key, d.hash FROM {} WHERE d.bucket IN ({})",
+            self.hashed(buckets),
+            batch.join(", ")
+        )
+    }
+
+    fn rows(&self, keys: &[&String]) -> String {
+        let keys: Vec<String> = keys.iter().map(|k| quote_literal(k)).collect();
+        format!(
+            "SELECT {} AS key, to_jsonb(t) AS row FROM {} t WHERE {} IN ({})",
+            self.key,
+            self.relation,
+            self.key,
+            keys.join(", ")
+        )
+    }
+}
@@ -0,0 +1,154 @@
+//! Scheduled schema drift detection.
+//!
+//! Each configured target is snapshotted on an interval and compared with the
+//! previous snapshot. Changes are appended to a per-target history file under
+//! `~/.fgp/services/neon/drift/`, and changes outside a deploy window raise a
+//! `schema_drift` notification.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api::NeonClient;
+use crate::config::{state_path, DeployWindow, DriftConfig, DriftTarget};
+use crate::models::SchemaSnapshot;
+use crate::notify::Notifier;
+use crate::schema::{self, SchemaDiff};
+
+/// History entries kept per target.
+const MAX_HISTORY: usize = 100;
+
+/// Persisted drift state for one target.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DriftState {
+    pub last_checked: Option<DateTime<Utc>>,
+    pub last_snapshot: Option<SchemaSnapshot>,
+    #[serde(default)]
+    pub history: Vec<DriftEntry>,
+}
+
+/// A detected schema change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftEntry {
+    pub detected_at: DateTime<Utc>,
+    pub in_deploy_window: bool,
+    pub diff: SchemaDiff,
+}
+
+/// Run drift checks for every target forever.
+pub async fn run(client: Arc<NeonClient>, config: DriftConfig, notifier: Arc<Notifier>) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(config.interval_secs.max(60)));
+
+    loop {
+        ticker.tick().await;
+        for target in &config.targets {
+            if let Err(e) = check_target(&client, target, &notifier).await {
+                tracing::warn!("Drift check for {} failed: {:#}", target.name, e);
+            }
+        }
+    }
+}
+
+/// Snapshot a target, record any change since the last check, and notify if unexpected.
+pub async fn check_target(
+    client: &NeonClient,
+    target: &DriftTarget,
+    notifier: &Notifier,
+) -> Result<Option<DriftEntry>> {
+    let snapshot = client
+        .get_schema_snapshot(&target.project_id, &target.branch_id, &target.database)
+        .await?;
+
+    let path = history_path(&target.name);
+    let mut state = load_state(&target.name)?;
+    let now = Utc::now();
+
+    let entry = state
+        .last_snapshot
+        .as_ref()
+        .map(|previous| schema::drift(previous, &snapshot))
+        .filter(|diff| !diff.is_empty())
+        .map(|diff| DriftEntry {
+            detected_at: now,
+            in_deploy_window: target.deploy_windows.iter().any(|w| in_window(w, now)),
+            diff,
+        });
+
+    if let Some(entry) = &entry {
+        if !entry.in_deploy_window {
+            notifier
+                .notify(
+                    "schema_drift",
+                    serde_json::json!({
+                        "target": target.name,
+                        "project_id": target.project_id,
+                        "branch_id": target.branch_id,
+                        "database": target.database,
+                        "diff": entry.diff,
+                    }),
+                )
+                .await;
+        }
+
+        state.history.push(entry.clone());
+        let excess = state.history.len().saturating_sub(MAX_HISTORY);
+        state.history.drain(..excess);
+    }
+
+    state.last_snapshot = Some(snapshot);
+    state.last_checked = Some(now);
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create drift directory")?;
+    }
+    std::fs::write(&path, serde_json::to_vec_pretty(&state)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    Ok(entry)
+}
+
+/// Load the persisted state for a target (empty if never checked).
+pub fn load_state(name: &str) -> Result<DriftState> {
+    let path = history_path(name);
+    match std::fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(DriftState::default()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+fn history_path(name: &str) -> PathBuf {
+    state_path("drift").join(format!("{}.json", name))
+}
+
+/// Whether `now` falls inside a deploy window.
+fn in_window(window: &DeployWindow, now: DateTime<Utc>) -> bool {
+    let day = now.weekday().to_string().to_ascii_lowercase();
+    if !window.days.is_empty() && !window.days.iter().any(|d| d.eq_ignore_ascii_case(&day)) {
+        return false;
+    }
+
+    let (Ok(start), Ok(end)) = (
+        NaiveTime::parse_from_str(&window.start, "%H:%M"),
+        NaiveTime::parse_from_str(&window.end, "%H:%M"),
+    ) else {
+        tracing::warn!(
+            "Ignoring deploy window with invalid times: {}-{}",
+            window.start,
+            window.end
+        );
+        return false;
+    };
+
+    let time = now.time();
+    if start <= end {
+        time >= start && time < end
+    } else {
+        // Window wraps past midnight
+        time >= start || time < end
+    }
+}
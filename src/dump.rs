@@ -0,0 +1,466 @@
+//! Logical dumps of tables to a local file.
+//!
+//! `neon.dump` writes the schema and/or rows of the chosen tables (or every
+//! user table) to `path`, either as SQL that recreates them or as
+//! newline-delimited JSON. Rows are read a batch at a time in key order
+//! (the primary key, or `ctid` for a table without one) and written as each
+//! batch arrives, so no table is held in memory whole.
+//!
+//! Batches are separate queries, so rows written to during a dump may be
+//! missed or seen twice; dump a branch nothing else writes to for a
+//! consistent copy. The file is written next to `path` and renamed into
+//! place once complete, so a failed dump leaves no partial file behind.
+//!
+//! Columns with `[masking]` rules are replaced in the query that reads them
+//! (see [`crate::masking`]), so a dump of production data can be loaded into
+//! a dev branch without its PII.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use crate::api::{quote_ident, quote_literal, result_rows, NeonClient};
+use crate::masking::{ColumnMask, Masker};
+use crate::redact::Redactor;
+use crate::script::ScriptTarget;
+
+/// Most rows read per query.
+const BATCH_ROWS: usize = 1_000;
+
+/// Rows per `INSERT` in a SQL dump.
+const ROWS_PER_INSERT: usize = 100;
+
+/// File format of a dump.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DumpFormat {
+    /// DDL, then `INSERT`s, then foreign keys and sequence positions, for
+    /// `psql -f` or `neon.sql_file`.
+    #[default]
+    Sql,
+    /// One JSON object per line: `{"ddl": ...}` for each schema statement,
+    /// then `{"table": ..., "row": {...}}` for each row.
+    Ndjson,
+}
+
+/// What goes into a dump.
+#[derive(Debug, Clone, Copy)]
+pub struct DumpOptions {
+    pub format: DumpFormat,
+    pub schema: bool,
+    pub data: bool,
+    /// Replace an existing file at the path.
+    pub overwrite: bool,
+}
+
+/// A dumped table.
+#[derive(Debug, Serialize)]
+pub struct TableDump {
+    pub table: String,
+    pub rows: u64,
+    /// Columns replaced by masking rules.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub masked: Vec<String>,
+}
+
+/// What a dump wrote.
+#[derive(Debug, Serialize)]
+pub struct DumpOutcome {
+    pub path: String,
+    pub format: DumpFormat,
+    pub tables: Vec<TableDump>,
+    pub rows: u64,
+    /// DDL statements written.
+    pub statements: usize,
+    pub bytes: u64,
+    /// Cells masked by secret redaction.
+    pub redacted: usize,
+    pub duration_ms: f64,
+}
+
+/// A table's dumpable columns and how to walk its rows.
+struct Layout {
+    /// Quoted, non-generated columns, in table order.
+    columns: Vec<String>,
+    /// Quoted primary-key columns, or `ctid`.
+    key: Vec<String>,
+    /// Sequences the table owns, with their current values.
+    sequences: Vec<(String, Option<i64>)>,
+}
+
+/// Dump `tables` (`(schema, table)` pairs; every user table when empty)
+/// to `path`, masking columns per `masker`.
+pub async fn dump(
+    client: &NeonClient,
+    target: &ScriptTarget<'_>,
+    tables: &[(String, String)],
+    path: &str,
+    options: DumpOptions,
+    redactor: Option<&Redactor>,
+    masker: Option<&Masker>,
+) -> Result<DumpOutcome> {
+    let started = Instant::now();
+    let tables = if tables.is_empty() {
+        user_tables(client, target).await?
+    } else {
+        tables.to_vec()
+    };
+    let relations: Vec<String> = tables
+        .iter()
+        .map(|(schema, table)| format!("{}.{}", quote_ident(schema), quote_ident(table)))
+        .collect();
+
+    let path = shellexpand::tilde(path).to_string();
+    if !options.overwrite && Path::new(&path).exists() {
+        anyhow::bail!("{} already exists; pass overwrite to replace it", path);
+    }
+    let partial = format!("{}.partial", path);
+    let mut out = BufWriter::new(
+        File::create(&partial).with_context(|| format!("Failed to create {}", partial))?,
+    );
+    let mut outcome = DumpOutcome {
+        path: path.clone(),
+        format: options.format,
+        tables: Vec::new(),
+        rows: 0,
+        statements: 0,
+        bytes: 0,
+        redacted: 0,
+        duration_ms: 0.0,
+    };
+
+    let written = async {
+        // Foreign keys go after the data so rows can load in any table order
+        let mut post_data = Vec::new();
+        if options.schema {
+            let ddl = client
+                .get_schema_ddl(
+                    target.project_id,
+                    target.branch_id,
+                    target.database,
+                    Some(relations.as_slice()),
+                )
+                .await?;
+            outcome.statements = ddl.len();
+            for statement in ddl {
+                if options.format == DumpFormat::Sql && statement.contains(" FOREIGN KEY (") {
+                    post_data.push(statement);
+                } else {
+                    write_ddl(&mut out, options.format, &statement)?;
+                }
+            }
+        }
+
+        if options.data {
+            for ((schema, table), relation) in tables.iter().zip(&relations) {
+                let masks = masker.map(|m| m.columns(schema, table)).unwrap_or_default();
+                let (dumped, redacted) = dump_rows(
+                    client,
+                    target,
+                    relation,
+                    options.format,
+                    redactor,
+                    &masks,
+                    &mut out,
+                )
+                .await?;
+                outcome.rows += dumped.rows;
+                outcome.redacted += redacted;
+                outcome.tables.push(dumped);
+            }
+        } else {
+            outcome.tables = relations
+                .iter()
+                .map(|relation| TableDump {
+                    table: relation.clone(),
+                    rows: 0,
+                    masked: Vec::new(),
+                })
+                .collect();
+        }
+
+        for statement in post_data {
+            write_ddl(&mut out, options.format, &statement)?;
+        }
+        out.flush()?;
+        anyhow::Ok(())
+    }
+    .await;
+    drop(out);
+
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+    std::fs::rename(&partial, &path)
+        .with_context(|| format!("Failed to move the dump into place at {}", path))?;
+
+    outcome.bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    outcome.duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+    Ok(outcome)
+}
+
+/// Write one table's rows, with `masks` replacing columns' values; returns
+/// what was written and the cells redacted.
+async fn dump_rows(
+    client: &NeonClient,
+    target: &ScriptTarget<'_>,
+    relation: &str,
+    format: DumpFormat,
+    redactor: Option<&Redactor>,
+    masks: &[ColumnMask],
+    out: &mut impl Write,
+) -> Result<(TableDump, usize)> {
+    let layout = layout(client, target, relation).await?;
+    let batch = BATCH_ROWS.min(client.max_rows()).max(1);
+    // Rules for columns the table doesn't have are skipped
+    let masks: Vec<&ColumnMask> = masks
+        .iter()
+        .filter(|m| layout.columns.contains(&quote_ident(&m.column)))
+        .collect();
+    let mask_of = |column: &str| masks.iter().find(|m| quote_ident(&m.column) == column);
+    let payload = match format {
+        DumpFormat::Sql => format!(
+            "json_build_array({})",
+            layout
+                .columns
+                .iter()
+                .map(|c| match mask_of(c) {
+                    Some(mask) => format!("({})", mask.expr),
+                    None => format!("t.{}::text", c),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        DumpFormat::Ndjson if masks.is_empty() => "to_jsonb(t)".to_string(),
+        DumpFormat::Ndjson => format!(
+            "to_jsonb(t) || jsonb_build_object({})",
+            masks
+                .iter()
+                .map(|m| format!("{}, {}", quote_literal(&m.column), m.expr))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let key_exprs: Vec<String> = layout.key.iter().map(|k| format!("t.{}", k)).collect();
+    let key_select = key_exprs
+        .iter()
+        .map(|k| format!("{}::text", k))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let (mut rows, mut redacted) = (0u64, 0usize);
+    let mut after: Option<Vec<String>> = None;
+    loop {
+        let filter = after
+            .as_ref()
+            .map(|values| {
+                format!(
+                    " WHERE ({}) > ({})",
+                    key_exprs.join(", "),
+                    values
+                        .iter()
+                        .map(|v| quote_literal(v))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+            .unwrap_or_default();
+        let query = format!(
+            "SELECT json_build_array({}) AS key, {} AS row FROM {} t{} ORDER BY {} LIMIT {}",
+            key_select,
+            payload,
+            relation,
+            filter,
+            key_exprs.join(", "),
+            batch
+        );
+        let result = client
+            .run_sql(target.project_id, target.branch_id, target.database, &query)
+            .await
+            .with_context(|| format!("Failed to read rows of {}", relation))?;
+
+        let page = result_rows(&result);
+        let mut values: Vec<Value> = page.iter().map(|r| json_field(r, "row")).collect();
+        if let Some(redactor) = redactor {
+            for value in &mut values {
+                redacted += redactor.redact_value(value);
+            }
+        }
+        match format {
+            DumpFormat::Sql => {
+                for chunk in values.chunks(ROWS_PER_INSERT) {
+                    write_insert(out, relation, &layout.columns, chunk)?;
+                }
+            }
+            DumpFormat::Ndjson => {
+                for value in values {
+                    let line = serde_json::json!({ "table": relation, "row": value });
+                    writeln!(out, "{}", line)?;
+                }
+            }
+        }
+        rows += page.len() as u64;
+
+        if page.len() < batch {
+            break;
+        }
+        let last = page.last().map(|r| json_field(r, "key"));
+        after = last
+            .and_then(|key| serde_json::from_value(key).ok())
+            .filter(|key: &Vec<String>| key.len() == layout.key.len());
+        if after.is_none() {
+            anyhow::bail!("Couldn't read the key of the last row of {}", relation);
+        }
+    }
+
+    if format == DumpFormat::Sql {
+        for (sequence, value) in &layout.sequences {
+            if let Some(value) = value {
+                writeln!(
+                    out,
+                    "SELECT pg_catalog.setval({}, {}, true);",
+                    quote_literal(sequence),
+                    value
+                )?;
+            }
+        }
+    }
+    let dumped = TableDump {
+        table: relation.to_string(),
+        rows,
+        masked: masks.iter().map(|m| m.column.clone()).collect(),
+    };
+    Ok((dumped, redacted))
+}
+
+/// Columns, key, and owned sequences of `relation`.
+async fn layout(client: &NeonClient, target: &ScriptTarget<'_>, relation: &str) -> Result<Layout> {
+    let regclass = format!("to_regclass({})", quote_literal(relation));
+    let query = format!(
+        "SELECT (SELECT json_agg(a.attname ORDER BY a.attnum) FROM pg_catalog.pg_attribute a WHERE a.attrelid = {rel} AND a.attnum > 0 AND NOT a.attisdropped AND a.attgenerated = '') AS columns, \
+         (SELECT json_agg(a.attname ORDER BY k.n) FROM pg_catalog.pg_index i CROSS JOIN LATERAL unnest(i.indkey::int2[]) WITH ORDINALITY AS k(attnum, n) JOIN pg_catalog.pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = k.attnum WHERE i.indrelid = {rel} AND i.indisprimary) AS key, \
+         (SELECT json_agg(json_build_array(s.name, pg_catalog.pg_sequence_last_value(s.name::regclass))) FROM (SELECT pg_catalog.pg_get_serial_sequence({name}, a.attname) AS name FROM pg_catalog.pg_attribute a WHERE a.attrelid = {rel} AND a.attnum > 0 AND NOT a.attisdropped) s WHERE s.name IS NOT NULL) AS sequences",
+        rel = regclass,
+        name = quote_literal(relation),
+    );
+    let result = client
+        .run_sql(target.project_id, target.branch_id, target.database, &query)
+        .await?;
+    let row = result_rows(&result).first().cloned().unwrap_or_default();
+
+    let names = |field: &str| -> Vec<String> {
+        serde_json::from_value::<Vec<String>>(json_field(&row, field))
+            .unwrap_or_default()
+            .iter()
+            .map(|name| quote_ident(name))
+            .collect()
+    };
+    let columns = names("columns");
+    if columns.is_empty() {
+        anyhow::bail!("Table {} not found, or has no columns to dump", relation);
+    }
+    let mut key = names("key");
+    if key.is_empty() {
+        key.push("ctid".to_string());
+    }
+    let sequences =
+        serde_json::from_value::<Vec<(String, Option<Value>)>>(json_field(&row, "sequences"))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, value)| {
+                let value = value.and_then(|v| match v {
+                    Value::String(s) => s.parse().ok(),
+                    v => v.as_i64(),
+                });
+                (name, value)
+            })
+            .collect();
+
+    Ok(Layout {
+        columns,
+        key,
+        sequences,
+    })
+}
+
+/// Every user table, as `(schema, table)`, partitions excluded.
+async fn user_tables(
+    client: &NeonClient,
+    target: &ScriptTarget<'_>,
+) -> Result<Vec<(String, String)>> {
+    let query = "SELECT json_agg(json_build_array(n.nspname, c.relname) ORDER BY n.nspname, c.relname) AS tables FROM pg_catalog.pg_class c JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace WHERE c.relkind = 'r' AND NOT c.relispartition AND n.nspname NOT IN ('pg_catalog', 'information_schema') AND n.nspname NOT LIKE 'pg\\_%' AND NOT EXISTS (SELECT 1 FROM pg_catalog.pg_depend d WHERE d.classid = 'pg_class'::regclass AND d.objid = c.oid AND d.deptype = 'e')";
+    let result = client
+        .run_sql(target.project_id, target.branch_id, target.database, query)
+        .await?;
+    let tables = result_rows(&result)
+        .first()
+        .map(|row| json_field(row, "tables"))
+        .unwrap_or_default();
+    let tables: Vec<(String, String)> = serde_json::from_value(tables).unwrap_or_default();
+    if tables.is_empty() {
+        anyhow::bail!("Database {} has no tables to dump", target.database);
+    }
+    Ok(tables)
+}
+
+fn write_ddl(out: &mut impl Write, format: DumpFormat, statement: &str) -> Result<()> {
+    match format {
+        DumpFormat::Sql => writeln!(out, "{};\n", statement)?,
+        DumpFormat::Ndjson => writeln!(out, "{}", serde_json::json!({ "ddl": statement }))?,
+    }
+    Ok(())
+}
+
+/// One multi-row `INSERT` of text values, which Postgres casts to each
+/// column's type. `OVERRIDING SYSTEM VALUE` keeps identity column values.
+fn write_insert(
+    out: &mut impl Write,
+    relation: &str,
+    columns: &[String],
+    rows: &[Value],
+) -> Result<()> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let tuples: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let values: Vec<String> = row
+                .as_array()
+                .map(|cells| {
+                    cells
+                        .iter()
+                        .map(|cell| match cell {
+                            Value::Null => "NULL".to_string(),
+                            Value::String(s) => quote_literal(s),
+                            other => quote_literal(&other.to_string()),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            format!("({})", values.join(", "))
+        })
+        .collect();
+    writeln!(
+        out,
+        "INSERT INTO {} ({}) OVERRIDING SYSTEM VALUE VALUES\n  {};",
+        relation,
+        columns.join(", "),
+        tuples.join(",\n  ")
+    )?;
+    Ok(())
+}
+
+/// A JSON column, which may arrive parsed or as JSON text.
+fn json_field(row: &Value, key: &str) -> Value {
+    match row.get(key) {
+        Some(Value::String(s)) => serde_json::from_str(s).unwrap_or(Value::String(s.clone())),
+        Some(v) => v.clone(),
+        None => Value::Null,
+    }
+}
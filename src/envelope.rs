@@ -0,0 +1,104 @@
+//! Uniform response envelope.
+//!
+//! Methods build results in whatever shape suits them; before a result goes
+//! out, [`wrap`] normalizes it so every method answers with the same fields:
+//!
+//! - `data`: the payload. List results (`{"projects": [...], "count": N}`)
+//!   become the bare array.
+//! - `pagination`: `next_cursor`, `next_offset`, `truncated`, `continuation`
+//!   when the method reported any of them, else null.
+//! - `warnings`: always an array of structured warnings (see [`crate::warnings`]),
+//!   including ones for partial and cached results.
+//! - `meta`: other top-level fields of a list result (such as `stale_count`).
+//! - `timing_ms` and `cached`.
+//! - `trace_id`: the caller's trace id, echoed back (null when not given).
+
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+use crate::warnings;
+
+/// Fields describing how to fetch more of a result.
+const PAGINATION_KEYS: &[&str] = &["next_cursor", "next_offset", "truncated", "continuation"];
+
+/// Wrap a method's result in the envelope.
+pub fn wrap(result: Value, elapsed: Duration, trace_id: Option<String>) -> Value {
+    let mut pagination = Map::new();
+    let mut warnings = Vec::new();
+    let mut meta = Map::new();
+    let mut cached = false;
+
+    let data = match result {
+        Value::Object(mut obj) => {
+            for key in PAGINATION_KEYS {
+                match obj.remove(*key) {
+                    // A complete result isn't worth a pagination block
+                    Some(Value::Bool(false)) | Some(Value::Null) | None => {}
+                    Some(v) => {
+                        pagination.insert(key.to_string(), v);
+                    }
+                }
+            }
+            match obj.remove("warnings") {
+                Some(Value::Array(items)) => warnings.extend(items),
+                Some(Value::Null) | None => {}
+                Some(other) => warnings.push(other),
+            }
+            if let Some(warning) = obj.remove("warning").filter(|w| !w.is_null()) {
+                warnings.push(warning);
+            }
+            if let Some(flag) = obj.remove("cached") {
+                cached = flag.as_bool().unwrap_or(false);
+            }
+            if let Some(age) = obj.remove("cached_age_secs").filter(|_| cached) {
+                warnings.push(warnings::warning_with(
+                    "cached",
+                    format!(
+                        "Served from the daemon cache ({}s old); pass fresh: true for current data",
+                        age
+                    ),
+                    serde_json::json!({ "age_secs": age }),
+                ));
+            }
+            unwrap_list(obj, &mut meta)
+        }
+        other => other,
+    };
+    let mut warnings: Vec<Value> = warnings.into_iter().map(warnings::normalize).collect();
+    if pagination.get("truncated") == Some(&Value::Bool(true)) {
+        let message = pagination
+            .get("continuation")
+            .and_then(|c| c.as_str())
+            .unwrap_or("Result is partial; see pagination for the rest");
+        warnings.push(warnings::warning("truncated", message));
+    }
+
+    serde_json::json!({
+        "data": data,
+        "pagination": if pagination.is_empty() { Value::Null } else { Value::Object(pagination) },
+        "warnings": warnings,
+        "meta": meta,
+        "timing_ms": elapsed.as_secs_f64() * 1000.0,
+        "cached": cached,
+        "trace_id": trace_id,
+    })
+}
+
+/// A `{"<items>": [...], "count": N, ...}` object as its array, moving its
+/// other fields to `meta`; any other object unchanged.
+fn unwrap_list(mut obj: Map<String, Value>, meta: &mut Map<String, Value>) -> Value {
+    let arrays: Vec<&String> = obj
+        .iter()
+        .filter(|(_, v)| v.is_array())
+        .map(|(k, _)| k)
+        .collect();
+    if !obj.contains_key("count") || arrays.len() != 1 {
+        return Value::Object(obj);
+    }
+
+    let key = arrays[0].clone();
+    let items = obj.remove(&key).unwrap_or(Value::Null);
+    obj.remove("count");
+    meta.extend(obj);
+    items
+}
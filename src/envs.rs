@@ -0,0 +1,292 @@
+//! Environment profiles.
+//!
+//! A call passing `env: "prod"` gets the `[envs.prod]` profile applied before
+//! it runs: its default project and branch fill in missing params, calls to
+//! projects outside the profile's set or to methods it doesn't allow are
+//! rejected, and its write mode decides whether a call that changes state
+//! runs, needs `confirm: true`, or is refused. A `neon.sql`,
+//! `neon.sql_template`, or `neon.compare_query` query, or a `neon.sql_batch`
+//! of queries, that looks like it only reads doesn't count as a write, but
+//! runs in a read-only transaction so Postgres refuses it if it does write
+//! after all.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::api::is_read_only;
+use crate::config::{unknown_preset, EnvProfile, WriteMode};
+use crate::idempotency::IdempotencyStore;
+
+/// Apply the profile named by the call's `env`, if any, returning the params
+/// to run the call with.
+pub fn apply(
+    envs: &BTreeMap<String, EnvProfile>,
+    method: &str,
+    mut params: HashMap<String, Value>,
+) -> Result<HashMap<String, Value>> {
+    let name = match params.get("env") {
+        None | Some(Value::Null) => return Ok(params),
+        Some(Value::String(name)) => name.clone(),
+        Some(_) => anyhow::bail!("env must be a string"),
+    };
+    let Some(env) = envs.get(&name) else {
+        return Err(unknown_preset("env", &name, envs.keys()));
+    };
+    let short = method.strip_prefix("neon.").unwrap_or(method);
+
+    if !env.allowed_methods.is_empty()
+        && !env
+            .allowed_methods
+            .iter()
+            .any(|m| m.strip_prefix("neon.").unwrap_or(m) == short)
+    {
+        anyhow::bail!("neon.{} isn't allowed in env {}", short, name);
+    }
+
+    if let Some(project_id) = &env.project_id {
+        params
+            .entry("project_id".into())
+            .or_insert_with(|| Value::String(project_id.clone()));
+    }
+    let project = params
+        .get("project_id")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    if let Some(project) = &project {
+        if !env.projects.is_empty() && !env.projects.contains(project) {
+            anyhow::bail!(
+                "Project {} isn't in env {} (allowed: {})",
+                project,
+                name,
+                env.projects.join(", ")
+            );
+        }
+    }
+    if let Some(branch_id) = &env.branch_id {
+        if project.is_some() && project == env.project_id {
+            params
+                .entry("branch_id".into())
+                .or_insert_with(|| Value::String(branch_id.clone()));
+        }
+    }
+
+    let confirmed = params.get("confirm").and_then(|v| v.as_bool()) == Some(true);
+    if is_write(short, &params) {
+        match env.writes {
+            WriteMode::Allow => {}
+            WriteMode::Confirm if confirmed => {}
+            WriteMode::Confirm => anyhow::bail!(
+                "neon.{} changes state in env {}; pass confirm: true to run it",
+                short,
+                name
+            ),
+            WriteMode::Deny => {
+                anyhow::bail!("Env {} is read-only; neon.{} changes state", name, short)
+            }
+        }
+    } else if runs_sql(short)
+        && !(env.writes == WriteMode::Allow || env.writes == WriteMode::Confirm && confirmed)
+    {
+        // The query only looks read-only; have Postgres hold it to that
+        params.insert("read_only".into(), Value::Bool(true));
+    }
+    Ok(params)
+}
+
+/// Whether a call may change state.
+fn is_write(method: &str, params: &HashMap<String, Value>) -> bool {
+    match method {
+        "sql" | "sql_template" | "compare_query" => params
+            .get("query")
+            .and_then(|v| v.as_str())
+            .is_none_or(|query| !is_read_only(query)),
+        // Each entry is a SQL string or {"query", "params"}
+        "sql_batch" => params
+            .get("queries")
+            .and_then(|v| v.as_array())
+            .is_none_or(|queries| {
+                queries.iter().any(|q| {
+                    q.as_str()
+                        .or_else(|| q.get("query").and_then(|v| v.as_str()))
+                        .is_none_or(|query| !is_read_only(query))
+                })
+            }),
+        _ => IdempotencyStore::is_mutating(method),
+    }
+}
+
+/// Methods that run the caller's SQL as given, so whether they write
+/// depends on it.
+fn runs_sql(method: &str) -> bool {
+    matches!(
+        method,
+        "sql" | "sql_template" | "compare_query" | "sql_batch"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn envs() -> BTreeMap<String, EnvProfile> {
+        let profile = |writes| EnvProfile {
+            projects: vec!["p-prod".into(), "p-audit".into()],
+            project_id: Some("p-prod".into()),
+            branch_id: Some("br-main".into()),
+            writes,
+            ..Default::default()
+        };
+        BTreeMap::from([
+            ("dev".to_string(), profile(WriteMode::Allow)),
+            ("staging".to_string(), profile(WriteMode::Confirm)),
+            ("prod".to_string(), profile(WriteMode::Deny)),
+            (
+                "ops".to_string(),
+                EnvProfile {
+                    allowed_methods: vec!["neon.list_projects".into(), "sql".into()],
+                    ..Default::default()
+                },
+            ),
+        ])
+    }
+
+    fn params(value: Value) -> HashMap<String, Value> {
+        match value {
+            Value::Object(map) => map.into_iter().collect(),
+            _ => panic!("params must be an object"),
+        }
+    }
+
+    #[test]
+    fn calls_without_env_are_untouched() {
+        let out = apply(
+            &envs(),
+            "neon.sql",
+            params(json!({"query": "DROP TABLE t"})),
+        )
+        .unwrap();
+        assert_eq!(out, params(json!({"query": "DROP TABLE t"})));
+        assert!(apply(&envs(), "neon.sql", params(json!({"env": "qa"}))).is_err());
+        assert!(apply(&envs(), "neon.sql", params(json!({"env": 1}))).is_err());
+    }
+
+    #[test]
+    fn defaults_fill_in_project_and_branch() {
+        let out = apply(
+            &envs(),
+            "neon.list_branches",
+            params(json!({"env": "prod"})),
+        )
+        .unwrap();
+        assert_eq!(out["project_id"], "p-prod");
+        assert_eq!(out["branch_id"], "br-main");
+
+        // The default branch belongs to the default project only
+        let out = apply(
+            &envs(),
+            "neon.list_branches",
+            params(json!({"env": "prod", "project_id": "p-audit"})),
+        )
+        .unwrap();
+        assert_eq!(out["project_id"], "p-audit");
+        assert!(!out.contains_key("branch_id"));
+    }
+
+    #[test]
+    fn projects_outside_the_env_are_refused() {
+        let err = apply(
+            &envs(),
+            "neon.list_branches",
+            params(json!({"env": "prod", "project_id": "p-other"})),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("isn't in env prod"), "{}", err);
+    }
+
+    #[test]
+    fn allowed_methods_limit_the_env() {
+        assert!(apply(&envs(), "neon.list_projects", params(json!({"env": "ops"}))).is_ok());
+        assert!(apply(
+            &envs(),
+            "neon.sql",
+            params(json!({"env": "ops", "query": "SELECT 1"}))
+        )
+        .is_ok());
+        assert!(apply(
+            &envs(),
+            "neon.delete_project",
+            params(json!({"env": "ops"}))
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn writes_follow_the_env_write_mode() {
+        let create = |env| params(json!({"env": env, "name": "feature"}));
+        assert!(apply(&envs(), "neon.create_branch", create("dev")).is_ok());
+
+        let err = apply(&envs(), "neon.create_branch", create("prod")).unwrap_err();
+        assert!(err.to_string().contains("read-only"), "{}", err);
+
+        let err = apply(&envs(), "neon.create_branch", create("staging")).unwrap_err();
+        assert!(err.to_string().contains("confirm: true"), "{}", err);
+        let mut confirmed = create("staging");
+        confirmed.insert("confirm".into(), json!(true));
+        assert!(apply(&envs(), "neon.create_branch", confirmed).is_ok());
+    }
+
+    #[test]
+    fn write_queries_count_as_writes() {
+        let update = |env| params(json!({"env": env, "query": "UPDATE t SET a = 1"}));
+        assert!(apply(&envs(), "neon.sql", update("prod")).is_err());
+        assert!(apply(&envs(), "neon.sql", update("staging")).is_err());
+        assert!(apply(&envs(), "neon.compare_query", update("prod")).is_err());
+        assert!(apply(&envs(), "neon.sql", params(json!({"env": "prod"}))).is_err());
+    }
+
+    #[test]
+    fn read_queries_run_read_only_unless_writes_are_allowed() {
+        let select = |env| params(json!({"env": env, "query": "SELECT 'insert' FROM t"}));
+
+        let out = apply(&envs(), "neon.sql", select("prod")).unwrap();
+        assert_eq!(out["read_only"], true);
+        let out = apply(&envs(), "neon.sql", select("staging")).unwrap();
+        assert_eq!(out["read_only"], true);
+
+        let mut confirmed = select("staging");
+        confirmed.insert("confirm".into(), json!(true));
+        let out = apply(&envs(), "neon.sql", confirmed).unwrap();
+        assert!(!out.contains_key("read_only"));
+        let out = apply(&envs(), "neon.sql", select("dev")).unwrap();
+        assert!(!out.contains_key("read_only"));
+
+        // Methods that don't run the caller's SQL aren't downgraded
+        let out = apply(
+            &envs(),
+            "neon.list_branches",
+            params(json!({"env": "prod"})),
+        )
+        .unwrap();
+        assert!(!out.contains_key("read_only"));
+    }
+
+    #[test]
+    fn batches_are_writes_if_any_query_writes() {
+        let reads = params(json!({
+            "env": "prod",
+            "queries": ["SELECT 1", {"query": "SELECT * FROM t WHERE id = $1", "params": [1]}],
+        }));
+        let out = apply(&envs(), "neon.sql_batch", reads).unwrap();
+        assert_eq!(out["read_only"], true);
+
+        let writes = params(json!({
+            "env": "prod",
+            "queries": ["SELECT 1", {"query": "DELETE FROM t"}],
+        }));
+        assert!(apply(&envs(), "neon.sql_batch", writes).is_err());
+        let malformed = params(json!({"env": "prod", "queries": [1]}));
+        assert!(apply(&envs(), "neon.sql_batch", malformed).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+//! Ephemeral branches.
+//!
+//! `neon.create_ephemeral_branch` creates a branch and records when it
+//! expires in `~/.fgp/services/neon/ephemeral.json`. A background task
+//! deletes each branch once it expires, so branches made for a test run or
+//! a preview go away even if whoever made them never cleans up. The record
+//! is on disk, so a restarted daemon still deletes branches created before
+//! the restart.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::api::{ApiError, NeonClient};
+use crate::config::state_path;
+use crate::models::Branch;
+
+/// How often expired branches are swept.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Serializes read-modify-write of the registry file across tasks.
+static STORE: Mutex<()> = Mutex::new(());
+
+/// A branch the daemon will delete.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EphemeralBranch {
+    pub project_id: String,
+    pub branch_id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Create a branch and record its expiry.
+pub async fn create(
+    client: &NeonClient,
+    project_id: &str,
+    name: Option<&str>,
+    parent_id: Option<&str>,
+    ttl: Duration,
+) -> Result<(Branch, EphemeralBranch)> {
+    let branch = client.create_branch(project_id, name, parent_id).await?;
+
+    let now = Utc::now();
+    let entry = EphemeralBranch {
+        project_id: project_id.to_string(),
+        branch_id: branch.id.clone(),
+        name: branch.name.clone(),
+        created_at: now,
+        expires_at: now + ChronoDuration::from_std(ttl)?,
+    };
+    if let Err(e) = update(|branches| branches.push(entry.clone())) {
+        // Don't leave behind a branch nothing will delete
+        if let Err(cleanup) = client.delete_branch(project_id, &branch.id).await {
+            tracing::warn!("Failed to delete branch {}: {:#}", branch.id, cleanup);
+        }
+        return Err(e).context("Failed to record ephemeral branch");
+    }
+
+    Ok((branch, entry))
+}
+
+/// Recorded ephemeral branches.
+pub fn list() -> Result<Vec<EphemeralBranch>> {
+    let _guard = STORE.lock().unwrap();
+    load()
+}
+
+/// Delete expired branches forever.
+pub async fn run(client: Arc<NeonClient>) {
+    let mut ticker = tokio::time::interval(SWEEP_INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        let expired: Vec<EphemeralBranch> = match list() {
+            Ok(branches) => branches
+                .into_iter()
+                .filter(|b| b.expires_at <= Utc::now())
+                .collect(),
+            Err(e) => {
+                tracing::warn!("Failed to read ephemeral branches: {:#}", e);
+                continue;
+            }
+        };
+
+        for branch in expired {
+            match reap(&client, &branch).await {
+                Ok(()) => tracing::info!("Deleted expired branch {}", branch.branch_id),
+                Err(e) => tracing::warn!(
+                    "Failed to delete expired branch {}: {:#}",
+                    branch.branch_id,
+                    e
+                ),
+            }
+        }
+    }
+}
+
+/// Delete an expired branch and forget it.
+async fn reap(client: &NeonClient, branch: &EphemeralBranch) -> Result<()> {
+    match client
+        .delete_branch(&branch.project_id, &branch.branch_id)
+        .await
+    {
+        Ok(()) => {}
+        // Already deleted by someone else; just forget it
+        Err(e) if ApiError::is_not_found(&e) => {}
+        Err(e) => return Err(e),
+    }
+
+    update(|branches| branches.retain(|b| b.branch_id != branch.branch_id))
+}
+
+fn update(change: impl FnOnce(&mut Vec<EphemeralBranch>)) -> Result<()> {
+    let _guard = STORE.lock().unwrap();
+    let mut branches = load()?;
+    change(&mut branches);
+
+    let path = state_path("ephemeral.json");
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create state directory")?;
+    }
+    std::fs::write(&path, serde_json::to_vec_pretty(&branches)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn load() -> Result<Vec<EphemeralBranch>> {
+    let path = state_path("ephemeral.json");
+    match std::fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
@@ -0,0 +1,94 @@
+//! Caller-selected response fields.
+//!
+//! Any call may pass `fields` to get back only some of the result's
+//! attributes: a comma-separated string of dotted paths (`"id,name,owner.email"`)
+//! or an array of paths or JSON pointers (`["/id", "/settings/quota"]`).
+//! Paths apply to the envelope's `data`; arrays are projected element by
+//! element, so `"id,name"` on a listing keeps those two keys of every item.
+//! Selected keys missing from an item are left out rather than failing.
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// Paths to keep, as a tree of keys.
+#[derive(Debug, Default)]
+pub struct Fields {
+    /// A path ends here, so the whole value is kept.
+    whole: bool,
+    children: BTreeMap<String, Fields>,
+}
+
+impl Fields {
+    /// The call's `fields`, if it passed any.
+    pub fn from_params(params: &HashMap<String, Value>) -> Result<Option<Self>> {
+        let paths: Vec<String> = match params.get("fields") {
+            None | Some(Value::Null) => return Ok(None),
+            Some(Value::String(list)) => list.split(',').map(|s| s.to_string()).collect(),
+            Some(Value::Array(items)) => items
+                .iter()
+                .map(|item| {
+                    item.as_str()
+                        .map(|s| s.to_string())
+                        .ok_or_else(|| anyhow::anyhow!("fields entries must be strings"))
+                })
+                .collect::<Result<_>>()?,
+            Some(_) => anyhow::bail!(
+                "fields must be a comma-separated string or an array of paths or JSON pointers"
+            ),
+        };
+
+        let mut fields = Fields::default();
+        for path in paths.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let segments = parse_path(path);
+            if segments.iter().any(|s| s.is_empty()) {
+                anyhow::bail!("Invalid field path {:?}", path);
+            }
+            fields.insert(segments);
+        }
+        if fields.children.is_empty() {
+            anyhow::bail!("fields must name at least one path");
+        }
+        Ok(Some(fields))
+    }
+
+    fn insert(&mut self, segments: Vec<String>) {
+        let mut node = self;
+        for segment in segments {
+            node = node.children.entry(segment).or_default();
+        }
+        node.whole = true;
+    }
+
+    /// Drop everything from `value` that isn't selected.
+    pub fn apply(&self, value: &mut Value) {
+        if self.whole {
+            return;
+        }
+        match value {
+            Value::Array(items) => items.iter_mut().for_each(|item| self.apply(item)),
+            Value::Object(obj) => {
+                let mut kept = Map::new();
+                for (key, child) in &self.children {
+                    if let Some(mut v) = obj.remove(key) {
+                        child.apply(&mut v);
+                        kept.insert(key.clone(), v);
+                    }
+                }
+                *obj = kept;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Segments of a JSON pointer (`/a/b`) or dotted path (`a.b`).
+fn parse_path(path: &str) -> Vec<String> {
+    match path.strip_prefix('/') {
+        Some(pointer) => pointer
+            .split('/')
+            .map(|s| s.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+        None => path.split('.').map(|s| s.to_string()).collect(),
+    }
+}
@@ -0,0 +1,137 @@
+//! Branch freezing.
+//!
+//! Freezing revokes INSERT/UPDATE/DELETE/TRUNCATE on every user table from all
+//! roles except the table owner, and records exactly what was revoked in
+//! `~/.fgp/services/neon/frozen/` so unfreezing restores the same grants.
+//! Tables created after the freeze are not covered.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::api::NeonClient;
+use crate::config::state_path;
+use crate::models::TableGrant;
+
+/// Recorded state of a frozen branch database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrozenBranch {
+    pub project_id: String,
+    pub branch_id: String,
+    pub database: String,
+    pub frozen_at: DateTime<Utc>,
+    /// Grants revoked by the freeze, restored on unfreeze.
+    pub revoked: Vec<TableGrant>,
+}
+
+/// Revoke write privileges from non-owner roles and record them.
+pub async fn freeze(
+    client: &NeonClient,
+    project_id: &str,
+    branch_id: &str,
+    database: &str,
+) -> Result<FrozenBranch> {
+    let path = state_file(project_id, branch_id, database);
+    if path.exists() {
+        anyhow::bail!(
+            "Branch {} ({}) is already frozen; unfreeze it first",
+            branch_id,
+            database
+        );
+    }
+
+    let revoked = client
+        .get_write_grants(project_id, branch_id, database)
+        .await?;
+    let frozen = FrozenBranch {
+        project_id: project_id.to_string(),
+        branch_id: branch_id.to_string(),
+        database: database.to_string(),
+        frozen_at: Utc::now(),
+        revoked,
+    };
+
+    // Record before revoking so a partial failure can still be undone
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create frozen state directory")?;
+    }
+    std::fs::write(&path, serde_json::to_vec_pretty(&frozen)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    client
+        .revoke_grants(project_id, branch_id, database, &frozen.revoked)
+        .await
+        .context("Freeze partially applied; run unfreeze to restore grants")?;
+
+    Ok(frozen)
+}
+
+/// Restore the grants recorded by [`freeze`] and clear the frozen state.
+pub async fn unfreeze(
+    client: &NeonClient,
+    project_id: &str,
+    branch_id: &str,
+    database: &str,
+) -> Result<FrozenBranch> {
+    let path = state_file(project_id, branch_id, database);
+    let frozen: FrozenBranch = match std::fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .with_context(|| format!("Failed to parse {}", path.display()))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            anyhow::bail!("Branch {} ({}) is not frozen", branch_id, database)
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+
+    client
+        .apply_grants(project_id, branch_id, database, &frozen.revoked)
+        .await?;
+
+    std::fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    Ok(frozen)
+}
+
+/// All frozen branch databases.
+pub fn list() -> Result<Vec<FrozenBranch>> {
+    let dir = state_path("frozen");
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", dir.display())),
+    };
+
+    let mut frozen = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let bytes = std::fs::read(&path)?;
+        match serde_json::from_slice::<FrozenBranch>(&bytes) {
+            Ok(state) => frozen.push(state),
+            Err(e) => tracing::warn!("Skipping unreadable {}: {}", path.display(), e),
+        }
+    }
+
+    frozen.sort_by(|a, b| a.frozen_at.cmp(&b.frozen_at));
+    Ok(frozen)
+}
+
+fn state_file(project_id: &str, branch_id: &str, database: &str) -> PathBuf {
+    // Parts come from request params, so keep them file-safe
+    let safe = |s: &str| -> String {
+        s.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect()
+    };
+    state_path("frozen").join(format!(
+        "{}__{}__{}.json",
+        safe(project_id),
+        safe(branch_id),
+        safe(database)
+    ))
+}
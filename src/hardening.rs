@@ -0,0 +1,90 @@
+//! Process hardening for the daemon.
+//!
+//! The daemon holds org-wide Neon credentials, so once the socket is bound it
+//! sheds everything it does not need to talk to the Neon API over HTTPS.
+//!
+//! Chroot is deliberately not used: the process still needs `/etc/resolv.conf`
+//! and friends for DNS, and the socket directory is already private to the
+//! daemon user after [`apply`].
+
+use anyhow::{Context, Result};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// Refuse to run as root unless explicitly allowed.
+pub fn check_not_root(allow_root: bool) -> Result<()> {
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    let euid = unsafe { libc::geteuid() };
+    if euid == 0 && !allow_root {
+        anyhow::bail!(
+            "Refusing to run as root. Start fgp-neon as an unprivileged user (or pass --allow-root)."
+        );
+    }
+    Ok(())
+}
+
+/// Lock down the process and its service directory after the socket is bound.
+pub fn apply(socket_path: &str) -> Result<()> {
+    // Anything created from here on (PID file, state) is private to the daemon user
+    // SAFETY: umask has no preconditions and cannot fail.
+    unsafe {
+        libc::umask(0o077);
+    }
+
+    restrict_permissions(Path::new(socket_path))?;
+    drop_privileges()?;
+
+    tracing::info!("Process hardening applied");
+    Ok(())
+}
+
+/// Make the service directory and socket accessible only to the daemon user.
+fn restrict_permissions(socket_path: &Path) -> Result<()> {
+    if let Some(dir) = socket_path.parent() {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+            .context("Failed to restrict service directory permissions")?;
+    }
+
+    if socket_path.exists() {
+        std::fs::set_permissions(socket_path, std::fs::Permissions::from_mode(0o600))
+            .context("Failed to restrict socket permissions")?;
+    }
+
+    Ok(())
+}
+
+/// Forbid privilege escalation and drop capabilities (Linux only).
+#[cfg(target_os = "linux")]
+fn drop_privileges() -> Result<()> {
+    // Never regain privileges through setuid binaries or file capabilities
+    // SAFETY: PR_SET_NO_NEW_PRIVS only takes integer arguments.
+    if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("Failed to set no_new_privs");
+    }
+
+    // Empty the capability bounding set. Unprivileged processes lack
+    // CAP_SETPCAP, so EPERM just means there was nothing to drop.
+    let last_cap = std::fs::read_to_string("/proc/sys/kernel/cap_last_cap")
+        .ok()
+        .and_then(|s| s.trim().parse::<libc::c_ulong>().ok())
+        .unwrap_or(40);
+
+    for cap in 0..=last_cap {
+        // SAFETY: PR_CAPBSET_DROP only takes integer arguments.
+        let rc = unsafe { libc::prctl(libc::PR_CAPBSET_DROP, cap, 0, 0, 0) };
+        if rc != 0 {
+            let err = std::io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EPERM) | Some(libc::EINVAL) => {}
+                _ => return Err(err).context("Failed to drop capability"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn drop_privileges() -> Result<()> {
+    Ok(())
+}
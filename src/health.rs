@@ -0,0 +1,113 @@
+//! Daemon health: subsystem statuses and the Neon API ping behind them.
+//!
+//! Each subsystem (the API, credentials, the response cache, background
+//! jobs, the operations watcher, the Postgres pool) is reported as healthy,
+//! degraded, or unhealthy with the reasons, and the daemon's status is the
+//! worst of them, so monitors can alert on a partial failure.
+//!
+//! `health`, the daemon's health checks, and `fgp-neon status` all ping the
+//! Neon API. Monitors polling every few seconds would spend API quota on
+//! identical answers, so a ping is reused for `health.cache_secs`. Callers
+//! arriving while a ping is in flight wait for it instead of starting their
+//! own.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::HealthConfig;
+
+/// How well a subsystem is working, from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Level {
+    Healthy,
+    /// Working, but slower or with less capacity than it should.
+    Degraded,
+    Unhealthy,
+}
+
+/// One subsystem's status and why it isn't healthy.
+#[derive(Debug, Clone, Serialize)]
+pub struct Subsystem {
+    pub status: Level,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reasons: Vec<String>,
+}
+
+impl Subsystem {
+    pub fn healthy() -> Self {
+        Self {
+            status: Level::Healthy,
+            reasons: Vec::new(),
+        }
+    }
+
+    /// Lower the status to `level` (if worse) and record why.
+    pub fn report(&mut self, level: Level, reason: impl Into<String>) {
+        self.status = self.status.max(level);
+        self.reasons.push(reason.into());
+    }
+}
+
+/// The daemon's status: its worst subsystem's.
+pub fn overall(subsystems: &BTreeMap<String, Subsystem>) -> Level {
+    subsystems
+        .values()
+        .map(|s| s.status)
+        .max()
+        .unwrap_or(Level::Healthy)
+}
+
+/// Outcome of one ping.
+#[derive(Debug, Clone)]
+pub struct Ping {
+    /// Whether the API answered successfully, or why the request failed.
+    pub result: Result<bool, String>,
+    pub latency_ms: f64,
+    /// Whether this ping was reused rather than sent for this caller.
+    pub cached: bool,
+}
+
+/// The last ping and when it was sent.
+pub struct PingCache {
+    window: Duration,
+    last: Mutex<Option<(Instant, Ping)>>,
+}
+
+impl PingCache {
+    pub fn new(config: &HealthConfig) -> Self {
+        Self {
+            window: Duration::from_secs(config.cache_secs),
+            last: Mutex::new(None),
+        }
+    }
+
+    /// The last ping if it's inside the window, else the result of `ping`.
+    pub fn get(&self, ping: impl FnOnce() -> Result<bool>) -> Ping {
+        // Held across the ping so concurrent callers share one request
+        let mut last = self.last.lock().unwrap();
+        if let Some((at, cached)) = last.as_ref() {
+            if at.elapsed() < self.window {
+                return Ping {
+                    cached: true,
+                    ..cached.clone()
+                };
+            }
+        }
+
+        let started = Instant::now();
+        let result = ping().map_err(|e| format!("{:#}", e));
+        let fresh = Ping {
+            result,
+            latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+            cached: false,
+        };
+        if !self.window.is_zero() {
+            *last = Some((started, fresh.clone()));
+        }
+        fresh
+    }
+}
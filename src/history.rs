@@ -0,0 +1,155 @@
+//! Query history.
+//!
+//! Every `neon.sql` call is appended to `~/.fgp/services/neon/history.jsonl`
+//! with its tag, target, timing, and outcome. The most recent entries are also
+//! kept in memory for `neon.history`. `neon.replay` runs an entry again by id.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::config::state_path;
+
+/// Entries kept in memory (and loaded from disk at startup).
+const MAX_ENTRIES: usize = 1000;
+
+/// One executed query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub at: DateTime<Utc>,
+    pub project_id: String,
+    pub branch_id: String,
+    pub database: String,
+    /// Query as the caller sent it, without the injected tag comment.
+    pub query: String,
+    /// Positional bind parameters, if any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub tag: Option<String>,
+    pub duration_ms: f64,
+    #[serde(default)]
+    pub rows: Option<usize>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Append-only query log.
+pub struct History {
+    path: PathBuf,
+    inner: Mutex<HistoryInner>,
+}
+
+struct HistoryInner {
+    next_id: u64,
+    entries: VecDeque<HistoryEntry>,
+}
+
+impl History {
+    /// Open the history file, loading its most recent entries.
+    pub fn open() -> Self {
+        let path = state_path("history.jsonl");
+        let mut entries = VecDeque::new();
+
+        if let Ok(file) = std::fs::File::open(&path) {
+            for line in std::io::BufReader::new(file).lines().map_while(Result::ok) {
+                if let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line) {
+                    entries.push_back(entry);
+                    if entries.len() > MAX_ENTRIES {
+                        entries.pop_front();
+                    }
+                }
+            }
+        }
+
+        let next_id = entries.back().map(|e| e.id + 1).unwrap_or(1);
+        Self {
+            path,
+            inner: Mutex::new(HistoryInner { next_id, entries }),
+        }
+    }
+
+    /// Assign an id to an entry and persist it.
+    pub fn record(&self, mut entry: HistoryEntry) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+        entry.id = inner.next_id;
+        inner.next_id += 1;
+
+        if let Err(e) = self.append(&entry) {
+            tracing::warn!("Failed to write query history: {:#}", e);
+        }
+
+        let id = entry.id;
+        inner.entries.push_back(entry);
+        if inner.entries.len() > MAX_ENTRIES {
+            inner.entries.pop_front();
+        }
+        id
+    }
+
+    /// Most recent entries first, optionally only those with a given tag.
+    pub fn recent(&self, limit: usize, tag: Option<&str>) -> Vec<HistoryEntry> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .entries
+            .iter()
+            .rev()
+            .filter(|e| match tag {
+                Some(t) => e.tag.as_deref() == Some(t),
+                None => true,
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// The entry with `id`, from memory or, for older entries, the file.
+    pub fn get(&self, id: u64) -> Option<HistoryEntry> {
+        let inner = self.inner.lock().unwrap();
+        if let Some(entry) = inner.entries.iter().find(|e| e.id == id) {
+            return Some(entry.clone());
+        }
+
+        let file = std::fs::File::open(&self.path).ok()?;
+        std::io::BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str::<HistoryEntry>(&line).ok())
+            .find(|e| e.id == id)
+    }
+
+    fn append(&self, entry: &HistoryEntry) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+}
+
+/// Validate a query tag and render it as a leading SQL comment.
+///
+/// Tags are restricted to a safe character set so they can't close the
+/// comment and inject SQL.
+pub fn tag_comment(tag: &str) -> anyhow::Result<String> {
+    let valid = !tag.is_empty()
+        && tag.len() <= 64
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':' | '/'));
+    if !valid {
+        anyhow::bail!(
+            "Invalid tag {:?}: use up to 64 letters, digits, or _ - . : /",
+            tag
+        );
+    }
+    Ok(format!("/* fgp:{} */ ", tag))
+}
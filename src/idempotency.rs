@@ -0,0 +1,217 @@
+//! Idempotency keys for mutating methods.
+//!
+//! A caller that may retry a mutation (a CI job re-running "create branch"
+//! after a timeout) passes the same `idempotency_key` each time. The first
+//! call runs and its result is remembered for `ttl_secs`; later calls with
+//! the key get the stored result back instead of acting again. Failed calls
+//! aren't remembered, so they can be retried. Keys live in memory and don't
+//! survive a daemon restart.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::IdempotencyConfig;
+
+/// Methods that change state and honor `idempotency_key`, without the
+/// `neon.` prefix. Other methods ignore the key.
+const MUTATING_METHODS: &[&str] = &[
+    "sql",
+    "sql_template",
+    "sql_file",
+    "transaction",
+    "migrate_apply",
+    "seed",
+    "import_csv",
+    "sql_batch",
+    "run_saved",
+    "replay",
+    "create_branch",
+    "pr_branch",
+    "tag_branch",
+    "delete_branch",
+    "restore_branch",
+    "reset_branch",
+    "apply",
+    "schema_restore",
+    "freeze_branch",
+    "unfreeze_branch",
+    "lease_connection",
+    "revoke_lease",
+    "create_ephemeral_branch",
+    "create_project_from_template",
+    "bootstrap",
+    "auth_create_integration",
+    "auth_delete_integration",
+    "auth_create_user",
+    "auth_delete_user",
+    "data_api_enable",
+    "add_jwks",
+    "mint_token",
+    "delete_jwks",
+    "create_project",
+    "update_project",
+    "delete_project",
+    "create_endpoint",
+    "create_read_replica",
+    "delete_endpoint",
+    "start_endpoint",
+    "suspend_endpoint",
+];
+
+enum Entry {
+    /// The first call with this key is still running.
+    Pending { fingerprint: String },
+    Done {
+        fingerprint: String,
+        result: Value,
+        at: Instant,
+    },
+}
+
+/// Recently used keys and their results.
+pub struct IdempotencyStore {
+    config: IdempotencyConfig,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl IdempotencyStore {
+    pub fn new(config: IdempotencyConfig) -> Self {
+        Self {
+            config,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether `method` changes state.
+    pub fn is_mutating(method: &str) -> bool {
+        MUTATING_METHODS.contains(&method.strip_prefix("neon.").unwrap_or(method))
+    }
+
+    /// The key to apply to a call, if it's a mutation that carries one.
+    pub fn key(method: &str, params: &HashMap<String, Value>) -> Result<Option<String>> {
+        let name = method.strip_prefix("neon.").unwrap_or(method);
+        let Some(key) = params.get("idempotency_key") else {
+            return Ok(None);
+        };
+        if !MUTATING_METHODS.contains(&name) {
+            return Ok(None);
+        }
+        match key.as_str() {
+            Some(k) if !k.is_empty() && k.len() <= 255 => Ok(Some(k.to_string())),
+            _ => anyhow::bail!(
+                "idempotency_key must be a non-empty string of at most 255 characters"
+            ),
+        }
+    }
+
+    /// Run `call` once per key, returning the stored result on replay.
+    pub fn run(
+        &self,
+        key: &str,
+        method: &str,
+        params: &HashMap<String, Value>,
+        call: impl FnOnce() -> Result<Value>,
+    ) -> Result<Value> {
+        let fingerprint = fingerprint(method, params);
+
+        {
+            let mut entries = self.entries.lock().unwrap();
+            self.prune(&mut entries);
+            match entries.get(key) {
+                Some(Entry::Done {
+                    fingerprint: stored,
+                    result,
+                    ..
+                }) if *stored == fingerprint => {
+                    let mut result = result.clone();
+                    if let Some(obj) = result.as_object_mut() {
+                        obj.insert("idempotent_replay".into(), serde_json::json!(true));
+                    }
+                    return Ok(result);
+                }
+                Some(Entry::Pending {
+                    fingerprint: stored,
+                }) if *stored == fingerprint => {
+                    anyhow::bail!(
+                        "A request with idempotency_key {:?} is still in progress; retry shortly",
+                        key
+                    );
+                }
+                Some(_) => anyhow::bail!(
+                    "idempotency_key {:?} was already used for a different request",
+                    key
+                ),
+                None => {}
+            }
+            entries.insert(
+                key.to_string(),
+                Entry::Pending {
+                    fingerprint: fingerprint.clone(),
+                },
+            );
+        }
+
+        let result = call();
+
+        let mut entries = self.entries.lock().unwrap();
+        match &result {
+            Ok(value) => {
+                entries.insert(
+                    key.to_string(),
+                    Entry::Done {
+                        fingerprint,
+                        result: value.clone(),
+                        at: Instant::now(),
+                    },
+                );
+            }
+            Err(_) => {
+                entries.remove(key);
+            }
+        }
+        result
+    }
+
+    /// Drop expired results, then the oldest ones past `max_keys`.
+    fn prune(&self, entries: &mut HashMap<String, Entry>) {
+        let ttl = Duration::from_secs(self.config.ttl_secs);
+        entries.retain(|_, entry| match entry {
+            Entry::Done { at, .. } => at.elapsed() < ttl,
+            Entry::Pending { .. } => true,
+        });
+
+        let mut done: Vec<(Instant, String)> = entries
+            .iter()
+            .filter_map(|(key, entry)| match entry {
+                Entry::Done { at, .. } => Some((*at, key.clone())),
+                Entry::Pending { .. } => None,
+            })
+            .collect();
+        if done.len() > self.config.max_keys {
+            done.sort();
+            let excess = done.len() - self.config.max_keys;
+            for (_, key) in done.into_iter().take(excess) {
+                entries.remove(&key);
+            }
+        }
+    }
+}
+
+/// Method and params (minus the key itself, the per-attempt trace id, and
+/// the response field selection and rendering) in a stable form.
+fn fingerprint(method: &str, params: &HashMap<String, Value>) -> String {
+    let name = method.strip_prefix("neon.").unwrap_or(method);
+    let params: BTreeMap<&String, &Value> = params
+        .iter()
+        .filter(|(k, _)| {
+            !matches!(
+                k.as_str(),
+                "idempotency_key" | "trace_id" | "fields" | "render" | "render_only"
+            )
+        })
+        .collect();
+    format!("{}:{}", name, serde_json::json!(params))
+}
@@ -0,0 +1,461 @@
+//! CSV import into an existing table.
+//!
+//! `neon.import_csv` reads a local CSV file a record at a time and loads it
+//! in batches, so the file is never held in memory whole. With the Postgres
+//! backend the rows stream through one `COPY ... FROM STDIN`, which loads
+//! everything or nothing. Otherwise each batch is a multi-row `INSERT` with
+//! bound parameters and commits on its own; a failing batch stops the import
+//! and the error gives its first line and the rows loaded before it.
+//!
+//! Values are sent as text and Postgres converts them to each column's type.
+//! As with `COPY ... (FORMAT csv)`, an unquoted field equal to the `null`
+//! string (empty by default) is NULL, and a quoted empty field is an empty
+//! string. Progress is published as `import_progress` events.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::time::Instant;
+
+use crate::api::{quote_ident, NeonClient, SqlPage, SqlTarget};
+use crate::config::SqlBackend;
+use crate::script::ScriptTarget;
+use crate::watch::{Event, EventBus, ImportProgress};
+
+/// Most bind parameters in one statement.
+const MAX_PARAMS: usize = 65_535;
+
+/// Which table column each CSV column goes to.
+pub enum ColumnMap {
+    /// The header names the table columns.
+    Header,
+    /// Table columns in CSV column order.
+    Positional(Vec<String>),
+    /// CSV header name to table column; other CSV columns are skipped.
+    Named(BTreeMap<String, String>),
+}
+
+/// What to import and how.
+pub struct CsvImport {
+    pub schema: String,
+    pub table: String,
+    pub path: String,
+    pub columns: ColumnMap,
+    pub delimiter: char,
+    pub quote: char,
+    /// Unquoted text read as NULL.
+    pub null: String,
+    /// Whether the first record is a header.
+    pub header: bool,
+    pub batch_rows: usize,
+    /// Load through `COPY` over a direct connection.
+    pub copy: bool,
+}
+
+/// What an import loaded.
+#[derive(Debug, Serialize)]
+pub struct ImportOutcome {
+    pub run_id: String,
+    pub table: String,
+    pub columns: Vec<String>,
+    /// `copy` or `insert`.
+    pub method: &'static str,
+    pub rows: u64,
+    pub batches: usize,
+    pub bytes: u64,
+    pub duration_ms: f64,
+}
+
+/// Load `import.path` into its table.
+pub async fn import(
+    client: &NeonClient,
+    events: Option<&EventBus>,
+    target: &ScriptTarget<'_>,
+    run_id: &str,
+    import: &CsvImport,
+) -> Result<ImportOutcome> {
+    let started = Instant::now();
+    let path = shellexpand::tilde(&import.path).to_string();
+    let file = File::open(&path).with_context(|| format!("Failed to open {}", path))?;
+    let total_bytes = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let mut reader = CsvReader {
+        input: BufReader::new(file),
+        delimiter: import.delimiter,
+        quote: import.quote,
+        null: import.null.clone(),
+        line: 0,
+        bytes: 0,
+    };
+
+    let header = if import.header {
+        let (_, fields) = reader
+            .next_record()?
+            .ok_or_else(|| anyhow::anyhow!("{} is empty", path))?;
+        Some(fields.into_iter().map(|f| f.unwrap_or_default()).collect())
+    } else {
+        None
+    };
+    let (width, mapping) = mapping(&import.columns, header)?;
+    let columns: Vec<String> = mapping.iter().map(|(_, c)| quote_ident(c)).collect();
+    let relation = format!(
+        "{}.{}",
+        quote_ident(&import.schema),
+        quote_ident(&import.table)
+    );
+
+    let mut outcome = ImportOutcome {
+        run_id: run_id.to_string(),
+        table: relation.clone(),
+        columns: mapping.iter().map(|(_, c)| c.clone()).collect(),
+        method: if import.copy { "copy" } else { "insert" },
+        rows: 0,
+        batches: 0,
+        bytes: 0,
+        duration_ms: 0.0,
+    };
+    let batch_rows = import
+        .batch_rows
+        .clamp(1, MAX_PARAMS / mapping.len().max(1));
+    let progress = |rows: u64, bytes: u64, error: Option<String>, done: bool| {
+        if let Some(events) = events {
+            events.publish(vec![progress_event(
+                target,
+                run_id,
+                ImportProgress {
+                    rows,
+                    bytes,
+                    total_bytes,
+                },
+                error,
+                done,
+            )]);
+        }
+    };
+
+    if import.copy {
+        let statement = format!(
+            "COPY {} ({}) FROM STDIN WITH (FORMAT csv)",
+            relation,
+            columns.join(", ")
+        );
+        let (mut rows, mut batches) = (0u64, 0usize);
+        let copied = client
+            .copy_in(
+                target.project_id,
+                target.branch_id,
+                target.database,
+                &statement,
+                || {
+                    let batch = next_batch(&mut reader, width, &mapping, batch_rows)?;
+                    if batch.is_empty() {
+                        return Ok(None);
+                    }
+                    rows += batch.len() as u64;
+                    batches += 1;
+                    progress(rows, reader.bytes, None, false);
+                    Ok(Some(copy_text(&batch)))
+                },
+            )
+            .await;
+        match copied {
+            Ok(copied) => {
+                outcome.rows = copied;
+                outcome.batches = batches;
+            }
+            Err(e) => {
+                progress(0, reader.bytes, Some(format!("{:#}", e)), true);
+                return Err(e.context(format!(
+                    "Import into {} failed near line {}; COPY loaded nothing",
+                    relation, reader.line
+                )));
+            }
+        }
+    } else {
+        loop {
+            let first_line = reader.line + 1;
+            let batch = match next_batch(&mut reader, width, &mapping, batch_rows) {
+                Ok(batch) => batch,
+                Err(e) => {
+                    progress(outcome.rows, reader.bytes, Some(format!("{:#}", e)), true);
+                    return Err(e.context(format!(
+                        "{} row(s) were imported before the failure",
+                        outcome.rows
+                    )));
+                }
+            };
+            if batch.is_empty() {
+                break;
+            }
+            let (statement, params) = insert_statement(&relation, &columns, &batch);
+            let page = SqlPage {
+                max_rows: client.max_rows(),
+                offset: 0,
+                backend: Some(SqlBackend::Http),
+                target: SqlTarget::Write,
+                role: None,
+                read_only: false,
+            };
+            if let Err(e) = client
+                .run_sql_page(
+                    target.project_id,
+                    target.branch_id,
+                    target.database,
+                    &statement,
+                    &params,
+                    page,
+                )
+                .await
+            {
+                progress(outcome.rows, reader.bytes, Some(format!("{:#}", e)), true);
+                return Err(e.context(format!(
+                    "Batch starting at line {} failed; {} row(s) were imported before it",
+                    first_line, outcome.rows
+                )));
+            }
+            outcome.rows += batch.len() as u64;
+            outcome.batches += 1;
+            progress(outcome.rows, reader.bytes, None, false);
+        }
+    }
+
+    progress(outcome.rows, reader.bytes, None, true);
+    outcome.bytes = reader.bytes;
+    outcome.duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+    Ok(outcome)
+}
+
+/// The CSV record width and `(CSV index, table column)` pairs.
+fn mapping(
+    columns: &ColumnMap,
+    header: Option<Vec<String>>,
+) -> Result<(usize, Vec<(usize, String)>)> {
+    match (columns, header) {
+        (ColumnMap::Header, Some(header)) => {
+            Ok((header.len(), header.into_iter().enumerate().collect()))
+        }
+        (ColumnMap::Header, None) => {
+            anyhow::bail!("Without a header, columns must list the table columns in CSV order")
+        }
+        (ColumnMap::Positional(columns), header) => {
+            if let Some(header) = header.filter(|h| h.len() != columns.len()) {
+                anyhow::bail!(
+                    "columns lists {} column(s) but the header has {}",
+                    columns.len(),
+                    header.len()
+                );
+            }
+            Ok((columns.len(), columns.iter().cloned().enumerate().collect()))
+        }
+        (ColumnMap::Named(_), None) => {
+            anyhow::bail!("Mapping columns by name needs a header")
+        }
+        (ColumnMap::Named(names), Some(header)) => {
+            let mut mapping = Vec::new();
+            for (csv, column) in names {
+                let index = header
+                    .iter()
+                    .position(|h| h == csv)
+                    .ok_or_else(|| anyhow::anyhow!("CSV has no column {:?}", csv))?;
+                mapping.push((index, column.clone()));
+            }
+            mapping.sort();
+            Ok((header.len(), mapping))
+        }
+    }
+}
+
+/// Up to `size` mapped rows; empty at the end of the file.
+fn next_batch(
+    reader: &mut CsvReader<impl BufRead>,
+    width: usize,
+    mapping: &[(usize, String)],
+    size: usize,
+) -> Result<Vec<Vec<Option<String>>>> {
+    let mut batch = Vec::with_capacity(size);
+    while batch.len() < size {
+        let Some((line, mut fields)) = reader.next_record()? else {
+            break;
+        };
+        if fields.len() != width {
+            anyhow::bail!(
+                "Line {} has {} field(s), expected {}",
+                line,
+                fields.len(),
+                width
+            );
+        }
+        batch.push(
+            mapping
+                .iter()
+                .map(|(index, _)| fields[*index].take())
+                .collect(),
+        );
+    }
+    Ok(batch)
+}
+
+/// A multi-row `INSERT` of `rows` as bound text parameters.
+fn insert_statement(
+    relation: &str,
+    columns: &[String],
+    rows: &[Vec<Option<String>>],
+) -> (String, Vec<Value>) {
+    let mut params = Vec::with_capacity(rows.len() * columns.len());
+    let tuples: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let placeholders: Vec<String> = row
+                .iter()
+                .map(|value| {
+                    params.push(value.clone().map_or(Value::Null, Value::String));
+                    format!("${}", params.len())
+                })
+                .collect();
+            format!("({})", placeholders.join(", "))
+        })
+        .collect();
+    let statement = format!(
+        "INSERT INTO {} ({}) VALUES {}",
+        relation,
+        columns.join(", "),
+        tuples.join(", ")
+    );
+    (statement, params)
+}
+
+/// `rows` as `COPY` CSV text: NULL unquoted and empty, other values quoted
+/// when they need it.
+fn copy_text(rows: &[Vec<Option<String>>]) -> Vec<u8> {
+    let mut out = String::new();
+    for row in rows {
+        let fields: Vec<String> = row
+            .iter()
+            .map(|value| match value {
+                None => String::new(),
+                Some(s) if s.is_empty() || s == "\\." || s.contains([',', '"', '\n', '\r']) => {
+                    format!("\"{}\"", s.replace('"', "\"\""))
+                }
+                Some(s) => s.clone(),
+            })
+            .collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out.into_bytes()
+}
+
+fn progress_event(
+    target: &ScriptTarget<'_>,
+    run_id: &str,
+    progress: ImportProgress,
+    error: Option<String>,
+    done: bool,
+) -> Event {
+    let status = match (&error, done) {
+        (Some(_), _) => "failed",
+        (None, true) => "finished",
+        (None, false) => "running",
+    };
+
+    Event {
+        seq: 0,
+        at: Utc::now(),
+        kind: "import_progress".into(),
+        project_id: target.project_id.to_string(),
+        operation_id: run_id.to_string(),
+        action: "import_csv".into(),
+        status: status.into(),
+        branch_id: Some(target.branch_id.to_string()),
+        endpoint_id: None,
+        error,
+        statement: None,
+        import: Some(progress),
+    }
+}
+
+/// RFC 4180 records from a reader, one at a time. Quoted fields may hold
+/// delimiters, doubled quotes, and line breaks.
+struct CsvReader<R> {
+    input: R,
+    delimiter: char,
+    quote: char,
+    null: String,
+    /// Last line read, 1-based.
+    line: usize,
+    bytes: u64,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    /// The next record and the line it starts on; blank lines are skipped.
+    /// Fields are `None` when NULL.
+    fn next_record(&mut self) -> Result<Option<(usize, Vec<Option<String>>)>> {
+        let mut text = String::new();
+        loop {
+            text.clear();
+            let read = self.input.read_line(&mut text)?;
+            if read == 0 {
+                return Ok(None);
+            }
+            self.bytes += read as u64;
+            self.line += 1;
+            if !text.trim_end_matches(['\r', '\n']).is_empty() {
+                break;
+            }
+        }
+
+        let start = self.line;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let (mut quoted, mut in_quotes) = (false, false);
+        loop {
+            let mut chars = text.chars().peekable();
+            while let Some(c) = chars.next() {
+                if in_quotes {
+                    if c != self.quote {
+                        field.push(c);
+                    } else if chars.peek() == Some(&self.quote) {
+                        field.push(c);
+                        chars.next();
+                    } else {
+                        in_quotes = false;
+                    }
+                } else if c == self.quote && field.is_empty() && !quoted {
+                    (quoted, in_quotes) = (true, true);
+                } else if c == self.delimiter {
+                    fields.push(self.finish(&mut field, &mut quoted));
+                } else if c != '\r' && c != '\n' {
+                    field.push(c);
+                }
+            }
+            if !in_quotes {
+                break;
+            }
+
+            // A quoted field running on to the next line
+            text.clear();
+            let read = self.input.read_line(&mut text)?;
+            if read == 0 {
+                anyhow::bail!("Quoted field starting on line {} is never closed", start);
+            }
+            self.bytes += read as u64;
+            self.line += 1;
+        }
+        fields.push(self.finish(&mut field, &mut quoted));
+
+        Ok(Some((start, fields)))
+    }
+
+    fn finish(&self, field: &mut String, quoted: &mut bool) -> Option<String> {
+        let value = std::mem::take(field);
+        let was_quoted = std::mem::replace(quoted, false);
+        if !was_quoted && value == self.null {
+            None
+        } else {
+            Some(value)
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Short-lived connection leases.
+//!
+//! `neon.lease_connection` creates a dedicated login role with a generated
+//! password and a Postgres `VALID UNTIL`, grants it membership in an existing
+//! role, and records the lease in `~/.fgp/services/neon/leases.json`. A
+//! background task drops each lease role once it expires, so credentials
+//! handed to a CI job stop working even if the job never cleans up.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::api::{quote_ident, url_encode, ApiError, NeonClient};
+use crate::config::state_path;
+
+/// How often expired leases are swept.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Serializes read-modify-write of the lease file across tasks.
+static STORE: Mutex<()> = Mutex::new(());
+
+/// An issued lease.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lease {
+    pub role: String,
+    pub project_id: String,
+    pub branch_id: String,
+    pub database: String,
+    /// Role whose privileges the lease role inherits.
+    pub granted_role: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Credentials returned to the caller; the password is never stored.
+#[derive(Debug, Serialize)]
+pub struct IssuedLease {
+    #[serde(flatten)]
+    pub lease: Lease,
+    pub password: String,
+    pub host: String,
+    pub connection_string: String,
+}
+
+/// Create a lease role and record it.
+pub async fn create(
+    client: &NeonClient,
+    project_id: &str,
+    branch_id: &str,
+    database: &str,
+    granted_role: &str,
+    ttl: Duration,
+) -> Result<IssuedLease> {
+    let now = Utc::now();
+    let expires_at = now + ChronoDuration::from_std(ttl)?;
+    let name = format!("fgp_lease_{:x}", now.timestamp_micros());
+
+    let host = client
+        .list_endpoints(project_id)
+        .await?
+        .into_iter()
+        .find(|e| e.branch_id == branch_id)
+        .map(|e| e.host)
+        .ok_or_else(|| anyhow::anyhow!("No endpoint found for branch {}", branch_id))?;
+
+    let role = client.create_role(project_id, branch_id, &name).await?;
+
+    // Record the lease as soon as the role exists, so the sweeper drops it
+    // even if the rest fails
+    let lease = Lease {
+        role: name.clone(),
+        project_id: project_id.to_string(),
+        branch_id: branch_id.to_string(),
+        database: database.to_string(),
+        granted_role: granted_role.to_string(),
+        created_at: now,
+        expires_at,
+    };
+    if let Err(e) = update(|leases| leases.push(lease.clone())) {
+        if let Err(cleanup) = client.delete_role(project_id, branch_id, &name).await {
+            tracing::warn!("Failed to drop lease role {}: {:#}", name, cleanup);
+        }
+        return Err(e);
+    }
+
+    let password = match configure(client, &lease, role.password).await {
+        Ok(password) => password,
+        Err(e) => {
+            if let Err(cleanup) = drop_lease(client, &lease).await {
+                tracing::warn!("Failed to drop lease role {}: {:#}", name, cleanup);
+            }
+            return Err(e);
+        }
+    };
+
+    Ok(IssuedLease {
+        connection_string: format!(
+            "postgres://{}:{}@{}/{}?sslmode=require",
+            url_encode(&name),
+            url_encode(&password),
+            host,
+            url_encode(database)
+        ),
+        lease,
+        password,
+        host,
+    })
+}
+
+/// Set a new lease role's expiry and grant, returning its password.
+async fn configure(client: &NeonClient, lease: &Lease, password: Option<String>) -> Result<String> {
+    let password = password
+        .ok_or_else(|| anyhow::anyhow!("Neon did not return a password for role {}", lease.role))?;
+    client
+        .wait_for_idle(&lease.project_id, Duration::from_secs(60))
+        .await?;
+
+    // Postgres enforces the expiry itself, even if the daemon is down
+    let statements = [
+        format!(
+            "ALTER ROLE {} VALID UNTIL '{}'",
+            quote_ident(&lease.role),
+            lease.expires_at.to_rfc3339()
+        ),
+        format!(
+            "GRANT {} TO {}",
+            quote_ident(&lease.granted_role),
+            quote_ident(&lease.role)
+        ),
+    ];
+    for statement in &statements {
+        client
+            .run_sql(
+                &lease.project_id,
+                &lease.branch_id,
+                &lease.database,
+                statement,
+            )
+            .await
+            .context("Failed to configure lease role")?;
+    }
+    Ok(password)
+}
+
+/// Drop a lease role now, before it expires.
+pub async fn revoke(client: &NeonClient, role: &str) -> Result<Lease> {
+    let lease = list()?
+        .into_iter()
+        .find(|l| l.role == role)
+        .ok_or_else(|| anyhow::anyhow!("No lease for role {:?}", role))?;
+    drop_lease(client, &lease).await?;
+    Ok(lease)
+}
+
+/// Active (recorded) leases.
+pub fn list() -> Result<Vec<Lease>> {
+    let _guard = STORE.lock().unwrap();
+    load()
+}
+
+/// Drop expired leases forever.
+pub async fn run(client: Arc<NeonClient>) {
+    let mut ticker = tokio::time::interval(SWEEP_INTERVAL);
+
+    loop {
+        ticker.tick().await;
+
+        let expired: Vec<Lease> = match list() {
+            Ok(leases) => leases
+                .into_iter()
+                .filter(|l| l.expires_at <= Utc::now())
+                .collect(),
+            Err(e) => {
+                tracing::warn!("Failed to read leases: {:#}", e);
+                continue;
+            }
+        };
+
+        for lease in expired {
+            match drop_lease(&client, &lease).await {
+                Ok(()) => tracing::info!("Dropped expired lease role {}", lease.role),
+                Err(e) => tracing::warn!("Failed to drop lease role {}: {:#}", lease.role, e),
+            }
+        }
+    }
+}
+
+/// Remove a lease role and forget the lease.
+async fn drop_lease(client: &NeonClient, lease: &Lease) -> Result<()> {
+    // Objects the role created would block the drop
+    let owner = quote_ident(&lease.granted_role);
+    let role = quote_ident(&lease.role);
+    for cleanup in [
+        format!("REASSIGN OWNED BY {} TO {}", role, owner),
+        format!("DROP OWNED BY {}", role),
+    ] {
+        if let Err(e) = client
+            .run_sql(
+                &lease.project_id,
+                &lease.branch_id,
+                &lease.database,
+                &cleanup,
+            )
+            .await
+        {
+            tracing::warn!("Failed to release objects owned by {}: {:#}", lease.role, e);
+        }
+    }
+
+    match client
+        .delete_role(&lease.project_id, &lease.branch_id, &lease.role)
+        .await
+    {
+        Ok(()) => {}
+        // Already gone (e.g. branch deleted); just forget it
+        Err(e) if ApiError::is_not_found(&e) => {}
+        Err(e) => return Err(e),
+    }
+
+    update(|leases| leases.retain(|l| l.role != lease.role))
+}
+
+fn update(change: impl FnOnce(&mut Vec<Lease>)) -> Result<()> {
+    let _guard = STORE.lock().unwrap();
+    let mut leases = load()?;
+    change(&mut leases);
+
+    let path = state_path("leases.json");
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create state directory")?;
+    }
+    std::fs::write(&path, serde_json::to_vec_pretty(&leases)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn load() -> Result<Vec<Lease>> {
+    let path = state_path("leases.json");
+    match std::fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
@@ -8,45 +8,80 @@
 //! fgp-neon start -f        # Start in foreground
 //! fgp-neon stop            # Stop daemon
 //! fgp-neon status          # Check daemon status
+//! fgp-neon verify          # End-to-end check without the daemon
+//! fgp-neon replay 42       # Re-run query history entry 42
+//! fgp-neon config check    # Validate the config file
+//! fgp-neon config show     # Effective settings and where they come from
+//! fgp-neon config set sql.backend postgres
 //! ```
 
 mod api;
+mod bootstrap;
+mod branch_tags;
+mod cache;
+mod canary;
+mod clients;
+mod compare;
+mod config;
+mod costguard;
+mod credentials;
+mod datadiff;
+mod drift;
+mod dump;
+mod envelope;
+mod envs;
+mod ephemeral;
+mod fields;
+mod freeze;
+mod hardening;
+mod health;
+mod history;
+mod idempotency;
+mod import;
+mod leases;
+mod masking;
+mod migrate;
 mod models;
+mod notify;
+mod previews;
+mod project_index;
+mod protocol;
+mod redact;
+mod render;
+mod results;
+mod saved;
+mod schema;
+mod script;
+mod seed;
 mod service;
+mod snapshots;
+mod spec;
+mod sqltext;
+mod stats;
+mod statsd;
+mod stub;
+mod supervisor;
+mod templates;
+mod trace;
+mod transform;
+mod update;
+mod usage;
+mod verify;
+mod warnings;
+mod watch;
 
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use fgp_daemon::{cleanup_socket, FgpServer};
-use serde::Deserialize;
+use clap::{Parser, Subcommand, ValueEnum};
+use fgp_daemon::{cleanup_socket, FgpServer, FgpService};
 use std::path::Path;
 use std::process::Command;
+use std::sync::Arc;
 
+use crate::clients::ClientRegistry;
+use crate::config::{set_value, Config, DEFAULT_CONFIG};
+use crate::credentials::Credentials;
 use crate::service::NeonService;
-
-/// Neonctl credentials file structure.
-#[derive(Deserialize)]
-struct NeonctlCredentials {
-    access_token: String,
-}
-
-/// Get Neon credentials from env var or neonctl config.
-fn get_neon_credentials() -> Result<String> {
-    // Try NEON_API_KEY first
-    if let Ok(key) = std::env::var("NEON_API_KEY") {
-        return Ok(key);
-    }
-
-    // Fall back to neonctl OAuth token
-    let creds_path = shellexpand::tilde("~/.config/neonctl/credentials.json").to_string();
-    let creds_json = std::fs::read_to_string(&creds_path).context(
-        "No NEON_API_KEY set and neonctl credentials not found. Run `neonctl auth` first.",
-    )?;
-
-    let creds: NeonctlCredentials =
-        serde_json::from_str(&creds_json).context("Failed to parse neonctl credentials")?;
-
-    Ok(creds.access_token)
-}
+use crate::stub::{Fixtures, StubService};
 
 const DEFAULT_SOCKET: &str = "~/.fgp/services/neon/daemon.sock";
 
@@ -55,10 +90,24 @@ const DEFAULT_SOCKET: &str = "~/.fgp/services/neon/daemon.sock";
 #[command(about = "FGP daemon for Neon serverless Postgres operations")]
 #[command(version)]
 struct Cli {
+    /// Output format for command results
+    #[arg(long, global = true, value_enum, default_value_t = Output::Text)]
+    output: Output,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// How commands report their results.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Output {
+    /// Lines for people to read
+    Text,
+    /// One JSON object on stdout, with `"ok"` and the command's fields;
+    /// errors are `{"ok": false, "error": ...}`
+    Json,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Start the FGP daemon
@@ -70,6 +119,23 @@ enum Commands {
         /// Run in foreground (don't daemonize)
         #[arg(short, long)]
         foreground: bool,
+
+        /// Allow running as root (not recommended)
+        #[arg(long)]
+        allow_root: bool,
+
+        /// Config file path
+        #[arg(short, long, default_value = DEFAULT_CONFIG)]
+        config: String,
+
+        /// Serve canned projects, branches, and SQL results instead of the
+        /// Neon API; needs no credentials
+        #[arg(long)]
+        stub: bool,
+
+        /// Fixtures for --stub (default: the bundled examples/stub-fixtures.json)
+        #[arg(long, requires = "stub")]
+        fixtures: Option<String>,
     },
 
     /// Stop the running daemon
@@ -85,19 +151,155 @@ enum Commands {
         #[arg(short, long, default_value = DEFAULT_SOCKET)]
         socket: String,
     },
+
+    /// Check credentials, org, config, and API access without starting the
+    /// daemon; exits nonzero on failure
+    Verify {
+        /// Config file path
+        #[arg(short, long, default_value = DEFAULT_CONFIG)]
+        config: String,
+
+        /// Project for the SQL check (with --branch)
+        #[arg(long, requires = "branch")]
+        project: Option<String>,
+
+        /// Branch to run `SELECT 1` on
+        #[arg(long, requires = "project")]
+        branch: Option<String>,
+
+        /// Database for the SQL check
+        #[arg(long, default_value = "neondb")]
+        database: String,
+    },
+
+    /// Re-run a recorded query from the daemon's history
+    Replay {
+        /// History entry id (see neon.history)
+        id: u64,
+
+        /// Run on this branch instead of the original
+        #[arg(long)]
+        branch: Option<String>,
+
+        /// Run on this database instead of the original
+        #[arg(long)]
+        database: Option<String>,
+
+        /// Socket path
+        #[arg(short, long, default_value = DEFAULT_SOCKET)]
+        socket: String,
+    },
+
+    /// Inspect the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Report type errors, missing fields, and unknown keys with their
+    /// lines; exits nonzero if there are any
+    Check {
+        /// Config file path
+        #[arg(short, long, default_value = DEFAULT_CONFIG)]
+        config: String,
+    },
+
+    /// Print every effective setting, marked as set in the file or a default
+    Show {
+        /// Config file path
+        #[arg(short, long, default_value = DEFAULT_CONFIG)]
+        config: String,
+    },
+
+    /// Set one setting in the config file, keeping its comments
+    Set {
+        /// Dotted key, e.g. sql.backend or cache.ttls.schema
+        key: String,
+
+        /// Value in TOML syntax; bare words are taken as strings
+        value: String,
+
+        /// Config file path
+        #[arg(short, long, default_value = DEFAULT_CONFIG)]
+        config: String,
+    },
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
-
-    match cli.command {
-        Commands::Start { socket, foreground } => cmd_start(socket, foreground),
-        Commands::Stop { socket } => cmd_stop(socket),
-        Commands::Status { socket } => cmd_status(socket),
+    let output = cli.output;
+
+    let result = match cli.command {
+        Commands::Start {
+            socket,
+            foreground,
+            allow_root,
+            config,
+            stub,
+            fixtures,
+        } => stub
+            .then(|| Fixtures::load(fixtures.as_deref()))
+            .transpose()
+            .and_then(|stub| cmd_start(socket, foreground, allow_root, config, stub, output)),
+        Commands::Stop { socket } => cmd_stop(socket, output),
+        Commands::Status { socket } => cmd_status(socket, output),
+        Commands::Verify {
+            config,
+            project,
+            branch,
+            database,
+        } => cmd_verify(config, project, branch, database, output),
+        Commands::Replay {
+            id,
+            branch,
+            database,
+            socket,
+        } => cmd_replay(id, branch, database, socket, output),
+        Commands::Config { command } => match command {
+            ConfigCommand::Check { config } => cmd_config_check(config, output),
+            ConfigCommand::Show { config } => cmd_config_show(config, output),
+            ConfigCommand::Set { key, value, config } => cmd_config_set(config, key, value, output),
+        },
+    };
+
+    match result {
+        Err(e) if output == Output::Json => {
+            print_json(serde_json::json!({ "ok": false, "error": format!("{:#}", e) }));
+            std::process::exit(1);
+        }
+        result => result,
     }
 }
 
-fn cmd_start(socket: String, foreground: bool) -> Result<()> {
+/// Print a command's result as one line of JSON.
+fn print_json(value: serde_json::Value) {
+    println!("{}", value);
+}
+
+/// What a started daemon serves.
+enum Backend {
+    /// The Neon API.
+    Neon {
+        credentials: Credentials,
+        org_id: String,
+    },
+    /// Fixtures, in stub mode.
+    Stub(Fixtures),
+}
+
+fn cmd_start(
+    socket: String,
+    foreground: bool,
+    allow_root: bool,
+    config: String,
+    stub: Option<Fixtures>,
+    output: Output,
+) -> Result<()> {
+    hardening::check_not_root(allow_root)?;
+
     let socket_path = shellexpand::tilde(&socket).to_string();
 
     // Create parent directory
@@ -105,51 +307,79 @@ fn cmd_start(socket: String, foreground: bool) -> Result<()> {
         std::fs::create_dir_all(parent).context("Failed to create socket directory")?;
     }
 
-    // Get API key BEFORE fork (credentials access needs parent process)
-    let api_key = get_neon_credentials()?;
+    let backend = match stub {
+        Some(fixtures) => Backend::Stub(fixtures),
+        None => {
+            // Get API key BEFORE fork (credentials access needs parent process)
+            let credentials = credentials::load()?;
+
+            // Get org_id from environment (required)
+            let org_id = std::env::var("NEON_ORG_ID").context(
+                "NEON_ORG_ID environment variable not set. Run `neonctl orgs list` to find your org_id.",
+            )?;
+            Backend::Neon {
+                credentials,
+                org_id,
+            }
+        }
+    };
 
-    // Get org_id from environment (required)
-    let org_id = std::env::var("NEON_ORG_ID").context(
-        "NEON_ORG_ID environment variable not set. Run `neonctl orgs list` to find your org_id.",
-    )?;
+    let config = Config::load(&config)?;
 
+    // Held until exit; a second start fails here instead of racing on the socket
     let pid_file = format!("{}.pid", socket_path);
+    let mut pid_lock = lock_pid_file(&pid_file)?;
+    let removed_stale = recover_stale_files(&socket_path, &pid_file)?;
 
-    println!("Starting fgp-neon daemon...");
-    println!("Socket: {}", socket_path);
-    println!("Org ID: {}", org_id);
+    match output {
+        Output::Text => {
+            if removed_stale {
+                println!("Removed stale socket from a previous run");
+            }
+            println!("Starting fgp-neon daemon...");
+            println!("Socket: {}", socket_path);
+            match &backend {
+                Backend::Neon { org_id, .. } => println!("Org ID: {}", org_id),
+                Backend::Stub(fixtures) => println!("Stub mode: {}", fixtures.summary()),
+            }
+        }
+        Output::Json => {
+            let mut started = serde_json::json!({
+                "ok": true,
+                "status": "starting",
+                "socket": socket_path,
+                "foreground": foreground,
+                "removed_stale_socket": removed_stale,
+            });
+            match &backend {
+                Backend::Neon { org_id, .. } => started["org_id"] = serde_json::json!(org_id),
+                Backend::Stub(fixtures) => started["stub"] = serde_json::json!(fixtures.summary()),
+            }
+            print_json(started);
+        }
+    }
 
     if foreground {
         // Foreground mode - initialize logging and run directly
-        tracing_subscriber::fmt()
-            .with_env_filter("fgp_neon=debug,fgp_daemon=debug")
-            .init();
-
-        let service = NeonService::new(api_key, org_id).context("Failed to create NeonService")?;
-        let server =
-            FgpServer::new(service, &socket_path).context("Failed to create FGP server")?;
-        server.serve().context("Server error")?;
+        init_logging(output);
+
+        write_pid(&mut pid_lock)?;
+        serve(backend, config, &socket_path)?;
     } else {
         // Background mode - daemonize first, THEN create service
-        // Tokio runtime must be created AFTER fork
+        // Tokio runtime must be created AFTER fork. The child inherits the
+        // PID file lock and records its own PID there.
         use daemonize::Daemonize;
 
-        let daemonize = Daemonize::new()
-            .pid_file(&pid_file)
-            .working_directory("/tmp");
+        let daemonize = Daemonize::new().working_directory("/tmp");
 
         match daemonize.start() {
             Ok(_) => {
                 // Child process: initialize logging and run server
-                tracing_subscriber::fmt()
-                    .with_env_filter("fgp_neon=debug,fgp_daemon=debug")
-                    .init();
-
-                let service =
-                    NeonService::new(api_key, org_id).context("Failed to create NeonService")?;
-                let server =
-                    FgpServer::new(service, &socket_path).context("Failed to create FGP server")?;
-                server.serve().context("Server error")?;
+                init_logging(output);
+
+                write_pid(&mut pid_lock)?;
+                serve(backend, config, &socket_path)?;
             }
             Err(e) => {
                 eprintln!("Failed to daemonize: {}", e);
@@ -161,7 +391,65 @@ fn cmd_start(socket: String, foreground: bool) -> Result<()> {
     Ok(())
 }
 
-fn cmd_stop(socket: String) -> Result<()> {
+/// Log to stdout, or to stderr when stdout carries `--output json` results.
+fn init_logging(output: Output) {
+    let logs = tracing_subscriber::fmt().with_env_filter("fgp_neon=debug,fgp_daemon=debug");
+    match output {
+        Output::Text => logs.init(),
+        Output::Json => logs.with_writer(std::io::stderr).init(),
+    }
+}
+
+/// Create the backend's service and serve it.
+fn serve(backend: Backend, config: Config, socket_path: &str) -> Result<()> {
+    match backend {
+        Backend::Neon {
+            credentials,
+            org_id,
+        } => {
+            let service = NeonService::new(credentials, org_id, config)
+                .context("Failed to create NeonService")?;
+            let registry = service.clients();
+            run_server(service, registry, socket_path)
+        }
+        Backend::Stub(fixtures) => {
+            let service = StubService::new(fixtures, &config);
+            let registry = service.clients();
+            run_server(service, registry, socket_path)
+        }
+    }
+}
+
+/// Bind the sockets, harden the process, then serve until stopped.
+///
+/// The FGP server listens on a private inner socket; clients connect to
+/// `socket_path`, where connection tracking and limits are applied.
+fn run_server<S: FgpService + Send + Sync + 'static>(
+    service: S,
+    registry: Arc<ClientRegistry>,
+    socket_path: &str,
+) -> Result<()> {
+    let inner_path = format!("{}.inner", socket_path);
+    let _ = std::fs::remove_file(&inner_path);
+
+    let server = FgpServer::new(service, &inner_path).context("Failed to create FGP server")?;
+    let listener = std::os::unix::net::UnixListener::bind(socket_path)
+        .with_context(|| format!("Failed to bind {}", socket_path))?;
+    hardening::apply(socket_path).context("Failed to harden daemon process")?;
+
+    let files = vec![
+        socket_path.to_string(),
+        inner_path.clone(),
+        format!("{}.pid", socket_path),
+    ];
+    let idle_registry = registry.clone();
+    std::thread::spawn(move || clients::shutdown_when_idle(idle_registry, files));
+    std::thread::spawn(move || clients::serve(listener, inner_path, registry));
+    server.serve().context("Server error")?;
+    Ok(())
+}
+
+fn cmd_stop(socket: String, output: Output) -> Result<()> {
     let socket_path = shellexpand::tilde(&socket).to_string();
     let pid_file = format!("{}.pid", socket_path);
 
@@ -169,7 +457,14 @@ fn cmd_stop(socket: String) -> Result<()> {
         if let Ok(client) = fgp_daemon::FgpClient::new(&socket_path) {
             if let Ok(response) = client.stop() {
                 if response.ok {
-                    println!("Daemon stopped.");
+                    match output {
+                        Output::Text => println!("Daemon stopped."),
+                        Output::Json => print_json(serde_json::json!({
+                            "ok": true,
+                            "status": "stopped",
+                            "socket": socket_path,
+                        })),
+                    }
                     return Ok(());
                 }
             }
@@ -185,7 +480,9 @@ fn cmd_stop(socket: String) -> Result<()> {
         anyhow::bail!("Refusing to stop PID {}: unexpected process", pid);
     }
 
-    println!("Stopping fgp-neon daemon (PID: {})...", pid);
+    if output == Output::Text {
+        println!("Stopping fgp-neon daemon (PID: {})...", pid);
+    }
 
     // Send SIGTERM
     unsafe {
@@ -199,11 +496,110 @@ fn cmd_stop(socket: String) -> Result<()> {
     let _ = cleanup_socket(&socket_path, Some(Path::new(&pid_file)));
     let _ = std::fs::remove_file(&pid_file);
 
-    println!("Daemon stopped.");
+    match output {
+        Output::Text => println!("Daemon stopped."),
+        Output::Json => print_json(serde_json::json!({
+            "ok": true,
+            "status": "stopped",
+            "socket": socket_path,
+            "pid": pid,
+        })),
+    }
+
+    Ok(())
+}
+
+/// Open the PID file and take an exclusive lock on it, failing with the
+/// running daemon's PID if another start holds it.
+fn lock_pid_file(pid_file: &str) -> Result<std::fs::File> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(pid_file)
+        .with_context(|| format!("Failed to open {}", pid_file))?;
+
+    // SAFETY: the descriptor is valid for the lifetime of `file`.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+            match std::fs::read_to_string(pid_file)
+                .ok()
+                .and_then(|s| s.trim().parse::<i32>().ok())
+            {
+                Some(pid) => anyhow::bail!("fgp-neon is already running (pid {})", pid),
+                None => anyhow::bail!("fgp-neon is already starting (lock held on {})", pid_file),
+            }
+        }
+        return Err(err).with_context(|| format!("Failed to lock {}", pid_file));
+    }
+
+    Ok(file)
+}
+
+/// Replace the locked PID file's contents with this process's PID.
+fn write_pid(file: &mut std::fs::File) -> Result<()> {
+    use std::io::{Seek, Write};
 
+    file.set_len(0)?;
+    file.seek(std::io::SeekFrom::Start(0))?;
+    writeln!(file, "{}", std::process::id())?;
+    file.flush()?;
     Ok(())
 }
 
+/// Clear a socket left behind by a daemon that crashed.
+///
+/// Called with the PID file lock held, so the PID it names is from an
+/// earlier run. A socket that accepts connections belongs to a live daemon,
+/// as does a PID naming a running fgp-neon process; both abort the start.
+/// Anything else is stale and removed; returns whether a socket was.
+fn recover_stale_files(socket_path: &str, pid_file: &str) -> Result<bool> {
+    let pid = std::fs::read_to_string(pid_file)
+        .ok()
+        .and_then(|s| s.trim().parse::<i32>().ok());
+
+    if Path::new(socket_path).exists() {
+        if std::os::unix::net::UnixStream::connect(socket_path).is_ok() {
+            match pid {
+                Some(pid) => anyhow::bail!("fgp-neon is already running (pid {})", pid),
+                None => anyhow::bail!("fgp-neon is already running on {}", socket_path),
+            }
+        }
+    }
+
+    if let Some(pid) = pid {
+        if pid_is_alive(pid) && pid_matches_process(pid, "fgp-neon") {
+            anyhow::bail!(
+                "fgp-neon (pid {}) is running but not accepting connections on {}; stop it first",
+                pid,
+                socket_path
+            );
+        }
+    }
+
+    // The PID file stays: it's locked, and gets this run's PID once started
+    if Path::new(socket_path).exists() {
+        let _ = cleanup_socket(socket_path, None);
+        let _ = std::fs::remove_file(socket_path);
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+/// Whether a process with this PID exists (signal 0 probes without sending).
+fn pid_is_alive(pid: i32) -> bool {
+    if pid <= 0 {
+        return false;
+    }
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
 fn pid_matches_process(pid: i32, expected_name: &str) -> bool {
     let output = Command::new("ps")
         .args(["-p", &pid.to_string(), "-o", "comm="])
@@ -218,41 +614,324 @@ fn pid_matches_process(pid: i32, expected_name: &str) -> bool {
     }
 }
 
-fn cmd_status(socket: String) -> Result<()> {
+fn cmd_status(socket: String, output: Output) -> Result<()> {
     let socket_path = shellexpand::tilde(&socket).to_string();
 
     // Check if socket exists
     if !Path::new(&socket_path).exists() {
-        println!("Status: NOT RUNNING");
-        println!("Socket {} does not exist", socket_path);
+        match output {
+            Output::Text => {
+                println!("Status: NOT RUNNING");
+                println!("Socket {} does not exist", socket_path);
+            }
+            Output::Json => print_json(serde_json::json!({
+                "ok": true,
+                "status": "not_running",
+                "socket": socket_path,
+            })),
+        }
         return Ok(());
     }
 
     // Try to connect and send health check
-    use std::io::{BufRead, BufReader, Write};
-    use std::os::unix::net::UnixStream;
+    let request = r#"{"id":"status","v":1,"method":"health","params":{}}"#;
+    let response = match send_request(&socket_path, request) {
+        Ok(response) => response,
+        Err(e) => {
+            match output {
+                Output::Text => {
+                    println!("Status: NOT RESPONDING");
+                    println!("Socket exists but connection failed: {}", e);
+                }
+                Output::Json => print_json(serde_json::json!({
+                    "ok": true,
+                    "status": "not_responding",
+                    "socket": socket_path,
+                    "error": e.to_string(),
+                })),
+            }
+            return Ok(());
+        }
+    };
+    let health: serde_json::Value = serde_json::from_str(&response).unwrap_or_default();
+
+    // Last background update check, if any
+    let request = r#"{"id":"status","v":1,"method":"neon.update_check","params":{"cached":true}}"#;
+    let update: serde_json::Value = send_request(&socket_path, request)
+        .ok()
+        .and_then(|response| serde_json::from_str(&response).ok())
+        .unwrap_or_default();
+    let update = &update["result"]["data"];
+
+    if output == Output::Json {
+        print_json(serde_json::json!({
+            "ok": true,
+            "status": "running",
+            "socket": socket_path,
+            "health": health["result"]["data"],
+            "update": update,
+        }));
+        return Ok(());
+    }
 
-    match UnixStream::connect(&socket_path) {
-        Ok(mut stream) => {
-            // Send health request
-            let request = r#"{"id":"status","v":1,"method":"health","params":{}}"#;
-            writeln!(stream, "{}", request)?;
-            stream.flush()?;
+    println!("Status: RUNNING");
+    println!("Socket: {}", socket_path);
+    println!("Health: {}", response.trim());
+    if let Some(line) = credentials_line(&health["result"]["data"]["credentials"]) {
+        println!("{}", line);
+    }
+    if update["update_available"].as_bool() == Some(true) {
+        println!(
+            "Update: {} available (running {}) {}",
+            update["latest_version"].as_str().unwrap_or("?"),
+            update["current_version"].as_str().unwrap_or("?"),
+            update["release_url"].as_str().unwrap_or("")
+        );
+    }
 
-            // Read response
-            let mut reader = BufReader::new(stream);
-            let mut response = String::new();
-            reader.read_line(&mut response)?;
+    Ok(())
+}
 
-            println!("Status: RUNNING");
-            println!("Socket: {}", socket_path);
-            println!("Health: {}", response.trim());
+/// When the daemon's credentials expire, or that the API refuses them.
+fn credentials_line(status: &serde_json::Value) -> Option<String> {
+    if status["rejected_at"].is_string() {
+        return Some(format!(
+            "Credentials: rejected by the Neon API since {}; run `neonctl auth` (or set NEON_API_KEY) and restart the daemon",
+            status["rejected_at"].as_str().unwrap_or("?")
+        ));
+    }
+    let minutes = status["expires_in_minutes"].as_i64()?;
+    Some(if minutes < 0 {
+        format!(
+            "Credentials: expired {} minutes ago; run `neonctl auth` and restart the daemon",
+            -minutes
+        )
+    } else {
+        format!("Credentials: expire in {} minutes", minutes)
+    })
+}
+
+fn cmd_verify(
+    config: String,
+    project: Option<String>,
+    branch: Option<String>,
+    database: String,
+    output: Output,
+) -> Result<()> {
+    let sql = project
+        .zip(branch)
+        .map(|(project_id, branch_id)| verify::SqlCheck {
+            project_id,
+            branch_id,
+            database,
+        });
+
+    let checks = verify::run(&config, sql, output == Output::Text);
+    let passed = checks.iter().all(|check| check.ok);
+    match output {
+        Output::Text if passed => println!("All checks passed."),
+        Output::Text => {}
+        Output::Json => print_json(serde_json::json!({ "ok": passed, "checks": checks })),
+    }
+
+    if !passed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn cmd_replay(
+    id: u64,
+    branch: Option<String>,
+    database: Option<String>,
+    socket: String,
+    output: Output,
+) -> Result<()> {
+    let socket_path = shellexpand::tilde(&socket).to_string();
+
+    let mut params = serde_json::json!({ "id": id });
+    if let Some(branch) = branch {
+        params["branch_id"] = serde_json::json!(branch);
+    }
+    if let Some(database) = database {
+        params["database"] = serde_json::json!(database);
+    }
+    let request = serde_json::json!({
+        "id": "replay",
+        "v": 1,
+        "method": "neon.replay",
+        "params": params,
+    });
+
+    let response = send_request(&socket_path, &request.to_string())
+        .with_context(|| format!("Failed to reach daemon at {}", socket_path))?;
+    let response: serde_json::Value =
+        serde_json::from_str(&response).context("Invalid response from daemon")?;
+    let failed = response["ok"].as_bool() == Some(false);
+    match output {
+        Output::Text => println!("{}", serde_json::to_string_pretty(&response)?),
+        // Already the daemon's JSON envelope, with its own `ok`
+        Output::Json => print_json(response),
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn cmd_config_check(config: String, output: Output) -> Result<()> {
+    let path = shellexpand::tilde(&config).to_string();
+
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            match output {
+                Output::Text => println!("{} not found; defaults apply.", path),
+                Output::Json => print_json(serde_json::json!({
+                    "ok": true,
+                    "path": path,
+                    "found": false,
+                })),
+            }
+            return Ok(());
         }
-        Err(e) => {
-            println!("Status: NOT RESPONDING");
-            println!("Socket exists but connection failed: {}", e);
+        Err(e) => return Err(e).with_context(|| format!("Failed to read config {}", path)),
+    };
+
+    let problems: Vec<serde_json::Value> = match Config::parse(&text) {
+        Ok((_, unknown)) => unknown
+            .iter()
+            .map(|key| serde_json::json!({ "message": key.to_string(), "key": key.path, "line": key.line }))
+            .collect(),
+        Err(e) => vec![serde_json::json!({ "message": format!("{:#}", e) })],
+    };
+    match output {
+        Output::Text if problems.is_empty() => println!("{}: OK", path),
+        Output::Text => {
+            for problem in &problems {
+                println!(
+                    "{}: {}",
+                    path,
+                    problem["message"].as_str().unwrap_or_default()
+                );
+            }
         }
+        Output::Json => print_json(serde_json::json!({
+            "ok": problems.is_empty(),
+            "path": path,
+            "found": true,
+            "problems": problems,
+        })),
+    }
+
+    if !problems.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn cmd_config_show(config: String, output: Output) -> Result<()> {
+    let path = shellexpand::tilde(&config).to_string();
+    let text = read_config_text(&path)?;
+    let found = text.is_some();
+    let text = text.unwrap_or_default();
+    let (effective, _) =
+        Config::parse(&text).with_context(|| format!("Failed to parse config {}", path))?;
+    let settings = effective.settings(&text)?;
+
+    if output == Output::Json {
+        let settings: Vec<serde_json::Value> = settings
+            .into_iter()
+            .map(|s| {
+                serde_json::json!({
+                    "key": s.key,
+                    "value": s.value,
+                    "source": if s.from_file { "file" } else { "default" },
+                })
+            })
+            .collect();
+        print_json(serde_json::json!({
+            "ok": true,
+            "path": path,
+            "found": found,
+            "settings": settings,
+        }));
+        return Ok(());
     }
 
+    if found {
+        println!("# {}", path);
+    } else {
+        println!("# {} (not found; all defaults)", path);
+    }
+    let lines: Vec<(String, bool)> = settings
+        .into_iter()
+        .map(|s| (format!("{} = {}", s.key, s.value), s.from_file))
+        .collect();
+    // Long values (lists, inline tables) don't push every comment right
+    let width = lines
+        .iter()
+        .map(|(line, _)| line.len())
+        .filter(|len| *len <= 60)
+        .max()
+        .unwrap_or(0);
+    for (line, from_file) in lines {
+        let source = if from_file { "file" } else { "default" };
+        println!("{:<width$}  # {}", line, source, width = width);
+    }
     Ok(())
 }
+
+fn cmd_config_set(config: String, key: String, value: String, output: Output) -> Result<()> {
+    let path = shellexpand::tilde(&config).to_string();
+    let text = read_config_text(&path)?.unwrap_or_default();
+    let updated = set_value(&text, &key, &value)?;
+
+    if let Some(parent) = Path::new(&path).parent() {
+        std::fs::create_dir_all(parent).context("Failed to create config directory")?;
+    }
+    // Written aside and renamed, so a failed write can't leave half a file
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, updated).with_context(|| format!("Failed to write {}", tmp))?;
+    std::fs::rename(&tmp, &path).with_context(|| format!("Failed to replace {}", path))?;
+
+    match output {
+        Output::Text => {
+            println!("Set {} = {} in {}", key, value, path);
+            println!("Restart the daemon to apply it.");
+        }
+        Output::Json => print_json(serde_json::json!({
+            "ok": true,
+            "path": path,
+            "key": key,
+            "value": value,
+            "restart_required": true,
+        })),
+    }
+    Ok(())
+}
+
+/// The config file's text, or `None` if it doesn't exist.
+fn read_config_text(path: &str) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read config {}", path)),
+    }
+}
+
+/// Send one request line to the daemon and read one response line.
+fn send_request(socket_path: &str, request: &str) -> std::io::Result<String> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(socket_path)?;
+    writeln!(stream, "{}", request)?;
+    stream.flush()?;
+
+    let mut reader = BufReader::new(stream);
+    let mut response = String::new();
+    reader.read_line(&mut response)?;
+    Ok(response)
+}
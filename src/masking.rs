@@ -0,0 +1,92 @@
+//! Column masking for data copied out of a branch.
+//!
+//! `[[masking.columns]]` rules name a table's column and how to replace its
+//! values: `null`, `hash` (an MD5 of the salted value), `fake_email` (an
+//! address derived from that hash), or `constant`. `neon.dump` applies them in
+//! the query that reads the rows, so unmasked values never leave the
+//! database. `hash` and `fake_email` are deterministic for a given salt, so
+//! equal values stay equal and joins on a masked column still line up. NULLs
+//! stay NULL except under `null`.
+
+use anyhow::Result;
+
+use crate::api::{quote_ident, quote_literal};
+use crate::config::{MaskKind, MaskingConfig};
+
+/// Configured masks.
+pub struct Masker {
+    config: MaskingConfig,
+}
+
+/// A masked column of one table.
+pub struct ColumnMask {
+    pub column: String,
+    /// Text expression over the row alias `t` replacing the column's value.
+    pub expr: String,
+}
+
+impl Masker {
+    /// Check the rules, failing on a `constant` without a value.
+    pub fn new(config: &MaskingConfig) -> Result<Self> {
+        for rule in &config.columns {
+            if rule.mask == MaskKind::Constant && rule.value.is_none() {
+                anyhow::bail!(
+                    "Masking rule for {}.{} is constant but has no value",
+                    rule.table,
+                    rule.column
+                );
+            }
+        }
+        Ok(Self {
+            config: config.clone(),
+        })
+    }
+
+    /// Whether dumps should be masked, given the caller's `mask`.
+    pub fn applies(&self, mask: bool) -> Result<bool> {
+        if !mask && !self.config.allow_override {
+            anyhow::bail!("mask: false is disabled by [masking] allow_override = false");
+        }
+        Ok(mask && !self.config.columns.is_empty())
+    }
+
+    /// Masks for `schema.table`'s columns. A rule's table is `schema.table`,
+    /// a table in `public`, or `*` for every table.
+    pub fn columns(&self, schema: &str, table: &str) -> Vec<ColumnMask> {
+        let qualified = format!("{}.{}", schema, table);
+        let mut masks: Vec<ColumnMask> = Vec::new();
+        for rule in &self.config.columns {
+            let matches = rule.table == "*"
+                || rule.table == qualified
+                || (schema == "public" && rule.table == table);
+            // The first matching rule for a column wins
+            if !matches || masks.iter().any(|m| m.column == rule.column) {
+                continue;
+            }
+
+            let value = format!("t.{}", quote_ident(&rule.column));
+            let hashed = format!(
+                "md5({} || {}::text)",
+                quote_literal(&self.config.salt),
+                value
+            );
+            let expr = match rule.mask {
+                MaskKind::Null => "NULL::text".to_string(),
+                MaskKind::Hash => hashed,
+                MaskKind::FakeEmail => {
+                    format!("'user_' || left({}, 12) || '@example.com'", hashed)
+                }
+                MaskKind::Constant => format!(
+                    "CASE WHEN {} IS NULL THEN NULL ELSE {} END",
+                    value,
+                    quote_literal(rule.value.as_deref().unwrap_or_default())
+                ),
+            };
+            masks.push(ColumnMask {
+                column: rule.column.clone(),
+                expr,
+            });
+        }
+        masks
+    }
+}
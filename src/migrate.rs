@@ -0,0 +1,384 @@
+//! Versioned SQL migrations.
+//!
+//! `neon.migrate_status`, `neon.migrate_plan`, and `neon.migrate_apply` work
+//! from a directory of `<version>_<name>.sql` files, where the version is a
+//! number (`0001_init.sql`, `20250101120000_add_users.sql`), taken in version
+//! order. Applied versions are recorded, with a checksum of the file, in
+//! `public.fgp_migrations` on the target database.
+//!
+//! Each migration runs in its own transaction, which first takes an advisory
+//! lock and records the version, then runs the file's statements. A second
+//! runner waits on the lock and then fails on the recorded version and rolls
+//! back, so a migration is never applied twice. Statements that can't run in
+//! a transaction (`CREATE INDEX CONCURRENTLY`, `VACUUM`) can't be used.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use crate::api::{result_rows, NeonClient, TxOptions, TxStatement};
+use crate::script::ScriptTarget;
+use crate::sqltext;
+
+/// Table recording applied migrations.
+const TABLE: &str = "public.fgp_migrations";
+
+/// Transaction-level advisory lock held while a migration runs ("fgp_migr").
+const LOCK_KEY: i64 = 0x6667_705f_6d69_6772;
+
+/// A migration file.
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub version: u64,
+    pub name: String,
+    pub checksum: String,
+    pub sql: String,
+}
+
+/// A migration recorded in the target database.
+#[derive(Debug, Clone)]
+struct Applied {
+    name: String,
+    checksum: String,
+    applied_at: String,
+}
+
+/// A migration's state on the target.
+#[derive(Debug, Serialize)]
+pub struct MigrationStatus {
+    pub version: u64,
+    pub name: String,
+    /// `applied`, `pending`, `modified` (applied, but the file has changed
+    /// since), or `missing` (applied, but no file has its version).
+    pub state: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub applied_at: Option<String>,
+}
+
+/// Every migration, local or applied, in version order.
+#[derive(Debug, Serialize)]
+pub struct Status {
+    /// Highest applied version.
+    pub current_version: Option<u64>,
+    pub applied: usize,
+    pub pending: usize,
+    pub migrations: Vec<MigrationStatus>,
+}
+
+/// What `neon.migrate_apply` would do.
+#[derive(Debug, Serialize)]
+pub struct Plan {
+    pub current_version: Option<u64>,
+    pub pending: Vec<PlannedMigration>,
+    /// Why apply would refuse to run; empty when it can.
+    pub problems: Vec<String>,
+}
+
+/// A migration apply would run.
+#[derive(Debug, Serialize)]
+pub struct PlannedMigration {
+    pub version: u64,
+    pub name: String,
+    pub statements: usize,
+}
+
+/// A migration apply ran.
+#[derive(Debug, Serialize)]
+pub struct AppliedMigration {
+    pub version: u64,
+    pub name: String,
+    pub statements: usize,
+    pub duration_ms: f64,
+}
+
+/// What an apply did.
+#[derive(Debug, Serialize)]
+pub struct ApplyOutcome {
+    pub applied: Vec<AppliedMigration>,
+    pub current_version: Option<u64>,
+    /// Migrations still pending, such as those past `target_version`.
+    pub pending: usize,
+}
+
+/// Read the migrations in a directory, in version order.
+pub fn load(dir: &str) -> Result<Vec<Migration>> {
+    let dir = shellexpand::tilde(dir).to_string();
+    let entries = std::fs::read_dir(&dir)
+        .with_context(|| format!("Failed to read migrations directory {}", dir))?;
+
+    let mut migrations: BTreeMap<u64, Migration> = BTreeMap::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("sql") {
+            continue;
+        }
+        let file = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default();
+        let Some((version, name)) = file
+            .split_once('_')
+            .and_then(|(v, name)| Some((v.parse::<u64>().ok()?, name)))
+        else {
+            anyhow::bail!(
+                "Migration {} must be named <version>_<name>.sql with a numeric version",
+                path.display()
+            );
+        };
+        let sql = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read migration {}", path.display()))?;
+
+        let migration = Migration {
+            version,
+            name: name.to_string(),
+            checksum: checksum(&sql),
+            sql,
+        };
+        if let Some(other) = migrations.insert(version, migration) {
+            anyhow::bail!(
+                "Migrations {}_{} and {} share version {}",
+                version,
+                other.name,
+                path.display(),
+                version
+            );
+        }
+    }
+
+    Ok(migrations.into_values().collect())
+}
+
+/// Compare local migrations with those applied on the target.
+pub async fn status(
+    client: &NeonClient,
+    target: &ScriptTarget<'_>,
+    migrations: &[Migration],
+) -> Result<Status> {
+    let applied = applied(client, target).await?;
+    Ok(build_status(migrations, &applied))
+}
+
+/// The migrations apply would run, and anything that stops it.
+pub async fn plan(
+    client: &NeonClient,
+    target: &ScriptTarget<'_>,
+    migrations: &[Migration],
+) -> Result<Plan> {
+    let applied = applied(client, target).await?;
+    Ok(build_plan(migrations, &applied))
+}
+
+/// Apply pending migrations in order, up to and including `target_version`.
+/// Stops at the first failure; migrations applied before it stay applied.
+pub async fn apply(
+    client: &NeonClient,
+    target: &ScriptTarget<'_>,
+    migrations: &[Migration],
+    target_version: Option<u64>,
+) -> Result<ApplyOutcome> {
+    let plan = build_plan(migrations, &applied(client, target).await?);
+    if !plan.problems.is_empty() {
+        anyhow::bail!("Refusing to migrate: {}", plan.problems.join("; "));
+    }
+
+    let mut outcome = ApplyOutcome {
+        applied: Vec::new(),
+        current_version: plan.current_version,
+        pending: plan.pending.len(),
+    };
+    let pending = migrations
+        .iter()
+        .filter(|m| plan.pending.iter().any(|p| p.version == m.version))
+        .take_while(|m| target_version.is_none_or(|t| m.version <= t));
+
+    for migration in pending {
+        let statements = sqltext::split_script(&migration.sql);
+        let mut transaction = vec![
+            TxStatement {
+                query: format!("SELECT pg_advisory_xact_lock({})", LOCK_KEY),
+                params: Vec::new(),
+            },
+            TxStatement {
+                query: format!("CREATE TABLE IF NOT EXISTS {} (version bigint PRIMARY KEY, name text NOT NULL, checksum text NOT NULL, applied_at timestamptz NOT NULL DEFAULT now())", TABLE),
+                params: Vec::new(),
+            },
+            // Fails once the lock is ours if another runner got there first
+            TxStatement {
+                query: format!(
+                    "INSERT INTO {} (version, name, checksum) VALUES ($1, $2, $3)",
+                    TABLE
+                ),
+                params: vec![
+                    serde_json::json!(migration.version),
+                    Value::String(migration.name.clone()),
+                    Value::String(migration.checksum.clone()),
+                ],
+            },
+        ];
+        transaction.extend(statements.iter().map(|s| TxStatement {
+            query: s.text.clone(),
+            params: Vec::new(),
+        }));
+
+        let started = Instant::now();
+        client
+            .run_transaction(
+                target.project_id,
+                target.branch_id,
+                target.database,
+                &transaction,
+                TxOptions::default(),
+            )
+            .await
+            .with_context(|| {
+                format!(
+                    "Migration {}_{} failed and was rolled back after {} migration(s) were applied",
+                    migration.version,
+                    migration.name,
+                    outcome.applied.len()
+                )
+            })?;
+
+        outcome.applied.push(AppliedMigration {
+            version: migration.version,
+            name: migration.name.clone(),
+            statements: statements.len(),
+            duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        });
+        outcome.current_version = outcome.current_version.max(Some(migration.version));
+        outcome.pending -= 1;
+    }
+
+    Ok(outcome)
+}
+
+fn build_status(migrations: &[Migration], applied: &BTreeMap<u64, Applied>) -> Status {
+    let mut states: BTreeMap<u64, MigrationStatus> = BTreeMap::new();
+    for migration in migrations {
+        let recorded = applied.get(&migration.version);
+        let state = match recorded {
+            None => "pending",
+            Some(a) if a.checksum != migration.checksum => "modified",
+            Some(_) => "applied",
+        };
+        states.insert(
+            migration.version,
+            MigrationStatus {
+                version: migration.version,
+                name: migration.name.clone(),
+                state,
+                applied_at: recorded.map(|a| a.applied_at.clone()),
+            },
+        );
+    }
+    for (version, a) in applied {
+        states.entry(*version).or_insert_with(|| MigrationStatus {
+            version: *version,
+            name: a.name.clone(),
+            state: "missing",
+            applied_at: Some(a.applied_at.clone()),
+        });
+    }
+
+    let migrations: Vec<MigrationStatus> = states.into_values().collect();
+    Status {
+        current_version: applied.keys().next_back().copied(),
+        applied: applied.len(),
+        pending: migrations.iter().filter(|m| m.state == "pending").count(),
+        migrations,
+    }
+}
+
+fn build_plan(migrations: &[Migration], applied: &BTreeMap<u64, Applied>) -> Plan {
+    let current_version = applied.keys().next_back().copied();
+    let mut plan = Plan {
+        current_version,
+        pending: Vec::new(),
+        problems: Vec::new(),
+    };
+
+    for migration in migrations {
+        match applied.get(&migration.version) {
+            Some(a) if a.checksum != migration.checksum => plan.problems.push(format!(
+                "migration {}_{} was changed after it was applied",
+                migration.version, migration.name
+            )),
+            Some(_) => {}
+            None => {
+                if current_version.is_some_and(|current| migration.version < current) {
+                    plan.problems.push(format!(
+                        "migration {}_{} is older than the applied version {}",
+                        migration.version,
+                        migration.name,
+                        current_version.unwrap_or_default()
+                    ));
+                }
+                plan.pending.push(PlannedMigration {
+                    version: migration.version,
+                    name: migration.name.clone(),
+                    statements: sqltext::split_script(&migration.sql).len(),
+                });
+            }
+        }
+    }
+
+    plan
+}
+
+/// Migrations recorded on the target; none before the first apply.
+async fn applied(client: &NeonClient, target: &ScriptTarget<'_>) -> Result<BTreeMap<u64, Applied>> {
+    let run = |query: String| async move {
+        let result = client
+            .run_sql(target.project_id, target.branch_id, target.database, &query)
+            .await?;
+        if result.get("truncated").and_then(|v| v.as_bool()) == Some(true) {
+            anyhow::bail!("More applied migrations than limits.max_rows; raise it to migrate");
+        }
+        anyhow::Ok(result_rows(&result).to_vec())
+    };
+
+    let exists = run(format!(
+        "SELECT to_regclass('{}') IS NOT NULL AS exists",
+        TABLE
+    ))
+    .await?;
+    if exists.first().and_then(|row| row.get("exists")) != Some(&Value::Bool(true)) {
+        return Ok(BTreeMap::new());
+    }
+
+    let rows = run(format!(
+        "SELECT version::text AS version, name, checksum, applied_at::text AS applied_at FROM {} ORDER BY version",
+        TABLE
+    ))
+    .await?;
+    let text = |row: &Value, name: &str| -> String {
+        row.get(name)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let version = text(row, "version").parse().ok()?;
+            Some((
+                version,
+                Applied {
+                    name: text(row, "name"),
+                    checksum: text(row, "checksum"),
+                    applied_at: text(row, "applied_at"),
+                },
+            ))
+        })
+        .collect())
+}
+
+/// FNV-1a of a migration's text, to notice it being edited once applied.
+fn checksum(text: &str) -> String {
+    let hash = text.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
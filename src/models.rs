@@ -1,6 +1,8 @@
 //! Data models for Neon API responses.
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Neon project.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +19,242 @@ pub struct Project {
     pub created_at: Option<String>,
     #[serde(default)]
     pub updated_at: Option<String>,
+    #[serde(default)]
+    pub settings: Option<ProjectSettings>,
+    /// Defaults applied to new compute endpoints.
+    #[serde(default)]
+    pub default_endpoint_settings: Option<DefaultEndpointSettings>,
+    /// Billable storage in bytes.
+    #[serde(default)]
+    pub synthetic_storage_size: Option<u64>,
+    #[serde(default)]
+    pub history_retention_seconds: Option<u64>,
+    #[serde(default)]
+    pub compute_last_active_at: Option<String>,
+    #[serde(default)]
+    pub owner_id: Option<String>,
+    /// Only returned when fetching a single project.
+    #[serde(default)]
+    pub owner: Option<ProjectOwner>,
+    /// Start of the current quota period.
+    #[serde(default)]
+    pub quota_reset_at: Option<String>,
+    /// Usage in the current quota period.
+    #[serde(default)]
+    pub compute_time_seconds: Option<u64>,
+    #[serde(default)]
+    pub active_time_seconds: Option<u64>,
+    #[serde(default)]
+    pub written_data_bytes: Option<u64>,
+    #[serde(default)]
+    pub data_transfer_bytes: Option<u64>,
+}
+
+impl Project {
+    /// Usage against each configured quota, keyed by metric.
+    pub fn quota_status(&self) -> BTreeMap<String, QuotaUsage> {
+        let Some(quota) = self.settings.as_ref().and_then(|s| s.quota.as_ref()) else {
+            return BTreeMap::new();
+        };
+
+        [
+            (
+                "active_time_seconds",
+                quota.active_time_seconds,
+                self.active_time_seconds,
+            ),
+            (
+                "compute_time_seconds",
+                quota.compute_time_seconds,
+                self.compute_time_seconds,
+            ),
+            (
+                "written_data_bytes",
+                quota.written_data_bytes,
+                self.written_data_bytes,
+            ),
+            (
+                "data_transfer_bytes",
+                quota.data_transfer_bytes,
+                self.data_transfer_bytes,
+            ),
+            (
+                "logical_size_bytes",
+                quota.logical_size_bytes,
+                self.synthetic_storage_size,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(metric, limit, used)| {
+            // Neon reports 0 for "no limit"
+            let limit = limit.filter(|l| *l > 0)?;
+            let used = used.unwrap_or(0);
+            Some((
+                metric.to_string(),
+                QuotaUsage {
+                    used,
+                    limit,
+                    percent: used as f64 * 100.0 / limit as f64,
+                    exceeded: used >= limit,
+                },
+            ))
+        })
+        .collect()
+    }
+}
+
+/// Project-level settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota: Option<ProjectQuota>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_ips: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enable_logical_replication: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_public_connections: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance_window: Option<serde_json::Value>,
+}
+
+/// Per-period limits; 0 means unlimited.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectQuota {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_time_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compute_time_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub written_data_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_transfer_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logical_size_bytes: Option<u64>,
+}
+
+/// Usage of one quota metric.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaUsage {
+    pub used: u64,
+    pub limit: u64,
+    pub percent: f64,
+    pub exceeded: bool,
+}
+
+/// Compute defaults for a project's new endpoints.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DefaultEndpointSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autoscaling_limit_min_cu: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autoscaling_limit_max_cu: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suspend_timeout_seconds: Option<i64>,
+}
+
+/// Body of a project create call.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreateProjectRequest {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pg_version: Option<i32>,
+    /// The project's first branch, role, and database.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<ProjectBranchRequest>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<ProjectSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_endpoint_settings: Option<DefaultEndpointSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_retention_seconds: Option<u64>,
+}
+
+/// Body of a branch restore call. Without an LSN or timestamp the branch is
+/// restored to the source branch's head.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RestoreBranchRequest {
+    pub source_branch_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_lsn: Option<String>,
+    /// RFC 3339 point in the source branch's history.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_timestamp: Option<String>,
+    /// Keep the branch's pre-restore state as a new branch with this name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preserve_under_name: Option<String>,
+}
+
+/// A restored branch and the operations applying the restore.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoredBranch {
+    pub branch: Branch,
+    #[serde(default)]
+    pub operations: Vec<Operation>,
+}
+
+/// Names for a new project's default branch, role, and database.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectBranchRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database_name: Option<String>,
+}
+
+/// Body of a project update call; only the fields set are changed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateProjectRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<ProjectSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_endpoint_settings: Option<DefaultEndpointSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_retention_seconds: Option<u64>,
+}
+
+/// A newly created project and the resources created with it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatedProject {
+    pub project: Project,
+    #[serde(default)]
+    pub branch: Option<Branch>,
+    #[serde(default)]
+    pub endpoints: Vec<Endpoint>,
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    #[serde(default)]
+    pub databases: Vec<Database>,
+    /// Connection strings with the new role's password; only returned here.
+    #[serde(default)]
+    pub connection_uris: Vec<ConnectionUri>,
+    #[serde(default)]
+    pub operations: Vec<Operation>,
+}
+
+/// A connection string returned when a project is created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionUri {
+    pub connection_uri: String,
+}
+
+/// Account that owns a project.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectOwner {
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub branches_limit: Option<u64>,
+    #[serde(default)]
+    pub subscription_type: Option<String>,
 }
 
 /// Neon branch.
@@ -33,6 +271,60 @@ pub struct Branch {
     pub updated_at: Option<String>,
     #[serde(default)]
     pub current_state: Option<String>,
+    /// Whether this is the project's default branch.
+    #[serde(default)]
+    pub default: bool,
+    /// Older name for `default`, still returned by the API.
+    #[serde(default)]
+    pub primary: bool,
+    /// Protected branches can't be deleted or reset.
+    #[serde(default)]
+    pub protected: bool,
+    /// Last time a compute on the branch was active.
+    #[serde(default)]
+    pub last_active_at: Option<String>,
+    /// State the branch is moving to while an operation runs.
+    #[serde(default)]
+    pub pending_state: Option<String>,
+    #[serde(default)]
+    pub state_changed_at: Option<String>,
+    #[serde(default)]
+    pub last_reset_at: Option<String>,
+    /// Point in the parent's history the branch was created from.
+    #[serde(default)]
+    pub parent_timestamp: Option<String>,
+    #[serde(default)]
+    pub parent_lsn: Option<String>,
+    /// Logical data size in bytes.
+    #[serde(default)]
+    pub logical_size: Option<u64>,
+    /// Usage in the current billing period.
+    #[serde(default)]
+    pub compute_time_seconds: Option<u64>,
+    #[serde(default)]
+    pub active_time_seconds: Option<u64>,
+    #[serde(default)]
+    pub written_data_bytes: Option<u64>,
+    #[serde(default)]
+    pub data_transfer_bytes: Option<u64>,
+    /// Annotation key/values, which listings return beside the branch
+    /// rather than inside it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Branch {
+    /// Days since the branch was last active, falling back to its last state
+    /// change or update when Neon reports no activity.
+    pub fn idle_days(&self, now: DateTime<Utc>) -> Option<f64> {
+        let last = self
+            .last_active_at
+            .as_deref()
+            .or(self.state_changed_at.as_deref())
+            .or(self.updated_at.as_deref())?;
+        let last = DateTime::parse_from_rfc3339(last).ok()?;
+        Some((now - last.with_timezone(&Utc)).num_minutes() as f64 / 1440.0)
+    }
 }
 
 /// Neon database.
@@ -48,6 +340,84 @@ pub struct Database {
     pub updated_at: Option<String>,
 }
 
+/// Neon compute endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Endpoint {
+    pub id: String,
+    pub project_id: String,
+    pub branch_id: String,
+    pub host: String,
+    /// `read_write` or `read_only`.
+    #[serde(rename = "type")]
+    pub endpoint_type: String,
+    #[serde(default)]
+    pub current_state: Option<String>,
+    #[serde(default)]
+    pub autoscaling_limit_min_cu: Option<f64>,
+    #[serde(default)]
+    pub autoscaling_limit_max_cu: Option<f64>,
+    #[serde(default)]
+    pub suspend_timeout_seconds: Option<i64>,
+    #[serde(default)]
+    pub pooler_enabled: Option<bool>,
+    #[serde(default)]
+    pub pooler_host: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+/// Neon Postgres role.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Role {
+    pub branch_id: String,
+    pub name: String,
+    /// Only returned when a role is created; never serialized back out.
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub protected: Option<bool>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+/// Neon control-plane operation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Operation {
+    pub id: String,
+    pub project_id: String,
+    #[serde(default)]
+    pub branch_id: Option<String>,
+    #[serde(default)]
+    pub endpoint_id: Option<String>,
+    pub action: String,
+    pub status: String,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+impl Operation {
+    /// Whether the operation has not reached a terminal state yet.
+    pub fn is_pending(&self) -> bool {
+        matches!(
+            self.status.as_str(),
+            "scheduling" | "running" | "cancelling"
+        )
+    }
+
+    /// Whether the operation ended unsuccessfully.
+    pub fn is_failed(&self) -> bool {
+        matches!(self.status.as_str(), "failed" | "error")
+    }
+}
+
 /// Database table info.
 #[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -70,16 +440,351 @@ pub struct ColumnInfo {
     pub column_default: Option<String>,
 }
 
-/// SQL query result.
-#[allow(dead_code)]
+/// Consumption history for one project.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct QueryResult {
+pub struct ProjectConsumption {
+    pub project_id: String,
+    #[serde(default)]
+    pub periods: Vec<ConsumptionPeriod>,
+}
+
+/// A billing period within [`ProjectConsumption`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsumptionPeriod {
+    #[serde(default)]
+    pub period_id: Option<String>,
+    #[serde(default)]
+    pub period_start: Option<String>,
+    #[serde(default)]
+    pub period_end: Option<String>,
+    #[serde(default)]
+    pub consumption: Vec<ConsumptionPoint>,
+}
+
+/// Usage metrics for one timeframe (hour, day, or month).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConsumptionPoint {
+    pub timeframe_start: String,
+    pub timeframe_end: String,
+    #[serde(default)]
+    pub active_time_seconds: u64,
+    #[serde(default)]
+    pub compute_time_seconds: u64,
+    #[serde(default)]
+    pub written_data_bytes: u64,
     #[serde(default)]
+    pub synthetic_storage_size_bytes: u64,
+    #[serde(default)]
+    pub data_storage_bytes_hour: u64,
+    #[serde(default)]
+    pub data_transfer_bytes: u64,
+}
+
+/// A Neon Auth integration on a project.
+///
+/// Keys are only returned when the integration is created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthIntegration {
+    pub auth_provider: String,
+    #[serde(default)]
+    pub auth_provider_project_id: Option<String>,
+    #[serde(default)]
+    pub branch_id: Option<String>,
+    #[serde(default)]
+    pub db_name: Option<String>,
+    #[serde(default)]
+    pub jwks_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pub_client_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_server_key: Option<String>,
+    #[serde(default)]
+    pub schema_name: Option<String>,
+    #[serde(default)]
+    pub table_name: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+/// Data API (PostgREST-compatible REST endpoint) for a branch database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataApi {
+    /// Base URL of the REST endpoint.
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+    /// `neon_auth` or `external`.
+    #[serde(default)]
+    pub auth_provider: Option<String>,
+    /// JWKS used to verify request JWTs.
+    #[serde(default)]
+    pub jwks_url: Option<String>,
+    #[serde(default)]
+    pub jwt_audience: Option<String>,
+}
+
+/// A JWKS URL trusted for JWT-authenticated connections to a project.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Jwks {
+    pub id: String,
+    pub project_id: String,
+    /// Branch the JWKS applies to; every branch when absent.
+    #[serde(default)]
+    pub branch_id: Option<String>,
+    pub jwks_url: String,
+    pub provider_name: String,
+    #[serde(default)]
+    pub jwt_audience: Option<String>,
+    /// Postgres roles a token's `role` claim may select.
+    #[serde(default)]
+    pub role_names: Vec<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+/// An API key as returned when it's created; `key` is only shown then.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: i64,
+    pub key: String,
+    pub name: String,
+    /// Project the key is limited to; org-wide when absent.
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+/// Column-level snapshot of a database schema.
+///
+/// Tables are keyed by `schema.table`, columns by name, so two snapshots can be
+/// diffed structurally and serialized deterministically.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SchemaSnapshot {
+    #[serde(default)]
+    pub tables: BTreeMap<String, TableDef>,
+}
+
+/// Table definition within a [`SchemaSnapshot`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TableDef {
+    #[serde(default)]
+    pub columns: BTreeMap<String, ColumnDef>,
+}
+
+/// Column definition within a [`TableDef`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnDef {
+    pub data_type: String,
+    #[serde(default = "default_true")]
+    pub nullable: bool,
+}
+
+/// A table's columns, keys, constraints, and indexes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableDetail {
+    pub schema: String,
+    pub table: String,
+    pub columns: Vec<TableColumn>,
+    #[serde(default)]
+    pub primary_key: Option<ConstraintInfo>,
+    /// Unique, check, and exclusion constraints.
+    #[serde(default)]
+    pub constraints: Vec<ConstraintInfo>,
+    /// Foreign keys from this table.
+    #[serde(default)]
+    pub foreign_keys: Vec<ForeignKeyInfo>,
+    /// Foreign keys in other tables that point at this one.
+    #[serde(default)]
+    pub referenced_by: Vec<ForeignKeyInfo>,
+    #[serde(default)]
+    pub indexes: Vec<IndexInfo>,
+}
+
+/// Every table and view in a database (or one schema), from `neon.introspect`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Introspection {
+    pub database: String,
+    /// The schema the listing was limited to, if any.
+    #[serde(default)]
+    pub schema: Option<String>,
+    pub tables: Vec<TableDetail>,
+    pub views: Vec<ViewInfo>,
+}
+
+/// A column within a [`TableDetail`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableColumn {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+/// An index on a table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexInfo {
+    pub name: String,
+    /// Indexed columns or expressions, in order.
+    pub columns: Vec<String>,
+    pub unique: bool,
+    pub primary: bool,
+    /// Access method: `btree`, `gin`, `hnsw`, ...
+    pub method: String,
+    /// `WHERE` clause of a partial index.
+    #[serde(default)]
+    pub predicate: Option<String>,
+    pub definition: String,
+}
+
+/// A primary key, unique, check, or exclusion constraint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstraintInfo {
+    pub name: String,
+    /// `primary_key`, `unique`, `check`, or `exclusion`.
+    pub kind: String,
+    /// Constrained columns; empty for a check on an expression.
+    #[serde(default)]
+    pub columns: Vec<String>,
+    pub definition: String,
+}
+
+/// A foreign key; tables are `schema.table`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForeignKeyInfo {
+    pub name: String,
+    pub table: String,
     pub columns: Vec<String>,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+    /// `no action`, `restrict`, `cascade`, `set null`, or `set default`.
+    pub on_update: String,
+    pub on_delete: String,
+    pub definition: String,
+}
+
+/// A view or materialized view.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViewInfo {
+    pub schema: String,
+    pub name: String,
+    pub materialized: bool,
+    pub owner: String,
+    /// Whether a materialized view has been refreshed; `None` for views.
+    #[serde(default)]
+    pub populated: Option<bool>,
+    /// The defining `SELECT`.
+    pub definition: String,
+}
+
+/// A function, procedure, or aggregate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionInfo {
+    pub schema: String,
+    pub name: String,
+    /// `function`, `procedure`, `aggregate`, or `window`.
+    pub kind: String,
+    /// Argument list, as in `DROP FUNCTION`: `a integer, b text`.
+    pub arguments: String,
+    /// Return type; `None` for procedures.
+    #[serde(default)]
+    pub returns: Option<String>,
+    pub language: String,
+    /// `immutable`, `stable`, or `volatile`.
+    pub volatility: String,
+    pub security_definer: bool,
+    pub owner: String,
+}
+
+/// An installed extension.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtensionInfo {
+    pub name: String,
+    pub version: String,
+    pub schema: String,
+    /// Version `ALTER EXTENSION ... UPDATE` would move to.
+    #[serde(default)]
+    pub default_version: Option<String>,
+    pub update_available: bool,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// A database's size and its largest tables.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseSizes {
+    pub database: String,
+    pub size_bytes: u64,
+    /// `size_bytes` as Postgres prints it: `1234 MB`.
+    pub size: String,
+    /// User tables in the database, before `limit`.
+    pub table_count: u64,
+    pub tables: Vec<TableSize>,
+}
+
+/// Storage used by one table; `total_bytes` is the sum of the others.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableSize {
+    pub schema: String,
+    pub name: String,
+    pub total_bytes: u64,
+    /// The table's own pages, including free space and visibility maps.
+    pub table_bytes: u64,
+    pub index_bytes: u64,
+    pub toast_bytes: u64,
+    /// `total_bytes` as Postgres prints it.
+    pub total: String,
+    /// Planner estimate; -1 for a table never analyzed.
+    pub rows_estimate: i64,
+}
+
+/// A table privilege held by a role (or `PUBLIC`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableGrant {
+    pub schema: String,
+    pub table: String,
+    pub role: String,
+    /// `INSERT`, `UPDATE`, `DELETE`, `TRUNCATE`, ...
+    pub privilege: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// SQL query result, the same from either backend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryResult {
+    /// Command tag of the (last) statement, e.g. `SELECT` or `INSERT`.
+    #[serde(default)]
+    pub command: String,
+    #[serde(default)]
+    pub columns: Vec<QueryColumn>,
+    /// Rows as objects keyed by column name.
+    #[serde(default)]
+    pub rows: Vec<serde_json::Map<String, serde_json::Value>>,
+    /// Rows returned, or affected by a write.
+    #[serde(default)]
+    pub row_count: u64,
+    /// Output of `COPY ... TO STDOUT`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub copy: Option<String>,
+}
+
+/// A result column.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryColumn {
+    pub name: String,
+    /// Postgres type OID, when the backend reports it.
     #[serde(default)]
-    pub rows: Vec<Vec<serde_json::Value>>,
+    pub type_oid: Option<u32>,
+    /// Type name for built-in types, e.g. `int4` or `timestamptz`.
     #[serde(default)]
-    pub row_count: i64,
+    pub data_type: Option<String>,
 }
 
 /// Neon API list response wrapper.
@@ -0,0 +1,46 @@
+//! Event notifications (log + optional webhook).
+
+use reqwest::Client;
+use serde_json::Value;
+
+use crate::config::NotifyConfig;
+
+/// Delivers daemon events to the log and, if configured, a webhook.
+pub struct Notifier {
+    client: Client,
+    webhook_url: Option<String>,
+}
+
+impl Notifier {
+    /// Create a notifier from config.
+    pub fn new(config: &NotifyConfig) -> Self {
+        Self {
+            client: Client::new(),
+            webhook_url: config.webhook_url.clone(),
+        }
+    }
+
+    /// Publish an event. Delivery failures are logged, never returned.
+    pub async fn notify(&self, event: &str, data: Value) {
+        tracing::warn!(event, %data, "Notification");
+
+        let Some(url) = &self.webhook_url else {
+            return;
+        };
+
+        let body = serde_json::json!({
+            "service": "neon",
+            "event": event,
+            "at": chrono::Utc::now().to_rfc3339(),
+            "data": data,
+        });
+
+        match self.client.post(url).json(&body).send().await {
+            Ok(response) if !response.status().is_success() => {
+                tracing::warn!("Webhook returned {} for {}", response.status(), event);
+            }
+            Err(e) => tracing::warn!("Failed to deliver {} webhook: {}", event, e),
+            Ok(_) => {}
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Preview environment dashboard data.
+//!
+//! `neon.previews` flattens a project's branch tree, endpoint state, sizes,
+//! and ages into one row per preview branch. Boards are cached per project;
+//! when the operations watcher runs, it marks a project stale whenever a
+//! branch or endpoint changes, so only changed projects are fetched again.
+//! Without the watcher every call fetches.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::api::NeonClient;
+use crate::models::{Branch, Endpoint};
+
+/// Event kinds that change what a board shows.
+const INVALIDATING_KINDS: &[&str] = &[
+    "branch_created",
+    "branch_deleted",
+    "endpoint_started",
+    "endpoint_suspended",
+    "operation_finished",
+];
+
+/// One project's preview environments.
+#[derive(Debug, Clone, Serialize)]
+pub struct PreviewBoard {
+    pub project_id: String,
+    pub default_branch_id: Option<String>,
+    pub previews: Vec<Preview>,
+    pub refreshed_at: DateTime<Utc>,
+}
+
+/// A non-default branch and the compute serving it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Preview {
+    pub branch_id: String,
+    pub name: String,
+    /// Parsed from names like `preview/pr-123-feature` or `pr-123`.
+    pub pr_number: Option<u64>,
+    pub parent_id: Option<String>,
+    pub parent_name: Option<String>,
+    pub created_at: Option<String>,
+    pub age_hours: Option<f64>,
+    pub state: Option<String>,
+    pub endpoint_id: Option<String>,
+    pub endpoint_state: Option<String>,
+    pub host: Option<String>,
+    pub logical_size_bytes: Option<u64>,
+    pub written_data_bytes: Option<u64>,
+    pub compute_time_seconds: Option<u64>,
+}
+
+/// Cached boards, invalidated by the operations watcher.
+#[derive(Default)]
+pub struct PreviewCache {
+    boards: Mutex<HashMap<String, PreviewBoard>>,
+}
+
+impl PreviewCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A cached board, if one is held for the project, with ages brought up
+    /// to date.
+    pub fn get(&self, project_id: &str) -> Option<PreviewBoard> {
+        let mut board = self.boards.lock().unwrap().get(project_id).cloned()?;
+        let now = Utc::now();
+        for preview in &mut board.previews {
+            preview.age_hours = age_hours(preview.created_at.as_deref(), now);
+        }
+        Some(board)
+    }
+
+    pub fn store(&self, board: PreviewBoard) {
+        self.boards
+            .lock()
+            .unwrap()
+            .insert(board.project_id.clone(), board);
+    }
+
+    /// Drop a project's board if an event of this kind affects it.
+    pub fn invalidate(&self, project_id: &str, kind: &str) {
+        if INVALIDATING_KINDS.contains(&kind) {
+            self.boards.lock().unwrap().remove(project_id);
+        }
+    }
+}
+
+/// Fetch a project's branches and endpoints and build its board.
+pub async fn load(client: &NeonClient, project_id: &str) -> Result<PreviewBoard> {
+    let (branches, endpoints) = tokio::try_join!(
+        client.list_branches(project_id),
+        client.list_endpoints(project_id),
+    )?;
+    Ok(build(project_id, &branches, &endpoints, Utc::now()))
+}
+
+fn build(
+    project_id: &str,
+    branches: &[Branch],
+    endpoints: &[Endpoint],
+    now: DateTime<Utc>,
+) -> PreviewBoard {
+    let names: HashMap<&str, &str> = branches
+        .iter()
+        .map(|b| (b.id.as_str(), b.name.as_str()))
+        .collect();
+
+    let mut previews: Vec<Preview> = branches
+        .iter()
+        .filter(|b| !b.default)
+        .map(|branch| {
+            // Prefer the read-write endpoint when a branch has replicas too
+            let endpoint = endpoints
+                .iter()
+                .filter(|e| e.branch_id == branch.id)
+                .min_by_key(|e| e.endpoint_type != "read_write");
+
+            Preview {
+                branch_id: branch.id.clone(),
+                name: branch.name.clone(),
+                pr_number: pr_number(&branch.name),
+                parent_id: branch.parent_id.clone(),
+                parent_name: branch
+                    .parent_id
+                    .as_deref()
+                    .and_then(|id| names.get(id))
+                    .map(|n| n.to_string()),
+                created_at: branch.created_at.clone(),
+                age_hours: age_hours(branch.created_at.as_deref(), now),
+                state: branch.current_state.clone(),
+                endpoint_id: endpoint.map(|e| e.id.clone()),
+                endpoint_state: endpoint.and_then(|e| e.current_state.clone()),
+                host: endpoint.map(|e| e.host.clone()),
+                logical_size_bytes: branch.logical_size,
+                written_data_bytes: branch.written_data_bytes,
+                compute_time_seconds: branch.compute_time_seconds,
+            }
+        })
+        .collect();
+    previews.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+
+    PreviewBoard {
+        project_id: project_id.to_string(),
+        default_branch_id: branches.iter().find(|b| b.default).map(|b| b.id.clone()),
+        previews,
+        refreshed_at: now,
+    }
+}
+
+fn age_hours(created_at: Option<&str>, now: DateTime<Utc>) -> Option<f64> {
+    let created = DateTime::parse_from_rfc3339(created_at?).ok()?;
+    Some((now - created.with_timezone(&Utc)).num_minutes() as f64 / 60.0)
+}
+
+/// PR number from a branch name: digits following `pr-` or `pr` at the start
+/// of a `/`-separated segment, as in `preview/pr-123-feature`.
+fn pr_number(name: &str) -> Option<u64> {
+    name.split('/').find_map(|segment| {
+        let rest = segment
+            .strip_prefix("pr-")
+            .or_else(|| segment.strip_prefix("pr"))?;
+        let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+        digits.parse().ok()
+    })
+}
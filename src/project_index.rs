@@ -0,0 +1,365 @@
+//! Local project index for large orgs.
+//!
+//! Listing hundreds of projects through the API takes seconds, which is too
+//! slow for interactive lookups. The index keeps each project's name, id,
+//! region, and configured tags in memory and answers `neon.projects_search`
+//! from there. A full refresh pages through the project list and applies
+//! only what changed (new, updated, and deleted projects). With
+//! `[project_index] enabled` it runs in the background every
+//! `interval_secs`; otherwise a search refreshes an index older than that
+//! before answering.
+//!
+//! When the operations watcher is on, refreshes between full ones are
+//! incremental: only projects with new operations in the event feed are
+//! fetched again, and one whose `updated_at` and tags are unchanged is kept
+//! as is. A full refresh still runs every `full_refresh_secs`, on request,
+//! and whenever the feed dropped events the index hadn't read yet, to pick
+//! up projects the watcher doesn't cover and deleted projects.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::api::{ApiError, NeonClient};
+use crate::config::ProjectIndexConfig;
+use crate::models::Project;
+use crate::watch::{Event, EventBus};
+
+/// A project as the index holds it.
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexedProject {
+    pub id: String,
+    pub name: String,
+    pub region_id: Option<String>,
+    pub tags: Vec<String>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+    /// Lowercased name, for matching.
+    #[serde(skip)]
+    name_lower: String,
+    /// Lowercased id, region, and tags, for matching.
+    #[serde(skip)]
+    other_lower: String,
+}
+
+impl IndexedProject {
+    fn new(project: Project, tags: Vec<String>) -> Self {
+        let other_lower = format!(
+            "{} {} {}",
+            project.id,
+            project.region_id.as_deref().unwrap_or(""),
+            tags.join(" ")
+        )
+        .to_lowercase();
+
+        Self {
+            name_lower: project.name.to_lowercase(),
+            other_lower,
+            id: project.id,
+            name: project.name,
+            region_id: project.region_id,
+            tags,
+            created_at: project.created_at,
+            updated_at: project.updated_at,
+        }
+    }
+
+    /// How well `query` (lowercased) matches, or `None` for no match.
+    fn score(&self, query: &str) -> Option<u32> {
+        if query.is_empty() {
+            return Some(0);
+        }
+        if self.name_lower == query || self.id == query {
+            return Some(100);
+        }
+        if self.name_lower.starts_with(query) {
+            return Some(80);
+        }
+        if self.name_lower.contains(query) {
+            return Some(60);
+        }
+        if self.other_lower.contains(query) {
+            return Some(40);
+        }
+        fuzzy_score(&self.name_lower, query)
+    }
+}
+
+/// Score for `query`'s characters appearing in order in `text`, fewer gaps
+/// scoring higher; `None` when they don't all appear.
+fn fuzzy_score(text: &str, query: &str) -> Option<u32> {
+    let mut chars = text.chars().enumerate();
+    let mut last: Option<usize> = None;
+    let mut gaps = 0;
+    for q in query.chars() {
+        let (i, _) = chars.find(|(_, c)| *c == q)?;
+        if last.is_some_and(|l| i != l + 1) {
+            gaps += 1;
+        }
+        last = Some(i);
+    }
+    Some(30u32.saturating_sub(gaps * 3).max(1))
+}
+
+/// What a refresh changed.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RefreshStats {
+    /// Whether the whole project list was read, rather than only the
+    /// projects the operations feed touched.
+    pub full: bool,
+    /// Projects fetched one by one by an incremental refresh.
+    pub fetched: usize,
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub total: usize,
+}
+
+/// A search result.
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub project: IndexedProject,
+    pub score: u32,
+}
+
+#[derive(Default)]
+struct IndexState {
+    projects: HashMap<String, IndexedProject>,
+    refreshed_at: Option<DateTime<Utc>>,
+    /// When the project list was last read in full.
+    listed_at: Option<DateTime<Utc>>,
+    /// Last event sequence an incremental refresh has applied.
+    cursor: u64,
+}
+
+/// In-memory project index.
+pub struct ProjectIndex {
+    config: ProjectIndexConfig,
+    state: RwLock<IndexState>,
+    /// Held while refreshing so concurrent searches don't all list projects.
+    refreshing: tokio::sync::Mutex<()>,
+}
+
+impl ProjectIndex {
+    pub fn new(config: ProjectIndexConfig) -> Self {
+        Self {
+            config,
+            state: RwLock::new(IndexState::default()),
+            refreshing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Whether the index was never built or is older than `interval_secs`.
+    pub fn is_stale(&self) -> bool {
+        let max_age = chrono::Duration::seconds(self.config.interval_secs as i64);
+        match self.state.read().unwrap().refreshed_at {
+            Some(at) => Utc::now() - at > max_age,
+            None => true,
+        }
+    }
+
+    pub fn refreshed_at(&self) -> Option<DateTime<Utc>> {
+        self.state.read().unwrap().refreshed_at
+    }
+
+    /// Bring the index up to date: incrementally from `events` (the
+    /// operations feed, when the watcher runs) where possible, otherwise by
+    /// re-listing every project. `full` forces a re-list.
+    pub async fn refresh(
+        &self,
+        client: &NeonClient,
+        events: Option<&EventBus>,
+        full: bool,
+    ) -> Result<RefreshStats> {
+        let _guard = self.refreshing.lock().await;
+
+        if let Some(events) = events.filter(|_| !full && self.listed_recently()) {
+            let cursor = self.state.read().unwrap().cursor;
+            let feed = events.since(cursor);
+            // A gap means the buffer dropped events before they were read
+            let complete = feed.first().is_none_or(|e| e.seq == cursor + 1);
+            if complete {
+                return self.refresh_touched(client, &feed).await;
+            }
+        }
+        self.refresh_all(client, events.map_or(0, |e| e.head()))
+            .await
+    }
+
+    /// Whether the last full re-list is within `full_refresh_secs`.
+    fn listed_recently(&self) -> bool {
+        let max_age = chrono::Duration::seconds(self.config.full_refresh_secs as i64);
+        self.state
+            .read()
+            .unwrap()
+            .listed_at
+            .is_some_and(|at| Utc::now() - at <= max_age)
+    }
+
+    /// Fetch the projects with operations in `feed` and apply the differences.
+    async fn refresh_touched(&self, client: &NeonClient, feed: &[Event]) -> Result<RefreshStats> {
+        let mut touched: Vec<&str> = Vec::new();
+        for event in feed {
+            // SQL progress events don't change project metadata
+            let metadata = !matches!(event.kind.as_str(), "script_statement" | "import_progress");
+            if metadata && !touched.contains(&event.project_id.as_str()) {
+                touched.push(&event.project_id);
+            }
+        }
+
+        let mut fetched = Vec::with_capacity(touched.len());
+        for project_id in &touched {
+            match client.get_project(project_id).await {
+                Ok(project) => fetched.push(Ok(project)),
+                Err(e) if ApiError::is_not_found(&e) => fetched.push(Err(project_id.to_string())),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut state = self.state.write().unwrap();
+        let (mut added, mut updated, mut removed) = (0, 0, 0);
+        for result in fetched {
+            let project = match result {
+                Ok(project) => project,
+                Err(deleted) => {
+                    removed += usize::from(state.projects.remove(&deleted).is_some());
+                    continue;
+                }
+            };
+            let tags = self.tags(&project);
+            match state.projects.get(&project.id) {
+                Some(old) if old.updated_at == project.updated_at && old.tags == tags => {}
+                old => {
+                    if old.is_some() {
+                        updated += 1;
+                    } else {
+                        added += 1;
+                    }
+                    state
+                        .projects
+                        .insert(project.id.clone(), IndexedProject::new(project, tags));
+                }
+            }
+        }
+
+        state.cursor = feed.last().map_or(state.cursor, |e| e.seq);
+        state.refreshed_at = Some(Utc::now());
+        Ok(RefreshStats {
+            full: false,
+            fetched: touched.len(),
+            added,
+            updated,
+            removed,
+            total: state.projects.len(),
+        })
+    }
+
+    /// Re-list projects and apply the differences. `cursor` is the event
+    /// feed's head beforehand, which the listing covers.
+    async fn refresh_all(&self, client: &NeonClient, cursor: u64) -> Result<RefreshStats> {
+        let listed = client.list_all_projects().await?;
+
+        let mut state = self.state.write().unwrap();
+        let mut projects = HashMap::with_capacity(listed.len());
+        let (mut added, mut updated) = (0, 0);
+        for project in listed {
+            let tags = self.tags(&project);
+            match state.projects.remove(&project.id) {
+                Some(old) if old.updated_at == project.updated_at && old.tags == tags => {
+                    projects.insert(old.id.clone(), old);
+                }
+                Some(_) => {
+                    updated += 1;
+                    projects.insert(project.id.clone(), IndexedProject::new(project, tags));
+                }
+                None => {
+                    added += 1;
+                    projects.insert(project.id.clone(), IndexedProject::new(project, tags));
+                }
+            }
+        }
+
+        let stats = RefreshStats {
+            full: true,
+            fetched: 0,
+            added,
+            updated,
+            removed: state.projects.len(),
+            total: projects.len(),
+        };
+        state.projects = projects;
+        state.refreshed_at = Some(Utc::now());
+        state.listed_at = state.refreshed_at;
+        state.cursor = cursor;
+        Ok(stats)
+    }
+
+    /// Projects matching `query` by id, name, region, or tag, best first.
+    /// `region` and `tag` filter exactly.
+    pub fn search(
+        &self,
+        query: &str,
+        region: Option<&str>,
+        tag: Option<&str>,
+        limit: usize,
+    ) -> (Vec<SearchHit>, usize) {
+        let query = query.trim().to_lowercase();
+        let state = self.state.read().unwrap();
+
+        let mut hits: Vec<SearchHit> = state
+            .projects
+            .values()
+            .filter(|p| region.is_none_or(|r| p.region_id.as_deref() == Some(r)))
+            .filter(|p| tag.is_none_or(|t| p.tags.iter().any(|pt| pt == t)))
+            .filter_map(|p| {
+                p.score(&query).map(|score| SearchHit {
+                    project: p.clone(),
+                    score,
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.project.name.cmp(&b.project.name))
+        });
+        hits.truncate(limit);
+        (hits, state.projects.len())
+    }
+
+    /// Configured tags for a project, by id or else by name.
+    fn tags(&self, project: &Project) -> Vec<String> {
+        self.config
+            .tags
+            .get(&project.id)
+            .or_else(|| self.config.tags.get(&project.name))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Refresh the index every `interval_secs` forever, incrementally from
+/// `events` when the operations watcher feeds it.
+pub async fn run(index: Arc<ProjectIndex>, client: Arc<NeonClient>, events: Option<Arc<EventBus>>) {
+    let interval = Duration::from_secs(index.config.interval_secs.max(30));
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+        match index.refresh(&client, events.as_deref(), false).await {
+            Ok(stats) => tracing::debug!(
+                "Project index refreshed ({}): {} added, {} updated, {} removed, {} total",
+                if stats.full { "full" } else { "incremental" },
+                stats.added,
+                stats.updated,
+                stats.removed,
+                stats.total
+            ),
+            Err(e) => tracing::warn!("Project index refresh failed: {:#}", e),
+        }
+    }
+}
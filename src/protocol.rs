@@ -0,0 +1,182 @@
+//! Socket protocol versions.
+//!
+//! Every request line carries the protocol version it was written for in
+//! `v`. A client may open its connection with a `hello` request naming the
+//! versions and optional features it understands; the relay answers it
+//! directly with the highest version both sides speak and the features both
+//! support, and holds the connection to them. A client that never sends
+//! `hello` gets version 1 with no optional features, which is exactly what
+//! the daemon spoke before negotiation existed, so old clients keep working.
+//! Requests for a version the daemon doesn't speak are refused with the
+//! supported range rather than passed on to be misread.
+
+use serde_json::Value;
+
+/// Oldest version the daemon still speaks.
+pub const MIN_VERSION: u64 = 1;
+/// Newest version the daemon speaks.
+pub const MAX_VERSION: u64 = 1;
+
+/// Several requests may be in flight on one connection, answered as they
+/// complete rather than in order. Without it, a connection's requests are
+/// handled one at a time.
+pub const PIPELINING: &str = "pipelining";
+
+/// Optional features the daemon supports.
+pub const FEATURES: &[&str] = &[PIPELINING];
+
+/// What a connection agreed to.
+#[derive(Debug, Clone)]
+pub struct Negotiated {
+    pub version: u64,
+    pub features: Vec<String>,
+}
+
+impl Negotiated {
+    pub fn pipelining(&self) -> bool {
+        self.features.iter().any(|f| f == PIPELINING)
+    }
+}
+
+impl Default for Negotiated {
+    fn default() -> Self {
+        Self {
+            version: MIN_VERSION,
+            features: Vec::new(),
+        }
+    }
+}
+
+/// Whether a request is a `hello` handshake.
+pub fn is_hello(request: &Value) -> bool {
+    matches!(
+        request.get("method").and_then(|m| m.as_str()),
+        Some("hello" | "neon.hello")
+    )
+}
+
+/// Settle a `hello` request's params (`min_version`, `max_version`,
+/// `features`) against what the daemon supports.
+pub fn negotiate(params: &Value) -> Result<Negotiated, String> {
+    let version = |key: &str| match params.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => v
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| format!("{} must be a positive integer", key)),
+    };
+    let max = version("max_version")?;
+    let min = version("min_version")?.or(max).unwrap_or(MIN_VERSION);
+    let max = max.unwrap_or(min);
+    if min > max {
+        return Err("min_version is above max_version".to_string());
+    }
+    if max < MIN_VERSION || min > MAX_VERSION {
+        return Err(format!(
+            "No common protocol version: client speaks {}-{}, daemon speaks {}-{}",
+            min, max, MIN_VERSION, MAX_VERSION
+        ));
+    }
+
+    let features = match params.get("features") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|f| f.as_str())
+            .filter(|f| FEATURES.contains(f))
+            .map(|f| f.to_string())
+            .collect(),
+        Some(_) => return Err("features must be an array of strings".to_string()),
+    };
+
+    Ok(Negotiated {
+        version: max.min(MAX_VERSION),
+        features,
+    })
+}
+
+/// The `hello` result describing a negotiation.
+pub fn hello_result(negotiated: &Negotiated) -> Value {
+    serde_json::json!({
+        "protocol": negotiated.version,
+        "features": negotiated.features,
+        "supported": {
+            "min_version": MIN_VERSION,
+            "max_version": MAX_VERSION,
+            "features": FEATURES,
+        },
+        "daemon_version": env!("CARGO_PKG_VERSION"),
+    })
+}
+
+/// Check a request's `v` against the connection's version. A request
+/// without `v` is taken to be written for the negotiated version.
+pub fn check_version(request: &Value, negotiated: &Negotiated) -> Result<(), String> {
+    match request.get("v") {
+        None | Some(Value::Null) => Ok(()),
+        Some(v) => match v.as_u64() {
+            Some(v) if v == negotiated.version => Ok(()),
+            Some(v) if (MIN_VERSION..=MAX_VERSION).contains(&v) => Err(format!(
+                "Request is for protocol {} but this connection negotiated {}",
+                v, negotiated.version
+            )),
+            _ => Err(format!(
+                "Unsupported protocol version {}; the daemon speaks {}-{} (send hello to negotiate)",
+                v, MIN_VERSION, MAX_VERSION
+            )),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn hello_without_params_gets_the_oldest_version() {
+        let negotiated = negotiate(&json!({})).unwrap();
+        assert_eq!(negotiated.version, MIN_VERSION);
+        assert!(negotiated.features.is_empty());
+    }
+
+    #[test]
+    fn highest_common_version_wins() {
+        let negotiated = negotiate(&json!({"min_version": 1, "max_version": 9})).unwrap();
+        assert_eq!(negotiated.version, MAX_VERSION);
+    }
+
+    #[test]
+    fn no_common_version_is_refused() {
+        let err =
+            negotiate(&json!({"min_version": MAX_VERSION + 1, "max_version": MAX_VERSION + 3}))
+                .unwrap_err();
+        assert!(err.starts_with("No common protocol version"), "{}", err);
+    }
+
+    #[test]
+    fn bad_version_ranges_are_refused() {
+        assert!(negotiate(&json!({"min_version": 2, "max_version": 1})).is_err());
+        assert!(negotiate(&json!({"max_version": "1"})).is_err());
+        assert!(negotiate(&json!({"min_version": -1})).is_err());
+    }
+
+    #[test]
+    fn only_supported_features_are_agreed() {
+        let negotiated = negotiate(&json!({"features": [PIPELINING, "compression", 3]})).unwrap();
+        assert_eq!(negotiated.features, [PIPELINING]);
+        assert!(negotiated.pipelining());
+        assert!(negotiate(&json!({"features": PIPELINING})).is_err());
+    }
+
+    #[test]
+    fn requests_must_match_the_negotiated_version() {
+        let negotiated = Negotiated::default();
+        assert!(check_version(&json!({"method": "neon.sql"}), &negotiated).is_ok());
+        assert!(check_version(&json!({"v": MIN_VERSION}), &negotiated).is_ok());
+
+        let err = check_version(&json!({"v": MAX_VERSION + 1}), &negotiated).unwrap_err();
+        assert!(err.starts_with("Unsupported protocol version"), "{}", err);
+        assert!(check_version(&json!({"v": "1"}), &negotiated).is_err());
+    }
+}
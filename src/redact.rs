@@ -0,0 +1,101 @@
+//! Secret redaction for SQL results.
+//!
+//! Application tables sometimes hold credentials (API keys, connection
+//! strings, tokens). Before a result leaves the daemon, every string cell is
+//! scanned against built-in and configured patterns and matches are replaced
+//! with `[REDACTED:<name>]`. Callers can opt out per call with
+//! `allow_secrets: true` unless the config forbids it.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::config::RedactionConfig;
+
+/// Patterns checked whenever redaction is enabled.
+const BUILTIN_PATTERNS: &[(&str, &str)] = &[
+    (
+        "connection_string",
+        r#"(?i)\b(?:postgres(?:ql)?|mysql|mongodb(?:\+srv)?|redis)://[^\s:@/]+:[^\s@/]+@[^\s'"]+"#,
+    ),
+    ("neon_api_key", r"\bnapi_[A-Za-z0-9]{20,}"),
+    ("aws_access_key", r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b"),
+    ("github_token", r"\bgh[pousr]_[A-Za-z0-9]{36,}"),
+    ("stripe_key", r"\b[rs]k_live_[A-Za-z0-9]{16,}"),
+    ("slack_token", r"\bxox[abprs]-[A-Za-z0-9-]{10,}"),
+    ("api_key", r"\bsk-[A-Za-z0-9_-]{20,}"),
+    (
+        "jwt",
+        r"\beyJ[A-Za-z0-9_-]{10,}\.[A-Za-z0-9_-]{10,}\.[A-Za-z0-9_-]{10,}",
+    ),
+    (
+        "private_key",
+        r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?(?:-----END [A-Z ]*PRIVATE KEY-----|$)",
+    ),
+];
+
+/// Compiled redaction patterns.
+pub struct Redactor {
+    enabled: bool,
+    allow_override: bool,
+    patterns: Vec<(String, Regex)>,
+}
+
+impl Redactor {
+    /// Compile built-in and configured patterns, failing on an invalid regex.
+    pub fn new(config: &RedactionConfig) -> Result<Self> {
+        let mut patterns = Vec::new();
+        for (name, pattern) in BUILTIN_PATTERNS {
+            patterns.push((name.to_string(), Regex::new(pattern)?));
+        }
+        for custom in &config.patterns {
+            let regex = Regex::new(&custom.pattern)
+                .with_context(|| format!("Invalid redaction pattern {:?}", custom.name))?;
+            patterns.push((custom.name.clone(), regex));
+        }
+
+        Ok(Self {
+            enabled: config.enabled,
+            allow_override: config.allow_override,
+            patterns,
+        })
+    }
+
+    /// Whether results should be redacted, given the caller's `allow_secrets`.
+    pub fn applies(&self, allow_secrets: bool) -> Result<bool> {
+        if allow_secrets && !self.allow_override {
+            anyhow::bail!("allow_secrets is disabled by [redaction] allow_override = false");
+        }
+        Ok(self.enabled && !allow_secrets)
+    }
+
+    /// Mask secrets in a SQL result's rows. Returns the number of cells changed.
+    pub fn redact(&self, result: &mut Value) -> usize {
+        match result.get_mut("rows") {
+            Some(rows) => self.redact_value(rows),
+            None => 0,
+        }
+    }
+
+    /// Mask secrets in every string of a value, such as rows outside a SQL
+    /// result. Returns the number of cells changed.
+    pub fn redact_value(&self, value: &mut Value) -> usize {
+        match value {
+            Value::String(s) => {
+                let mut changed = false;
+                for (name, regex) in &self.patterns {
+                    if regex.is_match(s) {
+                        *s = regex
+                            .replace_all(s, format!("[REDACTED:{}]", name).as_str())
+                            .into_owned();
+                        changed = true;
+                    }
+                }
+                usize::from(changed)
+            }
+            Value::Array(items) => items.iter_mut().map(|v| self.redact_value(v)).sum(),
+            Value::Object(map) => map.values_mut().map(|v| self.redact_value(v)).sum(),
+            _ => 0,
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Markdown rendering of results.
+//!
+//! Most callers are agents that turn JSON results into Markdown before
+//! showing them, so any call may pass `render: "markdown"` to get a compact
+//! rendering of `data` in the envelope's `rendered` field. Lists and SQL rows
+//! become a table, a single object a field/value table. Long cells are cut
+//! short and nested values are written as compact JSON, keeping tables small
+//! enough for a context window. With `render_only: true`, `data` is dropped
+//! and only the rendering is returned.
+//!
+//! SQL results and `neon.tables` also take `format` (`csv`, `markdown`, or
+//! `json`), which replaces the result's rows with that representation.
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Longest cell, in characters, before it's cut short.
+const MAX_CELL_CHARS: usize = 120;
+
+/// A call's rendering options.
+pub struct Render {
+    only: bool,
+}
+
+impl Render {
+    /// The call's `render` option, if it passed one.
+    pub fn from_params(params: &HashMap<String, Value>) -> Result<Option<Self>> {
+        match params.get("render") {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(format)) if format == "markdown" => Ok(Some(Self {
+                only: params
+                    .get("render_only")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false),
+            })),
+            Some(_) => anyhow::bail!("render must be \"markdown\""),
+        }
+    }
+
+    /// Add the rendering of an envelope's `data` to it.
+    pub fn apply(&self, response: &mut Value) {
+        let Some(obj) = response.as_object_mut() else {
+            return;
+        };
+        let rendered = obj.get("data").map(markdown).unwrap_or_default();
+        obj.insert("rendered".into(), Value::String(rendered));
+        if self.only {
+            obj.insert("data".into(), Value::Null);
+        }
+    }
+}
+
+/// Output format for a SQL result's rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Markdown,
+    /// Just the rows, as the response's `data`.
+    Json,
+}
+
+impl Format {
+    /// The call's `format`, if it passed one.
+    pub fn from_params(params: &HashMap<String, Value>) -> Result<Option<Self>> {
+        match params.get("format").and_then(|v| v.as_str()) {
+            None => Ok(None),
+            Some("csv") => Ok(Some(Self::Csv)),
+            Some("markdown") => Ok(Some(Self::Markdown)),
+            Some("json") => Ok(Some(Self::Json)),
+            Some(_) => anyhow::bail!("format must be \"csv\", \"markdown\", or \"json\""),
+        }
+    }
+
+    /// Replace a SQL result's `rows` and `columns` with this format: the
+    /// text in `output` for CSV and Markdown, or the bare rows for JSON.
+    /// Other fields (row counts, pagination) are kept.
+    pub fn apply(self, mut result: Value) -> Value {
+        let Some(obj) = result.as_object_mut() else {
+            return result;
+        };
+        let rows = match obj.remove("rows") {
+            Some(Value::Array(rows)) => rows,
+            _ => Vec::new(),
+        };
+        let columns = match obj.remove("columns") {
+            Some(Value::Array(columns)) => columns
+                .iter()
+                .filter_map(|c| c.get("name").and_then(|n| n.as_str()))
+                .map(|n| n.to_string())
+                .collect(),
+            _ => Vec::new(),
+        };
+        let columns = if columns.is_empty() {
+            object_keys(&rows)
+        } else {
+            columns
+        };
+
+        let output = match self {
+            Self::Csv => csv(&columns, &rows),
+            Self::Markdown => table(&columns, &rows),
+            Self::Json => {
+                // A list result, which the envelope unwraps to the bare rows
+                obj.insert("count".into(), serde_json::json!(rows.len()));
+                obj.insert("rows".into(), Value::Array(rows));
+                return result;
+            }
+        };
+        obj.insert("format".into(), serde_json::json!(self.name()));
+        obj.insert("output".into(), Value::String(output));
+        result
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Markdown => "markdown",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// RFC 4180 CSV of `rows` under `columns`, with a header line.
+fn csv(columns: &[String], rows: &[Value]) -> String {
+    fn field(text: &str) -> String {
+        if text.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        } else {
+            text.to_string()
+        }
+    }
+
+    let mut out = String::new();
+    let header: Vec<String> = columns.iter().map(|c| field(c)).collect();
+    out.push_str(&header.join(","));
+    out.push_str("\r\n");
+    for row in rows {
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|c| match row.get(c) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(s)) => field(s),
+                Some(other) => field(&other.to_string()),
+            })
+            .collect();
+        out.push_str(&cells.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// Render a value as compact Markdown.
+pub fn markdown(data: &Value) -> String {
+    match data {
+        // A SQL result: its rows, in column order
+        Value::Object(obj) if obj.get("rows").is_some_and(|r| r.is_array()) => {
+            let rows = obj["rows"].as_array().map(Vec::as_slice).unwrap_or(&[]);
+            let named: Vec<String> = obj
+                .get("columns")
+                .and_then(|c| c.as_array())
+                .map(|columns| {
+                    columns
+                        .iter()
+                        .filter_map(|c| c.get("name").and_then(|n| n.as_str()))
+                        .map(|n| n.to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+            let columns = if named.is_empty() {
+                object_keys(rows)
+            } else {
+                named
+            };
+            table(&columns, rows)
+        }
+        Value::Object(obj) => fields_table(obj),
+        Value::Array(items) if items.iter().all(|i| i.is_object()) => {
+            table(&object_keys(items), items)
+        }
+        Value::Array(items) => items
+            .iter()
+            .map(|i| format!("- {}", cell(i)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        other => cell(other),
+    }
+}
+
+/// A Markdown table of `rows` (objects) under `columns`.
+pub fn table(columns: &[String], rows: &[Value]) -> String {
+    if rows.is_empty() {
+        return "_No rows._".to_string();
+    }
+    if columns.is_empty() {
+        return format!("_{} empty row(s)._", rows.len());
+    }
+
+    let mut out = String::new();
+    let header: Vec<String> = columns.iter().map(|c| escape(c)).collect();
+    out.push_str(&format!("| {} |\n", header.join(" | ")));
+    out.push_str(&format!("|{}\n", " --- |".repeat(columns.len())));
+    for row in rows {
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|c| row.get(c).map(cell).unwrap_or_default())
+            .collect();
+        out.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    out.truncate(out.trim_end().len());
+    out
+}
+
+/// A two-column field/value table of an object.
+fn fields_table(obj: &Map<String, Value>) -> String {
+    if obj.is_empty() {
+        return "_Empty._".to_string();
+    }
+    let mut out = String::from("| field | value |\n| --- | --- |\n");
+    for (key, value) in obj {
+        out.push_str(&format!("| {} | {} |\n", escape(key), cell(value)));
+    }
+    out.truncate(out.trim_end().len());
+    out
+}
+
+/// Keys of a list of objects, in first-seen order.
+fn object_keys(rows: &[Value]) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    for key in rows
+        .iter()
+        .filter_map(|r| r.as_object())
+        .flat_map(|o| o.keys())
+    {
+        if !keys.contains(key) {
+            keys.push(key.clone());
+        }
+    }
+    keys
+}
+
+/// One value as table cell text.
+fn cell(value: &Value) -> String {
+    let text = match value {
+        Value::Null => return String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let text = if text.chars().count() > MAX_CELL_CHARS {
+        let cut: String = text.chars().take(MAX_CELL_CHARS - 1).collect();
+        format!("{}…", cut)
+    } else {
+        text
+    };
+    escape(&text)
+}
+
+/// Text made safe for a table cell.
+fn escape(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
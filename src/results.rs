@@ -0,0 +1,252 @@
+//! Large SQL results handed out in chunks.
+//!
+//! With `stream: true`, `neon.sql` runs the query for its first chunk only
+//! and returns those rows plus a `next_cursor`; `neon.fetch_chunk` runs it
+//! again for each following chunk with the next `LIMIT`/`OFFSET` window. Only
+//! the query is kept in the daemon, never its rows, and each chunk's
+//! response is its own, so `max_response_bytes` applies per chunk. A query
+//! is dropped once its last chunk is fetched, when it hasn't been read for
+//! `streaming.ttl_secs`, or when newer streams push it out of the store.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::api::SqlTarget;
+use crate::config::{SqlBackend, StreamingConfig};
+
+/// A streamed query, re-run once per chunk.
+#[derive(Debug, Clone)]
+pub struct StreamedQuery {
+    pub project_id: String,
+    pub branch_id: String,
+    pub database: String,
+    pub statement: String,
+    pub params: Vec<Value>,
+    pub backend: Option<SqlBackend>,
+    pub target: SqlTarget,
+    pub role: Option<String>,
+    pub read_only: bool,
+    /// Whether chunks are redacted.
+    pub redact: bool,
+}
+
+struct Stored {
+    query: StreamedQuery,
+    chunk_size: usize,
+    /// Most rows the whole stream returns.
+    max_rows: usize,
+    /// Offset of the next chunk's first row.
+    next_offset: usize,
+    rows_sent: usize,
+    /// Chunks handed out so far.
+    sent: usize,
+    expires: Instant,
+}
+
+/// The next chunk of a stream: which query to run, and its row window.
+pub struct Chunk {
+    pub id: String,
+    pub index: usize,
+    pub query: StreamedQuery,
+    pub offset: usize,
+    pub max_rows: usize,
+}
+
+/// Streamed queries waiting for their next chunk, by result id.
+pub struct ResultStore {
+    config: StreamingConfig,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    next_id: u64,
+    results: HashMap<String, Stored>,
+}
+
+impl ResultStore {
+    pub fn new(config: StreamingConfig) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(Inner {
+                next_id: 1,
+                results: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Default rows per chunk.
+    pub fn chunk_size(&self) -> usize {
+        self.config.chunk_size
+    }
+
+    /// Most rows a streamed query returns.
+    pub fn max_rows(&self) -> usize {
+        self.config.max_rows
+    }
+
+    /// Take `first`, the result of `query`'s first chunk, run from `offset`.
+    /// When more rows follow, keep the query and add `result_id` and a
+    /// `next_cursor` to the result.
+    pub fn start(
+        &self,
+        query: StreamedQuery,
+        first: Value,
+        offset: usize,
+        chunk_size: usize,
+        max_rows: usize,
+    ) -> Value {
+        let stored = Stored {
+            query,
+            chunk_size: chunk_size.max(1),
+            max_rows,
+            next_offset: offset,
+            rows_sent: 0,
+            sent: 0,
+            expires: Instant::now() + Duration::from_secs(self.config.ttl_secs),
+        };
+        let mut inner = self.inner.lock().unwrap();
+        let id = format!("res_{}", inner.next_id);
+        inner.next_id += 1;
+        inner.evict(self.config.max_results.saturating_sub(1));
+        inner.results.insert(id.clone(), stored);
+        inner.advance(&id, first, &self.config)
+    }
+
+    /// The chunk a `next_cursor` points at.
+    pub fn claim(&self, cursor: &str) -> Result<Chunk> {
+        let (id, index) = parse_cursor(cursor)?;
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.evict(usize::MAX);
+        let Some(stored) = inner.results.get(id) else {
+            anyhow::bail!(
+                "Result {} has expired or was fully fetched; run the query again",
+                id
+            );
+        };
+        // Each chunk starts where the previous one ended
+        if index != stored.sent {
+            anyhow::bail!(
+                "Expected chunk {} of result {}, got {}; chunks must be fetched in order",
+                stored.sent,
+                id,
+                index
+            );
+        }
+
+        Ok(Chunk {
+            id: id.to_string(),
+            index,
+            query: stored.query.clone(),
+            offset: stored.next_offset,
+            max_rows: stored
+                .chunk_size
+                .min(stored.max_rows.saturating_sub(stored.rows_sent)),
+        })
+    }
+
+    /// Record a fetched chunk and return it with its `next_cursor`.
+    pub fn finish(&self, chunk: &Chunk, result: Value) -> Result<Value> {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.results.get(&chunk.id) {
+            Some(stored) if stored.sent == chunk.index => {}
+            _ => anyhow::bail!(
+                "Chunk {} of result {} was already fetched",
+                chunk.index,
+                chunk.id
+            ),
+        }
+        Ok(inner.advance(&chunk.id, result, &self.config))
+    }
+}
+
+impl Inner {
+    /// Count a chunk's rows against its stream, dropping the stream after
+    /// its last chunk, and label the chunk for the caller.
+    fn advance(&mut self, id: &str, mut result: Value, config: &StreamingConfig) -> Value {
+        let Some(stored) = self.results.get_mut(id) else {
+            return result;
+        };
+        let Some(obj) = result.as_object_mut() else {
+            self.results.remove(id);
+            return result;
+        };
+
+        // Paging fields describe this chunk's LIMIT, not the stream
+        let more = obj.remove("truncated").and_then(|t| t.as_bool()) == Some(true);
+        let next_offset = obj.remove("next_offset").and_then(|o| o.as_u64());
+        obj.remove("continuation");
+        let rows = obj
+            .get("rows")
+            .and_then(|r| r.as_array())
+            .map_or(0, |r| r.len());
+
+        let index = stored.sent;
+        stored.sent += 1;
+        stored.rows_sent += rows;
+        stored.next_offset = next_offset.map_or(stored.next_offset + rows, |o| o as usize);
+        stored.expires = Instant::now() + Duration::from_secs(config.ttl_secs);
+        let at_limit = stored.rows_sent >= stored.max_rows;
+        let rows_sent = stored.rows_sent;
+
+        obj.insert("result_id".into(), serde_json::json!(id));
+        obj.insert("chunk".into(), serde_json::json!(index));
+        obj.insert("rows_sent".into(), serde_json::json!(rows_sent));
+        if more && !at_limit && rows > 0 {
+            obj.insert(
+                "next_cursor".into(),
+                serde_json::json!(cursor(id, index + 1)),
+            );
+        } else {
+            self.results.remove(id);
+            obj.insert("next_cursor".into(), Value::Null);
+            if more {
+                obj.insert("truncated".into(), serde_json::json!(true));
+                obj.insert(
+                    "continuation".into(),
+                    serde_json::json!(format!(
+                        "Stream stopped at {} rows ([streaming] max_rows or the call's max_rows).",
+                        rows_sent
+                    )),
+                );
+            }
+        }
+        result
+    }
+
+    /// Drop expired streams, then the oldest until at most `keep` remain.
+    fn evict(&mut self, keep: usize) {
+        let now = Instant::now();
+        self.results.retain(|_, r| r.expires > now);
+        while self.results.len() > keep {
+            let Some(oldest) = self
+                .results
+                .iter()
+                .min_by_key(|(_, r)| r.expires)
+                .map(|(id, _)| id.clone())
+            else {
+                break;
+            };
+            self.results.remove(&oldest);
+        }
+    }
+}
+
+fn cursor(id: &str, chunk: usize) -> String {
+    format!("{}:{}", id, chunk)
+}
+
+fn parse_cursor(cursor: &str) -> Result<(&str, usize)> {
+    cursor
+        .rsplit_once(':')
+        .and_then(|(id, chunk)| Some((id, chunk.parse().ok()?)))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid cursor {:?}; pass a next_cursor from neon.sql",
+                cursor
+            )
+        })
+}
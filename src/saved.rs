@@ -0,0 +1,99 @@
+//! Saved query library.
+//!
+//! Named, vetted SQL templates stored in `~/.fgp/services/neon/saved_queries.json`
+//! and executed by name through `neon.run_saved`. Queries use the same `:name`
+//! placeholders as `neon.sql_template`.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::config::{check_state_name, state_path};
+
+/// A stored query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedQuery {
+    pub name: String,
+    pub query: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Database used when the caller doesn't pass one.
+    #[serde(default)]
+    pub database: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Load all saved queries, keyed by name.
+pub fn load() -> Result<BTreeMap<String, SavedQuery>> {
+    let path = store_path();
+    match std::fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Look up one saved query.
+pub fn get(name: &str) -> Result<SavedQuery> {
+    load()?
+        .remove(name)
+        .ok_or_else(|| anyhow::anyhow!("No saved query named {:?}", name))
+}
+
+/// Create or replace a saved query, keeping its original creation time.
+pub fn save(
+    name: &str,
+    query: &str,
+    description: Option<String>,
+    database: Option<String>,
+) -> Result<SavedQuery> {
+    check_state_name("saved query name", name)?;
+
+    let mut queries = load()?;
+    let now = Utc::now();
+    let created_at = queries.get(name).map(|q| q.created_at).unwrap_or(now);
+    let saved = SavedQuery {
+        name: name.to_string(),
+        query: query.to_string(),
+        description,
+        database,
+        created_at,
+        updated_at: now,
+    };
+
+    queries.insert(name.to_string(), saved.clone());
+    write(&queries)?;
+    Ok(saved)
+}
+
+/// Delete a saved query. Returns whether it existed.
+pub fn delete(name: &str) -> Result<bool> {
+    let mut queries = load()?;
+    let existed = queries.remove(name).is_some();
+    if existed {
+        write(&queries)?;
+    }
+    Ok(existed)
+}
+
+fn write(queries: &BTreeMap<String, SavedQuery>) -> Result<()> {
+    let path = store_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create state directory")?;
+    }
+
+    // Write then rename so a crash never leaves a half-written library
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(queries)?)
+        .with_context(|| format!("Failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, &path).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+fn store_path() -> PathBuf {
+    state_path("saved_queries.json")
+}
@@ -0,0 +1,491 @@
+//! Schema snapshot parsing and comparison.
+//!
+//! Expected schemas arrive either as a [`SchemaSnapshot`] or as DDL. The DDL
+//! reader only understands `CREATE TABLE` column definitions, which is what
+//! validation compares; other statements are ignored.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{ColumnDef, SchemaSnapshot, TableDef};
+use crate::sqltext;
+
+/// Differences between an expected and an actual schema.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchemaDiff {
+    pub missing_tables: Vec<String>,
+    pub extra_tables: Vec<String>,
+    pub missing_columns: Vec<ColumnRef>,
+    pub extra_columns: Vec<ColumnRef>,
+    pub mismatched_columns: Vec<ColumnMismatch>,
+}
+
+/// A column identified by its table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnRef {
+    pub table: String,
+    pub column: String,
+}
+
+/// A column present on both sides with a different definition.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnMismatch {
+    pub table: String,
+    pub column: String,
+    pub expected: ColumnDef,
+    pub actual: ColumnDef,
+}
+
+impl SchemaDiff {
+    /// Whether the two schemas matched.
+    pub fn is_empty(&self) -> bool {
+        self.missing_tables.is_empty()
+            && self.extra_tables.is_empty()
+            && self.missing_columns.is_empty()
+            && self.extra_columns.is_empty()
+            && self.mismatched_columns.is_empty()
+    }
+}
+
+/// Compare `actual` against `expected`.
+///
+/// Extra tables are only reported for schemas that appear in `expected`, so
+/// objects owned by extensions in their own schemas don't show up as noise.
+pub fn diff(expected: &SchemaSnapshot, actual: &SchemaSnapshot) -> SchemaDiff {
+    compare(expected, actual, true)
+}
+
+/// Compare two snapshots of the same database taken at different times.
+///
+/// Unlike [`diff`], every new table counts, whatever schema it lives in.
+pub fn drift(previous: &SchemaSnapshot, current: &SchemaSnapshot) -> SchemaDiff {
+    compare(previous, current, false)
+}
+
+fn compare(expected: &SchemaSnapshot, actual: &SchemaSnapshot, scoped: bool) -> SchemaDiff {
+    let mut result = SchemaDiff::default();
+
+    let expected_schemas: Vec<&str> = expected
+        .tables
+        .keys()
+        .filter_map(|k| k.split_once('.').map(|(schema, _)| schema))
+        .collect();
+
+    for (name, expected_table) in &expected.tables {
+        let Some(actual_table) = actual.tables.get(name) else {
+            result.missing_tables.push(name.clone());
+            continue;
+        };
+
+        for (column, expected_col) in &expected_table.columns {
+            match actual_table.columns.get(column) {
+                None => result.missing_columns.push(ColumnRef {
+                    table: name.clone(),
+                    column: column.clone(),
+                }),
+                Some(actual_col)
+                    if normalize_type(&actual_col.data_type)
+                        != normalize_type(&expected_col.data_type)
+                        || actual_col.nullable != expected_col.nullable =>
+                {
+                    result.mismatched_columns.push(ColumnMismatch {
+                        table: name.clone(),
+                        column: column.clone(),
+                        expected: expected_col.clone(),
+                        actual: actual_col.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        for column in actual_table.columns.keys() {
+            if !expected_table.columns.contains_key(column) {
+                result.extra_columns.push(ColumnRef {
+                    table: name.clone(),
+                    column: column.clone(),
+                });
+            }
+        }
+    }
+
+    for name in actual.tables.keys() {
+        let in_scope = !scoped
+            || name
+                .split_once('.')
+                .map(|(schema, _)| expected_schemas.contains(&schema))
+                .unwrap_or(false);
+        if in_scope && !expected.tables.contains_key(name) {
+            result.extra_tables.push(name.clone());
+        }
+    }
+
+    result
+}
+
+/// Build a snapshot from the `CREATE TABLE` statements in a DDL script.
+///
+/// `CREATE TABLE ... AS` and `PARTITION OF` have no column list to read and
+/// are skipped along with every other statement.
+pub fn parse_ddl(ddl: &str) -> Result<SchemaSnapshot> {
+    let mut snapshot = SchemaSnapshot::default();
+
+    for statement in sqltext::split_statements(ddl) {
+        let statement = strip_comments(&statement);
+        let Some((name, rest)) = create_table(&statement).and_then(split_table_name) else {
+            continue;
+        };
+        let body = rest.trim_start();
+        if !body.starts_with('(') {
+            continue;
+        }
+        let close = matching_paren(body, 0)
+            .ok_or_else(|| anyhow::anyhow!("Unbalanced parentheses in CREATE TABLE {}", name))?;
+
+        let (schema, table) = split_qualified(name);
+        let table_def = parse_table_body(&body[1..close]);
+        snapshot
+            .tables
+            .insert(format!("{}.{}", schema, table), table_def);
+    }
+
+    Ok(snapshot)
+}
+
+/// The rest of a `CREATE [GLOBAL|LOCAL] [TEMP|TEMPORARY|UNLOGGED] TABLE
+/// [IF NOT EXISTS]` statement after those keywords.
+fn create_table(statement: &str) -> Option<&str> {
+    let mut rest = keyword(statement, "create")?;
+    if let Some(after) = keyword(rest, "global").or_else(|| keyword(rest, "local")) {
+        rest = after;
+    }
+    if let Some(after) = ["temporary", "temp", "unlogged"]
+        .iter()
+        .find_map(|kw| keyword(rest, kw))
+    {
+        rest = after;
+    }
+    rest = keyword(rest, "table")?;
+    if let Some(after) = keyword(rest, "if")
+        .and_then(|r| keyword(r, "not"))
+        .and_then(|r| keyword(r, "exists"))
+    {
+        rest = after;
+    }
+    Some(rest)
+}
+
+/// `text` after a leading `word`, matched case-insensitively as a whole word.
+fn keyword<'a>(text: &'a str, word: &str) -> Option<&'a str> {
+    let text = text.trim_start();
+    let head = text.get(..word.len())?;
+    let after = &text[word.len()..];
+    let whole = !after.bytes().next().is_some_and(is_ident_byte);
+    (head.eq_ignore_ascii_case(word) && whole).then_some(after)
+}
+
+/// Split a table name, qualified and quoted or not, off the front of `text`.
+fn split_table_name(text: &str) -> Option<(&str, &str)> {
+    let text = text.trim_start();
+    let mut end = 0;
+    loop {
+        let part = &text[end..];
+        let len = if part.starts_with('"') {
+            quoted_ident_len(part)?
+        } else {
+            part.bytes()
+                .take_while(|&b| is_ident_byte(b) || b == b'$')
+                .count()
+        };
+        if len == 0 {
+            return None;
+        }
+        end += len;
+        if !text[end..].starts_with('.') {
+            return Some((&text[..end], &text[end..]));
+        }
+        end += 1;
+    }
+}
+
+/// Length of the quoted identifier at the start of `text`, quotes included.
+fn quoted_ident_len(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '"' {
+            if chars.peek().map(|&(_, n)| n) != Some('"') {
+                return Some(i + 1);
+            }
+            chars.next();
+        }
+    }
+    None
+}
+
+/// Parse the column list of a CREATE TABLE statement.
+fn parse_table_body(body: &str) -> TableDef {
+    let mut table = TableDef::default();
+    let mut primary_key: Vec<String> = Vec::new();
+
+    for item in split_top_level(body, ',') {
+        let item = item.trim();
+        let lower = item.to_ascii_lowercase();
+
+        if lower.starts_with("primary key") {
+            if let (Some(open), Some(close)) = (item.find('('), item.rfind(')')) {
+                primary_key.extend(
+                    item[open + 1..close]
+                        .split(',')
+                        .map(|c| unquote_ident(c.trim())),
+                );
+            }
+            continue;
+        }
+        if [
+            "constraint",
+            "unique",
+            "check",
+            "foreign key",
+            "exclude",
+            "like",
+        ]
+        .iter()
+        .any(|kw| lower.starts_with(kw))
+        {
+            continue;
+        }
+
+        let (name, rest) = split_ident(item);
+        if name.is_empty() {
+            continue;
+        }
+
+        let rest_lower = rest.to_ascii_lowercase();
+        let type_end = COLUMN_CONSTRAINTS
+            .iter()
+            .filter_map(|kw| find_keyword(&rest_lower, kw))
+            .min()
+            .unwrap_or(rest.len());
+        let data_type = rest[..type_end].trim().to_string();
+        // Serial and identity columns are implicitly NOT NULL
+        let serial = matches!(
+            data_type.to_ascii_lowercase().as_str(),
+            "serial" | "serial2" | "serial4" | "serial8" | "smallserial" | "bigserial"
+        );
+        let nullable = !serial
+            && find_keyword(&rest_lower, "as identity").is_none()
+            && find_keyword(&rest_lower, "not null").is_none()
+            && find_keyword(&rest_lower, "primary key").is_none();
+
+        table.columns.insert(
+            name,
+            ColumnDef {
+                data_type,
+                nullable,
+            },
+        );
+    }
+
+    for column in primary_key {
+        if let Some(col) = table.columns.get_mut(&column) {
+            col.nullable = false;
+        }
+    }
+
+    table
+}
+
+/// Keywords that end the type portion of a column definition.
+const COLUMN_CONSTRAINTS: &[&str] = &[
+    "not null",
+    "null",
+    "default",
+    "primary key",
+    "references",
+    "unique",
+    "check",
+    "constraint",
+    "generated",
+    "collate",
+];
+
+/// Normalize a Postgres type name so DDL aliases compare equal to `format_type` output.
+pub fn normalize_type(data_type: &str) -> String {
+    let lower = data_type
+        .trim()
+        .to_ascii_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let (lower, array_suffix) = match lower.find("[]") {
+        Some(i) => (lower[..i].trim().to_string(), "[]"),
+        None => (lower, ""),
+    };
+
+    // `timestamp(3) without time zone` keeps its modifiers mid-name
+    let (base, args) = match (lower.find('('), lower.find(')')) {
+        (Some(open), Some(close)) if close > open => (
+            format!("{} {}", lower[..open].trim(), lower[close + 1..].trim())
+                .trim()
+                .to_string(),
+            lower[open..=close].replace(' ', ""),
+        ),
+        _ => (lower.clone(), String::new()),
+    };
+
+    let base = match base.as_str() {
+        "int" | "int4" | "serial" | "serial4" => "integer",
+        "int8" | "bigserial" | "serial8" => "bigint",
+        "int2" | "smallserial" | "serial2" => "smallint",
+        "varchar" => "character varying",
+        "char" | "bpchar" => "character",
+        "bool" => "boolean",
+        "float8" | "float" => "double precision",
+        "float4" => "real",
+        "decimal" => "numeric",
+        "timestamptz" => "timestamp with time zone",
+        "timestamp" => "timestamp without time zone",
+        "timetz" => "time with time zone",
+        "time" => "time without time zone",
+        other => other,
+    };
+
+    format!("{}{}{}", base, args, array_suffix)
+}
+
+/// Split `schema.table` into parts, defaulting the schema to `public`.
+fn split_qualified(name: &str) -> (String, String) {
+    let parts = split_top_level(name, '.');
+    match parts.as_slice() {
+        [schema, table] => (unquote_ident(schema.trim()), unquote_ident(table.trim())),
+        _ => ("public".to_string(), unquote_ident(name.trim())),
+    }
+}
+
+/// Split the leading identifier off a column definition.
+fn split_ident(item: &str) -> (String, &str) {
+    if let Some(stripped) = item.strip_prefix('"') {
+        if let Some(end) = stripped.find('"') {
+            return (stripped[..end].to_string(), &stripped[end + 1..]);
+        }
+    }
+    match item.find(char::is_whitespace) {
+        Some(i) => (item[..i].to_ascii_lowercase(), &item[i..]),
+        None => (item.to_ascii_lowercase(), ""),
+    }
+}
+
+/// Unquote an identifier, folding unquoted names to lower case like Postgres does.
+fn unquote_ident(ident: &str) -> String {
+    match ident.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(inner) => inner.replace("\"\"", "\""),
+        None => ident.to_ascii_lowercase(),
+    }
+}
+
+/// Find a keyword at a word boundary.
+fn find_keyword(haystack: &str, keyword: &str) -> Option<usize> {
+    let bytes = haystack.as_bytes();
+    let mut start = 0;
+    while let Some(i) = haystack[start..].find(keyword) {
+        let at = start + i;
+        let end = at + keyword.len();
+        let before_ok = at == 0 || !is_ident_byte(bytes[at - 1]);
+        let after_ok = end >= bytes.len() || !is_ident_byte(bytes[end]);
+        if before_ok && after_ok {
+            return Some(at);
+        }
+        start = at + 1;
+    }
+    None
+}
+
+fn is_ident_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+/// Split on `sep` outside parentheses and quotes.
+fn split_top_level(text: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut last = 0;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            '\'' if !in_double => in_single = !in_single,
+            '"' if !in_single => in_double = !in_double,
+            '(' if !in_single && !in_double => depth += 1,
+            ')' if !in_single && !in_double => depth -= 1,
+            c if c == sep && depth == 0 && !in_single && !in_double => {
+                parts.push(&text[last..i]);
+                last = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[last..]);
+    parts
+}
+
+/// Index of the parenthesis closing the one at `open`.
+fn matching_paren(text: &str, open: usize) -> Option<usize> {
+    let mut depth = 0i32;
+    let mut in_single = false;
+    let mut in_double = false;
+
+    for (i, c) in text[open..].char_indices() {
+        match c {
+            '\'' if !in_double => in_single = !in_single,
+            '"' if !in_single => in_double = !in_double,
+            '(' if !in_single && !in_double => depth += 1,
+            ')' if !in_single && !in_double => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Remove `--` line comments and `/* */` block comments.
+fn strip_comments(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut in_single = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_single = !in_single;
+                out.push(c);
+            }
+            '-' if !in_single && chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            '/' if !in_single && chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                out.push(' ');
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
@@ -0,0 +1,224 @@
+//! Multi-statement SQL scripts.
+//!
+//! A script is split into statements (see [`sqltext::split_script`]) and run
+//! one statement at a time, since the SQL endpoint takes one per request.
+//! Each finished statement is published as a `script_statement` event, so a
+//! caller can follow a long script through `neon.watch` (the event's
+//! `operation_id` is the run id). A failing statement is reported by number,
+//! line, and text, along with how many statements before it were applied.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use std::time::Instant;
+
+use crate::api::{quote_ident, result_rows, NeonClient};
+use crate::sqltext::{self, Statement};
+use crate::watch::{Event, EventBus, StatementProgress};
+
+/// Characters of a statement quoted in errors and results.
+const SNIPPET_LEN: usize = 120;
+
+/// Database a new project starts with, where setup SQL runs by default.
+pub const DEFAULT_DATABASE: &str = "neondb";
+
+/// Where a script runs.
+pub struct ScriptTarget<'a> {
+    pub project_id: &'a str,
+    pub branch_id: &'a str,
+    pub database: &'a str,
+}
+
+/// One statement's outcome.
+#[derive(Debug, Serialize)]
+pub struct StatementResult {
+    /// 1-based position in the script.
+    pub index: usize,
+    pub line: usize,
+    pub statement: String,
+    pub duration_ms: f64,
+    pub row_count: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// A script run's outcome.
+#[derive(Debug, Serialize)]
+pub struct ScriptOutcome {
+    pub run_id: String,
+    pub total: usize,
+    pub executed: usize,
+    pub failed: usize,
+    pub statements: Vec<StatementResult>,
+}
+
+/// Split a script file's text, failing on a script with no statements.
+pub fn parse(script: &str) -> Result<Vec<Statement>> {
+    let statements = sqltext::split_script(script);
+    if statements.is_empty() {
+        anyhow::bail!("Script contains no statements");
+    }
+    Ok(statements)
+}
+
+/// Read and split the script file at `path` (`~` expanded), described as
+/// `what` in errors. Returns the expanded path with the statements. Setup
+/// calls read their scripts before changing anything, so a bad path fails
+/// before anything is created.
+pub fn read(path: &str, what: &str) -> Result<(String, Vec<Statement>)> {
+    let path = shellexpand::tilde(path).to_string();
+    let script = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {} {}", what, path))?;
+    Ok((path, sqltext::split_script(&script)))
+}
+
+/// Create `extensions` in the target database, leaving ones already there.
+pub async fn create_extensions(
+    client: &NeonClient,
+    target: &ScriptTarget<'_>,
+    extensions: &[String],
+) -> Result<()> {
+    for extension in extensions {
+        let statement = format!("CREATE EXTENSION IF NOT EXISTS {}", quote_ident(extension));
+        client
+            .run_sql(
+                target.project_id,
+                target.branch_id,
+                target.database,
+                &statement,
+            )
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to create extension {} in {}",
+                    extension, target.database
+                )
+            })?;
+    }
+    Ok(())
+}
+
+/// Run `statements` in order. Stops at the first failure unless
+/// `continue_on_error`, in which case failures are recorded and skipped.
+pub async fn run(
+    client: &NeonClient,
+    events: Option<&EventBus>,
+    target: &ScriptTarget<'_>,
+    run_id: &str,
+    statements: &[Statement],
+    continue_on_error: bool,
+) -> Result<ScriptOutcome> {
+    let total = statements.len();
+    let mut outcome = ScriptOutcome {
+        run_id: run_id.to_string(),
+        total,
+        executed: 0,
+        failed: 0,
+        statements: Vec::with_capacity(total),
+    };
+
+    for (i, statement) in statements.iter().enumerate() {
+        let started = Instant::now();
+        let result = client
+            .run_sql(
+                target.project_id,
+                target.branch_id,
+                target.database,
+                &statement.text,
+            )
+            .await;
+        let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let error = result.as_ref().err().map(|e| format!("{:#}", e));
+        if let Some(events) = events {
+            events.publish(vec![progress_event(
+                target,
+                run_id,
+                i + 1,
+                total,
+                statement,
+                duration_ms,
+                error.clone(),
+            )]);
+        }
+
+        outcome.executed += 1;
+        let row_count = result.as_ref().ok().map(|r| {
+            r.get("row_count")
+                .and_then(|c| c.as_u64())
+                .unwrap_or(result_rows(r).len() as u64)
+        });
+        outcome.statements.push(StatementResult {
+            index: i + 1,
+            line: statement.line,
+            statement: snippet(&statement.text),
+            duration_ms,
+            row_count,
+            error,
+        });
+
+        if let Err(e) = result {
+            outcome.failed += 1;
+            if !continue_on_error {
+                return Err(e).with_context(|| {
+                    format!(
+                        "Statement {} of {} (line {}) failed after {} statement(s) were applied: {}",
+                        i + 1,
+                        total,
+                        statement.line,
+                        i,
+                        snippet(&statement.text)
+                    )
+                });
+            }
+        }
+    }
+
+    Ok(outcome)
+}
+
+fn progress_event(
+    target: &ScriptTarget<'_>,
+    run_id: &str,
+    index: usize,
+    total: usize,
+    statement: &Statement,
+    duration_ms: f64,
+    error: Option<String>,
+) -> Event {
+    let status = if error.is_some() {
+        "failed"
+    } else if index == total {
+        "finished"
+    } else {
+        "running"
+    };
+
+    Event {
+        seq: 0,
+        at: Utc::now(),
+        kind: "script_statement".into(),
+        project_id: target.project_id.to_string(),
+        operation_id: run_id.to_string(),
+        action: "sql_script".into(),
+        status: status.into(),
+        branch_id: Some(target.branch_id.to_string()),
+        endpoint_id: None,
+        error,
+        statement: Some(StatementProgress {
+            index,
+            total,
+            line: statement.line,
+            duration_ms,
+        }),
+        import: None,
+    }
+}
+
+/// The start of a statement on one line.
+fn snippet(text: &str) -> String {
+    let flat: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match flat.char_indices().nth(SNIPPET_LEN) {
+        Some((cut, _)) => format!("{}...", &flat[..cut]),
+        None => flat,
+    }
+}
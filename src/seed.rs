@@ -0,0 +1,85 @@
+//! Seed data for a branch.
+//!
+//! `neon.seed` runs a seed file, or every `.sql` file in a directory in file
+//! name order (`01_users.sql`, `02_orders.sql`), as one script, so a fresh
+//! branch can be filled the same way every time. With `truncate`, the tables
+//! the seed inserts into are emptied first by a single
+//! `TRUNCATE ... RESTART IDENTITY`, so rerunning a seed gives the same rows
+//! and ids rather than duplicates.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::path::Path;
+
+use crate::sqltext::{self, Statement};
+
+/// A seed's statements and the files they came from.
+pub struct Seed {
+    pub files: Vec<String>,
+    pub statements: Vec<Statement>,
+}
+
+/// Read a seed file, or the `.sql` files in a directory in name order.
+pub fn load(path: &str) -> Result<Seed> {
+    let path = shellexpand::tilde(path).to_string();
+    let files = if Path::new(&path).is_dir() {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&path)
+            .with_context(|| format!("Failed to read seed directory {}", path))?
+        {
+            let file = entry?.path();
+            if file.extension().and_then(|e| e.to_str()) == Some("sql") {
+                files.push(file.to_string_lossy().into_owned());
+            }
+        }
+        files.sort();
+        if files.is_empty() {
+            anyhow::bail!("Seed directory {} has no .sql files", path);
+        }
+        files
+    } else {
+        vec![path]
+    };
+
+    let mut statements = Vec::new();
+    for file in &files {
+        let text = std::fs::read_to_string(file)
+            .with_context(|| format!("Failed to read seed file {}", file))?;
+        statements.extend(sqltext::split_script(&text));
+    }
+    if statements.is_empty() {
+        anyhow::bail!("Seed contains no statements");
+    }
+
+    Ok(Seed { files, statements })
+}
+
+/// Tables the seed inserts into, as written and in first-use order.
+pub fn tables(statements: &[Statement]) -> Vec<String> {
+    let insert = Regex::new(
+        r#"(?is)^(?:\s+|--[^\n]*|/\*.*?\*/)*insert\s+into\s+((?:"(?:[^"]|"")+"|[\w$]+)(?:\s*\.\s*(?:"(?:[^"]|"")+"|[\w$]+))?)"#,
+    )
+    .expect("valid regex");
+
+    let mut tables: Vec<String> = Vec::new();
+    for statement in statements {
+        if let Some(name) = insert.captures(&statement.text).map(|c| c[1].to_string()) {
+            if !tables.contains(&name) {
+                tables.push(name);
+            }
+        }
+    }
+    tables
+}
+
+/// The statement emptying `tables` before a seed; `None` without tables.
+/// Fails rather than cascading if another table references one of them.
+pub fn truncate(tables: &[String]) -> Option<Statement> {
+    if tables.is_empty() {
+        return None;
+    }
+    Some(Statement {
+        text: format!("TRUNCATE {} RESTART IDENTITY", tables.join(", ")),
+        line: 0,
+    })
+}
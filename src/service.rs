@@ -1,33 +1,285 @@
 //! FGP service implementation for Neon.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use fgp_daemon::service::{HealthStatus, MethodInfo, ParamInfo};
 use fgp_daemon::FgpService;
+use futures_util::StreamExt;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
 use std::sync::Arc;
 use tokio::runtime::Runtime;
+use tracing::Instrument;
 
-use crate::api::NeonClient;
+use crate::api::{
+    is_ddl, is_pageable, IsolationLevel, NeonClient, SqlPage, SqlTarget, TablePage, TxOptions,
+    TxStatement,
+};
+use crate::bootstrap;
+use crate::branch_tags::{self, BranchTags, TagFilter, TagIndex, TagUpdate};
+use crate::cache::ResponseCache;
+use crate::canary::{self, CanaryProbes};
+use crate::clients::ClientRegistry;
+use crate::compare;
+use crate::config::{unknown_preset, Config, SqlBackend};
+use crate::costguard::CostGuard;
+use crate::credentials::Credentials;
+use crate::datadiff;
+use crate::drift;
+use crate::dump::{self, DumpFormat, DumpOptions};
+use crate::envelope;
+use crate::envs;
+use crate::ephemeral;
+use crate::fields::Fields;
+use crate::freeze;
+use crate::health::{self, Level, Ping, PingCache, Subsystem};
+use crate::history::{self, History, HistoryEntry};
+use crate::idempotency::IdempotencyStore;
+use crate::import::{self, ColumnMap, CsvImport};
+use crate::leases;
+use crate::masking::Masker;
+use crate::migrate;
+use crate::models::{
+    Branch, CreateProjectRequest, Endpoint, ProjectBranchRequest, RestoreBranchRequest,
+    SchemaSnapshot, UpdateProjectRequest,
+};
+use crate::notify::Notifier;
+use crate::previews::{self, PreviewCache};
+use crate::project_index::{self, ProjectIndex};
+use crate::redact::Redactor;
+use crate::render::{Format, Render};
+use crate::results::{ResultStore, StreamedQuery};
+use crate::saved;
+use crate::schema;
+use crate::script::{self, ScriptTarget};
+use crate::seed;
+use crate::snapshots;
+use crate::spec::{self, ApplyOptions, Spec};
+use crate::sqltext;
+use crate::statsd;
+use crate::supervisor::Supervisor;
+use crate::templates;
+use crate::trace;
+use crate::transform::Transforms;
+use crate::update::{self, UpdateChecker};
+use crate::usage;
+use crate::warnings;
+use crate::watch::{self, EventBus};
+
+/// Most queries `neon.sql_batch` runs at once.
+const MAX_BATCH_CONCURRENCY: usize = 8;
+
+/// Rows `neon.data_diff` fetches per side by default to compare key by key.
+const DATA_DIFF_MAX_ROWS: u64 = 100_000;
+
+/// Longest `expires_in_hours` `neon.mint_token` accepts: a year.
+const MAX_TOKEN_HOURS: u64 = 8760;
 
 /// FGP service for Neon operations.
 pub struct NeonService {
     client: Arc<NeonClient>,
     runtime: Runtime,
+    /// Runs SQL, so long queries don't hold up API and metadata calls.
+    sql_runtime: Runtime,
+    config: Config,
+    notifier: Arc<Notifier>,
+    events: Arc<EventBus>,
+    history: Arc<History>,
+    previews: Arc<PreviewCache>,
+    updates: Arc<UpdateChecker>,
+    supervisor: Arc<Supervisor>,
+    clients: Arc<ClientRegistry>,
+    redactor: Arc<Redactor>,
+    masker: Arc<Masker>,
+    idempotency: IdempotencyStore,
+    cache: ResponseCache,
+    ping: PingCache,
+    results: ResultStore,
+    project_index: Arc<ProjectIndex>,
+    canary: Arc<CanaryProbes>,
 }
 
 impl NeonService {
-    /// Create a new NeonService with the given API key and org_id.
-    pub fn new(api_key: String, org_id: String) -> Result<Self> {
-        let client = NeonClient::new(api_key, org_id)?;
-        let runtime = Runtime::new()?;
+    /// Create a new NeonService with the given API key, org_id, and config.
+    pub fn new(credentials: Credentials, org_id: String, config: Config) -> Result<Self> {
+        let client = NeonClient::new(&credentials, org_id, &config)?;
+        let runtime = build_runtime(
+            "neon-worker",
+            config.runtime.worker_threads,
+            config.runtime.max_blocking_threads,
+        )?;
+        let sql_runtime = build_runtime(
+            "neon-sql",
+            config.runtime.sql_worker_threads,
+            config.runtime.max_blocking_threads,
+        )?;
+        let notifier = Notifier::new(&config.notify);
+        let clients = ClientRegistry::new(config.clients.clone());
+        let redactor = Redactor::new(&config.redaction)?;
+        let masker = Masker::new(&config.masking)?;
+        let idempotency = IdempotencyStore::new(config.idempotency.clone());
+        let cache = ResponseCache::new(config.cache.clone());
+        let ping = PingCache::new(&config.health);
+        let results = ResultStore::new(config.streaming.clone());
+        let project_index = ProjectIndex::new(config.project_index.clone());
 
         Ok(Self {
             client: Arc::new(client),
             runtime,
+            sql_runtime,
+            config,
+            notifier: Arc::new(notifier),
+            events: Arc::new(EventBus::new()),
+            history: Arc::new(History::open()),
+            previews: Arc::new(PreviewCache::new()),
+            updates: Arc::new(UpdateChecker::new()?),
+            supervisor: Arc::new(Supervisor::new()),
+            clients: Arc::new(clients),
+            redactor: Arc::new(redactor),
+            masker: Arc::new(masker),
+            idempotency,
+            cache,
+            ping,
+            results,
+            project_index: Arc::new(project_index),
+            canary: Arc::new(CanaryProbes::default()),
         })
     }
 
+    /// Connection registry shared with the client relay.
+    pub fn clients(&self) -> Arc<ClientRegistry> {
+        self.clients.clone()
+    }
+
+    /// Start background jobs enabled in config, each under the supervisor.
+    fn spawn_background_tasks(&self) {
+        if !self.config.drift.targets.is_empty() {
+            tracing::info!(
+                "Schema drift detection enabled for {} target(s)",
+                self.config.drift.targets.len()
+            );
+            let (client, config, notifier) = (
+                self.client.clone(),
+                self.config.drift.clone(),
+                self.notifier.clone(),
+            );
+            self.runtime
+                .spawn(self.supervisor.clone().supervise("drift", move || {
+                    drift::run(client.clone(), config.clone(), notifier.clone())
+                }));
+        }
+
+        let client = self.client.clone();
+        self.runtime.spawn(
+            self.supervisor
+                .clone()
+                .supervise("leases", move || leases::run(client.clone())),
+        );
+
+        let client = self.client.clone();
+        self.runtime.spawn(
+            self.supervisor
+                .clone()
+                .supervise("ephemeral", move || ephemeral::run(client.clone())),
+        );
+
+        if self.config.project_index.enabled {
+            let (index, client) = (self.project_index.clone(), self.client.clone());
+            let events = self.config.watch.enabled.then(|| self.events.clone());
+            self.runtime
+                .spawn(self.supervisor.clone().supervise("project_index", move || {
+                    project_index::run(index.clone(), client.clone(), events.clone())
+                }));
+        }
+
+        if self.config.usage.enabled && !self.config.usage.thresholds.is_empty() {
+            tracing::info!(
+                "Usage alerts enabled for {} threshold(s)",
+                self.config.usage.thresholds.len()
+            );
+            let (client, config, notifier) = (
+                self.client.clone(),
+                self.config.usage.clone(),
+                self.notifier.clone(),
+            );
+            self.runtime
+                .spawn(self.supervisor.clone().supervise("usage", move || {
+                    usage::run(client.clone(), config.clone(), notifier.clone())
+                }));
+        }
+
+        if self.config.update_check.enabled {
+            let (updates, config) = (self.updates.clone(), self.config.update_check.clone());
+            self.runtime
+                .spawn(self.supervisor.clone().supervise("update_check", move || {
+                    update::run(updates.clone(), config.clone())
+                }));
+        }
+
+        if let Some(config) = &self.config.health.canary {
+            tracing::info!("Canary probes enabled for branch {}", config.branch_id);
+            let (client, config, probes) =
+                (self.client.clone(), config.clone(), self.canary.clone());
+            self.runtime
+                .spawn(self.supervisor.clone().supervise("canary", move || {
+                    canary::run(client.clone(), config.clone(), probes.clone())
+                }));
+        }
+
+        if self.config.statsd.enabled {
+            tracing::info!("StatsD export enabled to {}", self.config.statsd.address);
+            let config = self.config.statsd.clone();
+            let sources = Arc::new(statsd::Sources {
+                client: self.client.clone(),
+                clients: self.clients.clone(),
+                supervisor: self.supervisor.clone(),
+                canary: self.canary.clone(),
+            });
+            self.runtime
+                .spawn(self.supervisor.clone().supervise("statsd", move || {
+                    statsd::run(config.clone(), sources.clone())
+                }));
+        }
+
+        if self.config.watch.enabled {
+            tracing::info!("Operations watcher enabled");
+            let (client, config, events, notifier, previews) = (
+                self.client.clone(),
+                self.config.watch.clone(),
+                self.events.clone(),
+                self.notifier.clone(),
+                self.previews.clone(),
+            );
+            self.runtime
+                .spawn(self.supervisor.clone().supervise("watch", move || {
+                    watch::run(
+                        client.clone(),
+                        config.clone(),
+                        events.clone(),
+                        notifier.clone(),
+                        previews.clone(),
+                    )
+                }));
+        }
+    }
+
+    /// Run a SQL future on the SQL runtime's workers, keeping the call's
+    /// trace id and log span, and wait for it. `block_on` alone would poll it
+    /// on the calling thread instead.
+    fn on_sql_runtime<T: Send + 'static>(
+        &self,
+        future: impl Future<Output = T> + Send + 'static,
+    ) -> T {
+        let future = trace::carry(future).instrument(tracing::Span::current());
+        match self.sql_runtime.block_on(self.sql_runtime.spawn(future)) {
+            Ok(output) => output,
+            Err(e) => match e.try_into_panic() {
+                Ok(panic) => std::panic::resume_unwind(panic),
+                Err(e) => panic!("SQL task didn't finish: {}", e),
+            },
+        }
+    }
+
     /// Helper to get a i32 parameter with default.
     fn get_param_i32(params: &HashMap<String, Value>, key: &str, default: i32) -> i32 {
         params
@@ -42,30 +294,204 @@ impl NeonService {
         params.get(key).and_then(|v| v.as_str())
     }
 
+    /// Helper to get a required string parameter.
+    fn require_param_str(params: &HashMap<String, Value>, key: &str) -> Result<String> {
+        Self::get_param_str(params, key)
+            .map(|s| s.to_string())
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: {}", key))
+    }
+
+    /// Helper to get the named-values object for a SQL template.
+    fn named_params(params: &HashMap<String, Value>) -> Result<serde_json::Map<String, Value>> {
+        match params.get("params") {
+            Some(Value::Object(values)) => Ok(values.clone()),
+            Some(_) => anyhow::bail!("params must be an object of named values"),
+            None => Ok(serde_json::Map::new()),
+        }
+    }
+
+    /// Ping the Neon API, reusing a recent answer.
+    fn ping(&self) -> Ping {
+        self.ping.get(|| {
+            let client = self.client.clone();
+            self.runtime.block_on(async move { client.ping().await })
+        })
+    }
+
     /// Health check implementation.
     fn health(&self) -> Result<Value> {
-        let client = self.client.clone();
-        let ok = self.runtime.block_on(async move { client.ping().await })?;
+        let ping = self.ping();
+        let subsystems = self.subsystems(&ping);
 
         Ok(serde_json::json!({
-            "status": if ok { "healthy" } else { "unhealthy" },
-            "api_connected": ok,
+            "status": health::overall(&subsystems),
+            "api_connected": ping.result == Ok(true),
+            "subsystems": subsystems,
+            "credentials": self.client.auth_status(),
             "version": env!("CARGO_PKG_VERSION"),
+            "update_available": self.updates.last().update_available,
+            "canary": self.canary.last(),
+            "cached": ping.cached,
         }))
     }
 
+    /// Status of each subsystem: the API (from `ping`), credentials, the
+    /// response cache, background jobs, the operations watcher, the Postgres
+    /// pool, and the data plane (from the canary's latest probes).
+    fn subsystems(&self, ping: &Ping) -> BTreeMap<String, Subsystem> {
+        let config = &self.config.health;
+        let mut subsystems = BTreeMap::new();
+
+        let mut api = Subsystem::healthy();
+        match &ping.result {
+            Ok(true) if ping.latency_ms > config.slow_api_ms as f64 => api.report(
+                Level::Degraded,
+                format!("slow: {:.0}ms to answer", ping.latency_ms),
+            ),
+            Ok(true) => {}
+            Ok(false) => api.report(Level::Unhealthy, "API returned error"),
+            Err(e) => api.report(Level::Unhealthy, e.clone()),
+        }
+        subsystems.insert("neon_api".to_string(), api);
+
+        let mut credentials = Subsystem::healthy();
+        let auth = self.client.auth_status();
+        if let Some(at) = auth.rejected_at {
+            credentials.report(
+                Level::Unhealthy,
+                format!("rejected by the Neon API (401) since {}", at.to_rfc3339()),
+            );
+        }
+        match auth.expires_in_minutes {
+            Some(minutes) if minutes < 0 => credentials.report(
+                Level::Unhealthy,
+                format!("{} token expired {} minutes ago", auth.source, -minutes),
+            ),
+            Some(minutes) if minutes < config.expiry_warning_mins as i64 => credentials.report(
+                Level::Degraded,
+                format!("{} token expires in {} minutes", auth.source, minutes),
+            ),
+            _ => {}
+        }
+        subsystems.insert("credentials".to_string(), credentials);
+
+        let mut cache = Subsystem::healthy();
+        let (live, max_entries) = self.cache.usage();
+        if self.cache.enabled() && live >= max_entries {
+            cache.report(
+                Level::Degraded,
+                format!("full ({} entries); new results aren't cached", live),
+            );
+        }
+        subsystems.insert("cache".to_string(), cache);
+
+        // Jobs that are restarting after an exit; the others keep running
+        let tasks = self.supervisor.tasks();
+        let mut jobs = Subsystem::healthy();
+        let mut watcher = Subsystem::healthy();
+        for (name, task) in &tasks {
+            if task.running {
+                continue;
+            }
+            let reason = format!(
+                "{} restarting after {} restart(s); last exit: {}",
+                name,
+                task.restarts,
+                task.last_exit.as_deref().unwrap_or("unknown")
+            );
+            if name == "watch" {
+                watcher.report(Level::Unhealthy, reason);
+            } else {
+                jobs.report(Level::Degraded, reason);
+            }
+        }
+        let others = tasks.keys().filter(|name| *name != "watch").count();
+        if others > 0 && jobs.reasons.len() == others {
+            jobs.status = Level::Unhealthy;
+        }
+        subsystems.insert("scheduler".to_string(), jobs);
+        if self.config.watch.enabled {
+            subsystems.insert("watcher".to_string(), watcher);
+        }
+
+        let mut pool = Subsystem::healthy();
+        let pg = self.client.pool_health();
+        if pg.consecutive_failures > 0 {
+            let level = if pg.consecutive_failures >= config.pool_failures {
+                Level::Unhealthy
+            } else {
+                Level::Degraded
+            };
+            pool.report(
+                level,
+                format!(
+                    "{} failed connection(s) in a row; last: {}",
+                    pg.consecutive_failures,
+                    pg.last_error.as_deref().unwrap_or("unknown")
+                ),
+            );
+        }
+        subsystems.insert("pool".to_string(), pool);
+
+        if self.config.health.canary.is_some() {
+            subsystems.insert(
+                "data_plane".to_string(),
+                canary::subsystem(&self.canary.last(), config.slow_sql_ms),
+            );
+        }
+
+        subsystems
+    }
+
     /// List projects implementation.
     fn list_projects(&self, params: HashMap<String, Value>) -> Result<Value> {
         let limit = Self::get_param_i32(&params, "limit", 10);
+        let cursor = Self::get_param_str(&params, "cursor").map(|s| s.to_string());
+        let all = params.get("all").and_then(|v| v.as_bool()).unwrap_or(false);
         let client = self.client.clone();
 
-        let projects = self
-            .runtime
-            .block_on(async move { client.list_projects(Some(limit)).await })?;
+        let (projects, next_cursor) = self.runtime.block_on(async move {
+            if all {
+                Ok((client.list_all_projects().await?, None))
+            } else {
+                client.list_projects_page(limit, cursor.as_deref()).await
+            }
+        })?;
 
         Ok(serde_json::json!({
             "projects": projects,
             "count": projects.len(),
+            "next_cursor": next_cursor,
+        }))
+    }
+
+    /// Project search implementation.
+    fn projects_search(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let query = Self::get_param_str(&params, "query").unwrap_or("");
+        let region = Self::get_param_str(&params, "region_id");
+        let tag = Self::get_param_str(&params, "tag");
+        let limit = params.get("limit").and_then(|v| v.as_u64()).unwrap_or(20) as usize;
+        let force = params
+            .get("refresh")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let refreshed = if force || self.project_index.is_stale() {
+            let (index, client) = (self.project_index.clone(), self.client.clone());
+            let events = self.config.watch.enabled.then(|| self.events.clone());
+            let refresh = async move { index.refresh(&client, events.as_deref(), force).await };
+            Some(self.runtime.block_on(refresh)?)
+        } else {
+            None
+        };
+
+        let (hits, indexed) = self.project_index.search(query, region, tag, limit);
+        Ok(serde_json::json!({
+            "projects": hits,
+            "count": hits.len(),
+            "indexed": indexed,
+            "refreshed_at": self.project_index.refreshed_at(),
+            "refresh": refreshed,
         }))
     }
 
@@ -75,13 +501,56 @@ impl NeonService {
             .ok_or_else(|| anyhow::anyhow!("Missing required parameter: project_id"))?
             .to_string();
 
+        let expand: Vec<String> = match params.get("expand") {
+            Some(v) => serde_json::from_value(v.clone())
+                .map_err(|_| anyhow::anyhow!("expand must be an array of strings"))?,
+            None => Vec::new(),
+        };
+        for item in &expand {
+            if !matches!(item.as_str(), "branches" | "endpoints") {
+                anyhow::bail!("Unknown expand {:?} (branches, endpoints)", item);
+            }
+        }
+        let with_branches = expand.iter().any(|e| e == "branches");
+        let with_endpoints = expand.iter().any(|e| e == "endpoints");
+
         let client = self.client.clone();
 
-        let project = self
-            .runtime
-            .block_on(async move { client.get_project(&project_id).await })?;
+        let (project, branches, endpoints) = self.runtime.block_on(async move {
+            let (project, branches, endpoints) = tokio::join!(
+                client.get_project(&project_id),
+                async {
+                    if with_branches {
+                        client.list_branches(&project_id).await.map(Some)
+                    } else {
+                        Ok(None)
+                    }
+                },
+                async {
+                    if with_endpoints {
+                        client.list_endpoints(&project_id).await.map(Some)
+                    } else {
+                        Ok(None)
+                    }
+                },
+            );
+            anyhow::Ok((project?, branches?, endpoints?))
+        })?;
 
-        Ok(serde_json::to_value(project)?)
+        let quota_status = project.quota_status();
+        let quota_warnings = warnings::quotas(&project.id, &quota_status);
+        let mut value = serde_json::to_value(project)?;
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert("quota_status".into(), serde_json::to_value(quota_status)?);
+            warnings::extend(obj, quota_warnings);
+            if let Some(branches) = branches {
+                obj.insert("branches".into(), serde_json::to_value(branches)?);
+            }
+            if let Some(endpoints) = endpoints {
+                obj.insert("endpoints".into(), serde_json::to_value(endpoints)?);
+            }
+        }
+        Ok(value)
     }
 
     /// List branches implementation.
@@ -90,18 +559,159 @@ impl NeonService {
             .ok_or_else(|| anyhow::anyhow!("Missing required parameter: project_id"))?
             .to_string();
 
+        let stale_after_days = params
+            .get("stale_after_days")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(7.0);
+
+        let filter = TagFilter::from_params(&params);
+        let index = TagIndex::load()?;
+
         let client = self.client.clone();
 
         let branches = self
             .runtime
             .block_on(async move { client.list_branches(&project_id).await })?;
 
+        let now = chrono::Utc::now();
+        let listed = branches
+            .iter()
+            .map(|branch| (branch, index.tags(branch)))
+            .filter(|(_, tags)| filter.matches(tags, now))
+            .map(|(branch, tags)| Self::branch_entry(branch, &tags, now, stale_after_days))
+            .collect::<Result<Vec<_>>>()?;
+
         Ok(serde_json::json!({
-            "branches": branches,
-            "count": branches.len(),
+            "branches": listed,
+            "count": listed.len(),
+            "stale_count": listed.iter().filter(|b| b["stale"] == true).count(),
+        }))
+    }
+
+    /// Branches across projects implementation.
+    fn all_branches(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_ids: Option<Vec<String>> = params
+            .get("project_ids")
+            .map(|v| serde_json::from_value(v.clone()))
+            .transpose()
+            .map_err(|_| anyhow::anyhow!("project_ids must be an array of strings"))?;
+        let stale_after_days = params
+            .get("stale_after_days")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(7.0);
+        let stale_only = params
+            .get("stale_only")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let filter = TagFilter::from_params(&params);
+        let index = TagIndex::load()?;
+
+        let client = self.client.clone();
+
+        let (projects, listings) = self.runtime.block_on(async move {
+            let mut projects = client.list_all_projects().await?;
+            if let Some(ids) = &project_ids {
+                projects.retain(|p| ids.contains(&p.id));
+                if let Some(missing) = ids.iter().find(|id| !projects.iter().any(|p| p.id == **id))
+                {
+                    anyhow::bail!("Project not found: {}", missing);
+                }
+            }
+
+            let listings = futures_util::stream::iter(&projects)
+                .map(|project| {
+                    let client = &client;
+                    async move {
+                        (
+                            project.id.clone(),
+                            client.list_all_branches(&project.id).await,
+                        )
+                    }
+                })
+                .buffer_unordered(MAX_BATCH_CONCURRENCY)
+                .collect::<Vec<_>>()
+                .await;
+            anyhow::Ok((projects, listings))
+        })?;
+
+        // A project that fails to list is reported without failing the rest
+        let now = chrono::Utc::now();
+        let mut seen = std::collections::HashSet::new();
+        let mut listed = Vec::new();
+        let mut errors = Vec::new();
+        for (project_id, result) in listings {
+            let branches = match result {
+                Ok(branches) => branches,
+                Err(e) => {
+                    errors.push(serde_json::json!({
+                        "project_id": project_id,
+                        "error": format!("{:#}", e),
+                    }));
+                    continue;
+                }
+            };
+            let project = projects.iter().find(|p| p.id == project_id);
+            for branch in branches {
+                if !seen.insert(branch.id.clone()) {
+                    continue;
+                }
+                let tags = index.tags(&branch);
+                if !filter.matches(&tags, now) {
+                    continue;
+                }
+                let mut entry = Self::branch_entry(&branch, &tags, now, stale_after_days)?;
+                if stale_only && entry["stale"] != true {
+                    continue;
+                }
+                if let Some(obj) = entry.as_object_mut() {
+                    obj.insert(
+                        "project_name".into(),
+                        serde_json::json!(project.map(|p| &p.name)),
+                    );
+                    obj.insert(
+                        "region_id".into(),
+                        serde_json::json!(project.and_then(|p| p.region_id.as_ref())),
+                    );
+                }
+                listed.push(entry);
+            }
+        }
+        listed.sort_by(|a, b| {
+            (a["project_name"].as_str(), a["name"].as_str())
+                .cmp(&(b["project_name"].as_str(), b["name"].as_str()))
+        });
+
+        Ok(serde_json::json!({
+            "branches": listed,
+            "count": listed.len(),
+            "stale_count": listed.iter().filter(|b| b["stale"] == true).count(),
+            "project_count": projects.len(),
+            "errors": errors,
         }))
     }
 
+    /// A listed branch with its `tags`, `idle_days`, and whether it's `stale`
+    /// or `expired`. Default and protected branches are never reported stale.
+    fn branch_entry(
+        branch: &Branch,
+        tags: &BranchTags,
+        now: chrono::DateTime<chrono::Utc>,
+        stale_after_days: f64,
+    ) -> Result<Value> {
+        let idle_days = branch.idle_days(now);
+        let stale = !branch.default
+            && !branch.protected
+            && idle_days.is_some_and(|d| d >= stale_after_days);
+        let mut entry = serde_json::to_value(branch)?;
+        if let Some(obj) = entry.as_object_mut() {
+            obj.insert("idle_days".into(), serde_json::json!(idle_days));
+            obj.insert("stale".into(), serde_json::json!(stale));
+            obj.insert("tags".into(), serde_json::to_value(tags)?);
+            obj.insert("expired".into(), serde_json::json!(tags.expired(now)));
+        }
+        Ok(entry)
+    }
+
     /// List databases implementation.
     fn list_databases(&self, params: HashMap<String, Value>) -> Result<Value> {
         let project_id = Self::get_param_str(&params, "project_id")
@@ -134,380 +744,4236 @@ impl NeonService {
         let database = Self::get_param_str(&params, "database")
             .unwrap_or("neondb")
             .to_string();
+        let page = TablePage {
+            prefix: Self::get_param_str(&params, "prefix").map(|s| s.to_string()),
+            cursor: Self::get_param_str(&params, "cursor").map(|s| s.to_string()),
+            offset: params.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+            limit: params
+                .get("limit")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as usize),
+        };
+        let format = Format::from_params(&params)?;
 
         let client = self.client.clone();
 
-        let tables = self
-            .runtime
-            .block_on(async move { client.get_tables(&project_id, &branch_id, &database).await })?;
+        let tables = self.runtime.block_on(async move {
+            client
+                .get_tables(&project_id, &branch_id, &database, &page)
+                .await
+        })?;
 
-        Ok(tables)
+        Ok(match format {
+            Some(format) => format.apply(tables),
+            None => tables,
+        })
     }
 
-    /// Get table schema implementation.
-    fn get_table_schema(&self, params: HashMap<String, Value>) -> Result<Value> {
-        let project_id = Self::get_param_str(&params, "project_id")
-            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: project_id"))?
-            .to_string();
-        let branch_id = Self::get_param_str(&params, "branch_id")
-            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: branch_id"))?
-            .to_string();
+    /// Table detail implementation.
+    fn table_detail(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
         let database = Self::get_param_str(&params, "database")
             .unwrap_or("neondb")
             .to_string();
-        let table = Self::get_param_str(&params, "table")
-            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: table"))?
-            .to_string();
+        let table = Self::require_param_str(&params, "table")?;
+        // `schema.table` is accepted in place of a separate schema
+        let (schema, table) = match (
+            Self::get_param_str(&params, "schema"),
+            table.split_once('.'),
+        ) {
+            (Some(schema), _) => (schema.to_string(), table),
+            (None, Some((schema, name))) => (schema.to_string(), name.to_string()),
+            (None, None) => ("public".to_string(), table),
+        };
 
         let client = self.client.clone();
 
-        let schema = self.runtime.block_on(async move {
+        let detail = self.runtime.block_on(async move {
             client
-                .get_table_schema(&project_id, &branch_id, &database, &table)
+                .get_table_detail(&project_id, &branch_id, &database, &schema, &table)
                 .await
         })?;
 
-        Ok(schema)
+        Ok(serde_json::to_value(detail)?)
     }
 
-    /// Run SQL query implementation.
-    fn run_sql(&self, params: HashMap<String, Value>) -> Result<Value> {
-        let project_id = Self::get_param_str(&params, "project_id")
-            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: project_id"))?
-            .to_string();
-        let branch_id = Self::get_param_str(&params, "branch_id")
-            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: branch_id"))?
-            .to_string();
+    /// Whole-database introspection implementation.
+    fn introspect(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
         let database = Self::get_param_str(&params, "database")
             .unwrap_or("neondb")
             .to_string();
-        let query = Self::get_param_str(&params, "query")
-            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: query"))?
-            .to_string();
+        let schema = Self::get_param_str(&params, "schema").map(|s| s.to_string());
+        let client = self.client.clone();
+
+        let introspection = self.runtime.block_on(async move {
+            client
+                .get_introspection(&project_id, &branch_id, &database, schema.as_deref())
+                .await
+        })?;
 
+        Ok(serde_json::to_value(introspection)?)
+    }
+
+    /// Views implementation.
+    fn list_views(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let schema = Self::get_param_str(&params, "schema").map(|s| s.to_string());
         let client = self.client.clone();
 
-        let result = self.runtime.block_on(async move {
+        let views = self.runtime.block_on(async move {
             client
-                .run_sql(&project_id, &branch_id, &database, &query)
+                .list_views(&project_id, &branch_id, &database, schema.as_deref())
                 .await
         })?;
 
-        Ok(result)
+        Ok(serde_json::json!({
+            "views": views,
+            "count": views.len(),
+        }))
     }
 
-    /// Get user info implementation.
-    fn get_user(&self) -> Result<Value> {
+    /// Functions implementation.
+    fn list_functions(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let schema = Self::get_param_str(&params, "schema").map(|s| s.to_string());
         let client = self.client.clone();
 
-        let user = self
-            .runtime
-            .block_on(async move { client.get_user().await })?;
+        let functions = self.runtime.block_on(async move {
+            client
+                .list_functions(&project_id, &branch_id, &database, schema.as_deref())
+                .await
+        })?;
 
-        Ok(user)
+        Ok(serde_json::json!({
+            "functions": functions,
+            "count": functions.len(),
+        }))
     }
 
-    /// Create branch implementation.
-    fn create_branch(&self, params: HashMap<String, Value>) -> Result<Value> {
-        let project_id = Self::get_param_str(&params, "project_id")
-            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: project_id"))?
+    /// Extensions implementation.
+    fn list_extensions(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
             .to_string();
-        let name = Self::get_param_str(&params, "name").map(|s| s.to_string());
-        let parent_id = Self::get_param_str(&params, "parent_id").map(|s| s.to_string());
+        let client = self.client.clone();
 
+        let extensions = self.runtime.block_on(async move {
+            client
+                .list_extensions(&project_id, &branch_id, &database)
+                .await
+        })?;
+
+        Ok(serde_json::json!({
+            "extensions": extensions,
+            "count": extensions.len(),
+        }))
+    }
+
+    /// Sizes implementation.
+    fn sizes(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let sort = Self::get_param_str(&params, "sort")
+            .unwrap_or("total")
+            .to_string();
+        let limit = params.get("limit").and_then(|v| v.as_u64()).unwrap_or(20) as usize;
         let client = self.client.clone();
 
-        let branch = self.runtime.block_on(async move {
+        let sizes = self.runtime.block_on(async move {
             client
-                .create_branch(&project_id, name.as_deref(), parent_id.as_deref())
+                .get_sizes(&project_id, &branch_id, &database, &sort, limit)
                 .await
         })?;
 
-        Ok(serde_json::to_value(branch)?)
+        Ok(serde_json::to_value(sizes)?)
     }
 
-    /// Delete branch implementation.
-    fn delete_branch(&self, params: HashMap<String, Value>) -> Result<Value> {
+    /// Get table schema implementation.
+    fn get_table_schema(&self, params: HashMap<String, Value>) -> Result<Value> {
         let project_id = Self::get_param_str(&params, "project_id")
             .ok_or_else(|| anyhow::anyhow!("Missing required parameter: project_id"))?
             .to_string();
         let branch_id = Self::get_param_str(&params, "branch_id")
             .ok_or_else(|| anyhow::anyhow!("Missing required parameter: branch_id"))?
             .to_string();
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let table = Self::get_param_str(&params, "table")
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: table"))?
+            .to_string();
 
         let client = self.client.clone();
 
-        self.runtime
-            .block_on(async move { client.delete_branch(&project_id, &branch_id).await })?;
+        let schema = self.runtime.block_on(async move {
+            client
+                .get_table_schema(&project_id, &branch_id, &database, &table)
+                .await
+        })?;
 
-        Ok(serde_json::json!({ "deleted": true }))
+        Ok(schema)
     }
 
-    /// Get connection string implementation.
-    fn get_connection_string(&self, params: HashMap<String, Value>) -> Result<Value> {
-        let project_id = Self::get_param_str(&params, "project_id")
-            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: project_id"))?
+    /// Run SQL query implementation.
+    fn run_sql(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let query = Self::get_param_str(&params, "query")
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: query"))?
             .to_string();
-        let branch_id = Self::get_param_str(&params, "branch_id").map(|s| s.to_string());
-        let database = Self::get_param_str(&params, "database").map(|s| s.to_string());
-        let pooled = params
-            .get("pooled")
+
+        self.execute_sql(&params, query, Vec::new())
+    }
+
+    /// Named-parameter SQL template implementation.
+    fn sql_template(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let template = Self::require_param_str(&params, "query")?;
+        let values = Self::named_params(&params)?;
+
+        let (query, bound) = sqltext::bind_named(&template, &values)?;
+        self.execute_sql(&params, query, bound)
+    }
+
+    /// SQL script implementation.
+    fn sql_file(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let text = match (
+            Self::get_param_str(&params, "path"),
+            Self::get_param_str(&params, "script"),
+        ) {
+            (Some(path), None) => {
+                let path = shellexpand::tilde(path).to_string();
+                std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read script {}", path))?
+            }
+            (None, Some(script)) => script.to_string(),
+            _ => anyhow::bail!("Pass exactly one of path or script"),
+        };
+        let statements = script::parse(&text)?;
+        let run_id = Self::get_param_str(&params, "run_id")
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| format!("script-{}", chrono::Utc::now().timestamp_millis()));
+        let continue_on_error = params
+            .get("continue_on_error")
             .and_then(|v| v.as_bool())
             .unwrap_or(false);
 
-        let client = self.client.clone();
+        let (client, events) = (self.client.clone(), self.events.clone());
 
-        let result = self.runtime.block_on(async move {
-            client
-                .get_connection_string(
-                    &project_id,
-                    branch_id.as_deref(),
-                    database.as_deref(),
-                    pooled,
-                )
-                .await
+        let outcome = self.on_sql_runtime(async move {
+            let target = ScriptTarget {
+                project_id: &project_id,
+                branch_id: &branch_id,
+                database: &database,
+            };
+            script::run(
+                &client,
+                Some(&*events),
+                &target,
+                &run_id,
+                &statements,
+                continue_on_error,
+            )
+            .await
         })?;
 
-        Ok(result)
+        Ok(serde_json::to_value(outcome)?)
     }
-}
 
-impl FgpService for NeonService {
-    fn name(&self) -> &str {
-        "neon"
+    /// Migration status implementation.
+    fn migrate_status(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let (project_id, branch_id, database, migrations) = Self::migrate_params(&params)?;
+        let client = self.client.clone();
+
+        let status = self.on_sql_runtime(async move {
+            let target = ScriptTarget {
+                project_id: &project_id,
+                branch_id: &branch_id,
+                database: &database,
+            };
+            migrate::status(&client, &target, &migrations).await
+        })?;
+
+        Ok(serde_json::to_value(status)?)
     }
 
-    fn version(&self) -> &str {
-        env!("CARGO_PKG_VERSION")
+    /// Migration plan implementation.
+    fn migrate_plan(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let (project_id, branch_id, database, migrations) = Self::migrate_params(&params)?;
+        let client = self.client.clone();
+
+        let plan = self.on_sql_runtime(async move {
+            let target = ScriptTarget {
+                project_id: &project_id,
+                branch_id: &branch_id,
+                database: &database,
+            };
+            migrate::plan(&client, &target, &migrations).await
+        })?;
+
+        Ok(serde_json::to_value(plan)?)
     }
 
-    fn dispatch(&self, method: &str, params: HashMap<String, Value>) -> Result<Value> {
-        match method {
-            "health" => self.health(),
-            "projects" | "neon.projects" => self.list_projects(params),
-            "project" | "neon.project" => self.get_project(params),
-            "branches" | "neon.branches" => self.list_branches(params),
-            "databases" | "neon.databases" => self.list_databases(params),
-            "tables" | "neon.tables" => self.get_tables(params),
-            "schema" | "neon.schema" => self.get_table_schema(params),
-            "sql" | "neon.sql" => self.run_sql(params),
-            "user" | "neon.user" => self.get_user(),
-            "create_branch" | "neon.create_branch" => self.create_branch(params),
-            "delete_branch" | "neon.delete_branch" => self.delete_branch(params),
-            "connection_string" | "neon.connection_string" => self.get_connection_string(params),
-            _ => anyhow::bail!("Unknown method: {}", method),
+    /// Migration apply implementation.
+    fn migrate_apply(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let (project_id, branch_id, database, migrations) = Self::migrate_params(&params)?;
+        let target_version = params.get("target_version").and_then(|v| v.as_u64());
+        let client = self.client.clone();
+
+        let outcome = self.on_sql_runtime(async move {
+            let target = ScriptTarget {
+                project_id: &project_id,
+                branch_id: &branch_id,
+                database: &database,
+            };
+            migrate::apply(&client, &target, &migrations, target_version).await
+        })?;
+
+        Ok(serde_json::to_value(outcome)?)
+    }
+
+    /// Target and migrations shared by the `migrate_*` methods.
+    fn migrate_params(
+        params: &HashMap<String, Value>,
+    ) -> Result<(String, String, String, Vec<migrate::Migration>)> {
+        let project_id = Self::require_param_str(params, "project_id")?;
+        let branch_id = Self::require_param_str(params, "branch_id")?;
+        let database = Self::get_param_str(params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let migrations = migrate::load(&Self::require_param_str(params, "dir")?)?;
+        Ok((project_id, branch_id, database, migrations))
+    }
+
+    /// Seed implementation.
+    fn seed(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let seed = seed::load(&Self::require_param_str(&params, "path")?)?;
+        let truncated = if params.get("truncate").and_then(|v| v.as_bool()) == Some(true) {
+            seed::tables(&seed.statements)
+        } else {
+            Vec::new()
+        };
+        let statements: Vec<_> = seed::truncate(&truncated)
+            .into_iter()
+            .chain(seed.statements)
+            .collect();
+        let run_id = format!("seed-{}", chrono::Utc::now().timestamp_millis());
+
+        let (client, events) = (self.client.clone(), self.events.clone());
+
+        let outcome = self.on_sql_runtime(async move {
+            let target = ScriptTarget {
+                project_id: &project_id,
+                branch_id: &branch_id,
+                database: &database,
+            };
+            script::run(
+                &client,
+                Some(&*events),
+                &target,
+                &run_id,
+                &statements,
+                false,
+            )
+            .await
+        })?;
+
+        let mut result = serde_json::to_value(outcome)?;
+        result["files"] = serde_json::json!(seed.files);
+        result["truncated"] = serde_json::json!(truncated);
+        Ok(result)
+    }
+
+    /// CSV import implementation.
+    fn import_csv(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let table = Self::require_param_str(&params, "table")?;
+        let (schema, table) = match table.split_once('.') {
+            Some((schema, table)) => (schema.to_string(), table.to_string()),
+            None => ("public".to_string(), table),
+        };
+        let columns = match params.get("columns") {
+            None => ColumnMap::Header,
+            Some(Value::Array(_)) => ColumnMap::Positional(
+                serde_json::from_value(params["columns"].clone())
+                    .map_err(|_| anyhow::anyhow!("columns must be an array of strings"))?,
+            ),
+            Some(Value::Object(_)) => {
+                ColumnMap::Named(serde_json::from_value(params["columns"].clone()).map_err(
+                    |_| anyhow::anyhow!("columns must map CSV header names to column names"),
+                )?)
+            }
+            Some(_) => anyhow::bail!(
+                "columns must be an array of column names or an object of header: column"
+            ),
+        };
+        let single_char = |name: &str, default: char| -> Result<char> {
+            match Self::get_param_str(&params, name) {
+                None => Ok(default),
+                Some(s) => {
+                    let mut chars = s.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) if c != '\n' && c != '\r' => Ok(c),
+                        _ => anyhow::bail!("{} must be a single character", name),
+                    }
+                }
+            }
+        };
+        let backend = Self::backend_param(&params)?.unwrap_or(self.config.sql.backend);
+        let import = CsvImport {
+            schema,
+            table,
+            path: Self::require_param_str(&params, "path")?,
+            columns,
+            delimiter: single_char("delimiter", ',')?,
+            quote: single_char("quote", '"')?,
+            null: Self::get_param_str(&params, "null")
+                .unwrap_or("")
+                .to_string(),
+            header: params
+                .get("header")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true),
+            batch_rows: params
+                .get("batch_rows")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(500) as usize,
+            copy: backend == SqlBackend::Postgres,
+        };
+        if import.delimiter == import.quote {
+            anyhow::bail!("delimiter and quote must differ");
         }
+        let run_id = Self::get_param_str(&params, "run_id")
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| format!("import-{}", chrono::Utc::now().timestamp_millis()));
+
+        let (client, events) = (self.client.clone(), self.events.clone());
+
+        let outcome = self.on_sql_runtime(async move {
+            let target = ScriptTarget {
+                project_id: &project_id,
+                branch_id: &branch_id,
+                database: &database,
+            };
+            import::import(&client, Some(&*events), &target, &run_id, &import).await
+        })?;
+
+        Ok(serde_json::to_value(outcome)?)
     }
 
-    fn method_list(&self) -> Vec<MethodInfo> {
-        vec![
+    /// Dump implementation.
+    fn dump(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let path = Self::require_param_str(&params, "path")?;
+        let names: Vec<String> = match params.get("tables") {
+            Some(v) => serde_json::from_value(v.clone())
+                .map_err(|_| anyhow::anyhow!("tables must be an array of strings"))?,
+            None => Vec::new(),
+        };
+        // `schema.table`, or a table in `public`
+        let tables: Vec<(String, String)> = names
+            .iter()
+            .map(|name| match name.split_once('.') {
+                Some((schema, table)) => (schema.to_string(), table.to_string()),
+                None => ("public".to_string(), name.clone()),
+            })
+            .collect();
+        let format = params
+            .get("format")
+            .map(|v| serde_json::from_value::<DumpFormat>(v.clone()))
+            .transpose()
+            .map_err(|_| anyhow::anyhow!("format must be \"sql\" or \"ndjson\""))?
+            .unwrap_or_default();
+        let flag = |name: &str, default: bool| {
+            params
+                .get(name)
+                .and_then(|v| v.as_bool())
+                .unwrap_or(default)
+        };
+        let options = DumpOptions {
+            format,
+            schema: flag("schema", true),
+            data: flag("data", true),
+            overwrite: flag("overwrite", false),
+        };
+        if !options.schema && !options.data {
+            anyhow::bail!("Nothing to dump: schema and data are both false");
+        }
+        let redact = self.redactor.applies(Self::allow_secrets(&params))?;
+        let mask = self.masker.applies(flag("mask", true))?;
+
+        let client = self.client.clone();
+        let (redactor, masker) = (self.redactor.clone(), self.masker.clone());
+
+        let outcome = self.on_sql_runtime(async move {
+            let target = ScriptTarget {
+                project_id: &project_id,
+                branch_id: &branch_id,
+                database: &database,
+            };
+            dump::dump(
+                &client,
+                &target,
+                &tables,
+                &path,
+                options,
+                redact.then_some(redactor.as_ref()),
+                mask.then_some(masker.as_ref()),
+            )
+            .await
+        })?;
+
+        Ok(serde_json::to_value(outcome)?)
+    }
+
+    /// Transaction implementation.
+    fn transaction(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let statements = Self::statements_param(&params, "statements")?;
+        let isolation = params
+            .get("isolation_level")
+            .map(|v| serde_json::from_value::<IsolationLevel>(v.clone()))
+            .transpose()
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "isolation_level must be \"read_committed\", \"repeatable_read\", or \"serializable\""
+                )
+            })?
+            .unwrap_or_default();
+        let read_only = params
+            .get("read_only")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let backend = Self::backend_param(&params)?;
+        let redact = self.redactor.applies(Self::allow_secrets(&params))?;
+        let options = TxOptions {
+            isolation,
+            read_only,
+            backend,
+        };
+
+        let client = self.client.clone();
+        let mut results = self.on_sql_runtime(async move {
+            client
+                .run_transaction(&project_id, &branch_id, &database, &statements, options)
+                .await
+        })?;
+
+        let redacted: usize = if redact {
+            results.iter_mut().map(|r| self.redactor.redact(r)).sum()
+        } else {
+            0
+        };
+        let mut response = serde_json::json!({
+            "results": results,
+            "count": results.len(),
+            "committed": true,
+        });
+        if redacted > 0 {
+            response["redacted"] = serde_json::json!(redacted);
+        }
+        Ok(response)
+    }
+
+    /// Saved query library implementation (list/get/save/delete).
+    fn saved_queries(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let action = Self::get_param_str(&params, "action").unwrap_or("list");
+
+        match action {
+            "list" => {
+                let queries: Vec<_> = saved::load()?.into_values().collect();
+                Ok(serde_json::json!({
+                    "queries": queries,
+                    "count": queries.len(),
+                }))
+            }
+            "get" => {
+                let name = Self::require_param_str(&params, "name")?;
+                Ok(serde_json::to_value(saved::get(&name)?)?)
+            }
+            "save" => {
+                let name = Self::require_param_str(&params, "name")?;
+                let query = Self::require_param_str(&params, "query")?;
+                let description =
+                    Self::get_param_str(&params, "description").map(|s| s.to_string());
+                let database = Self::get_param_str(&params, "database").map(|s| s.to_string());
+                Ok(serde_json::to_value(saved::save(
+                    &name,
+                    &query,
+                    description,
+                    database,
+                )?)?)
+            }
+            "delete" => {
+                let name = Self::require_param_str(&params, "name")?;
+                Ok(serde_json::json!({
+                    "name": name,
+                    "deleted": saved::delete(&name)?,
+                }))
+            }
+            other => anyhow::bail!(
+                "Unknown action {:?}; expected list, get, save, or delete",
+                other
+            ),
+        }
+    }
+
+    /// Run a saved query by name implementation.
+    fn run_saved(&self, mut params: HashMap<String, Value>) -> Result<Value> {
+        let name = Self::require_param_str(&params, "name")?;
+        let saved = saved::get(&name)?;
+
+        if let (None, Some(database)) = (params.get("database"), &saved.database) {
+            params.insert("database".into(), Value::String(database.clone()));
+        }
+        if !params.contains_key("tag") {
+            params.insert("tag".into(), Value::String(format!("saved:{}", name)));
+        }
+
+        let values = Self::named_params(&params)?;
+
+        let (query, bound) = sqltext::bind_named(&saved.query, &values)?;
+        self.execute_sql(&params, query, bound)
+    }
+
+    /// Shared by `neon.sql` and `neon.sql_template`: resolves the target and
+    /// paging params, applies the tag, runs the query, and records history.
+    fn execute_sql(
+        &self,
+        params: &HashMap<String, Value>,
+        query: String,
+        bound: Vec<Value>,
+    ) -> Result<Value> {
+        let project_id = Self::require_param_str(params, "project_id")?;
+        let branch_id = Self::require_param_str(params, "branch_id")?;
+        let database = Self::get_param_str(params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let stream = params
+            .get("stream")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        // Streamed rows reach the caller a chunk per call, so the whole
+        // stream may go past the per-call row limit
+        let row_limit = if stream {
+            self.results.max_rows()
+        } else {
+            self.client.max_rows()
+        };
+        let max_rows = params
+            .get("max_rows")
+            .and_then(|v| v.as_u64())
+            .map(|v| (v as usize).min(row_limit))
+            .unwrap_or(row_limit);
+        let chunk_size = params
+            .get("chunk_size")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+            .unwrap_or(self.results.chunk_size());
+        let format = Format::from_params(params)?;
+        if stream && format.is_some() {
+            anyhow::bail!("format can't be combined with stream");
+        }
+        let offset = params.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        let tag = Self::get_param_str(params, "tag").map(|s| s.to_string());
+        let transforms = Transforms::from_params(params)?;
+        if stream && !transforms.is_empty() {
+            anyhow::bail!("Result transforms can't be combined with stream");
+        }
+        // Each chunk is fetched with its own LIMIT/OFFSET
+        if stream && !is_pageable(&query) {
+            anyhow::bail!("Only a single SELECT, WITH, or VALUES query can be streamed");
+        }
+        let redact = self.redactor.applies(Self::allow_secrets(params))?;
+        let guard = CostGuard::from_params(&self.config.cost_guard, params)?;
+        let backend = Self::backend_param(params)?;
+        let target = Self::target_param(params)?;
+        let role = Self::get_param_str(params, "role").map(|s| s.to_string());
+        let read_only = params
+            .get("read_only")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let statement = match &tag {
+            Some(t) => format!("{}{}", history::tag_comment(t)?, query),
+            None => query.clone(),
+        };
+        // Kept so later chunks can run the query again
+        let streamed = stream.then(|| StreamedQuery {
+            project_id: project_id.clone(),
+            branch_id: branch_id.clone(),
+            database: database.clone(),
+            statement: statement.clone(),
+            params: bound.clone(),
+            backend,
+            target,
+            role: role.clone(),
+            read_only,
+            redact,
+        });
+
+        let client = self.client.clone();
+        let (project, branch, db) = (project_id.clone(), branch_id.clone(), database.clone());
+        let sql_params = bound.clone();
+        let page_rows = if stream {
+            chunk_size.clamp(1, max_rows.max(1))
+        } else {
+            max_rows
+        };
+        let sql_role = role.clone();
+        let started = std::time::Instant::now();
+
+        let result = self.on_sql_runtime(async move {
+            let page = SqlPage {
+                max_rows: page_rows,
+                offset,
+                backend,
+                target,
+                role: sql_role.as_deref(),
+                read_only,
+            };
+            let mut estimate = None;
+            if let Some(guard) = guard {
+                estimate = client
+                    .estimate_plan(&project, &branch, &db, &statement, &sql_params, page.role)
+                    .await?;
+                if let Some(plan) = &estimate {
+                    guard.check(plan)?;
+                }
+            }
+            let mut result = client
+                .run_sql_page(&project, &branch, &db, &statement, &sql_params, page)
+                .await?;
+            if let (Some(plan), Some(obj)) = (estimate, result.as_object_mut()) {
+                obj.insert("estimate".into(), serde_json::to_value(plan)?);
+            }
+            anyhow::Ok(result)
+        });
+
+        let history_id = self.history.record(HistoryEntry {
+            id: 0,
+            at: chrono::Utc::now(),
+            project_id,
+            branch_id,
+            database,
+            query,
+            params: bound,
+            tag,
+            duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+            rows: result
+                .as_ref()
+                .ok()
+                .and_then(|r| r.get("rows"))
+                .and_then(|r| r.as_array())
+                .map(|r| r.len()),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        });
+
+        let mut result = result?;
+        // Redact before transforms so masked values can't leak through them
+        let redacted = if redact {
+            self.redactor.redact(&mut result)
+        } else {
+            0
+        };
+        if !transforms.is_empty() {
+            transforms.apply(&mut result)?;
+        }
+        if let Some(obj) = result.as_object_mut() {
+            obj.insert("history_id".into(), serde_json::json!(history_id));
+            if redacted > 0 {
+                obj.insert("redacted".into(), serde_json::json!(redacted));
+            }
+        }
+        if let Some(streamed) = streamed {
+            result = self
+                .results
+                .start(streamed, result, offset, chunk_size, max_rows);
+        } else if let Some(format) = format {
+            result = format.apply(result);
+        }
+        Ok(result)
+    }
+
+    /// Streamed result chunk implementation.
+    fn fetch_chunk(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let cursor = Self::require_param_str(&params, "cursor")?;
+        let chunk = self.results.claim(&cursor)?;
+
+        let client = self.client.clone();
+        let query = chunk.query.clone();
+        let (max_rows, offset) = (chunk.max_rows, chunk.offset);
+        let mut result = self.on_sql_runtime(async move {
+            let page = SqlPage {
+                max_rows,
+                offset,
+                backend: query.backend,
+                target: query.target,
+                role: query.role.as_deref(),
+                read_only: query.read_only,
+            };
+            client
+                .run_sql_page(
+                    &query.project_id,
+                    &query.branch_id,
+                    &query.database,
+                    &query.statement,
+                    &query.params,
+                    page,
+                )
+                .await
+        })?;
+        if chunk.query.redact {
+            let redacted = self.redactor.redact(&mut result);
+            if let (true, Some(obj)) = (redacted > 0, result.as_object_mut()) {
+                obj.insert("redacted".into(), serde_json::json!(redacted));
+            }
+        }
+        self.results.finish(&chunk, result)
+    }
+
+    /// Batch SQL implementation.
+    fn sql_batch(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let queries = Self::statements_param(&params, "queries")?;
+        let concurrency = params
+            .get("concurrency")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(1)
+            .clamp(1, MAX_BATCH_CONCURRENCY as u64) as usize;
+        let max_rows = params
+            .get("max_rows")
+            .and_then(|v| v.as_u64())
+            .map(|v| (v as usize).min(self.client.max_rows()))
+            .unwrap_or(self.client.max_rows());
+        let redact = self.redactor.applies(Self::allow_secrets(&params))?;
+        let guard = CostGuard::from_params(&self.config.cost_guard, &params)?;
+        let backend = Self::backend_param(&params)?;
+        let target = Self::target_param(&params)?;
+        let role = Self::get_param_str(&params, "role").map(|s| s.to_string());
+        let read_only = params
+            .get("read_only")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let tag = Self::get_param_str(&params, "tag").map(|s| s.to_string());
+        let comment = match &tag {
+            Some(t) => history::tag_comment(t)?,
+            None => String::new(),
+        };
+
+        let client = self.client.clone();
+        let (project, branch, db) = (project_id.clone(), branch_id.clone(), database.clone());
+        let batch = queries.clone();
+        let outcomes = self.on_sql_runtime(async move {
+            let page = SqlPage {
+                max_rows,
+                offset: 0,
+                backend,
+                target,
+                role: role.as_deref(),
+                read_only,
+            };
+            futures_util::stream::iter(batch)
+                .map(|q| {
+                    let (client, project, branch, db) = (&client, &project, &branch, &db);
+                    let statement = format!("{}{}", comment, q.query);
+                    async move {
+                        let started = std::time::Instant::now();
+                        let result = async {
+                            let mut estimate = None;
+                            if let Some(guard) = guard {
+                                estimate = client
+                                    .estimate_plan(
+                                        project, branch, db, &statement, &q.params, page.role,
+                                    )
+                                    .await?;
+                                if let Some(plan) = &estimate {
+                                    guard.check(plan)?;
+                                }
+                            }
+                            let mut result = client
+                                .run_sql_page(project, branch, db, &statement, &q.params, page)
+                                .await?;
+                            if let (Some(plan), Some(obj)) = (estimate, result.as_object_mut()) {
+                                obj.insert("estimate".into(), serde_json::to_value(plan)?);
+                            }
+                            anyhow::Ok(result)
+                        }
+                        .await;
+                        (result, started.elapsed().as_secs_f64() * 1000.0)
+                    }
+                })
+                .buffered(concurrency)
+                .collect::<Vec<_>>()
+                .await
+        });
+
+        let (mut succeeded, mut redacted) = (0, 0);
+        let mut results = Vec::with_capacity(outcomes.len());
+        for (index, (query, (result, duration_ms))) in queries.into_iter().zip(outcomes).enumerate()
+        {
+            let history_id = self.history.record(HistoryEntry {
+                id: 0,
+                at: chrono::Utc::now(),
+                project_id: project_id.clone(),
+                branch_id: branch_id.clone(),
+                database: database.clone(),
+                query: query.query,
+                params: query.params,
+                tag: tag.clone(),
+                duration_ms,
+                rows: result
+                    .as_ref()
+                    .ok()
+                    .and_then(|r| r.get("rows"))
+                    .and_then(|r| r.as_array())
+                    .map(|r| r.len()),
+                error: result.as_ref().err().map(|e| format!("{:#}", e)),
+            });
+            results.push(match result {
+                Ok(mut result) => {
+                    succeeded += 1;
+                    if redact {
+                        redacted += self.redactor.redact(&mut result);
+                    }
+                    serde_json::json!({
+                        "index": index,
+                        "ok": true,
+                        "duration_ms": duration_ms,
+                        "history_id": history_id,
+                        "result": result,
+                    })
+                }
+                Err(e) => serde_json::json!({
+                    "index": index,
+                    "ok": false,
+                    "duration_ms": duration_ms,
+                    "history_id": history_id,
+                    "error": format!("{:#}", e),
+                }),
+            });
+        }
+
+        let mut response = serde_json::json!({
+            "results": results,
+            "count": results.len(),
+            "succeeded": succeeded,
+            "failed": results.len() - succeeded,
+        });
+        if redacted > 0 {
+            response["redacted"] = serde_json::json!(redacted);
+        }
+        Ok(response)
+    }
+
+    /// A list of SQL statements, each a string or `{"query", "params"}`.
+    fn statements_param(params: &HashMap<String, Value>, name: &str) -> Result<Vec<TxStatement>> {
+        let statements: Vec<TxStatement> = params
+            .get(name)
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: {}", name))?
+            .iter()
+            .map(|s| match s {
+                Value::String(query) => Ok(TxStatement {
+                    query: query.clone(),
+                    params: Vec::new(),
+                }),
+                other => serde_json::from_value(other.clone()).map_err(|_| {
+                    anyhow::anyhow!(
+                        "Each entry of {} must be a string or {{\"query\", \"params\"}}",
+                        name
+                    )
+                }),
+            })
+            .collect::<Result<_>>()?;
+        if statements.is_empty() {
+            anyhow::bail!("{} must not be empty", name);
+        }
+        Ok(statements)
+    }
+
+    fn backend_param(params: &HashMap<String, Value>) -> Result<Option<SqlBackend>> {
+        params
+            .get("backend")
+            .map(|v| serde_json::from_value::<SqlBackend>(v.clone()))
+            .transpose()
+            .map_err(|_| anyhow::anyhow!("backend must be \"http\" or \"postgres\""))
+    }
+
+    fn target_param(params: &HashMap<String, Value>) -> Result<SqlTarget> {
+        Ok(params
+            .get("target")
+            .map(|v| serde_json::from_value::<SqlTarget>(v.clone()))
+            .transpose()
+            .map_err(|_| anyhow::anyhow!("target must be \"read\", \"write\", or \"auto\""))?
+            .unwrap_or_default())
+    }
+
+    fn allow_secrets(params: &HashMap<String, Value>) -> bool {
+        params
+            .get("allow_secrets")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// Get user info implementation.
+    fn get_user(&self) -> Result<Value> {
+        let client = self.client.clone();
+
+        let user = self
+            .runtime
+            .block_on(async move { client.get_user().await })?;
+
+        Ok(user)
+    }
+
+    /// Create branch implementation.
+    fn create_branch(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::get_param_str(&params, "project_id")
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: project_id"))?
+            .to_string();
+        let name = Self::get_param_str(&params, "name").map(|s| s.to_string());
+        let parent_id = Self::get_param_str(&params, "parent_id").map(|s| s.to_string());
+        let wait_ready = params
+            .get("wait_ready")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let timeout = std::time::Duration::from_secs(
+            params
+                .get("timeout_secs")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(120),
+        );
+        let update = TagUpdate::from_params(&params)?;
+        let tags = BranchTags::default().apply(&update);
+        tags.check_required(&self.config.branch_tags.required)?;
+        let annotation = tags.annotation();
+
+        let client = self.client.clone();
+        let project = project_id.clone();
+
+        let (branch, connection) = self.runtime.block_on(async move {
+            let branch = client
+                .create_annotated_branch(
+                    &project_id,
+                    name.as_deref(),
+                    parent_id.as_deref(),
+                    &annotation,
+                )
+                .await?;
+            if !wait_ready {
+                return anyhow::Ok((branch, None));
+            }
+
+            wait_until_ready(&client, &project_id, &branch.id, &database, timeout).await?;
+            let connection = client
+                .get_connection_string(&project_id, Some(&branch.id), Some(&database), None, false)
+                .await?;
+            anyhow::Ok((branch, Some(connection)))
+        })?;
+
+        // The index keeps the tags if annotations are lost or unsupported
+        if !tags.is_empty() {
+            if let Err(e) = branch_tags::set(&project, &branch.id, BranchTags::default(), &update) {
+                tracing::warn!("Failed to index tags of branch {}: {:#}", branch.id, e);
+            }
+        }
+
+        let mut result = serde_json::to_value(branch)?;
+        if let Some(obj) = result.as_object_mut() {
+            obj.insert("tags".into(), serde_json::to_value(&tags)?);
+            if let Some(connection) = connection {
+                obj.insert("connection".into(), connection);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Branch-per-PR implementation.
+    fn pr_branch(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let pr = match params.get("pr") {
+            Some(Value::Number(n)) if n.is_u64() => n.to_string(),
+            Some(Value::String(s)) => s.trim().trim_start_matches('#').to_string(),
+            None | Some(Value::Null) => anyhow::bail!("Missing required parameter: pr"),
+            Some(_) => anyhow::bail!("pr must be a PR number or name"),
+        };
+        if pr.is_empty()
+            || !pr
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            anyhow::bail!("pr must be a number or a name of letters, digits, '-', '_', or '.'");
+        }
+        let name = format!("pr-{}", pr);
+        let parent_id = Self::get_param_str(&params, "parent_id").map(|s| s.to_string());
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let timeout = std::time::Duration::from_secs(
+            params
+                .get("timeout_secs")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(120),
+        );
+
+        let client = self.client.clone();
+
+        let (branch, created, connection) = self.runtime.block_on(async move {
+            let existing = |branches: Vec<Branch>| branches.into_iter().find(|b| b.name == name);
+            let (branch, created) = match existing(client.list_branches(&project_id).await?) {
+                Some(branch) => (branch, false),
+                None => match client
+                    .create_branch(&project_id, Some(&name), parent_id.as_deref())
+                    .await
+                {
+                    Ok(branch) => (branch, true),
+                    // Another job for the same PR may have created it first
+                    Err(e) => match existing(client.list_branches(&project_id).await?) {
+                        Some(branch) => (branch, false),
+                        None => return Err(e),
+                    },
+                },
+            };
+
+            wait_until_ready(&client, &project_id, &branch.id, &database, timeout).await?;
+            let connection = client
+                .get_connection_string(&project_id, Some(&branch.id), Some(&database), None, true)
+                .await?;
+            anyhow::Ok((branch, created, connection))
+        })?;
+
+        Ok(serde_json::json!({
+            "branch": branch,
+            "created": created,
+            "connection_string": connection["connection_string"],
+            "connection": connection,
+        }))
+    }
+
+    /// Branch tagging implementation.
+    fn tag_branch(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let update = TagUpdate::from_params(&params)?;
+        if update.is_empty() {
+            anyhow::bail!("Pass owner, team, expires_at, or expires_in_hours");
+        }
+
+        let client = self.client.clone();
+        let branch = self.runtime.block_on({
+            let (client, project_id, branch_id) =
+                (client.clone(), project_id.clone(), branch_id.clone());
+            async move {
+                client
+                    .list_branches(&project_id)
+                    .await?
+                    .into_iter()
+                    .find(|b| b.id == branch_id)
+                    .ok_or_else(|| anyhow::anyhow!("Branch not found: {}", branch_id))
+            }
+        })?;
+
+        let current = TagIndex::load()?.tags(&branch);
+        // Keep the annotation in step, so the tags travel with the branch
+        let annotation = current.clone().apply(&update).annotation();
+        self.runtime.block_on({
+            let (project_id, branch_id) = (project_id.clone(), branch_id.clone());
+            async move {
+                client
+                    .annotate_branch(&project_id, &branch_id, &annotation)
+                    .await
+            }
+        })?;
+        let tags = branch_tags::set(&project_id, &branch_id, current, &update)?;
+
+        Ok(serde_json::json!({
+            "project_id": project_id,
+            "branch_id": branch_id,
+            "name": branch.name,
+            "tags": tags,
+            "expired": tags.expired(chrono::Utc::now()),
+        }))
+    }
+
+    /// Delete branch implementation.
+    fn delete_branch(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::get_param_str(&params, "project_id")
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: project_id"))?
+            .to_string();
+        let branch_id = Self::get_param_str(&params, "branch_id")
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: branch_id"))?
+            .to_string();
+
+        let client = self.client.clone();
+        let deleted = branch_id.clone();
+
+        self.runtime
+            .block_on(async move { client.delete_branch(&project_id, &branch_id).await })?;
+
+        if let Err(e) = branch_tags::forget(&deleted) {
+            tracing::warn!("Failed to drop tags of branch {}: {:#}", deleted, e);
+        }
+
+        Ok(serde_json::json!({ "deleted": true }))
+    }
+
+    /// Branch restore implementation.
+    fn restore_branch(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let source_branch_id = Self::get_param_str(&params, "source_branch_id")
+            .unwrap_or(&branch_id)
+            .to_string();
+        let source_lsn = Self::get_param_str(&params, "lsn").map(|s| s.to_string());
+        let source_timestamp = Self::get_param_str(&params, "timestamp")
+            .map(|t| {
+                chrono::DateTime::parse_from_rfc3339(t)
+                    .map(|t| t.with_timezone(&chrono::Utc).to_rfc3339())
+                    .map_err(|e| anyhow::anyhow!("timestamp must be RFC 3339: {}", e))
+            })
+            .transpose()?;
+
+        if source_lsn.is_some() && source_timestamp.is_some() {
+            anyhow::bail!("Pass either lsn or timestamp, not both");
+        }
+        // A branch's own head is where it already is
+        if source_branch_id == branch_id && source_lsn.is_none() && source_timestamp.is_none() {
+            anyhow::bail!(
+                "Restoring a branch from itself needs a timestamp or lsn; pass source_branch_id to restore from another branch's head"
+            );
+        }
+
+        let request = RestoreBranchRequest {
+            source_branch_id,
+            source_lsn,
+            source_timestamp,
+            preserve_under_name: Self::get_param_str(&params, "preserve_under_name")
+                .map(|s| s.to_string()),
+        };
+        let preserved_as = request.preserve_under_name.clone();
+        let client = self.client.clone();
+
+        let restored = self.runtime.block_on(async move {
+            client
+                .restore_branch(&project_id, &branch_id, &request)
+                .await
+        })?;
+
+        let operation_ids: Vec<&str> = restored.operations.iter().map(|o| o.id.as_str()).collect();
+        Ok(serde_json::json!({
+            "branch": restored.branch,
+            "operation_ids": operation_ids,
+            "operations": restored.operations,
+            "preserved_as": preserved_as,
+        }))
+    }
+
+    /// Branch reset implementation: a restore from the parent's head.
+    fn reset_branch(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let preserve_under_name =
+            Self::get_param_str(&params, "preserve_under_name").map(|s| s.to_string());
+        let preserved_as = preserve_under_name.clone();
+        let client = self.client.clone();
+
+        let (parent_id, restored) = self.runtime.block_on(async move {
+            let branch = client
+                .list_branches(&project_id)
+                .await?
+                .into_iter()
+                .find(|b| b.id == branch_id)
+                .ok_or_else(|| anyhow::anyhow!("Branch not found: {}", branch_id))?;
+            let Some(parent_id) = branch.parent_id else {
+                anyhow::bail!("Branch {} has no parent to reset from", branch_id);
+            };
+
+            let request = RestoreBranchRequest {
+                source_branch_id: parent_id.clone(),
+                preserve_under_name,
+                ..Default::default()
+            };
+            let restored = client
+                .restore_branch(&project_id, &branch_id, &request)
+                .await?;
+            anyhow::Ok((parent_id, restored))
+        })?;
+
+        let operation_ids: Vec<&str> = restored.operations.iter().map(|o| o.id.as_str()).collect();
+        Ok(serde_json::json!({
+            "branch": restored.branch,
+            "parent_id": parent_id,
+            "operation_ids": operation_ids,
+            "operations": restored.operations,
+            "preserved_as": preserved_as,
+        }))
+    }
+
+    /// Get connection string implementation.
+    fn get_connection_string(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::get_param_str(&params, "project_id")
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: project_id"))?
+            .to_string();
+        let branch_id = Self::get_param_str(&params, "branch_id").map(|s| s.to_string());
+        let database = Self::get_param_str(&params, "database").map(|s| s.to_string());
+        let role = Self::get_param_str(&params, "role").map(|s| s.to_string());
+        let pooled = params
+            .get("pooled")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let client = self.client.clone();
+
+        let result = self.runtime.block_on(async move {
+            client
+                .get_connection_string(
+                    &project_id,
+                    branch_id.as_deref(),
+                    database.as_deref(),
+                    role.as_deref(),
+                    pooled,
+                )
+                .await
+        })?;
+
+        Ok(result)
+    }
+
+    /// Column statistics implementation.
+    fn column_stats(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let schema = Self::get_param_str(&params, "schema")
+            .unwrap_or("public")
+            .to_string();
+        let table = Self::require_param_str(&params, "table")?;
+
+        let client = self.client.clone();
+
+        let mut stats = self.runtime.block_on(async move {
+            client
+                .get_column_stats(&project_id, &branch_id, &database, &schema, &table)
+                .await
+        })?;
+
+        let empty = stats
+            .get("rows")
+            .and_then(|r| r.as_array())
+            .map(|rows| rows.is_empty())
+            .unwrap_or(true);
+        if empty {
+            if let Some(obj) = stats.as_object_mut() {
+                obj.insert(
+                    "hint".into(),
+                    serde_json::json!("No statistics found. Run ANALYZE on the table first."),
+                );
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Table profiling implementation.
+    fn profile(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let schema = Self::get_param_str(&params, "schema")
+            .unwrap_or("public")
+            .to_string();
+        let table = Self::require_param_str(&params, "table")?;
+        let sample_rows = params
+            .get("sample_rows")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(10_000)
+            .min(100_000) as usize;
+
+        let client = self.client.clone();
+
+        self.runtime.block_on(async move {
+            client
+                .profile_table(
+                    &project_id,
+                    &branch_id,
+                    &database,
+                    &schema,
+                    &table,
+                    sample_rows,
+                )
+                .await
+        })
+    }
+
+    /// Resolve an expected schema from `snapshot`, `snapshot_file`, `ddl`, or `ddl_file`.
+    fn expected_schema(params: &HashMap<String, Value>) -> Result<SchemaSnapshot> {
+        if let Some(snapshot) = params.get("snapshot") {
+            return serde_json::from_value(snapshot.clone()).context("Invalid snapshot");
+        }
+        if let Some(path) = Self::get_param_str(params, "snapshot_file") {
+            let path = shellexpand::tilde(path).to_string();
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read snapshot file {}", path))?;
+            return serde_json::from_str(&text).context("Invalid snapshot file");
+        }
+        if let Some(ddl) = Self::get_param_str(params, "ddl") {
+            return schema::parse_ddl(ddl);
+        }
+        if let Some(path) = Self::get_param_str(params, "ddl_file") {
+            let path = shellexpand::tilde(path).to_string();
+            let ddl = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read DDL file {}", path))?;
+            return schema::parse_ddl(&ddl);
+        }
+        anyhow::bail!("Provide one of: ddl, ddl_file, snapshot, snapshot_file")
+    }
+
+    /// Schema validation implementation.
+    fn schema_validate(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let expected = Self::expected_schema(&params)?;
+
+        let client = self.client.clone();
+
+        let actual = self.runtime.block_on(async move {
+            client
+                .get_schema_snapshot(&project_id, &branch_id, &database)
+                .await
+        })?;
+
+        let diff = schema::diff(&expected, &actual);
+
+        Ok(serde_json::json!({
+            "valid": diff.is_empty(),
+            "expected_tables": expected.tables.len(),
+            "actual_tables": actual.tables.len(),
+            "diff": diff,
+        }))
+    }
+
+    /// Drift history implementation.
+    fn drift_history(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let names: Vec<String> = match Self::get_param_str(&params, "target") {
+            // Only configured targets, so the name can't reach outside the state dir
+            Some(name) if self.config.drift.targets.iter().any(|t| t.name == name) => {
+                vec![name.to_string()]
+            }
+            Some(name) => {
+                return Err(unknown_preset(
+                    "drift target",
+                    name,
+                    self.config.drift.targets.iter().map(|t| &t.name),
+                ))
+            }
+            None => self
+                .config
+                .drift
+                .targets
+                .iter()
+                .map(|t| t.name.clone())
+                .collect(),
+        };
+
+        let mut targets = Vec::new();
+        for name in names {
+            let state = drift::load_state(&name)?;
+            targets.push(serde_json::json!({
+                "target": name,
+                "last_checked": state.last_checked,
+                "tables": state.last_snapshot.map(|s| s.tables.len()),
+                "history": state.history,
+            }));
+        }
+
+        Ok(serde_json::json!({
+            "targets": targets,
+            "count": targets.len(),
+        }))
+    }
+
+    /// Declarative apply implementation.
+    fn apply(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let spec: Spec = match (
+            params.get("spec"),
+            Self::get_param_str(&params, "spec_file"),
+        ) {
+            (Some(spec), _) => serde_json::from_value(spec.clone()).context("Invalid spec")?,
+            (None, Some(path)) => Spec::from_file(path)?,
+            (None, None) => anyhow::bail!("Provide one of: spec, spec_file"),
+        };
+        let options = ApplyOptions {
+            execute: !params
+                .get("dry_run")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true),
+            prune: params
+                .get("prune")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+        };
+
+        let client = self.client.clone();
+
+        let steps = self
+            .runtime
+            .block_on(async move { spec::reconcile(&client, &spec, options).await })?;
+
+        Ok(serde_json::json!({
+            "dry_run": !options.execute,
+            "steps": steps,
+            "changes": steps.len(),
+        }))
+    }
+
+    /// Export live state as a spec implementation.
+    fn export_state(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_ids: Option<Vec<String>> = match params.get("project_id") {
+            Some(Value::String(id)) => Some(vec![id.clone()]),
+            Some(Value::Array(ids)) => Some(
+                ids.iter()
+                    .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                    .collect(),
+            ),
+            _ => None,
+        };
+        let format = Self::get_param_str(&params, "format")
+            .unwrap_or("json")
+            .to_string();
+        let output_file = Self::get_param_str(&params, "output_file").map(|s| s.to_string());
+
+        let client = self.client.clone();
+
+        let spec = self
+            .runtime
+            .block_on(async move { spec::export(&client, project_ids.as_deref()).await })?;
+
+        let mut result = serde_json::json!({
+            "spec": spec,
+            "projects": spec.projects.len(),
+            "format": format,
+        });
+
+        if format != "json" || output_file.is_some() {
+            let text = spec.render(&format)?;
+            match output_file {
+                Some(path) => {
+                    let path = shellexpand::tilde(&path).to_string();
+                    std::fs::write(&path, &text)
+                        .with_context(|| format!("Failed to write {}", path))?;
+                    result["output_file"] = serde_json::json!(path);
+                }
+                None => result["text"] = serde_json::json!(text),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Operations event stream implementation (long poll).
+    fn watch(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let since = params
+            .get("since")
+            .and_then(|v| v.as_u64())
+            .unwrap_or_else(|| self.events.head());
+        let timeout = params
+            .get("timeout_secs")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(30)
+            .min(60);
+        let project_id = Self::get_param_str(&params, "project_id").map(|s| s.to_string());
+        let kinds: Vec<String> = params
+            .get("kinds")
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        // Script and import progress is published whether or not the poller runs
+        let progress_only = !kinds.is_empty()
+            && kinds
+                .iter()
+                .all(|k| k == "script_statement" || k == "import_progress");
+        if !self.config.watch.enabled && !progress_only {
+            anyhow::bail!("Operations watcher is disabled. Set [watch] enabled = true in config.");
+        }
+
+        let events = self.events.clone();
+
+        let received = self.runtime.block_on(async move {
+            events
+                .wait_since(since, std::time::Duration::from_secs(timeout))
+                .await
+        });
+
+        // The cursor advances past filtered-out events too
+        let cursor = received.last().map(|e| e.seq).unwrap_or(since);
+        let matching: Vec<_> = received
+            .into_iter()
+            .filter(|e| match &project_id {
+                Some(p) => &e.project_id == p,
+                None => true,
+            })
+            .filter(|e| kinds.is_empty() || kinds.contains(&e.kind))
+            .collect();
+
+        Ok(serde_json::json!({
+            "events": matching,
+            "count": matching.len(),
+            "cursor": cursor,
+        }))
+    }
+
+    /// Endpoint warm-up implementation.
+    fn warm(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let probe = params
+            .get("probe")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        let timeout = params
+            .get("timeout_secs")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(60)
+            .min(300);
+
+        let client = self.client.clone();
+
+        self.runtime.block_on(async move {
+            let started = std::time::Instant::now();
+            let endpoint = client
+                .wake_endpoint(
+                    &project_id,
+                    &branch_id,
+                    std::time::Duration::from_secs(timeout),
+                )
+                .await?;
+            let wake_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+            let probe_ms = if probe {
+                let probe_start = std::time::Instant::now();
+                client
+                    .run_sql(&project_id, &branch_id, &database, "SELECT 1")
+                    .await
+                    .context("Endpoint is active but the probe query failed")?;
+                Some(probe_start.elapsed().as_secs_f64() * 1000.0)
+            } else {
+                None
+            };
+
+            Ok(serde_json::json!({
+                "endpoint_id": endpoint.id,
+                "host": endpoint.host,
+                "state": endpoint.current_state,
+                "wake_ms": wake_ms,
+                "probe_ms": probe_ms,
+                "ready": true,
+            }))
+        })
+    }
+
+    /// Cold-start statistics implementation.
+    fn stats(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::get_param_str(&params, "project_id");
+        let endpoints = self.client.cold_starts().report(project_id);
+
+        Ok(serde_json::json!({
+            "endpoints": endpoints,
+            "count": endpoints.len(),
+            "activity": self.clients.activity(),
+        }))
+    }
+
+    /// Query history implementation.
+    fn history(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let limit = params
+            .get("limit")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(50)
+            .min(1000) as usize;
+        let tag = Self::get_param_str(&params, "tag");
+        let entries = self.history.recent(limit, tag);
+
+        Ok(serde_json::json!({
+            "entries": entries,
+            "count": entries.len(),
+        }))
+    }
+
+    /// Replay implementation: run a history entry's query again, on its
+    /// branch or another one.
+    fn replay(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let id = params
+            .get("id")
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| anyhow::anyhow!("Missing required parameter: id"))?;
+        let entry = self
+            .history
+            .get(id)
+            .ok_or_else(|| anyhow::anyhow!("No history entry {}", id))?;
+
+        // The caller's options (max_rows, backend, format, ...) apply; the
+        // target and tag come from the entry unless overridden
+        let mut call = params.clone();
+        call.remove("id");
+        call.insert("project_id".into(), Value::String(entry.project_id.clone()));
+        call.entry("branch_id".into())
+            .or_insert_with(|| Value::String(entry.branch_id.clone()));
+        call.entry("database".into())
+            .or_insert_with(|| Value::String(entry.database.clone()));
+        match &entry.tag {
+            Some(tag) => call.insert("tag".into(), Value::String(tag.clone())),
+            None => call.remove("tag"),
+        };
+        // The env was checked against the caller's project, not the entry's
+        let call = envs::apply(&self.config.envs, "replay", call)?;
+
+        let mut result = self.execute_sql(&call, entry.query.clone(), entry.params.clone())?;
+        if let Some(obj) = result.as_object_mut() {
+            obj.insert(
+                "replayed_from".into(),
+                serde_json::json!({
+                    "id": entry.id,
+                    "at": entry.at,
+                    "branch_id": entry.branch_id,
+                    "database": entry.database,
+                    "duration_ms": entry.duration_ms,
+                    "rows": entry.rows,
+                    "error": entry.error,
+                }),
+            );
+        }
+        Ok(result)
+    }
+
+    /// Schema snapshot capture implementation.
+    fn schema_snapshot(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let label = Self::require_param_str(&params, "label")?;
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+
+        let client = self.client.clone();
+
+        let snapshot = self.runtime.block_on(async move {
+            snapshots::capture(&client, &label, &project_id, &branch_id, &database).await
+        })?;
+
+        Ok(serde_json::to_value(snapshot)?)
+    }
+
+    /// Schema snapshot listing implementation.
+    fn schema_snapshots(&self) -> Result<Value> {
+        let summaries: Vec<_> = snapshots::list()?
+            .into_iter()
+            .map(|s| {
+                serde_json::json!({
+                    "label": s.label,
+                    "project_id": s.project_id,
+                    "branch_id": s.branch_id,
+                    "database": s.database,
+                    "created_at": s.created_at,
+                    "statement_count": s.statements.len(),
+                })
+            })
+            .collect();
+
+        Ok(serde_json::json!({
+            "snapshots": summaries,
+            "count": summaries.len(),
+        }))
+    }
+
+    /// Schema snapshot restore implementation.
+    fn schema_restore(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let label = Self::require_param_str(&params, "label")?;
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let dry_run = params
+            .get("dry_run")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let snapshot = snapshots::load(&label)?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or(&snapshot.database)
+            .to_string();
+
+        if dry_run {
+            return Ok(serde_json::json!({
+                "label": label,
+                "dry_run": true,
+                "statements": snapshot.statements,
+            }));
+        }
+
+        let client = self.client.clone();
+
+        let applied = self.runtime.block_on(async move {
+            snapshots::restore(&client, &snapshot, &project_id, &branch_id, &database).await
+        })?;
+
+        Ok(serde_json::json!({
+            "label": label,
+            "dry_run": false,
+            "applied": applied,
+        }))
+    }
+
+    /// Branch freeze implementation.
+    fn freeze_branch(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+
+        let client = self.client.clone();
+
+        let frozen = self.runtime.block_on(async move {
+            freeze::freeze(&client, &project_id, &branch_id, &database).await
+        })?;
+
+        Ok(serde_json::json!({
+            "frozen": true,
+            "revoked_count": frozen.revoked.len(),
+            "state": frozen,
+        }))
+    }
+
+    /// Branch unfreeze implementation.
+    fn unfreeze_branch(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+
+        let client = self.client.clone();
+
+        let frozen = self.runtime.block_on(async move {
+            freeze::unfreeze(&client, &project_id, &branch_id, &database).await
+        })?;
+
+        Ok(serde_json::json!({
+            "frozen": false,
+            "restored_count": frozen.revoked.len(),
+            "frozen_at": frozen.frozen_at,
+        }))
+    }
+
+    /// Frozen branch listing implementation.
+    fn frozen_branches(&self) -> Result<Value> {
+        let frozen = freeze::list()?;
+
+        Ok(serde_json::json!({
+            "branches": frozen,
+            "count": frozen.len(),
+        }))
+    }
+
+    /// Connection lease implementation.
+    fn lease_connection(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let granted_role = Self::get_param_str(&params, "role")
+            .unwrap_or_else(|| self.config.default_role(&project_id, &database))
+            .to_string();
+        let ttl = params
+            .get("ttl_secs")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(3600)
+            .clamp(60, 86_400);
+
+        let client = self.client.clone();
+
+        let issued = self.runtime.block_on(async move {
+            leases::create(
+                &client,
+                &project_id,
+                &branch_id,
+                &database,
+                &granted_role,
+                std::time::Duration::from_secs(ttl),
+            )
+            .await
+        })?;
+
+        Ok(serde_json::to_value(issued)?)
+    }
+
+    /// Lease revocation implementation.
+    fn revoke_lease(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let role = Self::require_param_str(&params, "lease_role")?;
+        let client = self.client.clone();
+
+        let lease = self
+            .runtime
+            .block_on(async move { leases::revoke(&client, &role).await })?;
+
+        Ok(serde_json::json!({
+            "revoked": true,
+            "lease": lease,
+        }))
+    }
+
+    /// Lease listing implementation.
+    fn leases(&self) -> Result<Value> {
+        let leases = leases::list()?;
+
+        Ok(serde_json::json!({
+            "leases": leases,
+            "count": leases.len(),
+        }))
+    }
+
+    /// Ephemeral branch implementation.
+    fn create_ephemeral_branch(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let name = Self::get_param_str(&params, "name").map(|s| s.to_string());
+        let parent_id = Self::get_param_str(&params, "parent_id").map(|s| s.to_string());
+        let ttl = params
+            .get("ttl_seconds")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(3600)
+            .clamp(60, 7 * 86_400);
+
+        let client = self.client.clone();
+
+        let (branch, entry) = self.runtime.block_on(async move {
+            ephemeral::create(
+                &client,
+                &project_id,
+                name.as_deref(),
+                parent_id.as_deref(),
+                std::time::Duration::from_secs(ttl),
+            )
+            .await
+        })?;
+
+        Ok(serde_json::json!({
+            "branch": branch,
+            "expires_at": entry.expires_at,
+            "ttl_seconds": ttl,
+        }))
+    }
+
+    /// Ephemeral branch listing implementation.
+    fn ephemeral_branches(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::get_param_str(&params, "project_id");
+        let mut branches = ephemeral::list()?;
+        if let Some(project_id) = project_id {
+            branches.retain(|b| b.project_id == project_id);
+        }
+
+        Ok(serde_json::json!({
+            "branches": branches,
+            "count": branches.len(),
+        }))
+    }
+
+    /// Billing-period usage report implementation.
+    fn usage_report(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let month = Self::get_param_str(&params, "month").map(|s| s.to_string());
+        let project_ids = Self::project_ids(&params);
+
+        let client = self.client.clone();
+
+        let report = self.runtime.block_on(async move {
+            usage::report(&client, month.as_deref(), &project_ids).await
+        })?;
+
+        let markdown = report.to_markdown();
+        let mut value = serde_json::to_value(report)?;
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert("markdown".into(), Value::String(markdown));
+        }
+        Ok(value)
+    }
+
+    /// Consumption metrics implementation.
+    fn consumption(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let from = Self::get_param_str(&params, "from").map(|s| s.to_string());
+        let to = Self::get_param_str(&params, "to").map(|s| s.to_string());
+        let granularity = Self::get_param_str(&params, "granularity")
+            .unwrap_or("daily")
+            .to_string();
+        let project_ids = Self::project_ids(&params);
+
+        let client = self.client.clone();
+
+        let consumption = self.runtime.block_on(async move {
+            usage::consumption(
+                &client,
+                from.as_deref(),
+                to.as_deref(),
+                &granularity,
+                &project_ids,
+            )
+            .await
+        })?;
+
+        Ok(serde_json::to_value(consumption)?)
+    }
+
+    /// Helper to get `project_id` given as a string or an array of strings.
+    fn project_ids(params: &HashMap<String, Value>) -> Vec<String> {
+        match params.get("project_id") {
+            Some(Value::String(id)) => vec![id.clone()],
+            Some(Value::Array(ids)) => ids
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Template-based project creation implementation.
+    fn create_project_from_template(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let name = Self::require_param_str(&params, "name")?;
+        let template_name = Self::require_param_str(&params, "template")?;
+        let template = self
+            .config
+            .templates
+            .get(&template_name)
+            .cloned()
+            .ok_or_else(|| {
+                unknown_preset("template", &template_name, self.config.templates.keys())
+            })?;
+
+        let client = self.client.clone();
+
+        let outcome = self.runtime.block_on(async move {
+            templates::create_project(&client, &name, &template_name, &template).await
+        })?;
+
+        Ok(serde_json::to_value(outcome)?)
+    }
+
+    fn bootstrap(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let profile_name = Self::require_param_str(&params, "profile")?;
+        let branch_id = Self::get_param_str(&params, "branch_id").map(str::to_string);
+        let mut profile = self
+            .config
+            .bootstrap
+            .get(&profile_name)
+            .cloned()
+            .ok_or_else(|| {
+                unknown_preset(
+                    "bootstrap profile",
+                    &profile_name,
+                    self.config.bootstrap.keys(),
+                )
+            })?;
+
+        for database in &mut profile.databases {
+            if database.owner.is_none() {
+                database.owner = Some(
+                    self.config
+                        .default_role(&project_id, &database.name)
+                        .to_string(),
+                );
+            }
+        }
+
+        let client = self.client.clone();
+        let events = self.events.clone();
+
+        let outcome = self.runtime.block_on(async move {
+            let branch_id = match branch_id {
+                Some(id) => id,
+                None => {
+                    let branches = client.list_branches(&project_id).await?;
+                    branches
+                        .iter()
+                        .find(|b| b.default)
+                        .or_else(|| branches.first())
+                        .map(|b| b.id.clone())
+                        .ok_or_else(|| anyhow::anyhow!("Project {} has no branches", project_id))?
+                }
+            };
+            bootstrap::run(
+                &client,
+                &events,
+                &profile_name,
+                &profile,
+                &project_id,
+                &branch_id,
+            )
+            .await
+        })?;
+
+        Ok(serde_json::to_value(outcome)?)
+    }
+
+    fn compare_query(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let query = Self::require_param_str(&params, "query")?;
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let other_project_id = Self::get_param_str(&params, "other_project_id")
+            .map(str::to_string)
+            .unwrap_or_else(|| project_id.clone());
+        let other_branch_id = Self::require_param_str(&params, "other_branch_id")?;
+        let other_database = Self::get_param_str(&params, "other_database")
+            .map(str::to_string)
+            .unwrap_or_else(|| database.clone());
+        let redact = self.redactor.applies(Self::allow_secrets(&params))?;
+
+        let client = self.client.clone();
+        // Comparing results never needs to write
+        let page = SqlPage {
+            max_rows: client.max_rows(),
+            offset: 0,
+            backend: None,
+            target: SqlTarget::Auto,
+            role: None,
+            read_only: true,
+        };
+
+        let (mut left, mut right) = self.on_sql_runtime(async move {
+            let (left, right) = tokio::join!(
+                client.run_sql_page(&project_id, &branch_id, &database, &query, &[], page),
+                client.run_sql_page(
+                    &other_project_id,
+                    &other_branch_id,
+                    &other_database,
+                    &query,
+                    &[],
+                    page
+                ),
+            );
+            let left =
+                left.with_context(|| format!("Query failed on {}/{}", project_id, branch_id))?;
+            let right = right.with_context(|| {
+                format!("Query failed on {}/{}", other_project_id, other_branch_id)
+            })?;
+            anyhow::Ok((left, right))
+        })?;
+
+        // A diff of partial results would report rows that are merely cut off
+        for result in [&left, &right] {
+            if result.get("truncated").and_then(|v| v.as_bool()) == Some(true) {
+                anyhow::bail!(
+                    "Result exceeds limits.max_rows; narrow the query or raise the limit to compare"
+                );
+            }
+        }
+
+        if redact {
+            self.redactor.redact(&mut left);
+            self.redactor.redact(&mut right);
+        }
+
+        let diff = compare::diff(&left, &right);
+        Ok(serde_json::json!({
+            "diff": diff,
+            "left": left,
+            "right": right,
+        }))
+    }
+
+    /// Row-level table diff implementation.
+    fn data_diff(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let left = datadiff::Side {
+            branch_id: Self::require_param_str(&params, "branch_id")?,
+            project_id: project_id.clone(),
+            database: database.clone(),
+        };
+        let right = datadiff::Side {
+            project_id: Self::get_param_str(&params, "other_project_id")
+                .map(str::to_string)
+                .unwrap_or(project_id),
+            branch_id: Self::require_param_str(&params, "other_branch_id")?,
+            database: Self::get_param_str(&params, "other_database")
+                .map(str::to_string)
+                .unwrap_or(database),
+        };
+        let table = Self::require_param_str(&params, "table")?;
+        // `schema.table` is accepted in place of a separate schema
+        let (schema, table) = match (
+            Self::get_param_str(&params, "schema"),
+            table.split_once('.'),
+        ) {
+            (Some(schema), _) => (schema.to_string(), table),
+            (None, Some((schema, name))) => (schema.to_string(), name.to_string()),
+            (None, None) => ("public".to_string(), table),
+        };
+        let key = match params.get("key") {
+            None | Some(Value::Null) => None,
+            Some(Value::String(column)) => Some(vec![column.clone()]),
+            Some(Value::Array(columns)) => Some(
+                columns
+                    .iter()
+                    .map(|c| {
+                        c.as_str()
+                            .map(str::to_string)
+                            .ok_or_else(|| anyhow::anyhow!("key must list column names"))
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            Some(_) => anyhow::bail!("key must be a column name or a list of them"),
+        };
+        if key.as_ref().is_some_and(|k| k.is_empty()) {
+            anyhow::bail!("key must name at least one column");
+        }
+        let options = datadiff::DiffOptions {
+            schema,
+            table,
+            key,
+            samples: params
+                .get("samples")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0)
+                .min(datadiff::MAX_SAMPLES as u64) as usize,
+            max_rows: params
+                .get("max_rows")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(DATA_DIFF_MAX_ROWS),
+        };
+        let redact = self.redactor.applies(Self::allow_secrets(&params))?;
+
+        let client = self.client.clone();
+
+        let diff =
+            self.on_sql_runtime(
+                async move { datadiff::diff(&client, &left, &right, &options).await },
+            )?;
+
+        let mut response = serde_json::to_value(diff)?;
+        if redact {
+            if let Some(samples) = response.get_mut("samples") {
+                let redacted = self.redactor.redact_value(samples);
+                if redacted > 0 {
+                    response["redacted"] = serde_json::json!(redacted);
+                }
+            }
+        }
+        if response.get("complete").and_then(|v| v.as_bool()) == Some(false) {
+            response["warnings"] = serde_json::json!([warnings::warning(
+                "truncated",
+                "Not every differing bucket was compared; counts are lower bounds. Raise max_rows or limits.max_rows to compare more"
+            )]);
+        }
+        Ok(response)
+    }
+
+    fn auth_create_integration(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let role = Self::get_param_str(&params, "role")
+            .map(str::to_string)
+            .unwrap_or_else(|| self.config.default_role(&project_id, &database).to_string());
+
+        let client = self.client.clone();
+
+        let integration = self.runtime.block_on(async move {
+            client
+                .create_auth_integration(&project_id, &branch_id, &database, &role)
+                .await
+        })?;
+
+        Ok(serde_json::to_value(integration)?)
+    }
+
+    fn auth_integrations(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+
+        let client = self.client.clone();
+
+        let integrations = self
+            .runtime
+            .block_on(async move { client.list_auth_integrations(&project_id).await })?;
+
+        Ok(serde_json::json!({
+            "integrations": integrations,
+            "count": integrations.len(),
+        }))
+    }
+
+    fn auth_delete_integration(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+
+        let client = self.client.clone();
+        let id = project_id.clone();
+
+        self.runtime
+            .block_on(async move { client.delete_auth_integration(&id).await })?;
+
+        Ok(serde_json::json!({
+            "deleted": true,
+            "project_id": project_id,
+        }))
+    }
+
+    fn auth_users(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let include_deleted = params
+            .get("include_deleted")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let client = self.client.clone();
+
+        self.runtime.block_on(async move {
+            client
+                .list_auth_users(&project_id, &branch_id, &database, include_deleted)
+                .await
+        })
+    }
+
+    fn auth_create_user(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let email = Self::require_param_str(&params, "email")?;
+        let name = Self::get_param_str(&params, "name").map(str::to_string);
+
+        let client = self.client.clone();
+        let (id, address) = (project_id.clone(), email.clone());
+
+        let user_id = self.runtime.block_on(async move {
+            client
+                .create_auth_user(&id, &address, name.as_deref())
+                .await
+        })?;
+
+        Ok(serde_json::json!({
+            "id": user_id,
+            "email": email,
+            "project_id": project_id,
+        }))
+    }
+
+    fn auth_delete_user(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let user_id = Self::require_param_str(&params, "user_id")?;
+
+        let client = self.client.clone();
+        let (project, user) = (project_id.clone(), user_id.clone());
+
+        self.runtime
+            .block_on(async move { client.delete_auth_user(&project, &user).await })?;
+
+        Ok(serde_json::json!({
+            "deleted": true,
+            "project_id": project_id,
+            "user_id": user_id,
+        }))
+    }
+
+    fn data_api_enable(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+
+        let mut settings = serde_json::Map::new();
+        for key in [
+            "auth_provider",
+            "jwks_url",
+            "jwt_audience",
+            "add_default_grants",
+        ] {
+            if let Some(v) = params.get(key) {
+                settings.insert(key.to_string(), v.clone());
+            }
+        }
+
+        let client = self.client.clone();
+
+        let data_api = self.runtime.block_on(async move {
+            client
+                .enable_data_api(&project_id, &branch_id, &database, &Value::Object(settings))
+                .await
+        })?;
+
+        Ok(serde_json::json!({
+            "enabled": true,
+            "data_api": data_api,
+        }))
+    }
+
+    fn data_api_status(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+
+        let client = self.client.clone();
+
+        let data_api = self.runtime.block_on(async move {
+            client
+                .get_data_api(&project_id, &branch_id, &database)
+                .await
+        })?;
+
+        Ok(serde_json::json!({
+            "enabled": data_api.is_some(),
+            "data_api": data_api,
+        }))
+    }
+
+    fn list_jwks(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+
+        let client = self.client.clone();
+
+        let jwks = self
+            .runtime
+            .block_on(async move { client.list_jwks(&project_id).await })?;
+
+        Ok(serde_json::json!({
+            "jwks": jwks,
+            "count": jwks.len(),
+        }))
+    }
+
+    /// Scoped API key minting implementation.
+    fn mint_token(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::get_param_str(&params, "branch_id").map(|s| s.to_string());
+        let name = Self::get_param_str(&params, "name")
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| {
+                format!(
+                    "fgp-{}-{}",
+                    branch_id.as_deref().unwrap_or(&project_id),
+                    chrono::Utc::now().format("%Y%m%d%H%M%S")
+                )
+            });
+        let hours = match params.get("expires_in_hours") {
+            None | Some(Value::Null) => 24,
+            Some(v) => v
+                .as_u64()
+                .filter(|h| (1..=MAX_TOKEN_HOURS).contains(h))
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "expires_in_hours must be a whole number from 1 to {}",
+                        MAX_TOKEN_HOURS
+                    )
+                })?,
+        };
+        let revoke_by = chrono::Utc::now()
+            .checked_add_signed(chrono::Duration::hours(hours as i64))
+            .ok_or_else(|| anyhow::anyhow!("expires_in_hours is too far in the future"))?;
+
+        let client = self.client.clone();
+        let project = project_id.clone();
+        let key = self
+            .runtime
+            .block_on(async move { client.create_project_api_key(&project, &name).await })?;
+
+        let mut warnings = Vec::new();
+        if let Some(branch_id) = &branch_id {
+            warnings.push(warnings::warning(
+                "notice",
+                format!(
+                    "Neon API keys can't be limited to a branch; this key can act on every branch of project {}, not just {}",
+                    project_id, branch_id
+                ),
+            ));
+        }
+
+        let mut result = serde_json::to_value(&key)?;
+        if let Some(obj) = result.as_object_mut() {
+            obj.insert("project_id".into(), serde_json::json!(project_id));
+            obj.insert("branch_id".into(), serde_json::json!(branch_id));
+            obj.insert("scope".into(), serde_json::json!("project"));
+            obj.insert(
+                "revoke_by".into(),
+                serde_json::json!(revoke_by.to_rfc3339()),
+            );
+            obj.insert(
+                "reminder".into(),
+                serde_json::json!(format!(
+                    "Neon API keys don't expire on their own. Revoke key {} ({}) by {} in the Neon console under Organization settings > API keys; the key is only shown now.",
+                    key.id,
+                    key.name,
+                    revoke_by.format("%Y-%m-%d %H:%M UTC")
+                )),
+            );
+            obj.insert("warnings".into(), serde_json::json!(warnings));
+        }
+        Ok(result)
+    }
+
+    fn add_jwks(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let jwks_url = Self::require_param_str(&params, "jwks_url")?;
+        let provider_name = Self::require_param_str(&params, "provider_name")?;
+        let role_names: Vec<String> = params
+            .get("role_names")
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if role_names.is_empty() {
+            anyhow::bail!("role_names must list at least one Postgres role");
+        }
+
+        let mut settings = serde_json::Map::new();
+        for key in ["branch_id", "jwt_audience"] {
+            if let Some(v) = params.get(key) {
+                settings.insert(key.to_string(), v.clone());
+            }
+        }
+
+        let client = self.client.clone();
+
+        let jwks = self.runtime.block_on(async move {
+            client
+                .add_jwks(
+                    &project_id,
+                    &jwks_url,
+                    &provider_name,
+                    &role_names,
+                    &Value::Object(settings),
+                )
+                .await
+        })?;
+
+        Ok(serde_json::to_value(jwks)?)
+    }
+
+    fn delete_jwks(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let jwks_id = Self::require_param_str(&params, "jwks_id")?;
+
+        let client = self.client.clone();
+        let (project, id) = (project_id.clone(), jwks_id.clone());
+
+        self.runtime
+            .block_on(async move { client.delete_jwks(&project, &id).await })?;
+
+        Ok(serde_json::json!({
+            "deleted": true,
+            "project_id": project_id,
+            "jwks_id": jwks_id,
+        }))
+    }
+
+    fn previews(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let refresh = params
+            .get("refresh")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let pr_only = params
+            .get("pr_only")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        // Without the watcher nothing would invalidate a cached board
+        let cached = if self.config.watch.enabled && !refresh {
+            self.previews.get(&project_id)
+        } else {
+            None
+        };
+        let from_cache = cached.is_some();
+
+        let mut board = match cached {
+            Some(board) => board,
+            None => {
+                let client = self.client.clone();
+                let board = self
+                    .runtime
+                    .block_on(async move { previews::load(&client, &project_id).await })?;
+                self.previews.store(board.clone());
+                board
+            }
+        };
+
+        if pr_only {
+            board.previews.retain(|p| p.pr_number.is_some());
+        }
+
+        let mut result = serde_json::to_value(&board)?;
+        if let Some(obj) = result.as_object_mut() {
+            obj.insert("count".into(), serde_json::json!(board.previews.len()));
+            obj.insert("cached".into(), serde_json::json!(from_cache));
+        }
+        Ok(result)
+    }
+
+    fn update_check(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let cached = params
+            .get("cached")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        if cached {
+            return Ok(serde_json::to_value(self.updates.last())?);
+        }
+
+        let updates = self.updates.clone();
+        let status = self
+            .runtime
+            .block_on(async move { updates.check().await })?;
+
+        Ok(serde_json::to_value(status)?)
+    }
+
+    fn list_clients(&self) -> Result<Value> {
+        let clients = self.clients.list();
+
+        Ok(serde_json::json!({
+            "clients": clients,
+            "count": clients.len(),
+            "max_connections": self.clients.max_connections(),
+            "rejected_connections": self.clients.rejected_connections(),
+            "workers": self.clients.workers(),
+            "in_flight": self.clients.in_flight(),
+        }))
+    }
+
+    fn create_project(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let name = Self::require_param_str(&params, "name")?;
+        let branch = ProjectBranchRequest {
+            name: Self::get_param_str(&params, "default_branch_name").map(str::to_string),
+            role_name: Self::get_param_str(&params, "role_name").map(str::to_string),
+            database_name: Self::get_param_str(&params, "database_name").map(str::to_string),
+        };
+        let has_branch =
+            branch.name.is_some() || branch.role_name.is_some() || branch.database_name.is_some();
+        let request = CreateProjectRequest {
+            name,
+            region_id: Self::get_param_str(&params, "region_id").map(str::to_string),
+            pg_version: params
+                .get("pg_version")
+                .and_then(|v| v.as_i64())
+                .map(|v| v as i32),
+            branch: has_branch.then_some(branch),
+            settings: Self::typed_param(&params, "settings")?,
+            default_endpoint_settings: Self::typed_param(&params, "default_endpoint_settings")?,
+            history_retention_seconds: params
+                .get("history_retention_seconds")
+                .and_then(|v| v.as_u64()),
+        };
+        let wait = params.get("wait").and_then(|v| v.as_bool()).unwrap_or(true);
+
+        let client = self.client.clone();
+
+        let created = self.runtime.block_on(async move {
+            let created = client.create_project(&request).await?;
+            if wait {
+                client
+                    .wait_for_idle(&created.project.id, templates::PROVISION_TIMEOUT)
+                    .await?;
+            }
+            anyhow::Ok(created)
+        })?;
+
+        Ok(serde_json::to_value(created)?)
+    }
+
+    fn update_project(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let request = UpdateProjectRequest {
+            name: Self::get_param_str(&params, "name").map(str::to_string),
+            settings: Self::typed_param(&params, "settings")?,
+            default_endpoint_settings: Self::typed_param(&params, "default_endpoint_settings")?,
+            history_retention_seconds: params
+                .get("history_retention_seconds")
+                .and_then(|v| v.as_u64()),
+        };
+        if request.name.is_none()
+            && request.settings.is_none()
+            && request.default_endpoint_settings.is_none()
+            && request.history_retention_seconds.is_none()
+        {
+            anyhow::bail!(
+                "Provide at least one of: name, settings, default_endpoint_settings, history_retention_seconds"
+            );
+        }
+
+        let client = self.client.clone();
+
+        let project = self
+            .runtime
+            .block_on(async move { client.update_project(&project_id, &request).await })?;
+
+        Ok(serde_json::to_value(project)?)
+    }
+
+    fn delete_project(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+
+        let client = self.client.clone();
+
+        self.runtime
+            .block_on(async move { client.delete_project(&project_id).await })?;
+
+        Ok(serde_json::json!({ "deleted": true }))
+    }
+
+    /// Deserialize an optional object param into a typed model.
+    fn typed_param<T: serde::de::DeserializeOwned>(
+        params: &HashMap<String, Value>,
+        name: &str,
+    ) -> Result<Option<T>> {
+        params
+            .get(name)
+            .filter(|v| !v.is_null())
+            .map(|v| serde_json::from_value(v.clone()))
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("Invalid {}: {}", name, e))
+    }
+
+    fn list_endpoints(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::get_param_str(&params, "branch_id").map(str::to_string);
+
+        let client = self.client.clone();
+
+        let mut endpoints = self
+            .runtime
+            .block_on(async move { client.list_endpoints(&project_id).await })?;
+        if let Some(branch_id) = branch_id {
+            endpoints.retain(|e| e.branch_id == branch_id);
+        }
+
+        Ok(serde_json::json!({
+            "endpoints": endpoints,
+            "count": endpoints.len(),
+        }))
+    }
+
+    fn create_endpoint(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let endpoint_type = Self::get_param_str(&params, "type")
+            .unwrap_or("read_write")
+            .to_string();
+        if !matches!(endpoint_type.as_str(), "read_write" | "read_only") {
+            anyhow::bail!("type must be read_write or read_only");
+        }
+        let mut settings = serde_json::Map::new();
+        for key in [
+            "autoscaling_limit_min_cu",
+            "autoscaling_limit_max_cu",
+            "suspend_timeout_seconds",
+        ] {
+            if let Some(v) = params.get(key) {
+                settings.insert(key.to_string(), v.clone());
+            }
+        }
+
+        let client = self.client.clone();
+
+        let endpoint = self.runtime.block_on(async move {
+            client
+                .create_endpoint(
+                    &project_id,
+                    &branch_id,
+                    &endpoint_type,
+                    &Value::Object(settings),
+                )
+                .await
+        })?;
+
+        Ok(serde_json::to_value(endpoint)?)
+    }
+
+    fn delete_endpoint(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let endpoint_id = Self::require_param_str(&params, "endpoint_id")?;
+
+        let client = self.client.clone();
+
+        self.runtime
+            .block_on(async move { client.delete_endpoint(&project_id, &endpoint_id).await })?;
+
+        Ok(serde_json::json!({ "deleted": true }))
+    }
+
+    fn start_endpoint(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let endpoint_id = Self::require_param_str(&params, "endpoint_id")?;
+
+        let client = self.client.clone();
+
+        let endpoint = self
+            .runtime
+            .block_on(async move { client.start_endpoint(&project_id, &endpoint_id).await })?;
+
+        Ok(serde_json::to_value(endpoint)?)
+    }
+
+    fn suspend_endpoint(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let endpoint_id = Self::require_param_str(&params, "endpoint_id")?;
+
+        let client = self.client.clone();
+
+        let endpoint = self
+            .runtime
+            .block_on(async move { client.suspend_endpoint(&project_id, &endpoint_id).await })?;
+
+        Ok(serde_json::to_value(endpoint)?)
+    }
+
+    fn create_read_replica(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::require_param_str(&params, "branch_id")?;
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let role = Self::get_param_str(&params, "role").map(|s| s.to_string());
+        let number = |key: &str| params.get(key).and_then(|v| v.as_f64());
+        if let (Some(min), Some(max)) = (
+            number("autoscaling_limit_min_cu"),
+            number("autoscaling_limit_max_cu"),
+        ) {
+            if min > max {
+                anyhow::bail!("autoscaling_limit_min_cu is above autoscaling_limit_max_cu");
+            }
+        }
+        let mut settings = serde_json::Map::new();
+        for key in [
+            "autoscaling_limit_min_cu",
+            "autoscaling_limit_max_cu",
+            "suspend_timeout_seconds",
+        ] {
+            if let Some(v) = params.get(key) {
+                settings.insert(key.to_string(), v.clone());
+            }
+        }
+
+        let client = self.client.clone();
+        let project = project_id.clone();
+
+        let endpoint = self.runtime.block_on(async move {
+            client
+                .create_endpoint(&project, &branch_id, "read_only", &Value::Object(settings))
+                .await
+        })?;
+
+        self.replica(&project_id, endpoint, &database, role.as_deref())
+    }
+
+    fn list_replicas(&self, params: HashMap<String, Value>) -> Result<Value> {
+        let project_id = Self::require_param_str(&params, "project_id")?;
+        let branch_id = Self::get_param_str(&params, "branch_id").map(str::to_string);
+        let database = Self::get_param_str(&params, "database")
+            .unwrap_or("neondb")
+            .to_string();
+        let role = Self::get_param_str(&params, "role").map(|s| s.to_string());
+
+        let client = self.client.clone();
+        let project = project_id.clone();
+
+        let endpoints = self
+            .runtime
+            .block_on(async move { client.list_endpoints(&project).await })?;
+        let replicas = endpoints
+            .into_iter()
+            .filter(|e| e.endpoint_type == "read_only")
+            .filter(|e| branch_id.as_ref().is_none_or(|b| &e.branch_id == b))
+            .map(|e| self.replica(&project_id, e, &database, role.as_deref()))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(serde_json::json!({
+            "replicas": replicas,
+            "count": replicas.len(),
+        }))
+    }
+
+    /// A read replica endpoint with its connection details for `database`,
+    /// as `role` or the configured one.
+    fn replica(
+        &self,
+        project_id: &str,
+        endpoint: Endpoint,
+        database: &str,
+        role: Option<&str>,
+    ) -> Result<Value> {
+        let connection = self.runtime.block_on(
+            self.client
+                .endpoint_connection(project_id, &endpoint, database, role),
+        )?;
+        let mut replica = serde_json::to_value(endpoint)?;
+        if let Some(obj) = replica.as_object_mut() {
+            obj.insert("connection".into(), connection);
+        }
+        Ok(replica)
+    }
+
+    /// Run a method through the response cache and idempotency store.
+    fn call(&self, method: &str, params: HashMap<String, Value>) -> Result<Value> {
+        let cache_key = self.cache.key(method, &params);
+        if let Some(hit) = cache_key.as_ref().and_then(|k| self.cache.get(k, &params)) {
+            return Ok(hit);
+        }
+
+        let request = params.clone();
+        let result = match IdempotencyStore::key(method, &params)? {
+            Some(key) => self
+                .idempotency
+                .run(&key, method, &request, || self.route(method, params))?,
+            None => self.route(method, params)?,
+        };
+
+        if let Some(key) = cache_key {
+            self.cache.put(key, &request, &result);
+        } else if IdempotencyStore::is_mutating(method) {
+            match self.queries_run(method, &request) {
+                // Reads and row changes leave cached metadata as it was
+                Some(queries) => {
+                    let read_only =
+                        request.get("read_only").and_then(|v| v.as_bool()) == Some(true);
+                    if !read_only && queries.iter().any(|q| is_ddl(q)) {
+                        self.cache.invalidate_catalog(&request);
+                    }
+                }
+                None => self.cache.invalidate(&request),
+            }
+        }
+        Ok(result)
+    }
+
+    /// The caller's SQL a call ran, for methods that run nothing else;
+    /// `None` for other methods.
+    fn queries_run(&self, method: &str, params: &HashMap<String, Value>) -> Option<Vec<String>> {
+        let query = |name: &str| Self::get_param_str(params, name).map(str::to_string);
+        match method.strip_prefix("neon.").unwrap_or(method) {
+            "sql" | "sql_template" => query("query").map(|q| vec![q]),
+            "run_saved" => saved::get(&query("name")?).ok().map(|s| vec![s.query]),
+            "replay" => {
+                let id = params.get("id").and_then(|v| v.as_u64())?;
+                self.history.get(id).map(|e| vec![e.query])
+            }
+            "sql_batch" => Self::statements_param(params, "queries")
+                .ok()
+                .map(|queries| queries.into_iter().map(|q| q.query).collect()),
+            _ => None,
+        }
+    }
+
+    /// Run a method, returning its result before it is wrapped in the envelope.
+    fn route(&self, method: &str, params: HashMap<String, Value>) -> Result<Value> {
+        match method {
+            "health" => self.health(),
+            "projects" | "neon.projects" => self.list_projects(params),
+            "project" | "neon.project" => self.get_project(params),
+            "projects_search" | "neon.projects_search" => self.projects_search(params),
+            "branches" | "neon.branches" => self.list_branches(params),
+            "all_branches" | "neon.all_branches" => self.all_branches(params),
+            "databases" | "neon.databases" => self.list_databases(params),
+            "tables" | "neon.tables" => self.get_tables(params),
+            "schema" | "neon.schema" => self.get_table_schema(params),
+            "table_detail" | "neon.table_detail" => self.table_detail(params),
+            "introspect" | "neon.introspect" => self.introspect(params),
+            "views" | "neon.views" => self.list_views(params),
+            "functions" | "neon.functions" => self.list_functions(params),
+            "extensions" | "neon.extensions" => self.list_extensions(params),
+            "sizes" | "neon.sizes" => self.sizes(params),
+            "sql" | "neon.sql" => self.run_sql(params),
+            "sql_file" | "neon.sql_file" => self.sql_file(params),
+            "transaction" | "neon.transaction" => self.transaction(params),
+            "migrate_status" | "neon.migrate_status" => self.migrate_status(params),
+            "migrate_plan" | "neon.migrate_plan" => self.migrate_plan(params),
+            "migrate_apply" | "neon.migrate_apply" => self.migrate_apply(params),
+            "seed" | "neon.seed" => self.seed(params),
+            "import_csv" | "neon.import_csv" => self.import_csv(params),
+            "dump" | "neon.dump" => self.dump(params),
+            "sql_batch" | "neon.sql_batch" => self.sql_batch(params),
+            "mint_token" | "neon.mint_token" => self.mint_token(params),
+            "fetch_chunk" | "neon.fetch_chunk" => self.fetch_chunk(params),
+            "user" | "neon.user" => self.get_user(),
+            "create_branch" | "neon.create_branch" => self.create_branch(params),
+            "pr_branch" | "neon.pr_branch" => self.pr_branch(params),
+            "tag_branch" | "neon.tag_branch" => self.tag_branch(params),
+            "delete_branch" | "neon.delete_branch" => self.delete_branch(params),
+            "restore_branch" | "neon.restore_branch" => self.restore_branch(params),
+            "reset_branch" | "neon.reset_branch" => self.reset_branch(params),
+            "connection_string" | "neon.connection_string" => self.get_connection_string(params),
+            "column_stats" | "neon.column_stats" => self.column_stats(params),
+            "profile" | "neon.profile" => self.profile(params),
+            "schema_validate" | "neon.schema_validate" => self.schema_validate(params),
+            "drift_history" | "neon.drift_history" => self.drift_history(params),
+            "apply" | "neon.apply" => self.apply(params),
+            "export_state" | "neon.export_state" => self.export_state(params),
+            "watch" | "neon.watch" => self.watch(params),
+            "warm" | "neon.warm" => self.warm(params),
+            "stats" | "neon.stats" => self.stats(params),
+            "history" | "neon.history" => self.history(params),
+            "replay" | "neon.replay" => self.replay(params),
+            "sql_template" | "neon.sql_template" => self.sql_template(params),
+            "saved_queries" | "neon.saved_queries" => self.saved_queries(params),
+            "run_saved" | "neon.run_saved" => self.run_saved(params),
+            "schema_snapshot" | "neon.schema_snapshot" => self.schema_snapshot(params),
+            "schema_snapshots" | "neon.schema_snapshots" => self.schema_snapshots(),
+            "schema_restore" | "neon.schema_restore" => self.schema_restore(params),
+            "freeze_branch" | "neon.freeze_branch" => self.freeze_branch(params),
+            "unfreeze_branch" | "neon.unfreeze_branch" => self.unfreeze_branch(params),
+            "frozen_branches" | "neon.frozen_branches" => self.frozen_branches(),
+            "lease_connection" | "neon.lease_connection" => self.lease_connection(params),
+            "revoke_lease" | "neon.revoke_lease" => self.revoke_lease(params),
+            "leases" | "neon.leases" => self.leases(),
+            "create_ephemeral_branch" | "neon.create_ephemeral_branch" => {
+                self.create_ephemeral_branch(params)
+            }
+            "ephemeral_branches" | "neon.ephemeral_branches" => self.ephemeral_branches(params),
+            "usage_report" | "neon.usage_report" => self.usage_report(params),
+            "consumption" | "neon.consumption" => self.consumption(params),
+            "create_project_from_template" | "neon.create_project_from_template" => {
+                self.create_project_from_template(params)
+            }
+            "bootstrap" | "neon.bootstrap" => self.bootstrap(params),
+            "compare_query" | "neon.compare_query" => self.compare_query(params),
+            "data_diff" | "neon.data_diff" => self.data_diff(params),
+            "auth_create_integration" | "neon.auth_create_integration" => {
+                self.auth_create_integration(params)
+            }
+            "auth_integrations" | "neon.auth_integrations" => self.auth_integrations(params),
+            "auth_delete_integration" | "neon.auth_delete_integration" => {
+                self.auth_delete_integration(params)
+            }
+            "auth_users" | "neon.auth_users" => self.auth_users(params),
+            "auth_create_user" | "neon.auth_create_user" => self.auth_create_user(params),
+            "auth_delete_user" | "neon.auth_delete_user" => self.auth_delete_user(params),
+            "data_api_enable" | "neon.data_api_enable" => self.data_api_enable(params),
+            "data_api_status" | "neon.data_api_status" => self.data_api_status(params),
+            "list_jwks" | "neon.list_jwks" => self.list_jwks(params),
+            "add_jwks" | "neon.add_jwks" => self.add_jwks(params),
+            "delete_jwks" | "neon.delete_jwks" => self.delete_jwks(params),
+            "previews" | "neon.previews" => self.previews(params),
+            "update_check" | "neon.update_check" => self.update_check(params),
+            "clients" | "neon.clients" => self.list_clients(),
+            "create_project" | "neon.create_project" => self.create_project(params),
+            "update_project" | "neon.update_project" => self.update_project(params),
+            "delete_project" | "neon.delete_project" => self.delete_project(params),
+            "endpoints" | "neon.endpoints" => self.list_endpoints(params),
+            "create_endpoint" | "neon.create_endpoint" => self.create_endpoint(params),
+            "create_read_replica" | "neon.create_read_replica" => self.create_read_replica(params),
+            "replicas" | "neon.replicas" => self.list_replicas(params),
+            "delete_endpoint" | "neon.delete_endpoint" => self.delete_endpoint(params),
+            "start_endpoint" | "neon.start_endpoint" => self.start_endpoint(params),
+            "suspend_endpoint" | "neon.suspend_endpoint" => self.suspend_endpoint(params),
+            _ => anyhow::bail!("Unknown method: {}", method),
+        }
+    }
+}
+
+impl FgpService for NeonService {
+    fn name(&self) -> &str {
+        "neon"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn dispatch(&self, method: &str, params: HashMap<String, Value>) -> Result<Value> {
+        let started = std::time::Instant::now();
+        let trace_id = trace::from_params(&params)?;
+        let span = tracing::info_span!("call", method, trace_id = trace_id.as_deref());
+        let _entered = span.enter();
+
+        let fields = Fields::from_params(&params)?;
+        let render = Render::from_params(&params)?;
+        let deprecations = warnings::deprecations(method, &params);
+        let params = envs::apply(&self.config.envs, method, params)?;
+
+        let result = trace::scope(trace_id.as_deref(), || self.call(method, params))?;
+        let mut response = envelope::wrap(result, started.elapsed(), trace_id);
+        if let Some(Value::Array(warnings)) = response.get_mut("warnings") {
+            warnings.extend(deprecations);
+        }
+        if let (Some(fields), Some(data)) = (fields, response.get_mut("data")) {
+            fields.apply(data);
+        }
+        if let Some(render) = render {
+            render.apply(&mut response);
+        }
+        Ok(response)
+    }
+
+    fn method_list(&self) -> Vec<MethodInfo> {
+        vec![
+            MethodInfo {
+                name: "neon.projects".into(),
+                description: "List all Neon projects".into(),
+                params: vec![
+                    ParamInfo {
+                        name: "limit".into(),
+                        param_type: "integer".into(),
+                        required: false,
+                        default: Some(serde_json::json!(10)),
+                    },
+                    param("cursor", "string", false, None),
+                    param("all", "boolean", false, Some(serde_json::json!(false))),
+                    param("fresh", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.projects_search".into(),
+                description: "Search projects by name, id, region, or tag (substring and fuzzy) from a local index".into(),
+                params: vec![
+                    param("query", "string", false, None),
+                    param("region_id", "string", false, None),
+                    param("tag", "string", false, None),
+                    param("limit", "integer", false, Some(serde_json::json!(20))),
+                    param("refresh", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.project".into(),
+                description: "Get a specific project with settings, owner, and quota status".into(),
+                params: vec![
+                    ParamInfo {
+                        name: "project_id".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    param("expand", "array", false, None),
+                    param("fresh", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.branches".into(),
+                description: "List branches for a project, with tags, idle time, and staleness".into(),
+                params: vec![
+                    ParamInfo {
+                        name: "project_id".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    param("stale_after_days", "integer", false, Some(serde_json::json!(7))),
+                    param("owner", "string", false, None),
+                    param("team", "string", false, None),
+                    param("unowned", "boolean", false, Some(serde_json::json!(false))),
+                    param("expired", "boolean", false, Some(serde_json::json!(false))),
+                    param("fresh", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.all_branches".into(),
+                description: "List branches across every project, or the given ones, as one deduplicated list annotated with project, tags, idle time, and staleness".into(),
+                params: vec![
+                    param("project_ids", "array", false, None),
+                    param("stale_after_days", "integer", false, Some(serde_json::json!(7))),
+                    param("stale_only", "boolean", false, Some(serde_json::json!(false))),
+                    param("owner", "string", false, None),
+                    param("team", "string", false, None),
+                    param("unowned", "boolean", false, Some(serde_json::json!(false))),
+                    param("expired", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.databases".into(),
+                description: "List databases for a branch".into(),
+                params: vec![
+                    ParamInfo {
+                        name: "project_id".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "branch_id".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    param("fresh", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.tables".into(),
+                description: "List tables in a database".into(),
+                params: vec![
+                    ParamInfo {
+                        name: "project_id".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "branch_id".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "database".into(),
+                        param_type: "string".into(),
+                        required: false,
+                        default: Some(serde_json::json!("neondb")),
+                    },
+                    ParamInfo {
+                        name: "prefix".into(),
+                        param_type: "string".into(),
+                        required: false,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "limit".into(),
+                        param_type: "integer".into(),
+                        required: false,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "cursor".into(),
+                        param_type: "string".into(),
+                        required: false,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "offset".into(),
+                        param_type: "integer".into(),
+                        required: false,
+                        default: Some(serde_json::json!(0)),
+                    },
+                    param("fresh", "boolean", false, Some(serde_json::json!(false))),
+                    param("format", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.schema".into(),
+                description: "Get table schema".into(),
+                params: vec![
+                    ParamInfo {
+                        name: "project_id".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "branch_id".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "database".into(),
+                        param_type: "string".into(),
+                        required: false,
+                        default: Some(serde_json::json!("neondb")),
+                    },
+                    ParamInfo {
+                        name: "table".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    param("fresh", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.table_detail".into(),
+                description: "Describe a table: columns, primary key, unique/check constraints, foreign keys in both directions, and indexes".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("table", "string", true, None),
+                    param("schema", "string", false, Some(serde_json::json!("public"))),
+                    param("fresh", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.introspect".into(),
+                description: "Describe every table (columns, keys, constraints, foreign keys, indexes) and view in a database in one call".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("schema", "string", false, None),
+                    param("fresh", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.views".into(),
+                description: "List views and materialized views with their definitions".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("schema", "string", false, None),
+                    param("fresh", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.functions".into(),
+                description: "List functions, procedures, and aggregates with their signatures".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("schema", "string", false, None),
+                    param("fresh", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.extensions".into(),
+                description: "List installed extensions and whether an update is available".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("fresh", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.sizes".into(),
+                description: "Database size and the largest tables by total, table, index, or TOAST bytes".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("sort", "string", false, Some(serde_json::json!("total"))),
+                    param("limit", "integer", false, Some(serde_json::json!(20))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.sql".into(),
+                description: "Run a SQL query".into(),
+                params: vec![
+                    ParamInfo {
+                        name: "project_id".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "branch_id".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "database".into(),
+                        param_type: "string".into(),
+                        required: false,
+                        default: Some(serde_json::json!("neondb")),
+                    },
+                    ParamInfo {
+                        name: "query".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "max_rows".into(),
+                        param_type: "integer".into(),
+                        required: false,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "offset".into(),
+                        param_type: "integer".into(),
+                        required: false,
+                        default: Some(serde_json::json!(0)),
+                    },
+                    ParamInfo {
+                        name: "tag".into(),
+                        param_type: "string".into(),
+                        required: false,
+                        default: None,
+                    },
+                    param("columns", "array", false, None),
+                    param("limit", "integer", false, None),
+                    param("aggregate", "object", false, None),
+                    param("jsonpath", "string", false, None),
+                    param("allow_secrets", "boolean", false, Some(serde_json::json!(false))),
+                    param("cost_guard", "boolean", false, None),
+                    param("max_cost", "integer", false, None),
+                    param("max_estimated_rows", "integer", false, None),
+                    param("backend", "string", false, None),
+                    param("target", "string", false, Some(serde_json::json!("auto"))),
+                    param("stream", "boolean", false, Some(serde_json::json!(false))),
+                    param("chunk_size", "integer", false, None),
+                    param("format", "string", false, None),
+                    param("role", "string", false, None),
+                    param("read_only", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.user".into(),
+                description: "Get current user info".into(),
+                params: vec![],
+            },
+            MethodInfo {
+                name: "neon.create_branch".into(),
+                description: "Create a new branch, optionally tagged with an owner, team, and expiry".into(),
+                params: vec![
+                    ParamInfo {
+                        name: "project_id".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "name".into(),
+                        param_type: "string".into(),
+                        required: false,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "parent_id".into(),
+                        param_type: "string".into(),
+                        required: false,
+                        default: None,
+                    },
+                    param("wait_ready", "boolean", false, Some(serde_json::json!(false))),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("timeout_secs", "integer", false, Some(serde_json::json!(120))),
+                    param("owner", "string", false, None),
+                    param("team", "string", false, None),
+                    param("expires_at", "string", false, None),
+                    param("expires_in_hours", "number", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.tag_branch".into(),
+                description: "Set or clear a branch's owner, team, and expiry tags; an empty string clears one".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("owner", "string", false, None),
+                    param("team", "string", false, None),
+                    param("expires_at", "string", false, None),
+                    param("expires_in_hours", "number", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.pr_branch".into(),
+                description: "Create or fetch the pr-<n> branch for a pull request, wait until it accepts connections, and return its pooled connection string".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("pr", "string", true, None),
+                    param("parent_id", "string", false, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("timeout_secs", "integer", false, Some(serde_json::json!(120))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.delete_branch".into(),
+                description: "Delete a branch".into(),
+                params: vec![
+                    ParamInfo {
+                        name: "project_id".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "branch_id".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                ],
+            },
+            MethodInfo {
+                name: "neon.restore_branch".into(),
+                description: "Restore a branch to a timestamp or LSN in its own or another branch's history".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("source_branch_id", "string", false, None),
+                    param("timestamp", "string", false, None),
+                    param("lsn", "string", false, None),
+                    param("preserve_under_name", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.reset_branch".into(),
+                description: "Reset a branch to its parent's current state".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("preserve_under_name", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.connection_string".into(),
+                description: "Get connection string for a branch".into(),
+                params: vec![
+                    ParamInfo {
+                        name: "project_id".into(),
+                        param_type: "string".into(),
+                        required: true,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "branch_id".into(),
+                        param_type: "string".into(),
+                        required: false,
+                        default: None,
+                    },
+                    ParamInfo {
+                        name: "database".into(),
+                        param_type: "string".into(),
+                        required: false,
+                        default: Some(serde_json::json!("neondb")),
+                    },
+                    ParamInfo {
+                        name: "pooled".into(),
+                        param_type: "boolean".into(),
+                        required: false,
+                        default: Some(serde_json::json!(false)),
+                    },
+                    param("role", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.column_stats".into(),
+                description: "Get per-column statistics (null fraction, distinct estimate, MCVs, min/max) from pg_stats".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("schema", "string", false, Some(serde_json::json!("public"))),
+                    param("table", "string", true, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.profile".into(),
+                description: "Profile a table: row estimate, inferred types, null %, cardinality, sample values".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("schema", "string", false, Some(serde_json::json!("public"))),
+                    param("table", "string", true, None),
+                    param("sample_rows", "integer", false, Some(serde_json::json!(10_000))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.schema_validate".into(),
+                description: "Compare a branch's live schema against expected DDL or a schema snapshot".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("ddl", "string", false, None),
+                    param("ddl_file", "string", false, None),
+                    param("snapshot", "object", false, None),
+                    param("snapshot_file", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.drift_history".into(),
+                description: "Show recorded schema drift for configured drift targets".into(),
+                params: vec![param("target", "string", false, None)],
+            },
+            MethodInfo {
+                name: "neon.apply".into(),
+                description: "Plan (and with dry_run=false, apply) a declarative spec of projects, branches, endpoints, roles, and databases".into(),
+                params: vec![
+                    param("spec", "object", false, None),
+                    param("spec_file", "string", false, None),
+                    param("dry_run", "boolean", false, Some(serde_json::json!(true))),
+                    param("prune", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.export_state".into(),
+                description: "Export live projects, branches, endpoints, roles, and databases as a neon.apply spec".into(),
+                params: vec![
+                    param("project_id", "string", false, None),
+                    param("format", "string", false, Some(serde_json::json!("json"))),
+                    param("output_file", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.watch".into(),
+                description: "Wait for operation events (branch created, endpoint suspended, operation failed) after a cursor".into(),
+                params: vec![
+                    param("since", "integer", false, None),
+                    param("timeout_secs", "integer", false, Some(serde_json::json!(30))),
+                    param("project_id", "string", false, None),
+                    param("kinds", "array", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.warm".into(),
+                description: "Wake a branch's compute endpoint and wait until it is ready, optionally running a probe query".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("probe", "boolean", false, Some(serde_json::json!(true))),
+                    param("timeout_secs", "integer", false, Some(serde_json::json!(60))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.stats".into(),
+                description: "Per-endpoint cold-start counts and estimated resume latency for queries since daemon start, plus when each method and client was last used".into(),
+                params: vec![param("project_id", "string", false, None)],
+            },
+            MethodInfo {
+                name: "neon.history".into(),
+                description: "Recent neon.sql calls with tag, target, timing, and outcome".into(),
+                params: vec![
+                    param("limit", "integer", false, Some(serde_json::json!(50))),
+                    param("tag", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.replay".into(),
+                description: "Run a neon.history entry's query again, optionally on another branch or database".into(),
+                params: vec![
+                    param("id", "integer", true, None),
+                    param("branch_id", "string", false, None),
+                    param("database", "string", false, None),
+                    param("max_rows", "integer", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.sql_template".into(),
+                description: "Run a SQL query with :named placeholders bound from a params object".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("query", "string", true, None),
+                    param("params", "object", false, None),
+                    param("max_rows", "integer", false, None),
+                    param("offset", "integer", false, Some(serde_json::json!(0))),
+                    param("tag", "string", false, None),
+                    param("columns", "array", false, None),
+                    param("limit", "integer", false, None),
+                    param("aggregate", "object", false, None),
+                    param("jsonpath", "string", false, None),
+                    param("allow_secrets", "boolean", false, Some(serde_json::json!(false))),
+                    param("cost_guard", "boolean", false, None),
+                    param("max_cost", "integer", false, None),
+                    param("max_estimated_rows", "integer", false, None),
+                    param("backend", "string", false, None),
+                    param("target", "string", false, Some(serde_json::json!("auto"))),
+                    param("stream", "boolean", false, Some(serde_json::json!(false))),
+                    param("chunk_size", "integer", false, None),
+                    param("format", "string", false, None),
+                    param("role", "string", false, None),
+                    param("read_only", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.saved_queries".into(),
+                description: "List, get, save, or delete named queries in the shared library".into(),
+                params: vec![
+                    param("action", "string", false, Some(serde_json::json!("list"))),
+                    param("name", "string", false, None),
+                    param("query", "string", false, None),
+                    param("description", "string", false, None),
+                    param("database", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.run_saved".into(),
+                description: "Run a saved query by name, binding :named placeholders from params".into(),
+                params: vec![
+                    param("name", "string", true, None),
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, None),
+                    param("params", "object", false, None),
+                    param("max_rows", "integer", false, None),
+                    param("offset", "integer", false, Some(serde_json::json!(0))),
+                    param("tag", "string", false, None),
+                    param("columns", "array", false, None),
+                    param("limit", "integer", false, None),
+                    param("aggregate", "object", false, None),
+                    param("jsonpath", "string", false, None),
+                    param("allow_secrets", "boolean", false, Some(serde_json::json!(false))),
+                    param("cost_guard", "boolean", false, None),
+                    param("max_cost", "integer", false, None),
+                    param("max_estimated_rows", "integer", false, None),
+                    param("backend", "string", false, None),
+                    param("target", "string", false, Some(serde_json::json!("auto"))),
+                    param("stream", "boolean", false, Some(serde_json::json!(false))),
+                    param("chunk_size", "integer", false, None),
+                    param("format", "string", false, None),
+                    param("role", "string", false, None),
+                    param("read_only", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.schema_snapshot".into(),
+                description: "Save a branch database's DDL under a label".into(),
+                params: vec![
+                    param("label", "string", true, None),
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.schema_snapshots".into(),
+                description: "List saved schema snapshots".into(),
+                params: vec![],
+            },
+            MethodInfo {
+                name: "neon.schema_restore".into(),
+                description: "Apply a saved schema snapshot's DDL to an empty branch database".into(),
+                params: vec![
+                    param("label", "string", true, None),
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, None),
+                    param("dry_run", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.freeze_branch".into(),
+                description: "Revoke write privileges from non-owner roles on a branch database and record them".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.unfreeze_branch".into(),
+                description: "Restore the write privileges revoked by neon.freeze_branch".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.frozen_branches".into(),
+                description: "List frozen branch databases and the grants revoked from them".into(),
+                params: vec![],
+            },
+            MethodInfo {
+                name: "neon.lease_connection".into(),
+                description: "Issue a connection string for a temporary role that is dropped when the lease expires".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("role", "string", false, None),
+                    param("ttl_secs", "integer", false, Some(serde_json::json!(3600))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.revoke_lease".into(),
+                description: "Drop a lease role before it expires".into(),
+                params: vec![param("lease_role", "string", true, None)],
+            },
+            MethodInfo {
+                name: "neon.leases".into(),
+                description: "List outstanding connection leases".into(),
+                params: vec![],
+            },
+            MethodInfo {
+                name: "neon.create_ephemeral_branch".into(),
+                description: "Create a branch that the daemon deletes once its TTL expires".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("name", "string", false, None),
+                    param("parent_id", "string", false, None),
+                    param("ttl_seconds", "integer", false, Some(serde_json::json!(3600))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.ephemeral_branches".into(),
+                description: "List ephemeral branches and when each expires".into(),
+                params: vec![param("project_id", "string", false, None)],
+            },
+            MethodInfo {
+                name: "neon.usage_report".into(),
+                description: "Per-project (and, for the current month, per-branch) usage for a billing month, as JSON and Markdown".into(),
+                params: vec![
+                    param("month", "string", false, None),
+                    param("project_id", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.consumption".into(),
+                description: "Compute time, written data, data transfer, and synthetic storage per project over a time window, bucketed hourly, daily, or monthly".into(),
+                params: vec![
+                    param("from", "string", false, None),
+                    param("to", "string", false, None),
+                    param("granularity", "string", false, Some(serde_json::json!("daily"))),
+                    param("project_id", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.create_project_from_template".into(),
+                description: "Create a project from a configured template (region, Postgres version, compute limits, extensions, seed script)".into(),
+                params: vec![
+                    param("name", "string", true, None),
+                    param("template", "string", true, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.bootstrap".into(),
+                description: "Apply a configured day-zero setup to a branch: roles, databases, extensions, baseline migrations, then grant templates".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("profile", "string", true, None),
+                    param("branch_id", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.compare_query".into(),
+                description: "Run one query on two projects/branches and diff the results (columns, rows regardless of order, row order)".into(),
+                params: vec![
+                    param("query", "string", true, None),
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("other_project_id", "string", false, None),
+                    param("other_branch_id", "string", true, None),
+                    param("other_database", "string", false, None),
+                    param("allow_secrets", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.data_diff".into(),
+                description: "Compare a table's rows on two branches by key: added, removed, and changed counts, with optional sample rows".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("other_branch_id", "string", true, None),
+                    param("table", "string", true, None),
+                    param("schema", "string", false, Some(serde_json::json!("public"))),
+                    param("key", "array", false, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("other_project_id", "string", false, None),
+                    param("other_database", "string", false, None),
+                    param("samples", "integer", false, Some(serde_json::json!(0))),
+                    param("max_rows", "integer", false, Some(serde_json::json!(100_000))),
+                    param("allow_secrets", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.auth_create_integration".into(),
+                description: "Provision Neon Auth for a project; returns the client and server keys once".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("role", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.auth_integrations".into(),
+                description: "List a project's Neon Auth integrations".into(),
+                params: vec![param("project_id", "string", true, None)],
+            },
+            MethodInfo {
+                name: "neon.auth_delete_integration".into(),
+                description: "Remove a project's Neon Auth integration".into(),
+                params: vec![param("project_id", "string", true, None)],
+            },
+            MethodInfo {
+                name: "neon.auth_users".into(),
+                description: "List Neon Auth users synced into a branch database".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("include_deleted", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
             MethodInfo {
-                name: "neon.projects".into(),
-                description: "List all Neon projects".into(),
-                params: vec![ParamInfo {
-                    name: "limit".into(),
-                    param_type: "integer".into(),
-                    required: false,
-                    default: Some(serde_json::json!(10)),
-                }],
+                name: "neon.auth_create_user".into(),
+                description: "Create a Neon Auth user".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("email", "string", true, None),
+                    param("name", "string", false, None),
+                ],
             },
             MethodInfo {
-                name: "neon.project".into(),
-                description: "Get a specific project".into(),
-                params: vec![ParamInfo {
-                    name: "project_id".into(),
-                    param_type: "string".into(),
-                    required: true,
-                    default: None,
-                }],
+                name: "neon.auth_delete_user".into(),
+                description: "Delete a Neon Auth user".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("user_id", "string", true, None),
+                ],
             },
             MethodInfo {
-                name: "neon.branches".into(),
-                description: "List branches for a project".into(),
-                params: vec![ParamInfo {
-                    name: "project_id".into(),
-                    param_type: "string".into(),
-                    required: true,
-                    default: None,
-                }],
+                name: "neon.data_api_enable".into(),
+                description: "Enable the Data API (PostgREST-style REST endpoint) for a branch database; returns its URL".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("auth_provider", "string", false, None),
+                    param("jwks_url", "string", false, None),
+                    param("jwt_audience", "string", false, None),
+                    param("add_default_grants", "boolean", false, None),
+                ],
             },
             MethodInfo {
-                name: "neon.databases".into(),
-                description: "List databases for a branch".into(),
+                name: "neon.data_api_status".into(),
+                description: "Whether the Data API is enabled for a branch database, with its URL and JWT settings".into(),
                 params: vec![
-                    ParamInfo {
-                        name: "project_id".into(),
-                        param_type: "string".into(),
-                        required: true,
-                        default: None,
-                    },
-                    ParamInfo {
-                        name: "branch_id".into(),
-                        param_type: "string".into(),
-                        required: true,
-                        default: None,
-                    },
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
                 ],
             },
             MethodInfo {
-                name: "neon.tables".into(),
-                description: "List tables in a database".into(),
+                name: "neon.list_jwks".into(),
+                description: "List JWKS URLs trusted for JWT connections to a project".into(),
+                params: vec![param("project_id", "string", true, None)],
+            },
+            MethodInfo {
+                name: "neon.add_jwks".into(),
+                description: "Trust a JWKS URL for JWT connections and map its tokens to Postgres roles".into(),
                 params: vec![
-                    ParamInfo {
-                        name: "project_id".into(),
-                        param_type: "string".into(),
-                        required: true,
-                        default: None,
-                    },
-                    ParamInfo {
-                        name: "branch_id".into(),
-                        param_type: "string".into(),
-                        required: true,
-                        default: None,
-                    },
-                    ParamInfo {
-                        name: "database".into(),
-                        param_type: "string".into(),
-                        required: false,
-                        default: Some(serde_json::json!("neondb")),
-                    },
+                    param("project_id", "string", true, None),
+                    param("jwks_url", "string", true, None),
+                    param("provider_name", "string", true, None),
+                    param("role_names", "array", true, None),
+                    param("branch_id", "string", false, None),
+                    param("jwt_audience", "string", false, None),
                 ],
             },
             MethodInfo {
-                name: "neon.schema".into(),
-                description: "Get table schema".into(),
+                name: "neon.delete_jwks".into(),
+                description: "Stop trusting a JWKS URL".into(),
                 params: vec![
-                    ParamInfo {
-                        name: "project_id".into(),
-                        param_type: "string".into(),
-                        required: true,
-                        default: None,
-                    },
-                    ParamInfo {
-                        name: "branch_id".into(),
-                        param_type: "string".into(),
-                        required: true,
-                        default: None,
-                    },
-                    ParamInfo {
-                        name: "database".into(),
-                        param_type: "string".into(),
-                        required: false,
-                        default: Some(serde_json::json!("neondb")),
-                    },
-                    ParamInfo {
-                        name: "table".into(),
-                        param_type: "string".into(),
-                        required: true,
-                        default: None,
-                    },
+                    param("project_id", "string", true, None),
+                    param("jwks_id", "string", true, None),
                 ],
             },
             MethodInfo {
-                name: "neon.sql".into(),
-                description: "Run a SQL query".into(),
+                name: "neon.previews".into(),
+                description: "Preview environments for a dashboard: each non-default branch with its PR number, parent, endpoint state, size, and age".into(),
                 params: vec![
-                    ParamInfo {
-                        name: "project_id".into(),
-                        param_type: "string".into(),
-                        required: true,
-                        default: None,
-                    },
-                    ParamInfo {
-                        name: "branch_id".into(),
-                        param_type: "string".into(),
-                        required: true,
-                        default: None,
-                    },
-                    ParamInfo {
-                        name: "database".into(),
-                        param_type: "string".into(),
-                        required: false,
-                        default: Some(serde_json::json!("neondb")),
-                    },
-                    ParamInfo {
-                        name: "query".into(),
-                        param_type: "string".into(),
-                        required: true,
-                        default: None,
-                    },
+                    param("project_id", "string", true, None),
+                    param("pr_only", "boolean", false, Some(serde_json::json!(false))),
+                    param("refresh", "boolean", false, Some(serde_json::json!(false))),
                 ],
             },
             MethodInfo {
-                name: "neon.user".into(),
-                description: "Get current user info".into(),
+                name: "neon.update_check".into(),
+                description: "Compare the running version with the latest release".into(),
+                params: vec![param("cached", "boolean", false, Some(serde_json::json!(false)))],
+            },
+            MethodInfo {
+                name: "neon.clients".into(),
+                description: "Connected clients with peer uid/pid, connect time, and request counts".into(),
                 params: vec![],
             },
             MethodInfo {
-                name: "neon.create_branch".into(),
-                description: "Create a new branch".into(),
+                name: "neon.create_project".into(),
+                description: "Create a project; returns its branch, endpoint, role, database, and connection URIs".into(),
                 params: vec![
-                    ParamInfo {
-                        name: "project_id".into(),
-                        param_type: "string".into(),
-                        required: true,
-                        default: None,
-                    },
-                    ParamInfo {
-                        name: "name".into(),
-                        param_type: "string".into(),
-                        required: false,
-                        default: None,
-                    },
-                    ParamInfo {
-                        name: "parent_id".into(),
-                        param_type: "string".into(),
-                        required: false,
-                        default: None,
-                    },
+                    param("name", "string", true, None),
+                    param("region_id", "string", false, None),
+                    param("pg_version", "integer", false, None),
+                    param("default_branch_name", "string", false, None),
+                    param("role_name", "string", false, None),
+                    param("database_name", "string", false, None),
+                    param("settings", "object", false, None),
+                    param("default_endpoint_settings", "object", false, None),
+                    param("history_retention_seconds", "integer", false, None),
+                    param("wait", "boolean", false, Some(serde_json::json!(true))),
                 ],
             },
             MethodInfo {
-                name: "neon.delete_branch".into(),
-                description: "Delete a branch".into(),
+                name: "neon.update_project".into(),
+                description: "Rename a project or change its settings".into(),
                 params: vec![
-                    ParamInfo {
-                        name: "project_id".into(),
-                        param_type: "string".into(),
-                        required: true,
-                        default: None,
-                    },
-                    ParamInfo {
-                        name: "branch_id".into(),
-                        param_type: "string".into(),
-                        required: true,
-                        default: None,
-                    },
+                    param("project_id", "string", true, None),
+                    param("name", "string", false, None),
+                    param("settings", "object", false, None),
+                    param("default_endpoint_settings", "object", false, None),
+                    param("history_retention_seconds", "integer", false, None),
                 ],
             },
             MethodInfo {
-                name: "neon.connection_string".into(),
-                description: "Get connection string for a branch".into(),
+                name: "neon.delete_project".into(),
+                description: "Delete a project with all its branches and data".into(),
+                params: vec![param("project_id", "string", true, None)],
+            },
+            MethodInfo {
+                name: "neon.endpoints".into(),
+                description: "List compute endpoints with host, type, state, and autoscaling limits".into(),
                 params: vec![
-                    ParamInfo {
-                        name: "project_id".into(),
-                        param_type: "string".into(),
-                        required: true,
-                        default: None,
-                    },
-                    ParamInfo {
-                        name: "branch_id".into(),
-                        param_type: "string".into(),
-                        required: false,
-                        default: None,
-                    },
-                    ParamInfo {
-                        name: "database".into(),
-                        param_type: "string".into(),
-                        required: false,
-                        default: Some(serde_json::json!("neondb")),
-                    },
-                    ParamInfo {
-                        name: "pooled".into(),
-                        param_type: "boolean".into(),
-                        required: false,
-                        default: Some(serde_json::json!(false)),
-                    },
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.create_endpoint".into(),
+                description: "Create a read_write or read_only compute endpoint on a branch".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("type", "string", false, Some(serde_json::json!("read_write"))),
+                    param("autoscaling_limit_min_cu", "number", false, None),
+                    param("autoscaling_limit_max_cu", "number", false, None),
+                    param("suspend_timeout_seconds", "integer", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.create_read_replica".into(),
+                description: "Create a read_only endpoint on a branch and return its connection details".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("autoscaling_limit_min_cu", "number", false, None),
+                    param("autoscaling_limit_max_cu", "number", false, None),
+                    param("suspend_timeout_seconds", "integer", false, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("role", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.replicas".into(),
+                description: "List read replica endpoints with their connection details".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", false, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("role", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.delete_endpoint".into(),
+                description: "Delete a compute endpoint".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("endpoint_id", "string", true, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.start_endpoint".into(),
+                description: "Start a suspended compute endpoint".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("endpoint_id", "string", true, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.suspend_endpoint".into(),
+                description: "Suspend a compute endpoint; it resumes on the next connection".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("endpoint_id", "string", true, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.sql_file".into(),
+                description: "Run a multi-statement SQL script statement by statement, publishing per-statement progress events".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("path", "string", false, None),
+                    param("script", "string", false, None),
+                    param("run_id", "string", false, None),
+                    param("continue_on_error", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.migrate_status".into(),
+                description: "List migrations from a directory of <version>_<name>.sql files as applied, pending, modified, or missing on a branch".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("dir", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.migrate_plan".into(),
+                description: "Show the pending migrations neon.migrate_apply would run, and any problem that would stop it".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("dir", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.migrate_apply".into(),
+                description: "Apply pending migrations in version order, each in its own transaction under an advisory lock".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("dir", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("target_version", "integer", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.seed".into(),
+                description: "Run a seed SQL file, or a directory of them in name order, on a branch, optionally truncating the tables it fills first".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("path", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("truncate", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.import_csv".into(),
+                description: "Load a local CSV file into an existing table in batches, through COPY with the Postgres backend or multi-row INSERTs otherwise, publishing progress events".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("table", "string", true, None),
+                    param("path", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("columns", "object", false, None),
+                    param("header", "boolean", false, Some(serde_json::json!(true))),
+                    param("delimiter", "string", false, Some(serde_json::json!(","))),
+                    param("quote", "string", false, Some(serde_json::json!("\""))),
+                    param("null", "string", false, Some(serde_json::json!(""))),
+                    param("batch_rows", "integer", false, Some(serde_json::json!(500))),
+                    param("backend", "string", false, None),
+                    param("run_id", "string", false, None),
+                ],
+            },
+            MethodInfo {
+                name: "neon.dump".into(),
+                description: "Export the schema and/or rows of tables to a local SQL or newline-delimited JSON file, reading rows in batches".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("path", "string", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("tables", "array", false, None),
+                    param("format", "string", false, Some(serde_json::json!("sql"))),
+                    param("schema", "boolean", false, Some(serde_json::json!(true))),
+                    param("data", "boolean", false, Some(serde_json::json!(true))),
+                    param("overwrite", "boolean", false, Some(serde_json::json!(false))),
+                    param("mask", "boolean", false, Some(serde_json::json!(true))),
+                    param("allow_secrets", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.transaction".into(),
+                description: "Run statements atomically in one transaction on the branch's read-write endpoint, rolling back if any fails".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("statements", "array", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("isolation_level", "string", false, Some(serde_json::json!("read_committed"))),
+                    param("read_only", "boolean", false, Some(serde_json::json!(false))),
+                    param("backend", "string", false, None),
+                    param("allow_secrets", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.sql_batch".into(),
+                description: "Run independent queries against one branch, optionally concurrently, with per-query timing and errors".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", true, None),
+                    param("queries", "array", true, None),
+                    param("database", "string", false, Some(serde_json::json!("neondb"))),
+                    param("concurrency", "integer", false, Some(serde_json::json!(1))),
+                    param("max_rows", "integer", false, None),
+                    param("backend", "string", false, None),
+                    param("target", "string", false, Some(serde_json::json!("auto"))),
+                    param("allow_secrets", "boolean", false, Some(serde_json::json!(false))),
+                    param("tag", "string", false, None),
+                    param("cost_guard", "boolean", false, None),
+                    param("max_cost", "integer", false, None),
+                    param("max_estimated_rows", "integer", false, None),
+                    param("role", "string", false, None),
+                    param("read_only", "boolean", false, Some(serde_json::json!(false))),
+                ],
+            },
+            MethodInfo {
+                name: "neon.fetch_chunk".into(),
+                description: "Fetch the next chunk of a result streamed by neon.sql".into(),
+                params: vec![param("cursor", "string", true, None)],
+            },
+            MethodInfo {
+                name: "neon.mint_token".into(),
+                description: "Create an API key limited to one project to hand to a downstream job, with a reminder to revoke it".into(),
+                params: vec![
+                    param("project_id", "string", true, None),
+                    param("branch_id", "string", false, None),
+                    param("name", "string", false, None),
+                    param("expires_in_hours", "integer", false, Some(serde_json::json!(24))),
                 ],
             },
         ]
@@ -531,36 +4997,124 @@ impl FgpService for NeonService {
                     Err(e)
                 }
             }
-        })
+        })?;
+
+        self.spawn_background_tasks();
+        Ok(())
     }
 
     fn health_check(&self) -> HashMap<String, HealthStatus> {
         let mut checks = HashMap::new();
 
-        let client = self.client.clone();
-        let start = std::time::Instant::now();
-        let result = self.runtime.block_on(async move { client.ping().await });
+        let ping = self.ping();
+        let subsystems = self.subsystems(&ping);
 
-        let latency = start.elapsed().as_secs_f64() * 1000.0;
+        // Checks are pass/fail, so a degraded subsystem passes; its reasons
+        // are in `health`
+        for (name, subsystem) in &subsystems {
+            let status = match subsystem.status {
+                Level::Unhealthy => HealthStatus::unhealthy(subsystem.reasons.join("; ")),
+                _ if name == "neon_api" => HealthStatus::healthy_with_latency(ping.latency_ms),
+                _ => HealthStatus::healthy(),
+            };
+            checks.insert(name.clone(), status);
+        }
 
-        match result {
-            Ok(true) => {
-                checks.insert(
-                    "neon_api".into(),
-                    HealthStatus::healthy_with_latency(latency),
-                );
-            }
-            Ok(false) => {
-                checks.insert(
-                    "neon_api".into(),
-                    HealthStatus::unhealthy("API returned error"),
-                );
-            }
-            Err(e) => {
-                checks.insert("neon_api".into(), HealthStatus::unhealthy(e.to_string()));
-            }
+        for (name, task) in self.supervisor.tasks() {
+            let status = if task.running {
+                HealthStatus::healthy()
+            } else {
+                HealthStatus::unhealthy(format!(
+                    "restarting after {} restart(s); last exit: {}",
+                    task.restarts,
+                    task.last_exit.as_deref().unwrap_or("unknown")
+                ))
+            };
+            checks.insert(format!("task:{}", name), status);
+        }
+
+        for probe in self.canary.last() {
+            let status = match &probe.error {
+                Some(error) => HealthStatus::unhealthy(error.clone()),
+                None => HealthStatus::healthy_with_latency(probe.latency_ms),
+            };
+            checks.insert(format!("canary:{}", probe.route), status);
         }
 
         checks
     }
 }
+
+/// A multi-threaded runtime; `worker_threads` of 0 means one per CPU core.
+fn build_runtime(
+    name: &str,
+    worker_threads: usize,
+    max_blocking_threads: usize,
+) -> Result<Runtime> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder
+        .enable_all()
+        .thread_name(name)
+        .max_blocking_threads(max_blocking_threads.max(1));
+    if worker_threads > 0 {
+        builder.worker_threads(worker_threads);
+    }
+    builder
+        .build()
+        .with_context(|| format!("Failed to start the {} runtime", name))
+}
+
+/// Wait until a branch's compute is provisioned and accepts connections,
+/// adding a read-write endpoint if it has none.
+async fn wait_until_ready(
+    client: &NeonClient,
+    project_id: &str,
+    branch_id: &str,
+    database: &str,
+    timeout: std::time::Duration,
+) -> Result<()> {
+    let deadline = std::time::Instant::now() + timeout;
+    let remaining = || deadline.saturating_duration_since(std::time::Instant::now());
+    client.wait_for_idle(project_id, remaining()).await?;
+
+    // Branches created without endpoints have no compute to connect to
+    let has_compute = client
+        .list_endpoints(project_id)
+        .await?
+        .iter()
+        .any(|e| e.branch_id == branch_id && e.endpoint_type == "read_write");
+    if !has_compute {
+        client
+            .create_endpoint(project_id, branch_id, "read_write", &Value::Null)
+            .await?;
+        client.wait_for_idle(project_id, remaining()).await?;
+    }
+
+    // Provisioned isn't the same as accepting connections
+    loop {
+        match client
+            .run_sql(project_id, branch_id, database, "SELECT 1")
+            .await
+        {
+            Ok(_) => return Ok(()),
+            Err(e) if remaining().is_zero() => {
+                return Err(e.context(format!(
+                    "Branch {} did not accept connections within {}s",
+                    branch_id,
+                    timeout.as_secs()
+                )));
+            }
+            Err(_) => tokio::time::sleep(std::time::Duration::from_secs(1)).await,
+        }
+    }
+}
+
+/// Shorthand for building a [`ParamInfo`].
+fn param(name: &str, param_type: &str, required: bool, default: Option<Value>) -> ParamInfo {
+    ParamInfo {
+        name: name.into(),
+        param_type: param_type.into(),
+        required,
+        default,
+    }
+}
@@ -0,0 +1,139 @@
+//! Labeled schema snapshots.
+//!
+//! `neon.schema_snapshot` stores the DDL of a branch's database under a label
+//! in `~/.fgp/services/neon/schema_snapshots/<label>.json`;
+//! `neon.schema_restore` replays it onto an empty branch. Only schema is
+//! captured, never data.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::api::NeonClient;
+use crate::config::{check_state_name, state_path};
+
+/// A stored schema snapshot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DdlSnapshot {
+    pub label: String,
+    pub project_id: String,
+    pub branch_id: String,
+    pub database: String,
+    pub created_at: DateTime<Utc>,
+    /// Statements in the order they must run.
+    pub statements: Vec<String>,
+}
+
+/// Capture and store a snapshot, replacing any snapshot with the same label.
+pub async fn capture(
+    client: &NeonClient,
+    label: &str,
+    project_id: &str,
+    branch_id: &str,
+    database: &str,
+) -> Result<DdlSnapshot> {
+    check_state_name("snapshot label", label)?;
+
+    let statements = client
+        .get_schema_ddl(project_id, branch_id, database, None)
+        .await?;
+    let snapshot = DdlSnapshot {
+        label: label.to_string(),
+        project_id: project_id.to_string(),
+        branch_id: branch_id.to_string(),
+        database: database.to_string(),
+        created_at: Utc::now(),
+        statements,
+    };
+
+    let path = snapshot_path(label);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create snapshot directory")?;
+    }
+    std::fs::write(&path, serde_json::to_vec_pretty(&snapshot)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    Ok(snapshot)
+}
+
+/// Load a snapshot by label.
+pub fn load(label: &str) -> Result<DdlSnapshot> {
+    check_state_name("snapshot label", label)?;
+
+    let path = snapshot_path(label);
+    match std::fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            anyhow::bail!("No schema snapshot labeled {:?}", label)
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// All stored snapshots, newest first.
+pub fn list() -> Result<Vec<DdlSnapshot>> {
+    let dir = state_path("schema_snapshots");
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", dir.display())),
+    };
+
+    let mut snapshots = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        match std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(serde_json::from_slice::<DdlSnapshot>(&bytes)?))
+        {
+            Ok(snapshot) => snapshots.push(snapshot),
+            Err(e) => tracing::warn!("Skipping unreadable snapshot {}: {:#}", path.display(), e),
+        }
+    }
+
+    snapshots.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(snapshots)
+}
+
+/// Apply a snapshot's DDL to a branch whose database has no user tables.
+///
+/// Statements run one at a time; on failure the error names the statement,
+/// and earlier statements stay applied.
+pub async fn restore(
+    client: &NeonClient,
+    snapshot: &DdlSnapshot,
+    project_id: &str,
+    branch_id: &str,
+    database: &str,
+) -> Result<usize> {
+    let existing = client
+        .get_schema_snapshot(project_id, branch_id, database)
+        .await?;
+    if !existing.tables.is_empty() {
+        anyhow::bail!(
+            "Database {} on branch {} already has {} table(s); restore requires an empty database",
+            database,
+            branch_id,
+            existing.tables.len()
+        );
+    }
+
+    let total = snapshot.statements.len();
+    for (i, statement) in snapshot.statements.iter().enumerate() {
+        client
+            .run_sql(project_id, branch_id, database, statement)
+            .await
+            .with_context(|| format!("Statement {}/{} failed: {}", i + 1, total, statement))?;
+    }
+
+    Ok(total)
+}
+
+fn snapshot_path(label: &str) -> PathBuf {
+    state_path("schema_snapshots").join(format!("{}.json", label))
+}
@@ -0,0 +1,558 @@
+//! Declarative desired-state specs and reconciliation.
+//!
+//! A [`Spec`] lists projects and, per branch, the endpoints, roles, and
+//! databases that should exist. [`reconcile`] walks the spec against live Neon
+//! state and returns the steps needed to converge; with `execute` set it also
+//! performs them in order, waiting for Neon operations between steps.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::api::NeonClient;
+use crate::models::{Branch, CreateProjectRequest, Endpoint};
+
+/// How long to wait for Neon operations after each change.
+const OPERATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Desired state for a set of projects.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Spec {
+    #[serde(default)]
+    pub projects: Vec<ProjectSpec>,
+}
+
+/// Desired state for one project, matched by name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectSpec {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pg_version: Option<i32>,
+    #[serde(default)]
+    pub branches: Vec<BranchSpec>,
+}
+
+/// Desired state for one branch, matched by name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BranchSpec {
+    pub name: String,
+    /// Parent branch name; defaults to the project's default branch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub endpoints: Vec<EndpointSpec>,
+    #[serde(default)]
+    pub roles: Vec<RoleSpec>,
+    #[serde(default)]
+    pub databases: Vec<DatabaseSpec>,
+}
+
+/// Desired compute endpoint, matched by type in order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointSpec {
+    #[serde(rename = "type", default = "default_endpoint_type")]
+    pub endpoint_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autoscaling_limit_min_cu: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autoscaling_limit_max_cu: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suspend_timeout_seconds: Option<i64>,
+}
+
+/// Desired Postgres role.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoleSpec {
+    pub name: String,
+}
+
+/// Desired database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatabaseSpec {
+    pub name: String,
+    #[serde(default = "default_owner")]
+    pub owner: String,
+}
+
+fn default_endpoint_type() -> String {
+    "read_write".into()
+}
+
+fn default_owner() -> String {
+    "neondb_owner".into()
+}
+
+impl Spec {
+    /// Load a spec from a `.json`, `.yaml`/`.yml`, or `.toml` file.
+    pub fn from_file(path: &str) -> Result<Self> {
+        let path = shellexpand::tilde(path).to_string();
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read spec file {}", path))?;
+
+        if path.ends_with(".toml") {
+            toml::from_str(&text).with_context(|| format!("Invalid spec file {}", path))
+        } else if path.ends_with(".yaml") || path.ends_with(".yml") {
+            serde_yaml::from_str(&text).with_context(|| format!("Invalid spec file {}", path))
+        } else {
+            serde_json::from_str(&text).with_context(|| format!("Invalid spec file {}", path))
+        }
+    }
+
+    /// Render the spec as `json`, `yaml`, or `toml`.
+    pub fn render(&self, format: &str) -> Result<String> {
+        match format {
+            "json" => Ok(serde_json::to_string_pretty(self)?),
+            "yaml" | "yml" => Ok(serde_yaml::to_string(self)?),
+            "toml" => Ok(toml::to_string_pretty(self)?),
+            other => anyhow::bail!(
+                "Unsupported spec format: {} (use json, yaml, or toml)",
+                other
+            ),
+        }
+    }
+}
+
+impl EndpointSpec {
+    /// Settings that differ from `live` (all set settings when creating).
+    fn changed_settings(&self, live: Option<&Endpoint>) -> Value {
+        let mut settings = serde_json::Map::new();
+
+        if let Some(min) = self.autoscaling_limit_min_cu {
+            if live.and_then(|e| e.autoscaling_limit_min_cu) != Some(min) {
+                settings.insert("autoscaling_limit_min_cu".into(), serde_json::json!(min));
+            }
+        }
+        if let Some(max) = self.autoscaling_limit_max_cu {
+            if live.and_then(|e| e.autoscaling_limit_max_cu) != Some(max) {
+                settings.insert("autoscaling_limit_max_cu".into(), serde_json::json!(max));
+            }
+        }
+        if let Some(timeout) = self.suspend_timeout_seconds {
+            if live.and_then(|e| e.suspend_timeout_seconds) != Some(timeout) {
+                settings.insert("suspend_timeout_seconds".into(), serde_json::json!(timeout));
+            }
+        }
+
+        Value::Object(settings)
+    }
+}
+
+/// A single change needed to converge on the spec.
+#[derive(Debug, Serialize)]
+pub struct Step {
+    pub action: &'static str,
+    pub resource: String,
+    #[serde(skip_serializing_if = "Value::is_null")]
+    pub details: Value,
+}
+
+/// How [`reconcile`] behaves.
+#[derive(Debug, Clone, Copy)]
+pub struct ApplyOptions {
+    /// Perform the steps instead of only planning them.
+    pub execute: bool,
+    /// Delete branches, databases, and roles missing from the spec. Only
+    /// sections the spec declares are pruned, and root branches are never
+    /// deleted.
+    pub prune: bool,
+}
+
+/// Compare the spec with live state, returning (and optionally executing) the plan.
+pub async fn reconcile(
+    client: &NeonClient,
+    spec: &Spec,
+    options: ApplyOptions,
+) -> Result<Vec<Step>> {
+    let mut steps = Vec::new();
+    let projects = client.list_all_projects().await?;
+
+    for project_spec in &spec.projects {
+        let project_id = match projects.iter().find(|p| p.name == project_spec.name) {
+            Some(project) => Some(project.id.clone()),
+            None => {
+                steps.push(Step {
+                    action: "create_project",
+                    resource: project_spec.name.clone(),
+                    details: serde_json::json!({
+                        "region_id": project_spec.region_id,
+                        "pg_version": project_spec.pg_version,
+                    }),
+                });
+
+                if options.execute {
+                    let request = CreateProjectRequest {
+                        name: project_spec.name.clone(),
+                        region_id: project_spec.region_id.clone(),
+                        pg_version: project_spec.pg_version,
+                        ..Default::default()
+                    };
+                    let project = client.create_project(&request).await?.project;
+                    client.wait_for_idle(&project.id, OPERATION_TIMEOUT).await?;
+                    Some(project.id)
+                } else {
+                    None
+                }
+            }
+        };
+
+        reconcile_branches(
+            client,
+            project_spec,
+            project_id.as_deref(),
+            options,
+            &mut steps,
+        )
+        .await?;
+    }
+
+    Ok(steps)
+}
+
+/// Reconcile the branches of one project and everything under them.
+async fn reconcile_branches(
+    client: &NeonClient,
+    project_spec: &ProjectSpec,
+    project_id: Option<&str>,
+    options: ApplyOptions,
+    steps: &mut Vec<Step>,
+) -> Result<()> {
+    let (mut branches, endpoints): (Vec<Branch>, Vec<Endpoint>) = match project_id {
+        Some(id) => (
+            client.list_branches(id).await?,
+            client.list_endpoints(id).await?,
+        ),
+        None => (Vec::new(), Vec::new()),
+    };
+
+    for branch_spec in &project_spec.branches {
+        let resource = format!("{}/{}", project_spec.name, branch_spec.name);
+
+        let branch_id = match branches.iter().find(|b| b.name == branch_spec.name) {
+            Some(branch) => Some(branch.id.clone()),
+            None => {
+                let parent_id = match &branch_spec.parent {
+                    Some(parent) => match branches.iter().find(|b| &b.name == parent) {
+                        Some(b) => Some(b.id.clone()),
+                        None if options.execute => {
+                            anyhow::bail!("Parent branch {} of {} not found", parent, resource)
+                        }
+                        None => None,
+                    },
+                    None => None,
+                };
+
+                steps.push(Step {
+                    action: "create_branch",
+                    resource: resource.clone(),
+                    details: serde_json::json!({ "parent": branch_spec.parent }),
+                });
+
+                match (options.execute, project_id) {
+                    (true, Some(pid)) => {
+                        let branch = client
+                            .create_branch(pid, Some(&branch_spec.name), parent_id.as_deref())
+                            .await?;
+                        client.wait_for_idle(pid, OPERATION_TIMEOUT).await?;
+                        let id = branch.id.clone();
+                        branches.push(branch);
+                        Some(id)
+                    }
+                    _ => None,
+                }
+            }
+        };
+
+        let target = project_id.zip(branch_id.as_deref());
+        reconcile_endpoints(
+            client,
+            branch_spec,
+            &resource,
+            target,
+            &endpoints,
+            options,
+            steps,
+        )
+        .await?;
+        reconcile_roles_and_databases(client, branch_spec, &resource, target, options, steps)
+            .await?;
+    }
+
+    if options.prune && !project_spec.branches.is_empty() {
+        if let Some(pid) = project_id {
+            for branch in &branches {
+                let wanted = project_spec.branches.iter().any(|b| b.name == branch.name);
+                let is_parent = branches
+                    .iter()
+                    .any(|b| b.parent_id.as_deref() == Some(branch.id.as_str()));
+                if wanted || branch.parent_id.is_none() || is_parent {
+                    continue;
+                }
+
+                steps.push(Step {
+                    action: "delete_branch",
+                    resource: format!("{}/{}", project_spec.name, branch.name),
+                    details: serde_json::json!({ "branch_id": branch.id }),
+                });
+                if options.execute {
+                    client.delete_branch(pid, &branch.id).await?;
+                    client.wait_for_idle(pid, OPERATION_TIMEOUT).await?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reconcile compute endpoints on a branch. Endpoints are never pruned.
+async fn reconcile_endpoints(
+    client: &NeonClient,
+    branch_spec: &BranchSpec,
+    resource: &str,
+    target: Option<(&str, &str)>,
+    endpoints: &[Endpoint],
+    options: ApplyOptions,
+    steps: &mut Vec<Step>,
+) -> Result<()> {
+    let live: Vec<&Endpoint> = match target {
+        Some((_, branch_id)) => endpoints
+            .iter()
+            .filter(|e| e.branch_id == branch_id)
+            .collect(),
+        None => Vec::new(),
+    };
+    let mut used = vec![false; live.len()];
+
+    for (i, endpoint_spec) in branch_spec.endpoints.iter().enumerate() {
+        let endpoint_resource = format!("{}/endpoint[{}]", resource, i);
+        let matched = (0..live.len())
+            .find(|&j| !used[j] && live[j].endpoint_type == endpoint_spec.endpoint_type);
+
+        match matched {
+            Some(j) => {
+                used[j] = true;
+                let changes = endpoint_spec.changed_settings(Some(live[j]));
+                if changes.as_object().map(|o| o.is_empty()).unwrap_or(true) {
+                    continue;
+                }
+
+                steps.push(Step {
+                    action: "update_endpoint",
+                    resource: endpoint_resource,
+                    details: serde_json::json!({
+                        "endpoint_id": live[j].id,
+                        "changes": changes,
+                    }),
+                });
+                if let (true, Some((pid, _))) = (options.execute, target) {
+                    client.update_endpoint(pid, &live[j].id, &changes).await?;
+                    client.wait_for_idle(pid, OPERATION_TIMEOUT).await?;
+                }
+            }
+            None => {
+                let settings = endpoint_spec.changed_settings(None);
+                steps.push(Step {
+                    action: "create_endpoint",
+                    resource: endpoint_resource,
+                    details: serde_json::json!({
+                        "type": endpoint_spec.endpoint_type,
+                        "settings": settings,
+                    }),
+                });
+                if let (true, Some((pid, bid))) = (options.execute, target) {
+                    client
+                        .create_endpoint(pid, bid, &endpoint_spec.endpoint_type, &settings)
+                        .await?;
+                    client.wait_for_idle(pid, OPERATION_TIMEOUT).await?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reconcile roles and databases on a branch.
+///
+/// Roles are created before databases (which may need them as owners) and
+/// pruned after them (a role that owns a database can't be dropped). A role
+/// that owns a database the branch keeps is never pruned.
+async fn reconcile_roles_and_databases(
+    client: &NeonClient,
+    branch_spec: &BranchSpec,
+    resource: &str,
+    target: Option<(&str, &str)>,
+    options: ApplyOptions,
+    steps: &mut Vec<Step>,
+) -> Result<()> {
+    let (roles, databases) = match target {
+        Some((pid, bid)) => (
+            client.list_roles(pid, bid).await?,
+            client.list_databases(pid, bid).await?,
+        ),
+        None => (Vec::new(), Vec::new()),
+    };
+
+    for role_spec in &branch_spec.roles {
+        if roles.iter().any(|r| r.name == role_spec.name) {
+            continue;
+        }
+        steps.push(Step {
+            action: "create_role",
+            resource: format!("{}/role:{}", resource, role_spec.name),
+            details: Value::Null,
+        });
+        if let (true, Some((pid, bid))) = (options.execute, target) {
+            client.create_role(pid, bid, &role_spec.name).await?;
+            client.wait_for_idle(pid, OPERATION_TIMEOUT).await?;
+        }
+    }
+
+    for db_spec in &branch_spec.databases {
+        if databases.iter().any(|d| d.name == db_spec.name) {
+            continue;
+        }
+        steps.push(Step {
+            action: "create_database",
+            resource: format!("{}/db:{}", resource, db_spec.name),
+            details: serde_json::json!({ "owner": db_spec.owner }),
+        });
+        if let (true, Some((pid, bid))) = (options.execute, target) {
+            client
+                .create_database(pid, bid, &db_spec.name, &db_spec.owner)
+                .await?;
+            client.wait_for_idle(pid, OPERATION_TIMEOUT).await?;
+        }
+    }
+
+    if !options.prune {
+        return Ok(());
+    }
+
+    if !branch_spec.databases.is_empty() {
+        for db in &databases {
+            if branch_spec.databases.iter().any(|d| d.name == db.name) {
+                continue;
+            }
+            steps.push(Step {
+                action: "delete_database",
+                resource: format!("{}/db:{}", resource, db.name),
+                details: Value::Null,
+            });
+            if let (true, Some((pid, bid))) = (options.execute, target) {
+                client.delete_database(pid, bid, &db.name).await?;
+                client.wait_for_idle(pid, OPERATION_TIMEOUT).await?;
+            }
+        }
+    }
+
+    if !branch_spec.roles.is_empty() {
+        // Databases left after pruning keep their owners, spec'd or not
+        let kept = databases.iter().filter(|db| {
+            branch_spec.databases.is_empty()
+                || branch_spec.databases.iter().any(|d| d.name == db.name)
+        });
+        let owners: Vec<&str> = kept
+            .map(|db| db.owner_name.as_str())
+            .chain(branch_spec.databases.iter().map(|d| d.owner.as_str()))
+            .collect();
+
+        for role in &roles {
+            let wanted = branch_spec.roles.iter().any(|r| r.name == role.name);
+            if wanted || role.protected == Some(true) || owners.contains(&role.name.as_str()) {
+                continue;
+            }
+            steps.push(Step {
+                action: "delete_role",
+                resource: format!("{}/role:{}", resource, role.name),
+                details: Value::Null,
+            });
+            if let (true, Some((pid, bid))) = (options.execute, target) {
+                client.delete_role(pid, bid, &role.name).await?;
+                client.wait_for_idle(pid, OPERATION_TIMEOUT).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Capture live projects as a spec that [`reconcile`] would leave unchanged.
+///
+/// Exports every project in the org unless `project_ids` is given.
+pub async fn export(client: &NeonClient, project_ids: Option<&[String]>) -> Result<Spec> {
+    let projects = match project_ids {
+        Some(ids) => {
+            let mut projects = Vec::with_capacity(ids.len());
+            for id in ids {
+                projects.push(client.get_project(id).await?);
+            }
+            projects
+        }
+        None => client.list_all_projects().await?,
+    };
+
+    let mut spec = Spec::default();
+
+    for project in projects {
+        let branches = client.list_branches(&project.id).await?;
+        let endpoints = client.list_endpoints(&project.id).await?;
+
+        let mut branch_specs = Vec::with_capacity(branches.len());
+        for branch in &branches {
+            let parent = branch.parent_id.as_ref().and_then(|pid| {
+                branches
+                    .iter()
+                    .find(|b| &b.id == pid)
+                    .map(|b| b.name.clone())
+            });
+
+            let roles = client.list_roles(&project.id, &branch.id).await?;
+            let databases = client.list_databases(&project.id, &branch.id).await?;
+
+            branch_specs.push(BranchSpec {
+                name: branch.name.clone(),
+                parent,
+                endpoints: endpoints
+                    .iter()
+                    .filter(|e| e.branch_id == branch.id)
+                    .map(|e| EndpointSpec {
+                        endpoint_type: e.endpoint_type.clone(),
+                        autoscaling_limit_min_cu: e.autoscaling_limit_min_cu,
+                        autoscaling_limit_max_cu: e.autoscaling_limit_max_cu,
+                        suspend_timeout_seconds: e.suspend_timeout_seconds,
+                    })
+                    .collect(),
+                roles: roles
+                    .into_iter()
+                    .map(|r| RoleSpec { name: r.name })
+                    .collect(),
+                databases: databases
+                    .into_iter()
+                    .map(|d| DatabaseSpec {
+                        name: d.name,
+                        owner: d.owner_name,
+                    })
+                    .collect(),
+            });
+        }
+
+        spec.projects.push(ProjectSpec {
+            name: project.name,
+            region_id: project.region_id,
+            pg_version: project.pg_version,
+            branches: branch_specs,
+        });
+    }
+
+    Ok(spec)
+}
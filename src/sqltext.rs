@@ -0,0 +1,397 @@
+//! SQL text handling: named placeholders and statement splitting.
+//!
+//! `bind_named` rewrites `:name` placeholders to Postgres positional
+//! parameters (`$1`, `$2`, ...) and collects the matching values, so callers
+//! can pass an object instead of a positional array. Values are always sent
+//! as bind parameters, never spliced into the SQL text.
+//!
+//! `split_script` breaks a script into statements for the SQL endpoint,
+//! which runs one statement per request, noting the line each starts on.
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+/// Bind `:name` placeholders in `query` to values from `params`.
+///
+/// Placeholders inside string literals, quoted identifiers, dollar-quoted
+/// bodies, and comments are left alone, as are `::type` casts. A name used
+/// more than once maps to the same positional parameter. Missing and unused
+/// params are both errors, since either usually means a typo.
+pub fn bind_named(query: &str, params: &Map<String, Value>) -> Result<(String, Vec<Value>)> {
+    let mut sql = String::with_capacity(query.len());
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = query;
+
+    while let Some(c) = rest.chars().next() {
+        let skip = match (opaque_len(rest), c) {
+            (Some(len), _) => len,
+            (None, ':') if rest.starts_with("::") => 2,
+            (None, ':') => {
+                let name_len = identifier_len(&rest[1..]);
+                if name_len > 0 {
+                    let name = &rest[1..1 + name_len];
+                    let index = match names.iter().position(|n| n == name) {
+                        Some(i) => i + 1,
+                        None => {
+                            names.push(name.to_string());
+                            names.len()
+                        }
+                    };
+                    sql.push_str(&format!("${}", index));
+                    rest = &rest[1 + name_len..];
+                    continue;
+                }
+                1
+            }
+            (None, _) => c.len_utf8(),
+        };
+        sql.push_str(&rest[..skip]);
+        rest = &rest[skip..];
+    }
+
+    let missing: Vec<_> = names
+        .iter()
+        .filter(|n| !params.contains_key(n.as_str()))
+        .cloned()
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!("Missing template parameter(s): {}", missing.join(", "));
+    }
+
+    let unused: Vec<_> = params
+        .keys()
+        .filter(|k| !names.contains(k))
+        .cloned()
+        .collect();
+    if !unused.is_empty() {
+        anyhow::bail!("Unused template parameter(s): {}", unused.join(", "));
+    }
+
+    let values = names.iter().map(|n| bind_value(&params[n])).collect();
+    Ok((sql, values))
+}
+
+/// A statement from a script.
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub text: String,
+    /// 1-based line the statement starts on.
+    pub line: usize,
+}
+
+/// Split a script into statements at top-level semicolons.
+///
+/// Semicolons inside string literals, quoted identifiers, dollar-quoted
+/// bodies (function definitions), comments, and `BEGIN ATOMIC ... END`
+/// function bodies don't split. Empty and comment-only statements are
+/// dropped.
+pub fn split_script(script: &str) -> Vec<Statement> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+    let mut previous_word = String::new();
+    // Open BEGIN ATOMIC and CASE blocks inside an atomic body
+    let mut atomic_depth = 0usize;
+
+    while let Some(c) = script[pos..].chars().next() {
+        if c == ';' && atomic_depth == 0 {
+            push_statement(&mut statements, script, start, pos);
+            pos += 1;
+            start = pos;
+            continue;
+        }
+        // Before words, so the `E` of an escape string isn't read as one
+        if let Some(len) = opaque_len(&script[pos..]) {
+            pos += len;
+            continue;
+        }
+
+        let word_len = identifier_len(&script[pos..]);
+        let at_word_start = !script[..pos]
+            .chars()
+            .next_back()
+            .is_some_and(|p| p.is_ascii_alphanumeric() || p == '_' || p == '$');
+        if word_len > 0 && at_word_start {
+            let word = script[pos..pos + word_len].to_ascii_lowercase();
+            match word.as_str() {
+                "atomic" if previous_word == "begin" => atomic_depth += 1,
+                "case" if atomic_depth > 0 => atomic_depth += 1,
+                "end" if atomic_depth > 0 => atomic_depth -= 1,
+                _ => {}
+            }
+            previous_word = word;
+            pos += word_len;
+            continue;
+        }
+        pos += c.len_utf8();
+    }
+    push_statement(&mut statements, script, start, script.len());
+
+    statements
+}
+
+/// Statement texts of a script; see [`split_script`].
+pub fn split_statements(script: &str) -> Vec<String> {
+    split_script(script).into_iter().map(|s| s.text).collect()
+}
+
+fn push_statement(statements: &mut Vec<Statement>, script: &str, start: usize, end: usize) {
+    let raw = &script[start..end];
+    let text = raw.trim();
+    if strip_comments(text).trim().is_empty() {
+        return;
+    }
+    let leading = raw.len() - raw.trim_start().len();
+    statements.push(Statement {
+        text: text.to_string(),
+        line: script[..start + leading].matches('\n').count() + 1,
+    });
+}
+
+/// `text` with comments removed (literals kept as-is).
+fn strip_comments(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = opaque_len(rest).unwrap_or(c.len_utf8());
+        if !(rest.starts_with("--") || rest.starts_with("/*")) {
+            out.push_str(&rest[..len]);
+        }
+        rest = &rest[len..];
+    }
+    out
+}
+
+/// Length of a string literal, quoted identifier, dollar-quoted body, or
+/// comment at the start of `s`, whose contents must not be interpreted.
+pub(crate) fn opaque_len(s: &str) -> Option<usize> {
+    let c = s.chars().next()?;
+    match c {
+        '\'' | '"' => Some(quoted_len(s, c)),
+        'e' | 'E' if s[1..].starts_with('\'') => Some(1 + escape_string_len(&s[1..])),
+        '-' if s.starts_with("--") => Some(s.find('\n').unwrap_or(s.len())),
+        '/' if s.starts_with("/*") => Some(block_comment_len(s)),
+        '$' => dollar_quoted_len(s),
+        _ => None,
+    }
+}
+
+/// Objects and arrays are passed as JSON text (for `json`/`jsonb` columns).
+fn bind_value(value: &Value) -> Value {
+    match value {
+        Value::Object(_) | Value::Array(_) => Value::String(value.to_string()),
+        other => other.clone(),
+    }
+}
+
+/// Length of a quoted literal or identifier, including doubled-quote escapes.
+fn quoted_len(s: &str, quote: char) -> usize {
+    let mut chars = s.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            if chars.peek().map(|&(_, n)| n) == Some(quote) {
+                chars.next();
+            } else {
+                return i + 1;
+            }
+        }
+    }
+    s.len()
+}
+
+/// Length of the body and quotes of an `E'...'` string, where a backslash
+/// escapes the next character.
+fn escape_string_len(s: &str) -> usize {
+    let mut chars = s.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '\'' if chars.peek().map(|&(_, n)| n) == Some('\'') => {
+                chars.next();
+            }
+            '\'' => return i + 1,
+            _ => {}
+        }
+    }
+    s.len()
+}
+
+/// Length of a `/* ... */` comment, which may nest.
+fn block_comment_len(s: &str) -> usize {
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < s.len() {
+        let rest = &s.as_bytes()[i..];
+        if rest.starts_with(b"/*") {
+            depth += 1;
+            i += 2;
+        } else if rest.starts_with(b"*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    s.len()
+}
+
+/// Length of a `$tag$ ... $tag$` block, if `s` starts with one.
+fn dollar_quoted_len(s: &str) -> Option<usize> {
+    let tag_end = s[1..].find('$')? + 2;
+    let tag = &s[..tag_end];
+    if !tag[1..tag_end - 1]
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_')
+        || tag[1..].starts_with(|c: char| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let body_end = s[tag_end..].find(tag)?;
+    Some(tag_end + body_end + tag.len())
+}
+
+fn identifier_len(s: &str) -> usize {
+    let mut len = 0;
+    for (i, c) in s.char_indices() {
+        let valid = if i == 0 {
+            c.is_ascii_alphabetic() || c == '_'
+        } else {
+            c.is_ascii_alphanumeric() || c == '_'
+        };
+        if !valid {
+            break;
+        }
+        len = i + c.len_utf8();
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn dollar_quoted_function_body_does_not_split() {
+        let script = "CREATE FUNCTION f() RETURNS int AS $body$ SELECT 1; SELECT 2; $body$ LANGUAGE sql;\nSELECT f();";
+        assert_eq!(
+            split_statements(script),
+            [
+                "CREATE FUNCTION f() RETURNS int AS $body$ SELECT 1; SELECT 2; $body$ LANGUAGE sql",
+                "SELECT f()",
+            ]
+        );
+    }
+
+    #[test]
+    fn escape_string_backslash_quote_does_not_end_it() {
+        let script = r"SELECT E'it\'s; fine'; SELECT e'a\\'; SELECT 'x''y;z'";
+        assert_eq!(
+            split_statements(script),
+            [
+                r"SELECT E'it\'s; fine'",
+                r"SELECT e'a\\'",
+                "SELECT 'x''y;z'"
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_block_comments_do_not_split() {
+        let script = "SELECT 1 /* outer /* inner; */ still comment; */ + 1; SELECT 2";
+        assert_eq!(
+            split_statements(script),
+            [
+                "SELECT 1 /* outer /* inner; */ still comment; */ + 1",
+                "SELECT 2",
+            ]
+        );
+        assert_eq!(opaque_len("/* a /* b */ c */ d"), Some(17));
+    }
+
+    #[test]
+    fn semicolon_in_quoted_identifier_does_not_split() {
+        let script = r#"SELECT 1 AS "a;b", 2 AS "c""d;"; SELECT 3"#;
+        assert_eq!(
+            split_statements(script),
+            [r#"SELECT 1 AS "a;b", 2 AS "c""d;""#, "SELECT 3"]
+        );
+    }
+
+    #[test]
+    fn trailing_comment_only_statement_is_dropped() {
+        let script = "SELECT 1;\n-- done;\n/* really; */\n";
+        assert_eq!(split_statements(script), ["SELECT 1"]);
+    }
+
+    #[test]
+    fn statements_keep_their_start_lines() {
+        let lines: Vec<usize> = split_script("SELECT 1;\n\n  SELECT\n2;")
+            .iter()
+            .map(|s| s.line)
+            .collect();
+        assert_eq!(lines, [1, 3]);
+    }
+
+    #[test]
+    fn opaque_len_of_literals_and_comments() {
+        assert_eq!(opaque_len("'a''b' rest"), Some(6));
+        assert_eq!(opaque_len(r"E'a\'b' rest"), Some(7));
+        assert_eq!(opaque_len("-- note\nSELECT"), Some(7));
+        assert_eq!(opaque_len("$$ x $$ rest"), Some(7));
+        assert_eq!(opaque_len("$1, $2"), None);
+        assert_eq!(opaque_len("ELSE 'b'"), None);
+    }
+
+    fn values(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn bind_named_numbers_placeholders_in_order() {
+        let (sql, bound) = bind_named(
+            "SELECT * FROM t WHERE a = :a AND b = :b OR a2 = :a",
+            &values(json!({"a": 1, "b": "x"})),
+        )
+        .unwrap();
+        assert_eq!(sql, "SELECT * FROM t WHERE a = $1 AND b = $2 OR a2 = $1");
+        assert_eq!(bound, [json!(1), json!("x")]);
+    }
+
+    #[test]
+    fn bind_named_skips_literals_comments_and_casts() {
+        let query = r#"SELECT ':a', E'\':a', ":a", $$:a$$, x::int -- :a
+FROM t /* :a */ WHERE id = :id::bigint"#;
+        let (sql, bound) = bind_named(query, &values(json!({"id": 7}))).unwrap();
+        assert_eq!(
+            sql,
+            r#"SELECT ':a', E'\':a', ":a", $$:a$$, x::int -- :a
+FROM t /* :a */ WHERE id = $1::bigint"#
+        );
+        assert_eq!(bound, [json!(7)]);
+    }
+
+    #[test]
+    fn bind_named_rejects_missing_and_unused_params() {
+        let missing = bind_named("SELECT :a, :b", &values(json!({"a": 1}))).unwrap_err();
+        assert_eq!(missing.to_string(), "Missing template parameter(s): b");
+
+        let unused = bind_named("SELECT :a", &values(json!({"a": 1, "c": 2}))).unwrap_err();
+        assert_eq!(unused.to_string(), "Unused template parameter(s): c");
+    }
+
+    #[test]
+    fn bind_named_sends_objects_as_json_text() {
+        let (_, bound) =
+            bind_named("SELECT :doc::jsonb", &values(json!({"doc": {"k": [1, 2]}}))).unwrap();
+        assert_eq!(bound, [json!(r#"{"k":[1,2]}"#)]);
+    }
+}
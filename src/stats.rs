@@ -0,0 +1,121 @@
+//! Cold-start tracking for compute endpoints.
+//!
+//! Every SQL call looks up its endpoint first, from a listing at most
+//! `[sql] endpoint_cache_secs` old that's marked active after each query, so
+//! the endpoint's state just before the query is known. A query that starts while the compute is not
+//! `active` pays for the resume; its latency minus the endpoint's typical warm
+//! latency estimates how long the resume took.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Resume samples retained per endpoint.
+const MAX_SAMPLES: usize = 100;
+
+/// Per-endpoint query and cold-start counters.
+#[derive(Default)]
+pub struct ColdStartStats {
+    endpoints: Mutex<HashMap<String, EndpointStats>>,
+}
+
+#[derive(Default)]
+struct EndpointStats {
+    project_id: String,
+    branch_id: String,
+    queries: u64,
+    warm_queries: u64,
+    warm_total_ms: f64,
+    /// Latency of recent queries that hit a suspended compute, oldest first.
+    cold_latencies_ms: VecDeque<f64>,
+    cold_starts: u64,
+    last_cold_start: Option<DateTime<Utc>>,
+}
+
+/// Reported view of one endpoint's counters.
+#[derive(Debug, Serialize)]
+pub struct EndpointReport {
+    pub endpoint_id: String,
+    pub project_id: String,
+    pub branch_id: String,
+    pub queries: u64,
+    pub cold_starts: u64,
+    pub cold_start_rate: f64,
+    pub avg_warm_latency_ms: Option<f64>,
+    /// Estimated resume time (cold latency minus average warm latency).
+    pub avg_resume_ms: Option<f64>,
+    pub max_resume_ms: Option<f64>,
+    pub last_cold_start: Option<DateTime<Utc>>,
+}
+
+impl ColdStartStats {
+    /// Record one query against an endpoint.
+    pub fn record(
+        &self,
+        endpoint_id: &str,
+        project_id: &str,
+        branch_id: &str,
+        was_active: bool,
+        latency_ms: f64,
+    ) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let stats = endpoints.entry(endpoint_id.to_string()).or_default();
+        stats.project_id = project_id.to_string();
+        stats.branch_id = branch_id.to_string();
+        stats.queries += 1;
+
+        if was_active {
+            stats.warm_queries += 1;
+            stats.warm_total_ms += latency_ms;
+        } else {
+            stats.cold_starts += 1;
+            stats.last_cold_start = Some(Utc::now());
+            stats.cold_latencies_ms.push_back(latency_ms);
+            if stats.cold_latencies_ms.len() > MAX_SAMPLES {
+                stats.cold_latencies_ms.pop_front();
+            }
+        }
+    }
+
+    /// Snapshot counters, optionally for a single project.
+    pub fn report(&self, project_id: Option<&str>) -> Vec<EndpointReport> {
+        let endpoints = self.endpoints.lock().unwrap();
+        let mut reports: Vec<_> = endpoints
+            .iter()
+            .filter(|(_, s)| match project_id {
+                Some(p) => s.project_id == p,
+                None => true,
+            })
+            .map(|(id, s)| s.report(id))
+            .collect();
+        reports.sort_by(|a, b| a.endpoint_id.cmp(&b.endpoint_id));
+        reports
+    }
+}
+
+impl EndpointStats {
+    fn report(&self, endpoint_id: &str) -> EndpointReport {
+        let avg_warm =
+            (self.warm_queries > 0).then(|| self.warm_total_ms / self.warm_queries as f64);
+        let resumes: Vec<f64> = self
+            .cold_latencies_ms
+            .iter()
+            .map(|ms| (ms - avg_warm.unwrap_or(0.0)).max(0.0))
+            .collect();
+
+        EndpointReport {
+            endpoint_id: endpoint_id.to_string(),
+            project_id: self.project_id.clone(),
+            branch_id: self.branch_id.clone(),
+            queries: self.queries,
+            cold_starts: self.cold_starts,
+            cold_start_rate: self.cold_starts as f64 / self.queries.max(1) as f64,
+            avg_warm_latency_ms: avg_warm,
+            avg_resume_ms: (!resumes.is_empty())
+                .then(|| resumes.iter().sum::<f64>() / resumes.len() as f64),
+            max_resume_ms: resumes.iter().copied().reduce(f64::max),
+            last_cold_start: self.last_cold_start,
+        }
+    }
+}
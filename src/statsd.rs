@@ -0,0 +1,235 @@
+//! StatsD export of the daemon's counters.
+//!
+//! For monitoring stacks that receive metrics rather than polling the daemon,
+//! `[statsd] enabled` sends what `neon.stats` and `health` report to a StatsD
+//! agent over UDP every `interval_secs`: calls per method, connected clients
+//! and in-flight requests, queries and cold starts per endpoint, the Postgres
+//! pool, background tasks, and the canary's latest probes. Running totals go
+//! out as counters of the change since the last flush (`|c`), current values
+//! as gauges (`|g`). With `datadog`, tags use DogStatsD's `|#key:value`
+//! suffix; plain StatsD has no tags, so their values are appended to the
+//! metric name instead.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+use crate::api::NeonClient;
+use crate::canary::CanaryProbes;
+use crate::clients::ClientRegistry;
+use crate::config::StatsdConfig;
+use crate::supervisor::Supervisor;
+
+/// Largest datagram sent, to stay under a typical MTU.
+const MAX_PACKET_BYTES: usize = 1432;
+
+/// Where the exported values come from.
+pub struct Sources {
+    pub client: Arc<NeonClient>,
+    pub clients: Arc<ClientRegistry>,
+    pub supervisor: Arc<Supervisor>,
+    pub canary: Arc<CanaryProbes>,
+}
+
+/// Send metrics every `interval_secs` forever.
+pub async fn run(config: StatsdConfig, sources: Arc<Sources>) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(config.interval_secs.max(1)));
+    let mut emitter = Emitter::new(config);
+
+    loop {
+        ticker.tick().await;
+        emitter.collect(&sources);
+        if let Err(e) = emitter.flush().await {
+            tracing::warn!("StatsD flush failed: {:#}", e);
+        }
+    }
+}
+
+/// Formats metric lines and remembers the counter totals already sent.
+struct Emitter {
+    config: StatsdConfig,
+    socket: Option<UdpSocket>,
+    /// Last total sent per counter line, so each flush sends the change.
+    counters: HashMap<String, u64>,
+    lines: Vec<String>,
+}
+
+impl Emitter {
+    fn new(config: StatsdConfig) -> Self {
+        Self {
+            config,
+            socket: None,
+            counters: HashMap::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    /// Queue a line for every exported value.
+    fn collect(&mut self, sources: &Sources) {
+        let activity = sources.clients.activity();
+        for method in &activity.methods {
+            self.count(
+                "requests",
+                method.calls,
+                &[("method", method.method.as_str())],
+            );
+        }
+        self.gauge("clients.connected", activity.clients.len() as f64, &[]);
+        self.gauge(
+            "requests.in_flight",
+            sources.clients.in_flight() as f64,
+            &[],
+        );
+        self.count(
+            "connections.rejected",
+            sources.clients.rejected_connections(),
+            &[],
+        );
+        self.gauge("idle_secs", activity.idle_secs as f64, &[]);
+
+        for endpoint in sources.client.cold_starts().report(None) {
+            let tags = [
+                ("project", endpoint.project_id.as_str()),
+                ("endpoint", endpoint.endpoint_id.as_str()),
+            ];
+            self.count("sql.queries", endpoint.queries, &tags);
+            self.count("sql.cold_starts", endpoint.cold_starts, &tags);
+            if let Some(ms) = endpoint.avg_resume_ms {
+                self.gauge("sql.resume_ms", ms, &tags);
+            }
+        }
+
+        let pool = sources.client.pool_health();
+        self.gauge("pool.idle", pool.idle as f64, &[]);
+        self.gauge("pool.failures", pool.consecutive_failures as f64, &[]);
+
+        for (name, task) in sources.supervisor.tasks() {
+            let tags = [("task", name.as_str())];
+            self.gauge("tasks.running", u8::from(task.running) as f64, &tags);
+            self.count("tasks.restarts", task.restarts as u64, &tags);
+        }
+
+        for probe in sources.canary.last() {
+            let tags = [("route", probe.route)];
+            self.gauge("canary.ok", u8::from(probe.ok) as f64, &tags);
+            self.gauge("canary.latency_ms", probe.latency_ms, &tags);
+        }
+    }
+
+    fn gauge(&mut self, name: &str, value: f64, tags: &[(&str, &str)]) {
+        let line = self.line(name, &value.to_string(), "g", tags);
+        self.lines.push(line);
+    }
+
+    /// Queue the change in a running total since the last flush, if any.
+    fn count(&mut self, name: &str, total: u64, tags: &[(&str, &str)]) {
+        let key = self.line(name, "", "c", tags);
+        let last = self.counters.insert(key, total).unwrap_or(0);
+        let delta = total.saturating_sub(last);
+        if delta > 0 {
+            let line = self.line(name, &delta.to_string(), "c", tags);
+            self.lines.push(line);
+        }
+    }
+
+    /// `<prefix>.<name>:<value>|<kind>`, with tags as the config says.
+    fn line(&self, name: &str, value: &str, kind: &str, tags: &[(&str, &str)]) -> String {
+        let mut metric = match self.config.prefix.trim_end_matches('.') {
+            "" => name.to_string(),
+            prefix => format!("{}.{}", prefix, name),
+        };
+        if !self.config.datadog {
+            for (_, tag) in tags {
+                metric.push('.');
+                metric.push_str(&sanitize(tag));
+            }
+            return format!("{}:{}|{}", metric, value, kind);
+        }
+
+        let tags: Vec<String> = self
+            .config
+            .tags
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .chain(tags.iter().copied())
+            .map(|(k, v)| format!("{}:{}", sanitize(k), sanitize(v)))
+            .collect();
+        if tags.is_empty() {
+            format!("{}:{}|{}", metric, value, kind)
+        } else {
+            format!("{}:{}|{}|#{}", metric, value, kind, tags.join(","))
+        }
+    }
+
+    /// Send the queued lines, several per datagram.
+    async fn flush(&mut self) -> Result<()> {
+        let lines = std::mem::take(&mut self.lines);
+        if lines.is_empty() {
+            return Ok(());
+        }
+        if self.socket.is_none() {
+            self.socket = Some(connect(&self.config.address).await?);
+        }
+        let socket = self.socket.as_ref().expect("connected above");
+
+        let result = send(socket, &lines).await;
+        if result.is_err() {
+            // Resolve the address again next time, in case the agent moved
+            self.socket = None;
+        }
+        result
+    }
+}
+
+/// A UDP socket connected to the agent at `address`.
+async fn connect(address: &str) -> Result<UdpSocket> {
+    let target = tokio::net::lookup_host(address)
+        .await
+        .with_context(|| format!("Failed to resolve StatsD address {}", address))?
+        .next()
+        .with_context(|| format!("StatsD address {} did not resolve", address))?;
+    let local = if target.is_ipv6() {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket
+        .connect(target)
+        .await
+        .with_context(|| format!("Failed to connect to StatsD at {}", target))?;
+    Ok(socket)
+}
+
+/// Send `lines`, as many per datagram as fit.
+async fn send(socket: &UdpSocket, lines: &[String]) -> Result<()> {
+    let mut packet = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        packet.push_str(line);
+        let next = lines.get(i + 1).map_or(0, |l| l.len() + 1);
+        if next == 0 || packet.len() + next > MAX_PACKET_BYTES {
+            socket
+                .send(packet.as_bytes())
+                .await
+                .context("Failed to send StatsD packet")?;
+            packet.clear();
+        } else {
+            packet.push('\n');
+        }
+    }
+    Ok(())
+}
+
+/// A tag key or value with characters StatsD lines reserve replaced.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            ':' | '|' | ',' | '#' | '@' | '\n' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
@@ -0,0 +1,490 @@
+//! Stub mode: `fgp-neon start --stub`.
+//!
+//! Serves projects, branches, databases, endpoints, and SQL results from a
+//! fixtures file instead of the Neon API, so tools can be built and tested
+//! against the daemon offline and in CI without a Neon account. Responses
+//! use the same envelope as the real service. Branches created or deleted
+//! through the stub only change its in-memory copy of the fixtures.
+//!
+//! Fixtures are JSON with `projects`, `branches`, `databases`, `endpoints`
+//! (objects shaped like the Neon API's), and `queries`. A query fixture
+//! matches by `query` (compared ignoring case, extra whitespace, and a
+//! trailing semicolon) or by a regex `pattern`, and answers with a `result`
+//! (`columns`, `rows`, optionally `command` and `row_count`) or an `error`.
+//! Without `--fixtures`, `examples/stub-fixtures.json` is served.
+
+use anyhow::{Context, Result};
+use fgp_daemon::service::{HealthStatus, MethodInfo, ParamInfo};
+use fgp_daemon::FgpService;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::clients::ClientRegistry;
+use crate::config::Config;
+use crate::envelope;
+use crate::trace;
+
+/// Fixtures served when no file is given.
+const BUILTIN: &str = include_str!("../examples/stub-fixtures.json");
+
+/// Canned data the stub serves.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fixtures {
+    #[serde(default)]
+    projects: Vec<Map<String, Value>>,
+    #[serde(default)]
+    branches: Vec<Map<String, Value>>,
+    #[serde(default)]
+    databases: Vec<Map<String, Value>>,
+    #[serde(default)]
+    endpoints: Vec<Map<String, Value>>,
+    #[serde(default)]
+    queries: Vec<QueryFixture>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QueryFixture {
+    #[serde(default)]
+    query: Option<String>,
+    #[serde(default)]
+    pattern: Option<String>,
+    /// `pattern`, compiled on load.
+    #[serde(skip)]
+    regex: Option<Regex>,
+    #[serde(default)]
+    result: Option<Map<String, Value>>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+impl Fixtures {
+    /// Load fixtures from `path`, or the built-in ones.
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let (text, source) = match path {
+            Some(path) => {
+                let path = shellexpand::tilde(path).to_string();
+                let text = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read fixtures {}", path))?;
+                (text, path)
+            }
+            None => (BUILTIN.to_string(), "built-in fixtures".to_string()),
+        };
+        let mut fixtures: Self =
+            serde_json::from_str(&text).with_context(|| format!("Failed to parse {}", source))?;
+
+        for (i, query) in fixtures.queries.iter_mut().enumerate() {
+            if query.query.is_some() == query.pattern.is_some() {
+                anyhow::bail!("queries[{}] needs exactly one of query or pattern", i);
+            }
+            if query.result.is_some() == query.error.is_some() {
+                anyhow::bail!("queries[{}] needs exactly one of result or error", i);
+            }
+            if let Some(pattern) = &query.pattern {
+                query.regex = Some(
+                    Regex::new(pattern)
+                        .with_context(|| format!("queries[{}] has an invalid pattern", i))?,
+                );
+            }
+        }
+        Ok(fixtures)
+    }
+
+    /// How much the fixtures hold, for the startup banner.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} project(s), {} branch(es), {} query fixture(s)",
+            self.projects.len(),
+            self.branches.len(),
+            self.queries.len()
+        )
+    }
+}
+
+/// FGP service answering from fixtures.
+pub struct StubService {
+    fixtures: Mutex<Fixtures>,
+    clients: Arc<ClientRegistry>,
+    next_branch: Mutex<u64>,
+}
+
+impl StubService {
+    pub fn new(fixtures: Fixtures, config: &Config) -> Self {
+        Self {
+            fixtures: Mutex::new(fixtures),
+            clients: Arc::new(ClientRegistry::new(config.clients.clone())),
+            next_branch: Mutex::new(1),
+        }
+    }
+
+    /// Connection registry shared with the client relay.
+    pub fn clients(&self) -> Arc<ClientRegistry> {
+        self.clients.clone()
+    }
+
+    fn route(&self, method: &str, params: &HashMap<String, Value>) -> Result<Value> {
+        let mut guard = self.fixtures.lock().unwrap();
+        let fixtures = &mut *guard;
+        let short = method.strip_prefix("neon.").unwrap_or(method);
+        match short {
+            "health" => Ok(serde_json::json!({
+                "status": "healthy",
+                "stub": true,
+                "version": env!("CARGO_PKG_VERSION"),
+            })),
+            "projects" => Ok(listing("projects", fixtures.projects.clone())),
+            "project" => {
+                let project_id = require(params, "project_id")?;
+                find(&fixtures.projects, "id", project_id)
+                    .cloned()
+                    .map(Value::Object)
+                    .ok_or_else(|| anyhow::anyhow!("Project {} not found", project_id))
+            }
+            "branches" => {
+                let project_id = require(params, "project_id")?;
+                Ok(listing(
+                    "branches",
+                    matching(&fixtures.branches, "project_id", project_id),
+                ))
+            }
+            "all_branches" => Ok(listing("branches", fixtures.branches.clone())),
+            "databases" => {
+                require(params, "project_id")?;
+                let branch_id = require(params, "branch_id")?;
+                Ok(listing(
+                    "databases",
+                    matching(&fixtures.databases, "branch_id", branch_id),
+                ))
+            }
+            "endpoints" => {
+                let project_id = require(params, "project_id")?;
+                let mut endpoints = matching(&fixtures.endpoints, "project_id", project_id);
+                if let Some(branch_id) = params.get("branch_id").and_then(|v| v.as_str()) {
+                    endpoints.retain(|e| field(e, "branch_id") == Some(branch_id));
+                }
+                Ok(listing("endpoints", endpoints))
+            }
+            "create_branch" => self.create_branch(fixtures, params),
+            "delete_branch" => {
+                require(params, "project_id")?;
+                let branch_id = require(params, "branch_id")?;
+                let before = fixtures.branches.len();
+                fixtures
+                    .branches
+                    .retain(|b| field(b, "id") != Some(branch_id));
+                if fixtures.branches.len() == before {
+                    anyhow::bail!("Branch {} not found", branch_id);
+                }
+                fixtures
+                    .databases
+                    .retain(|d| field(d, "branch_id") != Some(branch_id));
+                fixtures
+                    .endpoints
+                    .retain(|e| field(e, "branch_id") != Some(branch_id));
+                Ok(serde_json::json!({ "deleted": true }))
+            }
+            "connection_string" => {
+                let project_id = require(params, "project_id")?;
+                let branch_id = params.get("branch_id").and_then(|v| v.as_str());
+                let database = params
+                    .get("database")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("neondb");
+                let endpoint = fixtures
+                    .endpoints
+                    .iter()
+                    .filter(|e| field(e, "project_id") == Some(project_id))
+                    .find(|e| branch_id.is_none_or(|b| field(e, "branch_id") == Some(b)))
+                    .ok_or_else(|| anyhow::anyhow!("No endpoint found for project"))?;
+                let host = field(endpoint, "host").unwrap_or("localhost");
+                Ok(serde_json::json!({
+                    "connection_string": format!("postgres://neondb_owner:stub@{}/{}?sslmode=require", host, database),
+                    "host": host,
+                    "port": 5432,
+                    "user": "neondb_owner",
+                    "password": "stub",
+                    "database": database,
+                    "pooled": false,
+                    "branch_id": field(endpoint, "branch_id"),
+                    "endpoint_id": field(endpoint, "id"),
+                }))
+            }
+            "sql" => {
+                require(params, "project_id")?;
+                require(params, "branch_id")?;
+                query_result(&fixtures.queries, require(params, "query")?)
+            }
+            _ => anyhow::bail!(
+                "neon.{} isn't available in stub mode; it serves health, projects, project, branches, databases, endpoints, create_branch, delete_branch, connection_string, and sql",
+                short
+            ),
+        }
+    }
+
+    fn create_branch(
+        &self,
+        fixtures: &mut Fixtures,
+        params: &HashMap<String, Value>,
+    ) -> Result<Value> {
+        let project_id = require(params, "project_id")?;
+        if find(&fixtures.projects, "id", project_id).is_none() {
+            anyhow::bail!("Project {} not found", project_id);
+        }
+        let id = {
+            let mut next = self.next_branch.lock().unwrap();
+            *next += 1;
+            format!("br-stub-{}", *next - 1)
+        };
+        let name = params
+            .get("name")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| id.clone());
+        let parent_id = params
+            .get("parent_id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .or_else(|| {
+                fixtures
+                    .branches
+                    .iter()
+                    .filter(|b| field(b, "project_id") == Some(project_id))
+                    .find(|b| b.get("default") == Some(&Value::Bool(true)))
+                    .and_then(|b| field(b, "id"))
+                    .map(|s| s.to_string())
+            });
+
+        let branch = serde_json::json!({
+            "id": id,
+            "project_id": project_id,
+            "parent_id": parent_id,
+            "name": name,
+            "default": false,
+            "current_state": "ready",
+            "created_at": chrono::Utc::now().to_rfc3339(),
+        });
+        if let Value::Object(branch) = &branch {
+            fixtures.branches.push(branch.clone());
+        }
+        // The child starts with its parent's databases
+        let inherited: Vec<Map<String, Value>> = fixtures
+            .databases
+            .iter()
+            .filter(|d| parent_id.is_some() && field(d, "branch_id") == parent_id.as_deref())
+            .map(|d| {
+                let mut d = d.clone();
+                d.insert("branch_id".into(), Value::String(id.clone()));
+                d
+            })
+            .collect();
+        fixtures.databases.extend(inherited);
+        Ok(branch)
+    }
+}
+
+impl FgpService for StubService {
+    fn name(&self) -> &str {
+        "neon"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn dispatch(&self, method: &str, params: HashMap<String, Value>) -> Result<Value> {
+        let started = std::time::Instant::now();
+        let trace_id = trace::from_params(&params)?;
+        let result = self.route(method, &params)?;
+        Ok(envelope::wrap(result, started.elapsed(), trace_id))
+    }
+
+    fn method_list(&self) -> Vec<MethodInfo> {
+        let method = |name: &str, description: &str, params: &[(&str, &str, bool)]| MethodInfo {
+            name: name.into(),
+            description: description.into(),
+            params: params
+                .iter()
+                .map(|(name, param_type, required)| ParamInfo {
+                    name: (*name).into(),
+                    param_type: (*param_type).into(),
+                    required: *required,
+                    default: None,
+                })
+                .collect(),
+        };
+        vec![
+            method("neon.projects", "List fixture projects", &[]),
+            method(
+                "neon.project",
+                "Get a fixture project",
+                &[("project_id", "string", true)],
+            ),
+            method(
+                "neon.branches",
+                "List a project's fixture branches",
+                &[("project_id", "string", true)],
+            ),
+            method("neon.all_branches", "List every fixture branch", &[]),
+            method(
+                "neon.databases",
+                "List a branch's fixture databases",
+                &[
+                    ("project_id", "string", true),
+                    ("branch_id", "string", true),
+                ],
+            ),
+            method(
+                "neon.endpoints",
+                "List a project's fixture endpoints",
+                &[
+                    ("project_id", "string", true),
+                    ("branch_id", "string", false),
+                ],
+            ),
+            method(
+                "neon.create_branch",
+                "Add a branch to the stub's fixtures",
+                &[
+                    ("project_id", "string", true),
+                    ("name", "string", false),
+                    ("parent_id", "string", false),
+                ],
+            ),
+            method(
+                "neon.delete_branch",
+                "Remove a branch from the stub's fixtures",
+                &[
+                    ("project_id", "string", true),
+                    ("branch_id", "string", true),
+                ],
+            ),
+            method(
+                "neon.connection_string",
+                "A connection string for a fixture endpoint",
+                &[
+                    ("project_id", "string", true),
+                    ("branch_id", "string", false),
+                    ("database", "string", false),
+                ],
+            ),
+            method(
+                "neon.sql",
+                "Answer a query from the query fixtures",
+                &[
+                    ("project_id", "string", true),
+                    ("branch_id", "string", true),
+                    ("query", "string", true),
+                ],
+            ),
+        ]
+    }
+
+    fn on_start(&self) -> Result<()> {
+        tracing::info!(
+            "Serving fixtures in stub mode: {}",
+            self.fixtures.lock().unwrap().summary()
+        );
+        Ok(())
+    }
+
+    fn health_check(&self) -> HashMap<String, HealthStatus> {
+        HashMap::from([("stub".to_string(), HealthStatus::healthy())])
+    }
+}
+
+fn require<'a>(params: &'a HashMap<String, Value>, key: &str) -> Result<&'a str> {
+    params
+        .get(key)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing required parameter: {}", key))
+}
+
+fn field<'a>(item: &'a Map<String, Value>, key: &str) -> Option<&'a str> {
+    item.get(key).and_then(|v| v.as_str())
+}
+
+fn find<'a>(
+    items: &'a [Map<String, Value>],
+    key: &str,
+    value: &str,
+) -> Option<&'a Map<String, Value>> {
+    items.iter().find(|item| field(item, key) == Some(value))
+}
+
+fn matching(items: &[Map<String, Value>], key: &str, value: &str) -> Vec<Map<String, Value>> {
+    items
+        .iter()
+        .filter(|item| field(item, key) == Some(value))
+        .cloned()
+        .collect()
+}
+
+/// A list result in the shape the real methods return.
+fn listing(key: &str, items: Vec<Map<String, Value>>) -> Value {
+    let count = items.len();
+    let mut result = Map::new();
+    result.insert(
+        key.into(),
+        Value::Array(items.into_iter().map(Value::Object).collect()),
+    );
+    result.insert("count".into(), serde_json::json!(count));
+    Value::Object(result)
+}
+
+/// Query text compared loosely: case, runs of whitespace, and a trailing
+/// semicolon don't matter.
+fn normalize(query: &str) -> String {
+    query
+        .trim()
+        .trim_end_matches(';')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// The first fixture matching `query`, as a SQL result.
+fn query_result(fixtures: &[QueryFixture], query: &str) -> Result<Value> {
+    let normalized = normalize(query);
+    let fixture = fixtures
+        .iter()
+        .find(|f| match (&f.query, &f.regex) {
+            (Some(text), _) => normalize(text) == normalized,
+            (None, Some(regex)) => regex.is_match(query.trim()),
+            (None, None) => false,
+        })
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "No stub result for query {:?}; add it to the fixtures' queries",
+                query
+            )
+        })?;
+    if let Some(error) = &fixture.error {
+        anyhow::bail!("{}", error);
+    }
+
+    let mut result = fixture.result.clone().unwrap_or_default();
+    let rows = result
+        .get("rows")
+        .and_then(|r| r.as_array())
+        .map_or(0, |r| r.len());
+    result
+        .entry("rows")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    result
+        .entry("columns")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    result
+        .entry("row_count")
+        .or_insert_with(|| serde_json::json!(rows));
+    result.entry("command").or_insert_with(|| {
+        let verb = query.split_whitespace().next().unwrap_or("");
+        Value::String(verb.to_uppercase())
+    });
+    result.insert("truncated".into(), Value::Bool(false));
+    Ok(Value::Object(result))
+}
@@ -0,0 +1,119 @@
+//! Background task supervision.
+//!
+//! Every background loop (drift checks, the operations watcher, lease
+//! expiry, usage polling, update checks) runs under the [`Supervisor`]. A
+//! loop that panics or returns is restarted with exponential backoff, and
+//! each task's state is reported by `health_check`.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// First restart delay; doubles per consecutive failure.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// A run at least this long resets the backoff.
+const STABLE_RUN: Duration = Duration::from_secs(300);
+
+/// Liveness of one supervised task.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskState {
+    pub running: bool,
+    pub restarts: u32,
+    pub started_at: DateTime<Utc>,
+    /// Why the task last stopped.
+    pub last_exit: Option<String>,
+}
+
+/// Restarts background tasks and tracks their state.
+#[derive(Default)]
+pub struct Supervisor {
+    tasks: Mutex<BTreeMap<String, TaskState>>,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// State of every supervised task, by name.
+    pub fn tasks(&self) -> BTreeMap<String, TaskState> {
+        self.tasks.lock().unwrap().clone()
+    }
+
+    /// Run the task built by `start` forever, restarting it when it exits.
+    ///
+    /// Spawn the returned future on the runtime.
+    pub fn supervise<F, Fut>(
+        self: Arc<Self>,
+        name: &str,
+        start: F,
+    ) -> impl Future<Output = ()> + Send + 'static
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let name = name.to_string();
+
+        async move {
+            let mut backoff = INITIAL_BACKOFF;
+            loop {
+                self.update(&name, |state| {
+                    state.running = true;
+                    state.started_at = Utc::now();
+                });
+                let started = Instant::now();
+
+                let exit = match tokio::spawn(start()).await {
+                    Ok(()) => "exited".to_string(),
+                    Err(e) if e.is_panic() => {
+                        format!("panicked: {}", panic_message(e.into_panic()))
+                    }
+                    Err(e) => format!("cancelled: {}", e),
+                };
+
+                if started.elapsed() >= STABLE_RUN {
+                    backoff = INITIAL_BACKOFF;
+                }
+                tracing::error!(
+                    "Background task {} {}; restarting in {}s",
+                    name,
+                    exit,
+                    backoff.as_secs()
+                );
+                self.update(&name, |state| {
+                    state.running = false;
+                    state.restarts += 1;
+                    state.last_exit = Some(exit);
+                });
+
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+
+    fn update(&self, name: &str, f: impl FnOnce(&mut TaskState)) {
+        let mut tasks = self.tasks.lock().unwrap();
+        let state = tasks.entry(name.to_string()).or_insert_with(|| TaskState {
+            running: false,
+            restarts: 0,
+            started_at: Utc::now(),
+            last_exit: None,
+        });
+        f(state);
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
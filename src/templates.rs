@@ -0,0 +1,92 @@
+//! Project creation from configured templates.
+//!
+//! A template fixes region, Postgres version, and default compute settings,
+//! then creates extensions and runs a seed script on the new project's
+//! default branch, so every project starts from the same baseline.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::api::NeonClient;
+use crate::config::ProjectTemplate;
+use crate::models::{CreateProjectRequest, DefaultEndpointSettings, Project};
+use crate::script::{self, ScriptTarget, DEFAULT_DATABASE};
+
+/// Wait for project provisioning before running SQL.
+pub const PROVISION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// What a template-based creation did.
+#[derive(Debug, Serialize)]
+pub struct TemplateOutcome {
+    pub template: String,
+    pub project: Project,
+    pub branch_id: String,
+    pub extensions: Vec<String>,
+    pub seed_statements: usize,
+}
+
+/// Create a project and apply a template to it.
+pub async fn create_project(
+    client: &NeonClient,
+    name: &str,
+    template_name: &str,
+    template: &ProjectTemplate,
+) -> Result<TemplateOutcome> {
+    let seed = match &template.seed_script {
+        Some(path) => script::read(path, "seed script")?.1,
+        None => Vec::new(),
+    };
+
+    let request = CreateProjectRequest {
+        name: name.to_string(),
+        region_id: template.region_id.clone(),
+        pg_version: template.pg_version,
+        default_endpoint_settings: Some(DefaultEndpointSettings {
+            autoscaling_limit_min_cu: template.autoscaling_limit_min_cu,
+            autoscaling_limit_max_cu: template.autoscaling_limit_max_cu,
+            suspend_timeout_seconds: template.suspend_timeout_seconds,
+        }),
+        ..Default::default()
+    };
+    let project = client.create_project(&request).await?.project;
+    client.wait_for_idle(&project.id, PROVISION_TIMEOUT).await?;
+
+    // A new project has exactly one branch
+    let branch_id = client
+        .list_branches(&project.id)
+        .await?
+        .into_iter()
+        .next()
+        .map(|b| b.id)
+        .ok_or_else(|| anyhow::anyhow!("Project {} has no branches", project.id))?;
+
+    let target = ScriptTarget {
+        project_id: &project.id,
+        branch_id: &branch_id,
+        database: DEFAULT_DATABASE,
+    };
+    script::create_extensions(client, &target, &template.extensions).await?;
+
+    for (i, statement) in seed.iter().enumerate() {
+        client
+            .run_sql(&project.id, &branch_id, DEFAULT_DATABASE, &statement.text)
+            .await
+            .with_context(|| {
+                format!(
+                    "Seed statement {}/{} failed on project {}",
+                    i + 1,
+                    seed.len(),
+                    project.id
+                )
+            })?;
+    }
+
+    Ok(TemplateOutcome {
+        template: template_name.to_string(),
+        project,
+        branch_id,
+        extensions: template.extensions.clone(),
+        seed_statements: seed.len(),
+    })
+}
@@ -0,0 +1,74 @@
+//! Caller-supplied trace ids.
+//!
+//! A call's `trace_id` param is echoed in the response envelope, recorded on
+//! the call's log span, and sent to Neon as `X-Trace-Id` on every API request
+//! the call makes, so one id follows a request from the caller through the
+//! daemon to Neon. A call's async work mostly runs on the dispatching thread
+//! (inside `block_on`), so the id is held in a thread-local for the call's
+//! duration; work spawned onto another runtime's workers takes it along with
+//! [`carry`].
+
+use anyhow::Result;
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+
+/// Header carrying the trace id on requests to Neon.
+const HEADER: &str = "x-trace-id";
+
+thread_local! {
+    static CURRENT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+tokio::task_local! {
+    /// The trace id of a task spawned by [`carry`].
+    static TASK: Option<String>;
+}
+
+/// The call's `trace_id`, if it passed one.
+pub fn from_params(params: &HashMap<String, Value>) -> Result<Option<String>> {
+    match params.get("trace_id") {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(id))
+            if !id.is_empty() && id.len() <= 128 && id.bytes().all(|b| b.is_ascii_graphic()) =>
+        {
+            Ok(Some(id.clone()))
+        }
+        Some(_) => anyhow::bail!(
+            "trace_id must be a non-empty string of at most 128 printable ASCII characters"
+        ),
+    }
+}
+
+/// Run `f` with `trace_id` as the current trace id.
+pub fn scope<T>(trace_id: Option<&str>, f: impl FnOnce() -> T) -> T {
+    let previous = CURRENT.with(|c| c.replace(trace_id.map(|s| s.to_string())));
+    let result = f();
+    CURRENT.with(|c| *c.borrow_mut() = previous);
+    result
+}
+
+/// Wrap `future` to run with the current trace id on whichever thread
+/// polls it.
+pub fn carry<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    TASK.scope(current(), future)
+}
+
+fn current() -> Option<String> {
+    TASK.try_with(|id| id.clone())
+        .unwrap_or_else(|_| CURRENT.with(|c| c.borrow().clone()))
+}
+
+/// Headers to add to an outbound Neon request: the trace id, if any.
+pub fn headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(value) = current()
+        .as_deref()
+        .and_then(|id| HeaderValue::from_str(id).ok())
+    {
+        headers.insert(HEADER, value);
+    }
+    headers
+}
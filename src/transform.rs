@@ -0,0 +1,349 @@
+//! Post-processing for SQL results.
+//!
+//! Lightweight clients can ask the daemon to aggregate, project, cap, and
+//! extract from a result instead of re-implementing that munging themselves.
+//! Transforms run in a fixed order: aggregate, columns, limit, then JSONPath.
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// Requested transforms; all optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Transforms {
+    /// Keep only these columns, in this order.
+    pub columns: Option<Vec<String>>,
+    /// Keep at most this many rows.
+    pub limit: Option<usize>,
+    /// Replace rows with grouped aggregates.
+    pub aggregate: Option<Aggregate>,
+    /// JSONPath evaluated against the result; matches go in `extracted`.
+    pub jsonpath: Option<String>,
+}
+
+/// Grouped aggregation over result rows.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Aggregate {
+    pub group_by: Vec<String>,
+    /// Include a `count` per group.
+    pub count: bool,
+    pub sum: Vec<String>,
+    pub avg: Vec<String>,
+    pub min: Vec<String>,
+    pub max: Vec<String>,
+}
+
+impl Transforms {
+    /// Read transforms from `neon.sql` params.
+    pub fn from_params(params: &HashMap<String, Value>) -> Result<Self> {
+        let mut fields = Map::new();
+        for key in ["columns", "limit", "aggregate", "jsonpath"] {
+            if let Some(v) = params.get(key) {
+                fields.insert(key.to_string(), v.clone());
+            }
+        }
+        serde_json::from_value(Value::Object(fields))
+            .map_err(|e| anyhow::anyhow!("Invalid result transform: {}", e))
+    }
+
+    /// Whether no transform was requested.
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_none()
+            && self.limit.is_none()
+            && self.aggregate.is_none()
+            && self.jsonpath.is_none()
+    }
+
+    /// Apply transforms to a SQL result in place.
+    pub fn apply(&self, result: &mut Value) -> Result<()> {
+        let Some(obj) = result.as_object_mut() else {
+            return Ok(());
+        };
+        let mut rows = match obj.remove("rows") {
+            Some(Value::Array(rows)) => rows,
+            _ => Vec::new(),
+        };
+
+        if let Some(aggregate) = &self.aggregate {
+            rows = aggregate.apply(&rows);
+            obj.remove("columns");
+            obj.insert("aggregated".into(), Value::Bool(true));
+        }
+
+        if let Some(columns) = &self.columns {
+            rows = rows
+                .into_iter()
+                .map(|row| {
+                    let picked: Map<String, Value> = columns
+                        .iter()
+                        .map(|c| (c.clone(), row.get(c).cloned().unwrap_or(Value::Null)))
+                        .collect();
+                    Value::Object(picked)
+                })
+                .collect();
+            if let Some(Value::Array(fields)) = obj.get_mut("columns") {
+                fields.retain(|f| {
+                    f.get("name")
+                        .and_then(|n| n.as_str())
+                        .is_some_and(|n| columns.iter().any(|c| c == n))
+                });
+            }
+        }
+
+        if let Some(limit) = self.limit {
+            if rows.len() > limit {
+                rows.truncate(limit);
+                obj.insert("limited".into(), Value::Bool(true));
+            }
+        }
+
+        obj.insert("row_count".into(), Value::from(rows.len()));
+        obj.insert("rows".into(), Value::Array(rows));
+
+        if let Some(path) = &self.jsonpath {
+            let extracted = jsonpath(result, path)?;
+            if let Some(obj) = result.as_object_mut() {
+                obj.insert("extracted".into(), Value::Array(extracted));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Aggregate {
+    fn apply(&self, rows: &[Value]) -> Vec<Value> {
+        let mut groups: BTreeMap<String, (Vec<Value>, Vec<&Value>)> = BTreeMap::new();
+        for row in rows {
+            let key_values: Vec<Value> = self
+                .group_by
+                .iter()
+                .map(|c| row.get(c).cloned().unwrap_or(Value::Null))
+                .collect();
+            let key = Value::Array(key_values.clone()).to_string();
+            groups
+                .entry(key)
+                .or_insert_with(|| (key_values, Vec::new()))
+                .1
+                .push(row);
+        }
+
+        groups
+            .into_values()
+            .map(|(key_values, members)| {
+                let mut out: Map<String, Value> =
+                    self.group_by.iter().cloned().zip(key_values).collect();
+
+                if self.count {
+                    out.insert("count".into(), Value::from(members.len()));
+                }
+                for column in &self.sum {
+                    let sum: f64 = numbers(&members, column).sum();
+                    out.insert(format!("sum_{}", column), Value::from(sum));
+                }
+                for column in &self.avg {
+                    let values: Vec<f64> = numbers(&members, column).collect();
+                    let avg = (!values.is_empty())
+                        .then(|| values.iter().sum::<f64>() / values.len() as f64);
+                    out.insert(format!("avg_{}", column), serde_json::json!(avg));
+                }
+                for column in &self.min {
+                    let min = numbers(&members, column).reduce(f64::min);
+                    out.insert(format!("min_{}", column), serde_json::json!(min));
+                }
+                for column in &self.max {
+                    let max = numbers(&members, column).reduce(f64::max);
+                    out.insert(format!("max_{}", column), serde_json::json!(max));
+                }
+
+                Value::Object(out)
+            })
+            .collect()
+    }
+}
+
+/// Numeric values of a column, skipping nulls and non-numbers. Neon returns
+/// `numeric` and `bigint` as strings, so those are parsed.
+fn numbers<'a>(rows: &'a [&'a Value], column: &'a str) -> impl Iterator<Item = f64> + 'a {
+    rows.iter().filter_map(move |row| {
+        let v = row.get(column)?;
+        v.as_f64()
+            .or_else(|| v.as_str().and_then(|s| s.parse().ok()))
+    })
+}
+
+/// Evaluate a JSONPath subset: `$`, `.key`, `['key']`, `[n]`, `[*]`, and `.*`.
+fn jsonpath(root: &Value, path: &str) -> Result<Vec<Value>> {
+    let rest = path
+        .trim()
+        .strip_prefix('$')
+        .ok_or_else(|| anyhow::anyhow!("JSONPath must start with $"))?;
+
+    let mut current = vec![root];
+    let mut rest = rest;
+    while !rest.is_empty() {
+        let (segment, remaining) = next_segment(rest)
+            .ok_or_else(|| anyhow::anyhow!("Unsupported JSONPath near {:?}", rest))?;
+        rest = remaining;
+
+        current = current
+            .into_iter()
+            .flat_map(|v| -> Vec<&Value> {
+                match &segment {
+                    Segment::Key(k) => v.get(k.as_str()).into_iter().collect(),
+                    Segment::Index(i) => v.get(*i).into_iter().collect(),
+                    Segment::Wildcard => match v {
+                        Value::Array(a) => a.iter().collect(),
+                        Value::Object(o) => o.values().collect(),
+                        _ => Vec::new(),
+                    },
+                }
+            })
+            .collect();
+    }
+
+    Ok(current.into_iter().cloned().collect())
+}
+
+enum Segment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+fn next_segment(s: &str) -> Option<(Segment, &str)> {
+    if let Some(rest) = s.strip_prefix(".*") {
+        return Some((Segment::Wildcard, rest));
+    }
+    if let Some(rest) = s.strip_prefix('.') {
+        let end = rest.find(['.', '[']).unwrap_or(rest.len());
+        if end == 0 {
+            return None;
+        }
+        return Some((Segment::Key(rest[..end].to_string()), &rest[end..]));
+    }
+
+    let rest = s.strip_prefix('[')?;
+    let end = rest.find(']')?;
+    let inner = rest[..end].trim();
+    let after = &rest[end + 1..];
+
+    let segment = if inner == "*" {
+        Segment::Wildcard
+    } else if let Some(key) = inner
+        .strip_prefix('\'')
+        .and_then(|k| k.strip_suffix('\''))
+        .or_else(|| inner.strip_prefix('"').and_then(|k| k.strip_suffix('"')))
+    {
+        Segment::Key(key.to_string())
+    } else {
+        Segment::Index(inner.parse().ok()?)
+    };
+    Some((segment, after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn result() -> Value {
+        json!({
+            "columns": [{"name": "region"}, {"name": "name"}, {"name": "total"}],
+            "rows": [
+                {"region": "eu", "name": "a", "total": 10},
+                {"region": "us", "name": "b", "total": "2.5"},
+                {"region": "eu", "name": "c", "total": 20},
+                {"region": "us", "name": "d", "total": null},
+            ],
+            "row_count": 4,
+        })
+    }
+
+    #[test]
+    fn jsonpath_follows_keys_indexes_and_wildcards() {
+        let root = result();
+        assert_eq!(jsonpath(&root, "$.rows[0].name").unwrap(), [json!("a")]);
+        assert_eq!(
+            jsonpath(&root, "$['rows'][1]['name']").unwrap(),
+            [json!("b")]
+        );
+        assert_eq!(
+            jsonpath(&root, "$.rows[*].name").unwrap(),
+            [json!("a"), json!("b"), json!("c"), json!("d")]
+        );
+        assert_eq!(jsonpath(&root, "$.columns.*.name").unwrap().len(), 3);
+        assert_eq!(jsonpath(&root, "$").unwrap(), [root.clone()]);
+        assert!(jsonpath(&root, "$.rows[9].name").unwrap().is_empty());
+    }
+
+    #[test]
+    fn jsonpath_rejects_what_it_doesnt_support() {
+        let root = result();
+        let err = jsonpath(&root, "$..[*]").unwrap_err();
+        assert!(
+            err.to_string().starts_with("Unsupported JSONPath"),
+            "{}",
+            err
+        );
+        assert!(jsonpath(&root, "$..name").is_err());
+        assert!(jsonpath(&root, "$.rows[?(@.total)]").is_err());
+        assert!(jsonpath(&root, "rows[0]").is_err());
+    }
+
+    #[test]
+    fn aggregate_groups_and_parses_numeric_strings() {
+        let aggregate = Aggregate {
+            group_by: vec!["region".into()],
+            count: true,
+            sum: vec!["total".into()],
+            avg: vec!["total".into()],
+            min: vec!["total".into()],
+            max: vec!["total".into()],
+        };
+        let rows = result()["rows"].as_array().unwrap().clone();
+        assert_eq!(
+            aggregate.apply(&rows),
+            [
+                json!({"region": "eu", "count": 2, "sum_total": 30.0, "avg_total": 15.0,
+                       "min_total": 10.0, "max_total": 20.0}),
+                json!({"region": "us", "count": 2, "sum_total": 2.5, "avg_total": 2.5,
+                       "min_total": 2.5, "max_total": 2.5}),
+            ]
+        );
+    }
+
+    #[test]
+    fn aggregate_of_no_numbers_is_null() {
+        let aggregate = Aggregate {
+            avg: vec!["name".into()],
+            min: vec!["name".into()],
+            ..Default::default()
+        };
+        let rows = result()["rows"].as_array().unwrap().clone();
+        assert_eq!(
+            aggregate.apply(&rows),
+            [json!({"avg_name": null, "min_name": null})]
+        );
+    }
+
+    #[test]
+    fn transforms_run_in_order() {
+        let transforms = Transforms {
+            columns: Some(vec!["name".into()]),
+            limit: Some(2),
+            jsonpath: Some("$.rows[*].name".into()),
+            ..Default::default()
+        };
+        let mut out = result();
+        transforms.apply(&mut out).unwrap();
+        assert_eq!(out["rows"], json!([{"name": "a"}, {"name": "b"}]));
+        assert_eq!(out["columns"], json!([{"name": "name"}]));
+        assert_eq!(out["row_count"], 2);
+        assert_eq!(out["limited"], true);
+        assert_eq!(out["extracted"], json!(["a", "b"]));
+    }
+}
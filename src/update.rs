@@ -0,0 +1,136 @@
+//! Release update check.
+//!
+//! Compares the running version with the latest GitHub release of this
+//! repository. `neon.update_check` checks on demand; with `[update_check]
+//! enabled = true` the daemon also checks periodically, and `fgp-neon status`
+//! prints the last result.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config::UpdateCheckConfig;
+
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Result of the most recent check.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateStatus {
+    pub current_version: String,
+    pub latest_version: Option<String>,
+    pub update_available: bool,
+    pub release_url: Option<String>,
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+/// Fetches the latest release and remembers the last result.
+pub struct UpdateChecker {
+    http: reqwest::Client,
+    last: Mutex<Option<UpdateStatus>>,
+}
+
+impl UpdateChecker {
+    pub fn new() -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .user_agent(concat!("fgp-neon/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Self {
+            http,
+            last: Mutex::new(None),
+        })
+    }
+
+    /// The last check's result, or an unchecked status if none ran yet.
+    pub fn last(&self) -> UpdateStatus {
+        self.last
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| UpdateStatus {
+                current_version: CURRENT_VERSION.to_string(),
+                latest_version: None,
+                update_available: false,
+                release_url: None,
+                checked_at: None,
+            })
+    }
+
+    /// Query the latest release now.
+    pub async fn check(&self) -> Result<UpdateStatus> {
+        #[derive(Deserialize)]
+        struct Release {
+            tag_name: String,
+            html_url: String,
+        }
+
+        let url = format!(
+            "{}/releases/latest",
+            env!("CARGO_PKG_REPOSITORY")
+                .replace("https://github.com/", "https://api.github.com/repos/")
+        );
+        let response = self
+            .http
+            .get(&url)
+            .header("Accept", "application/vnd.github+json")
+            .send()
+            .await
+            .context("Failed to reach GitHub")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Release lookup failed: {} - {}", status, text);
+        }
+
+        let release: Release = response.json().await.context("Failed to parse release")?;
+        let latest = release.tag_name.trim_start_matches('v').to_string();
+
+        let status = UpdateStatus {
+            current_version: CURRENT_VERSION.to_string(),
+            update_available: is_newer(&latest, CURRENT_VERSION),
+            latest_version: Some(latest),
+            release_url: Some(release.html_url),
+            checked_at: Some(Utc::now()),
+        };
+        *self.last.lock().unwrap() = Some(status.clone());
+        Ok(status)
+    }
+}
+
+/// Check for a new release every `interval_secs`, logging when one appears.
+pub async fn run(checker: Arc<UpdateChecker>, config: UpdateCheckConfig) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(config.interval_secs.max(3600)));
+
+    loop {
+        ticker.tick().await;
+        match checker.check().await {
+            Ok(status) if status.update_available => tracing::info!(
+                "fgp-neon {} is available (running {})",
+                status.latest_version.as_deref().unwrap_or("?"),
+                status.current_version
+            ),
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Update check failed: {:#}", e),
+        }
+    }
+}
+
+/// Whether dotted version `candidate` is newer than `current`. Pre-release
+/// and build suffixes are ignored.
+fn is_newer(candidate: &str, current: &str) -> bool {
+    fn parts(version: &str) -> Vec<u64> {
+        version
+            .split(['-', '+'])
+            .next()
+            .unwrap_or("")
+            .split('.')
+            .map(|p| p.parse().unwrap_or(0))
+            .collect()
+    }
+    parts(candidate) > parts(current)
+}
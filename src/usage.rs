@@ -0,0 +1,526 @@
+//! Usage polling, threshold alerts, and billing-period reports.
+//!
+//! The background poller fetches today's (UTC) consumption for the org on an
+//! interval and compares it with the configured daily thresholds. Each
+//! threshold fires at most once per project per day, so an overrun is
+//! reported the day it starts.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api::NeonClient;
+use crate::config::{UsageConfig, UsageThreshold};
+use crate::models::ConsumptionPoint;
+use crate::notify::Notifier;
+
+/// Metric names accepted in thresholds.
+const METRICS: &[&str] = &["compute_hours", "active_hours", "written_gb", "storage_gb"];
+
+const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// Poll usage and evaluate thresholds forever.
+pub async fn run(client: Arc<NeonClient>, config: UsageConfig, notifier: Arc<Notifier>) {
+    for threshold in &config.thresholds {
+        if !METRICS.contains(&threshold.metric.as_str()) {
+            tracing::warn!(
+                "Ignoring usage threshold with unknown metric {:?} (expected one of {:?})",
+                threshold.metric,
+                METRICS
+            );
+        }
+    }
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(config.interval_secs.max(300)));
+    let mut fired: HashSet<(NaiveDate, usize, String)> = HashSet::new();
+
+    loop {
+        ticker.tick().await;
+        let now = Utc::now();
+        let today = now.date_naive();
+        fired.retain(|(day, _, _)| *day == today);
+
+        match check(&client, &config, now).await {
+            Ok(breaches) => {
+                for breach in breaches {
+                    if !fired.insert((today, breach.threshold, breach.project_id.clone())) {
+                        continue;
+                    }
+                    let threshold = &config.thresholds[breach.threshold];
+                    notifier
+                        .notify(
+                            "usage_threshold_exceeded",
+                            serde_json::json!({
+                                "project_id": breach.project_id,
+                                "metric": threshold.metric,
+                                "limit": threshold.limit,
+                                "value": breach.value,
+                                "day": today.to_string(),
+                            }),
+                        )
+                        .await;
+                }
+            }
+            Err(e) => tracing::warn!("Usage poll failed: {:#}", e),
+        }
+    }
+}
+
+/// A threshold exceeded by one project.
+struct Breach {
+    /// Index into `UsageConfig::thresholds`.
+    threshold: usize,
+    project_id: String,
+    value: f64,
+}
+
+/// Fetch today's consumption and return thresholds currently exceeded.
+async fn check(
+    client: &NeonClient,
+    config: &UsageConfig,
+    now: DateTime<Utc>,
+) -> Result<Vec<Breach>> {
+    // Scope the request when every threshold names a project
+    let project_ids: Vec<String> = if config.thresholds.iter().all(|t| t.project_id.is_some()) {
+        config
+            .thresholds
+            .iter()
+            .filter_map(|t| t.project_id.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let start_of_day = now
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .map(|t| t.and_utc())
+        .unwrap_or(now);
+    let projects = client
+        .get_consumption(
+            &start_of_day.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            &now.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            "daily",
+            &project_ids,
+        )
+        .await?;
+
+    let mut breaches = Vec::new();
+    for project in &projects {
+        let points: Vec<&ConsumptionPoint> = project
+            .periods
+            .iter()
+            .flat_map(|p| p.consumption.iter())
+            .collect();
+
+        for (index, threshold) in config.thresholds.iter().enumerate() {
+            if let Some(id) = &threshold.project_id {
+                if id != &project.project_id {
+                    continue;
+                }
+            }
+            let Some(value) = metric_value(threshold, &points) else {
+                continue;
+            };
+            if value > threshold.limit {
+                breaches.push(Breach {
+                    threshold: index,
+                    project_id: project.project_id.clone(),
+                    value,
+                });
+            }
+        }
+    }
+
+    Ok(breaches)
+}
+
+/// Value of a threshold's metric over today's points (`None` for unknown metrics).
+fn metric_value(threshold: &UsageThreshold, points: &[&ConsumptionPoint]) -> Option<f64> {
+    let totals = UsageTotals::from_points(points);
+
+    match threshold.metric.as_str() {
+        "compute_hours" => Some(totals.compute_hours),
+        "active_hours" => Some(totals.active_hours),
+        "written_gb" => Some(totals.written_gb),
+        "storage_gb" => Some(latest_storage_gb(points)),
+        _ => None,
+    }
+}
+
+/// Storage is a level, not a total, so use the latest reading.
+fn latest_storage_gb(points: &[&ConsumptionPoint]) -> f64 {
+    points
+        .iter()
+        .max_by(|a, b| a.timeframe_start.cmp(&b.timeframe_start))
+        .map(|p| p.synthetic_storage_size_bytes as f64 / GB)
+        .unwrap_or(0.0)
+}
+
+/// Usage for one billing month.
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    /// `YYYY-MM`.
+    pub month: String,
+    pub from: String,
+    pub to: String,
+    pub projects: Vec<ProjectUsage>,
+    pub totals: UsageTotals,
+    /// Whether per-branch figures are included (current month only).
+    pub includes_branches: bool,
+}
+
+/// Usage for one project in a [`UsageReport`].
+#[derive(Debug, Serialize)]
+pub struct ProjectUsage {
+    pub project_id: String,
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+    /// Latest synthetic storage size in the period.
+    pub storage_gb: f64,
+    pub branches: Vec<BranchUsage>,
+}
+
+/// Usage for one branch in a [`ProjectUsage`].
+#[derive(Debug, Serialize)]
+pub struct BranchUsage {
+    pub branch_id: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+/// Summed usage figures.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct UsageTotals {
+    pub compute_hours: f64,
+    pub active_hours: f64,
+    pub written_gb: f64,
+}
+
+impl UsageTotals {
+    fn from_points(points: &[&ConsumptionPoint]) -> Self {
+        let sum = |f: fn(&ConsumptionPoint) -> u64| points.iter().map(|p| f(p)).sum::<u64>() as f64;
+
+        Self {
+            compute_hours: sum(|p| p.compute_time_seconds) / 3600.0,
+            active_hours: sum(|p| p.active_time_seconds) / 3600.0,
+            written_gb: sum(|p| p.written_data_bytes) / GB,
+        }
+    }
+
+    fn add(&mut self, other: UsageTotals) {
+        self.compute_hours += other.compute_hours;
+        self.active_hours += other.active_hours;
+        self.written_gb += other.written_gb;
+    }
+}
+
+/// Build a usage report for a `YYYY-MM` month (the current month when `None`).
+///
+/// Project totals come from the consumption history API. Neon only reports
+/// per-branch usage for the current billing period, so branch rows are
+/// included only when reporting the current month.
+pub async fn report(
+    client: &NeonClient,
+    month: Option<&str>,
+    project_ids: &[String],
+) -> Result<UsageReport> {
+    let now = Utc::now();
+    let first = match month {
+        Some(m) => NaiveDate::parse_from_str(&format!("{}-01", m), "%Y-%m-%d")
+            .with_context(|| format!("Invalid month {:?}; expected YYYY-MM", m))?,
+        None => now.date_naive().with_day(1).unwrap_or(now.date_naive()),
+    };
+    let next = if first.month() == 12 {
+        NaiveDate::from_ymd_opt(first.year() + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(first.year(), first.month() + 1, 1)
+    }
+    .ok_or_else(|| anyhow::anyhow!("Invalid month"))?;
+
+    let from = first.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    let to = next
+        .and_hms_opt(0, 0, 0)
+        .unwrap_or_default()
+        .and_utc()
+        .min(now);
+    if from >= to {
+        anyhow::bail!("Month {} has not started yet", first.format("%Y-%m"));
+    }
+    let current = to == now;
+
+    let consumption = client
+        .get_consumption(
+            &from.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            &to.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            "daily",
+            project_ids,
+        )
+        .await?;
+    let names: HashMap<String, String> = client
+        .list_projects(Some(400))
+        .await?
+        .into_iter()
+        .map(|p| (p.id, p.name))
+        .collect();
+
+    let mut projects = Vec::new();
+    let mut totals = UsageTotals::default();
+    for project in consumption {
+        let points: Vec<&ConsumptionPoint> = project
+            .periods
+            .iter()
+            .flat_map(|p| p.consumption.iter())
+            .collect();
+        let project_totals = UsageTotals::from_points(&points);
+        let storage_gb = latest_storage_gb(&points);
+
+        let branches = if current {
+            let mut branches: Vec<BranchUsage> = client
+                .list_branches(&project.project_id)
+                .await?
+                .into_iter()
+                .map(|b| BranchUsage {
+                    branch_id: b.id,
+                    name: b.name,
+                    totals: UsageTotals {
+                        compute_hours: b.compute_time_seconds.unwrap_or(0) as f64 / 3600.0,
+                        active_hours: b.active_time_seconds.unwrap_or(0) as f64 / 3600.0,
+                        written_gb: b.written_data_bytes.unwrap_or(0) as f64 / GB,
+                    },
+                })
+                .collect();
+            branches.sort_by(|a, b| b.totals.compute_hours.total_cmp(&a.totals.compute_hours));
+            branches
+        } else {
+            Vec::new()
+        };
+
+        totals.add(project_totals);
+        projects.push(ProjectUsage {
+            name: names.get(&project.project_id).cloned(),
+            project_id: project.project_id,
+            totals: project_totals,
+            storage_gb,
+            branches,
+        });
+    }
+    projects.sort_by(|a, b| b.totals.compute_hours.total_cmp(&a.totals.compute_hours));
+
+    Ok(UsageReport {
+        month: first.format("%Y-%m").to_string(),
+        from: from.to_rfc3339(),
+        to: to.to_rfc3339(),
+        projects,
+        totals,
+        includes_branches: current,
+    })
+}
+
+/// Consumption over an arbitrary window, bucket by bucket.
+#[derive(Debug, Serialize)]
+pub struct Consumption {
+    pub from: String,
+    pub to: String,
+    /// `hourly`, `daily`, or `monthly`.
+    pub granularity: String,
+    pub projects: Vec<ProjectConsumptionWindow>,
+    pub count: usize,
+    pub totals: ConsumptionTotals,
+}
+
+/// One project's buckets in a [`Consumption`].
+#[derive(Debug, Serialize)]
+pub struct ProjectConsumptionWindow {
+    pub project_id: String,
+    pub buckets: Vec<ConsumptionPoint>,
+    pub totals: ConsumptionTotals,
+}
+
+/// Consumption summed over buckets, in API units and dashboard units.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct ConsumptionTotals {
+    pub compute_time_seconds: u64,
+    pub active_time_seconds: u64,
+    pub written_data_bytes: u64,
+    pub data_transfer_bytes: u64,
+    /// Latest reading; storage is a level, not a total.
+    pub synthetic_storage_size_bytes: u64,
+    pub compute_hours: f64,
+    pub written_gb: f64,
+    pub data_transfer_gb: f64,
+    pub storage_gb: f64,
+}
+
+impl ConsumptionTotals {
+    fn from_points(points: &[&ConsumptionPoint]) -> Self {
+        let sum = |f: fn(&ConsumptionPoint) -> u64| points.iter().map(|p| f(p)).sum::<u64>();
+        let storage = points
+            .iter()
+            .max_by(|a, b| a.timeframe_start.cmp(&b.timeframe_start))
+            .map(|p| p.synthetic_storage_size_bytes)
+            .unwrap_or(0);
+
+        Self {
+            compute_time_seconds: sum(|p| p.compute_time_seconds),
+            active_time_seconds: sum(|p| p.active_time_seconds),
+            written_data_bytes: sum(|p| p.written_data_bytes),
+            data_transfer_bytes: sum(|p| p.data_transfer_bytes),
+            synthetic_storage_size_bytes: storage,
+            ..Self::default()
+        }
+        .with_units()
+    }
+
+    /// Storage across projects adds up, unlike storage across time.
+    fn add(&mut self, other: ConsumptionTotals) {
+        self.compute_time_seconds += other.compute_time_seconds;
+        self.active_time_seconds += other.active_time_seconds;
+        self.written_data_bytes += other.written_data_bytes;
+        self.data_transfer_bytes += other.data_transfer_bytes;
+        self.synthetic_storage_size_bytes += other.synthetic_storage_size_bytes;
+        *self = self.with_units();
+    }
+
+    fn with_units(self) -> Self {
+        Self {
+            compute_hours: self.compute_time_seconds as f64 / 3600.0,
+            written_gb: self.written_data_bytes as f64 / GB,
+            data_transfer_gb: self.data_transfer_bytes as f64 / GB,
+            storage_gb: self.synthetic_storage_size_bytes as f64 / GB,
+            ..self
+        }
+    }
+}
+
+/// Consumption between `from` and `to` (RFC 3339 or `YYYY-MM-DD`) in
+/// `granularity` buckets. `to` defaults to now and `from` to one day, 30
+/// days, or a year earlier for hourly, daily, and monthly buckets.
+pub async fn consumption(
+    client: &NeonClient,
+    from: Option<&str>,
+    to: Option<&str>,
+    granularity: &str,
+    project_ids: &[String],
+) -> Result<Consumption> {
+    let default_span = match granularity {
+        "hourly" => chrono::Duration::days(1),
+        "daily" => chrono::Duration::days(30),
+        "monthly" => chrono::Duration::days(365),
+        other => anyhow::bail!(
+            "Unknown granularity {:?}; expected hourly, daily, or monthly",
+            other
+        ),
+    };
+    let to = match to {
+        Some(t) => parse_time(t)?,
+        None => Utc::now(),
+    };
+    let from = match from {
+        Some(f) => parse_time(f)?,
+        None => to - default_span,
+    };
+    if from >= to {
+        anyhow::bail!("from must be before to");
+    }
+
+    let history = client
+        .get_consumption(
+            &from.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            &to.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            granularity,
+            project_ids,
+        )
+        .await?;
+
+    let mut projects = Vec::new();
+    let mut totals = ConsumptionTotals::default();
+    for project in history {
+        let mut buckets: Vec<ConsumptionPoint> = project
+            .periods
+            .into_iter()
+            .flat_map(|p| p.consumption)
+            .collect();
+        buckets.sort_by(|a, b| a.timeframe_start.cmp(&b.timeframe_start));
+        let project_totals = ConsumptionTotals::from_points(&buckets.iter().collect::<Vec<_>>());
+        totals.add(project_totals);
+        projects.push(ProjectConsumptionWindow {
+            project_id: project.project_id,
+            buckets,
+            totals: project_totals,
+        });
+    }
+    projects.sort_by(|a, b| b.totals.compute_hours.total_cmp(&a.totals.compute_hours));
+
+    Ok(Consumption {
+        from: from.to_rfc3339(),
+        to: to.to_rfc3339(),
+        granularity: granularity.to_string(),
+        count: projects.len(),
+        projects,
+        totals,
+    })
+}
+
+/// An RFC 3339 timestamp, or a date meaning its UTC midnight.
+fn parse_time(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Ok(t.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|d| d.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+        .with_context(|| format!("Invalid time {:?}; expected RFC 3339 or YYYY-MM-DD", value))
+}
+
+impl UsageReport {
+    /// Render as Markdown tables for a cost review doc.
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let _ = writeln!(md, "## Neon usage: {}\n", self.month);
+        let _ = writeln!(md, "Period: {} to {}\n", self.from, self.to);
+        let _ = writeln!(
+            md,
+            "| Project | Compute hours | Active hours | Written GB | Storage GB |"
+        );
+        let _ = writeln!(md, "|---|---:|---:|---:|---:|");
+        for p in &self.projects {
+            let _ = writeln!(
+                md,
+                "| {} | {:.2} | {:.2} | {:.2} | {:.2} |",
+                p.name.as_deref().unwrap_or(&p.project_id),
+                p.totals.compute_hours,
+                p.totals.active_hours,
+                p.totals.written_gb,
+                p.storage_gb
+            );
+        }
+        let _ = writeln!(
+            md,
+            "| **Total** | **{:.2}** | **{:.2}** | **{:.2}** | |",
+            self.totals.compute_hours, self.totals.active_hours, self.totals.written_gb
+        );
+
+        for p in self.projects.iter().filter(|p| !p.branches.is_empty()) {
+            let _ = writeln!(md, "\n### {}\n", p.name.as_deref().unwrap_or(&p.project_id));
+            let _ = writeln!(md, "| Branch | Compute hours | Active hours | Written GB |");
+            let _ = writeln!(md, "|---|---:|---:|---:|");
+            for b in &p.branches {
+                let _ = writeln!(
+                    md,
+                    "| {} | {:.2} | {:.2} | {:.2} |",
+                    b.name, b.totals.compute_hours, b.totals.active_hours, b.totals.written_gb
+                );
+            }
+        }
+
+        md
+    }
+}
@@ -0,0 +1,168 @@
+//! `fgp-neon verify`: an end-to-end check run without the daemon.
+//!
+//! Meant as a CI preflight: it resolves credentials, the org, and the
+//! config, lists one project, and optionally runs `SELECT 1` on a branch,
+//! printing each step and stopping at the first failure. With
+//! `--output json` the steps are reported together as one object instead.
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use std::future::Future;
+use std::time::Instant;
+
+use crate::api::NeonClient;
+use crate::config::Config;
+use crate::credentials;
+
+/// A branch to run the SQL check against.
+pub struct SqlCheck {
+    pub project_id: String,
+    pub branch_id: String,
+    pub database: String,
+}
+
+/// One step's outcome.
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    /// What was found, or why the step failed.
+    pub detail: String,
+    pub elapsed_ms: u64,
+}
+
+/// Run every check up to the first failure, printing each as it finishes
+/// when `echo` is set.
+pub fn run(config_path: &str, sql: Option<SqlCheck>, echo: bool) -> Vec<Check> {
+    let mut checks = Checks {
+        echo,
+        done: Vec::new(),
+    };
+    let Some(credentials) = checks.step("credentials", || {
+        let credentials = credentials::load()?;
+        let detail = match credentials.expires_at {
+            Some(at) if at <= Utc::now() => anyhow::bail!(
+                "{} token expired at {}; run `neonctl auth`",
+                credentials.source,
+                at
+            ),
+            Some(at) => format!(
+                "from {}, expire in {} minutes",
+                credentials.source,
+                (at - Utc::now()).num_minutes()
+            ),
+            None => format!("from {}", credentials.source),
+        };
+        Ok((credentials, detail))
+    }) else {
+        return checks.done;
+    };
+    let Some(org_id) = checks.step("org", || {
+        let org_id = std::env::var("NEON_ORG_ID")
+            .ok()
+            .filter(|id| !id.is_empty())
+            .context("NEON_ORG_ID is not set. Run `neonctl orgs list` to find your org_id.")?;
+        Ok((org_id.clone(), org_id))
+    }) else {
+        return checks.done;
+    };
+    let Some(config) = checks.step("config", || {
+        Ok((Config::load(config_path)?, config_path.to_string()))
+    }) else {
+        return checks.done;
+    };
+    let Some((runtime, client)) = checks.step("client", || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("Failed to start runtime")?;
+        let client = NeonClient::new(&credentials, org_id, &config)?;
+        Ok(((runtime, client), "ready".to_string()))
+    }) else {
+        return checks.done;
+    };
+
+    runtime.block_on(async {
+        // Listing projects proves both the key and access to the org
+        let listed = checks
+            .step_async("projects", async {
+                let projects = client.list_projects(Some(1)).await?;
+                let detail = match projects.first() {
+                    Some(project) => format!("listed {} ({})", project.id, project.name),
+                    None => "org has no projects".to_string(),
+                };
+                Ok(((), detail))
+            })
+            .await;
+        if listed.is_none() {
+            return;
+        }
+
+        let Some(sql) = sql else {
+            return;
+        };
+        checks
+            .step_async("sql", async {
+                client
+                    .run_sql(&sql.project_id, &sql.branch_id, &sql.database, "SELECT 1")
+                    .await?;
+                Ok((
+                    (),
+                    format!("SELECT 1 on {}/{}", sql.branch_id, sql.database),
+                ))
+            })
+            .await;
+    });
+    checks.done
+}
+
+/// Completed checks.
+struct Checks {
+    echo: bool,
+    done: Vec<Check>,
+}
+
+impl Checks {
+    /// Run one check and record its outcome; `check` returns its value and
+    /// a line describing it.
+    fn step<T>(&mut self, name: &str, check: impl FnOnce() -> Result<(T, String)>) -> Option<T> {
+        let started = Instant::now();
+        self.report(name, started, check())
+    }
+
+    async fn step_async<T>(
+        &mut self,
+        name: &str,
+        check: impl Future<Output = Result<(T, String)>>,
+    ) -> Option<T> {
+        let started = Instant::now();
+        self.report(name, started, check.await)
+    }
+
+    fn report<T>(
+        &mut self,
+        name: &str,
+        started: Instant,
+        result: Result<(T, String)>,
+    ) -> Option<T> {
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        let (value, ok, detail) = match result {
+            Ok((value, detail)) => (Some(value), true, detail),
+            Err(e) => (None, false, format!("{:#}", e)),
+        };
+        if self.echo && ok {
+            println!("ok    {:<12} {} ({}ms)", name, detail, elapsed_ms);
+        } else if self.echo {
+            println!("FAIL  {:<12} {}", name, detail);
+        }
+
+        self.done.push(Check {
+            name: name.to_string(),
+            ok,
+            detail,
+            elapsed_ms,
+        });
+        value
+    }
+}
@@ -0,0 +1,112 @@
+//! Structured warnings.
+//!
+//! Non-fatal information for the caller goes in the envelope's `warnings`
+//! array rather than the daemon log. Each warning is an object with a
+//! machine-readable `code` and a human-readable `message`, plus fields
+//! specific to its code:
+//!
+//! - `deprecated`: the call used a param that will be removed (`param`,
+//!   `use_instead`)
+//! - `truncated`: the result is partial; `pagination` says how to get more
+//! - `cached`: the result came from the response cache (`age_secs`)
+//! - `near_quota` / `quota_exceeded`: a project is at 80% or more of a quota
+//!   (`metric`, `percent`)
+//! - `notice`: anything else a method reports
+
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::models::QuotaUsage;
+
+/// Quota use, in percent, from which a project's quotas are warned about.
+const NEAR_QUOTA_PERCENT: f64 = 80.0;
+
+/// Params still accepted but due for removal: method, param, replacement.
+/// None at present; add an entry when renaming a param so old callers are
+/// told before it goes.
+const DEPRECATED_PARAMS: &[(&str, &str, &str)] = &[];
+
+/// A warning with `code` and `message`.
+pub fn warning(code: &str, message: impl Into<String>) -> Value {
+    serde_json::json!({ "code": code, "message": message.into() })
+}
+
+/// A warning with extra fields.
+pub fn warning_with(code: &str, message: impl Into<String>, fields: Value) -> Value {
+    let mut warning = warning(code, message);
+    if let (Some(obj), Value::Object(fields)) = (warning.as_object_mut(), fields) {
+        obj.extend(fields);
+    }
+    warning
+}
+
+/// A method's warning in the structured shape: strings become `notice`s and
+/// objects without a code get one.
+pub fn normalize(item: Value) -> Value {
+    match item {
+        Value::Object(mut obj) => {
+            if !obj.get("code").is_some_and(|c| c.is_string()) {
+                obj.insert("code".into(), Value::String("notice".into()));
+            }
+            Value::Object(obj)
+        }
+        Value::String(message) => warning("notice", message),
+        other => warning("notice", other.to_string()),
+    }
+}
+
+/// Warnings for deprecated params the call passed.
+pub fn deprecations(method: &str, params: &HashMap<String, Value>) -> Vec<Value> {
+    let short = method.strip_prefix("neon.").unwrap_or(method);
+    DEPRECATED_PARAMS
+        .iter()
+        .filter(|(m, param, _)| *m == short && params.contains_key(*param))
+        .map(|(_, param, use_instead)| {
+            warning_with(
+                "deprecated",
+                format!(
+                    "{} is deprecated and will be removed; use {}",
+                    param, use_instead
+                ),
+                serde_json::json!({ "param": param, "use_instead": use_instead }),
+            )
+        })
+        .collect()
+}
+
+/// Warnings for quotas a project is close to or over.
+pub fn quotas(project_id: &str, status: &BTreeMap<String, QuotaUsage>) -> Vec<Value> {
+    status
+        .iter()
+        .filter(|(_, usage)| usage.percent >= NEAR_QUOTA_PERCENT)
+        .map(|(metric, usage)| {
+            let (code, state) = if usage.exceeded {
+                ("quota_exceeded", "has exceeded")
+            } else {
+                ("near_quota", "is near")
+            };
+            warning_with(
+                code,
+                format!(
+                    "Project {} {} its {} quota ({:.0}% of {})",
+                    project_id, state, metric, usage.percent, usage.limit
+                ),
+                serde_json::json!({ "metric": metric, "percent": usage.percent }),
+            )
+        })
+        .collect()
+}
+
+/// Add warnings to a result object's `warnings`.
+pub fn extend(result: &mut Map<String, Value>, warnings: Vec<Value>) {
+    if warnings.is_empty() {
+        return;
+    }
+    let mut existing = match result.remove("warnings") {
+        Some(Value::Array(items)) => items,
+        Some(Value::Null) | None => Vec::new(),
+        Some(other) => vec![other],
+    };
+    existing.extend(warnings);
+    result.insert("warnings".into(), Value::Array(existing));
+}
@@ -0,0 +1,286 @@
+//! Operations watcher and event bus.
+//!
+//! A background poller tails each project's operations feed and turns state
+//! changes into [`Event`]s; SQL scripts publish their progress here too. Events get a monotonically increasing sequence
+//! number, are kept in a bounded buffer, and are delivered to `neon.watch`
+//! long-poll subscribers (who resume from the last sequence they saw) and, for
+//! configured kinds, to the notifier.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::api::NeonClient;
+use crate::config::WatchConfig;
+use crate::models::Operation;
+use crate::notify::Notifier;
+use crate::previews::PreviewCache;
+
+/// Events retained for subscribers that fall behind.
+const BUFFER_SIZE: usize = 1000;
+
+/// Operations fetched per project per poll.
+const OPERATIONS_PER_POLL: i32 = 50;
+
+/// A state change observed in the operations feed.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    /// `branch_created`, `branch_deleted`, `endpoint_started`,
+    /// `endpoint_suspended`, `operation_failed`, `operation_finished`,
+    /// `script_statement`, or `import_progress`.
+    pub kind: String,
+    pub project_id: String,
+    pub operation_id: String,
+    pub action: String,
+    pub status: String,
+    pub branch_id: Option<String>,
+    pub endpoint_id: Option<String>,
+    pub error: Option<String>,
+    /// Position in a SQL script, for `script_statement` events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statement: Option<StatementProgress>,
+    /// Rows loaded so far, for `import_progress` events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub import: Option<ImportProgress>,
+}
+
+/// Progress of a SQL script run, carried by `script_statement` events.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatementProgress {
+    /// 1-based statement number.
+    pub index: usize,
+    pub total: usize,
+    pub line: usize,
+    pub duration_ms: f64,
+}
+
+/// Progress of a CSV import, carried by `import_progress` events.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportProgress {
+    pub rows: u64,
+    /// File bytes read, out of `total_bytes`.
+    pub bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// Ordered, bounded buffer of events with async wake-ups.
+pub struct EventBus {
+    inner: Mutex<BusInner>,
+    notify: Notify,
+}
+
+struct BusInner {
+    next_seq: u64,
+    events: VecDeque<Event>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(BusInner {
+                next_seq: 1,
+                events: VecDeque::new(),
+            }),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Sequence number of the newest event (0 if none yet).
+    pub fn head(&self) -> u64 {
+        self.inner.lock().unwrap().next_seq - 1
+    }
+
+    /// Append events in order, assigning sequence numbers.
+    pub fn publish(&self, events: Vec<Event>) -> Vec<Event> {
+        if events.is_empty() {
+            return events;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        let mut published = Vec::with_capacity(events.len());
+        for mut event in events {
+            event.seq = inner.next_seq;
+            inner.next_seq += 1;
+            inner.events.push_back(event.clone());
+            published.push(event);
+        }
+        while inner.events.len() > BUFFER_SIZE {
+            inner.events.pop_front();
+        }
+        drop(inner);
+
+        self.notify.notify_waiters();
+        published
+    }
+
+    /// Events with `seq > since`, without waiting.
+    pub fn since(&self, since: u64) -> Vec<Event> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .events
+            .iter()
+            .filter(|e| e.seq > since)
+            .cloned()
+            .collect()
+    }
+
+    /// Wait up to `timeout` for events with `seq > since`.
+    pub async fn wait_since(&self, since: u64, timeout: Duration) -> Vec<Event> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            // Register interest before checking so a publish in between isn't missed
+            let mut notified = std::pin::pin!(self.notify.notified());
+            notified.as_mut().enable();
+
+            let events = self.since(since);
+            if !events.is_empty() {
+                return events;
+            }
+
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return Vec::new();
+            }
+        }
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Poll operations for the configured projects forever, invalidating preview
+/// boards of projects whose branches or endpoints changed.
+pub async fn run(
+    client: Arc<NeonClient>,
+    config: WatchConfig,
+    bus: Arc<EventBus>,
+    notifier: Arc<Notifier>,
+    previews: Arc<PreviewCache>,
+) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(config.interval_secs.max(5)));
+    let mut watcher = Watcher::default();
+
+    loop {
+        ticker.tick().await;
+        match watcher.poll(&client, &config).await {
+            Ok(events) => {
+                for event in bus.publish(events) {
+                    previews.invalidate(&event.project_id, &event.kind);
+                    if event.endpoint_id.is_some() || event.kind.starts_with("branch_") {
+                        client.invalidate_routes(&event.project_id);
+                    }
+                    if config.notify_kinds.contains(&event.kind) {
+                        if let Ok(data) = serde_json::to_value(&event) {
+                            notifier.notify(&event.kind, data).await;
+                        }
+                    }
+                }
+            }
+            Err(e) => tracing::warn!("Operations watcher poll failed: {:#}", e),
+        }
+    }
+}
+
+/// Deduplication state across polls.
+#[derive(Default)]
+struct Watcher {
+    /// Last status seen per operation, per project.
+    seen: HashMap<String, HashMap<String, String>>,
+}
+
+impl Watcher {
+    /// Fetch operations and return new state changes, oldest first.
+    async fn poll(&mut self, client: &NeonClient, config: &WatchConfig) -> Result<Vec<Event>> {
+        let project_ids = if config.project_ids.is_empty() {
+            client
+                .list_projects(Some(400))
+                .await?
+                .into_iter()
+                .map(|p| p.id)
+                .collect()
+        } else {
+            config.project_ids.clone()
+        };
+
+        let mut events = Vec::new();
+        for project_id in project_ids {
+            let operations = match client
+                .list_operations(&project_id, OPERATIONS_PER_POLL)
+                .await
+            {
+                Ok(ops) => ops,
+                Err(e) => {
+                    tracing::warn!("Failed to list operations for {}: {:#}", project_id, e);
+                    continue;
+                }
+            };
+
+            // The first poll of a project only establishes a baseline
+            let bootstrap = !self.seen.contains_key(&project_id);
+            let seen = self.seen.entry(project_id.clone()).or_default();
+
+            for op in operations.iter().rev() {
+                let changed = seen.get(&op.id) != Some(&op.status);
+                if changed && !bootstrap && !op.is_pending() {
+                    events.push(event_for(op));
+                }
+            }
+
+            // Only remember operations still in the feed window
+            *seen = operations
+                .into_iter()
+                .map(|op| (op.id, op.status))
+                .collect();
+        }
+
+        events.sort_by(|a, b| a.at.cmp(&b.at));
+        Ok(events)
+    }
+}
+
+/// Describe a terminal operation as an event.
+fn event_for(op: &Operation) -> Event {
+    let kind = if op.is_failed() {
+        "operation_failed"
+    } else {
+        match op.action.as_str() {
+            "create_timeline" | "create_branch" => "branch_created",
+            "delete_timeline" => "branch_deleted",
+            "start_compute" => "endpoint_started",
+            "suspend_compute" => "endpoint_suspended",
+            _ => "operation_finished",
+        }
+    };
+
+    let at = op
+        .updated_at
+        .as_deref()
+        .or(op.created_at.as_deref())
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(Utc::now);
+
+    Event {
+        seq: 0,
+        at,
+        kind: kind.to_string(),
+        project_id: op.project_id.clone(),
+        operation_id: op.id.clone(),
+        action: op.action.clone(),
+        status: op.status.clone(),
+        branch_id: op.branch_id.clone(),
+        endpoint_id: op.endpoint_id.clone(),
+        error: op.error.clone(),
+        statement: None,
+        import: None,
+    }
+}